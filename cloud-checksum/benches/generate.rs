@@ -1,3 +1,4 @@
+use cloud_checksum::checksum::Ctx;
 use cloud_checksum::io::sums::channel::ChannelReader;
 use cloud_checksum::task::generate::GenerateTaskBuilder;
 use cloud_checksum::test::TestFileBuilder;
@@ -6,17 +7,11 @@ use std::path::Path;
 use tokio::fs::File;
 use tokio::runtime::Runtime;
 
-async fn channel_reader(path: &Path) {
+async fn channel_reader(path: &Path, ctxs: Vec<Ctx>) {
     let reader = ChannelReader::new(File::open(path).await.unwrap(), 100);
 
     let result = GenerateTaskBuilder::default()
-        .with_context(vec![
-            "sha1".parse().unwrap(),
-            "sha256".parse().unwrap(),
-            "md5".parse().unwrap(),
-            "crc32".parse().unwrap(),
-            "crc32c".parse().unwrap(),
-        ])
+        .with_context(ctxs)
         .with_reader(reader)
         .build()
         .await
@@ -28,14 +23,41 @@ async fn channel_reader(path: &Path) {
     black_box(result);
 }
 
+fn ctxs(checksums: &[&str]) -> Vec<Ctx> {
+    checksums
+        .iter()
+        .map(|checksum| checksum.parse().unwrap())
+        .collect()
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let bench_file = TestFileBuilder::default()
         .generate_bench_defaults()
         .unwrap();
 
     c.bench_function("generate with channel reader", |b| {
+        b.to_async(Runtime::new().unwrap()).iter(|| {
+            channel_reader(
+                &bench_file,
+                ctxs(&["sha1", "sha256", "md5", "crc32", "crc32c"]),
+            )
+        })
+    });
+
+    // The AWS ETag checksums split the input into parts, which is measured separately from the
+    // flat checksums above.
+    c.bench_function("generate aws etag", |b| {
         b.to_async(Runtime::new().unwrap())
-            .iter(|| channel_reader(&bench_file))
+            .iter(|| channel_reader(&bench_file, ctxs(&["md5-aws-8mib"])))
+    });
+
+    c.bench_function("generate aws etag with multiple part sizes", |b| {
+        b.to_async(Runtime::new().unwrap()).iter(|| {
+            channel_reader(
+                &bench_file,
+                ctxs(&["md5-aws-1mib-3mib-5mib-8mib-13mib-21mib"]),
+            )
+        })
     });
 }
 
@@ -0,0 +1,19 @@
+//! Sums files may come from untrusted sources, so reading arbitrary bytes as a sums file must
+//! return an error rather than panic.
+//!
+
+#![no_main]
+
+use cloud_checksum::checksum::file::{SumsFile, SumsFormat};
+use futures_util::FutureExt;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Reading a slice does not wait on anything, so it completes on the first poll.
+    if let Some(Ok(sums_file)) = SumsFile::read_from_slice(data).now_or_never() {
+        // A sums file that could be read can also be written.
+        sums_file.to_json_string().unwrap();
+    }
+
+    let _ = SumsFormat::Msgpack.from_slice(data);
+});
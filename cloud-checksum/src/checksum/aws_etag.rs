@@ -2,15 +2,22 @@
 //! of the parts of a file.
 //!
 
+use crate::checksum::checkpoint::CtxCheckpoint;
 use crate::checksum::standard::StandardCtx;
-use crate::error::Error::ParseError;
+use crate::cli::Strength;
+use crate::error::Error::{GenerateError, ParseError};
 use crate::error::{Error, Result};
 use crate::io::Provider;
+use futures_util::StreamExt;
 use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use tokio::fs;
+use tokio_util::io::ReaderStream;
+use tracing::warn;
 
 /// Constant for 1 MiB.
 pub const MIB: u64 = 1024 * 1024;
@@ -19,6 +26,13 @@ pub const GIB: u64 = 1024 * 1024 * 1024;
 /// Constant for 1 GB.
 pub const GB: u64 = 1000 * 1000 * 1000;
 
+/// The minimum part size of an S3 multipart upload. This does not apply to the last part.
+pub const S3_MIN_PART_SIZE: u64 = 5 * MIB;
+/// The maximum part size of an S3 multipart upload.
+pub const S3_MAX_PART_SIZE: u64 = 5 * GIB;
+/// The maximum number of parts in an S3 multipart upload.
+pub const S3_MAX_PARTS: u64 = 10000;
+
 /// Defines the "best" order for part sizes that should be preferenced when copying/generating
 /// new checksums. This list takes into account defaults that are likely to show up in the AWS CLI
 /// and SDKs.
@@ -82,6 +96,60 @@ pub const fn part_size_position(part_size: u64) -> Option<usize> {
     None
 }
 
+/// Predict the `ETag` that S3 reports for a local file which is uploaded using a multipart
+/// upload with the part size. This is the exact `ETag` header value, i.e. the quoted MD5 of
+/// the part MD5s followed by the number of parts, e.g. `"ec1e29805585d04a93eb8cf464b68c43-2"`.
+/// Without a part size, this predicts the `ETag` of a single `PutObject` upload, which is the
+/// quoted MD5 of the whole file without a part count.
+pub async fn predict_e_tag(file: &Path, part_size: Option<u64>) -> Result<String> {
+    let file_size = fs::metadata(file).await?.len();
+
+    let Some(part_size) = part_size else {
+        // A single upload has the same size limit as a part.
+        if file_size > S3_MAX_PART_SIZE {
+            return Err(ParseError(format!(
+                "file size `{}` is too large for a single upload",
+                file_size
+            )));
+        }
+
+        let mut ctx = StandardCtx::from_str("md5")?;
+        let mut stream = ReaderStream::new(fs::File::open(file).await?);
+        while let Some(chunk) = stream.next().await {
+            ctx.update(Arc::from(chunk?.as_ref()))?;
+        }
+
+        let digest = ctx.finalize()?;
+        return Ok(format!("\"{}\"", ctx.digest_to_string(&digest)));
+    };
+
+    // Only the last part can be smaller than the minimum part size.
+    if part_size == 0
+        || part_size > S3_MAX_PART_SIZE
+        || file_size.div_ceil(part_size) > S3_MAX_PARTS
+        || (file_size > part_size && part_size < S3_MIN_PART_SIZE)
+    {
+        return Err(ParseError(format!(
+            "invalid part size `{}` for a multipart upload with file size `{}`",
+            part_size, file_size
+        )));
+    }
+
+    let mut ctx = AWSETagCtx::new(
+        StandardCtx::from_str("md5")?,
+        PartMode::PartSizes(vec![part_size]),
+        Some(file_size),
+    );
+
+    let mut stream = ReaderStream::new(fs::File::open(file).await?);
+    while let Some(chunk) = stream.next().await {
+        ctx.update(Arc::from(chunk?.as_ref()))?;
+    }
+
+    let digest = ctx.finalize()?;
+    Ok(ctx.digest_to_e_tag(&digest))
+}
+
 /// Calculate checksums using an AWS ETag style.
 #[derive(Debug, Clone)]
 pub struct AWSETagCtx {
@@ -90,11 +158,12 @@ pub struct AWSETagCtx {
     current_part_size: u64,
     current_bytes: u64,
     total_bytes: u64,
-    remainder: Option<Arc<[u8]>>,
     part_checksums: Vec<(u64, Vec<u8>)>,
     n_checksums: u64,
     ctx: StandardCtx,
     file_size: Option<u64>,
+    exact_parts: bool,
+    strict_s3: bool,
 }
 
 impl Ord for AWSETagCtx {
@@ -144,10 +213,14 @@ impl Hash for AWSETagCtx {
 }
 
 /// The mode to operate aws etags in. Part numbers calculate parts using the total file size.
-/// Part sizes can operate without the file size.
+/// Aligned part numbers do the same, but round the part size up to the nearest MiB, like some
+/// upload tools do. Minimum part numbers use the largest part size that still results in at
+/// least that many parts. Part sizes can operate without the file size.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub enum PartMode {
     PartNumber(u64),
+    AlignedPartNumber(u64),
+    MinPartNumber(u64),
     PartSizes(Vec<u64>),
 }
 
@@ -160,14 +233,33 @@ impl AWSETagCtx {
             current_part_size: 0,
             current_bytes: 0,
             total_bytes: 0,
-            remainder: None,
             part_checksums: vec![],
             n_checksums: 0,
             ctx,
             file_size,
+            exact_parts: false,
+            strict_s3: false,
         }
     }
 
+    /// Create a checksummer from the exact size of every part, e.g. the parts reported by
+    /// `GetObjectAttributes` for an object uploaded with varying part sizes. Unlike `new`, the
+    /// part sizes are used as they are rather than being normalized by `update_part_sizes`,
+    /// and finalizing errors if the data does not cover exactly these parts.
+    pub fn from_exact_parts(ctx: StandardCtx, part_sizes: Vec<u64>) -> Result<Self> {
+        if part_sizes.is_empty() || part_sizes.contains(&0) {
+            return Err(ParseError(
+                "exact part sizes must contain at least one part and no empty parts".to_string(),
+            ));
+        }
+
+        let file_size = part_sizes.iter().sum();
+        Ok(Self {
+            exact_parts: true,
+            ..Self::new(ctx, PartMode::PartSizes(part_sizes), Some(file_size))
+        })
+    }
+
     /// Update the part sizes so that they represent the correct part sizes for the file size.
     /// This takes two steps, first it iterates forward to determine the correct number of part
     /// sizes, and then it removes duplicate part sizes from the back as they are assumed to be
@@ -176,6 +268,10 @@ impl AWSETagCtx {
         let PartMode::PartSizes(part_sizes) = &mut self.part_mode else {
             return;
         };
+        // Exact part sizes are already the true parts, so there is nothing to correct.
+        if self.exact_parts {
+            return;
+        }
 
         Self::iterate_part_sizes(self.file_size.unwrap_or(self.total_bytes), part_sizes);
         Self::remove_duplicates(part_sizes);
@@ -225,8 +321,9 @@ impl AWSETagCtx {
             // If the counter is less than the current part size, stop here, and set
             // the index to remove bytes from.
             if file_size <= *part_size {
-                // The ending part size needs to be updated with the remaining bytes.
-                *part_size = file_size;
+                // The ending part size needs to be updated with the remaining bytes. An empty
+                // file still has a single part, which uses the minimum part size.
+                *part_size = file_size.max(1);
                 remove_from = Some(i + 1);
                 file_size = file_size.saturating_sub(*part_size);
                 break;
@@ -256,75 +353,96 @@ impl AWSETagCtx {
 
     /// Update using data.
     pub fn update(&mut self, data: Arc<[u8]>) -> Result<()> {
-        let len = u64::try_from(data.len())?;
+        let mut start = 0;
+        loop {
+            if self.current_part_size == 0 {
+                self.current_part_size = self.next_part_size()?;
+            }
 
-        if self.current_part_size == 0 {
-            self.current_part_size = self.next_part_size()?;
-        }
+            let len = u64::try_from(data.len() - start)?;
+            let remaining = self
+                .current_part_size
+                .checked_sub(self.current_bytes)
+                .ok_or_else(|| Error::aws_error("part size too large".to_string()))?;
+
+            // If the rest of the data fits in the current part, then update as usual, tracking
+            // the byte position. A part that is exactly full is only split once more data
+            // arrives, or when finalizing.
+            if len <= remaining {
+                self.current_bytes += len;
+                self.total_bytes += len;
+
+                if start == 0 {
+                    self.ctx.update(data)?;
+                } else {
+                    self.ctx.update(Arc::from(&data[start..]))?;
+                }
 
-        if self.current_bytes + len > self.current_part_size {
-            // If the current byte position is greater than the part size, then split into a new
-            // part checksum.
-            let (data, remainder) = data.split_at(usize::try_from(
-                self.current_part_size
-                    .checked_sub(self.current_bytes)
-                    .ok_or_else(|| Error::aws_error("part size too large".to_string()))?,
-            )?);
+                return Ok(());
+            }
 
-            self.ctx.update(Arc::from(data))?;
+            // Otherwise, fill the current part and split into a new part checksum. A single
+            // update can span any number of parts, so continue with the rest of the data.
+            let end = start + usize::try_from(remaining)?;
+            self.ctx.update(Arc::from(&data[start..end]))?;
+            self.total_bytes += remaining;
             self.part_checksums
                 .push((self.current_part_size, self.ctx.finalize()?));
 
-            // Reset the current bytes and any remainder bytes.
-            self.current_bytes = u64::try_from(remainder.len())?;
-            self.remainder = Some(Arc::from(remainder));
-
-            // Reset the context for next chunk.
+            // Reset the context and byte position for the next part.
             self.ctx = self.ctx.reset();
-
-            // Update the part size.
+            self.current_bytes = 0;
             self.current_part_size = self.next_part_size()?;
-        } else {
-            // Otherwise update as usual, tracking the byte position.
-            self.update_with_remainder()?;
-
-            self.current_bytes += len;
-            self.total_bytes += len;
-
-            self.ctx.update(data)?;
-        }
 
-        Ok(())
-    }
-
-    /// Update the checksummer context with remainder bytes.
-    fn update_with_remainder(&mut self) -> Result<()> {
-        let remainder = self.remainder.take();
-        if let Some(remainder) = remainder {
-            self.ctx.update(remainder)?;
-            self.remainder = None;
+            start = end;
         }
-        Ok(())
     }
 
     /// Finalize the checksum.
     pub fn finalize(&mut self) -> Result<Vec<u8>> {
+        if let (true, PartMode::PartSizes(part_sizes)) = (self.exact_parts, &self.part_mode) {
+            let expected = part_sizes.iter().sum::<u64>();
+            if self.total_bytes != expected {
+                return Err(GenerateError(format!(
+                    "read {} bytes, but the exact part sizes cover {} bytes",
+                    self.total_bytes, expected
+                )));
+            }
+        }
+
         // Add the last part checksum.
-        if self.remainder.is_some() || self.current_bytes != 0 {
-            self.update_with_remainder()?;
+        if self.current_bytes != 0 {
             self.part_checksums
                 .push((self.current_bytes, self.ctx.finalize()?));
 
-            self.total_bytes += self.current_bytes;
-
             // Reset the context for merged chunks.
             self.ctx = self.ctx.reset();
         }
 
+        // An empty input is a single empty part rather than no parts at all.
+        if self.part_checksums.is_empty() {
+            self.part_checksums.push((0, self.ctx.finalize()?));
+            self.ctx = self.ctx.reset();
+        }
+
         self.update_part_sizes();
 
         // Then merge the part checksums and compute a single checksum.
         self.n_checksums = u64::try_from(self.part_checksums.len())?;
+        if self.strict_s3 {
+            self.check_s3_parts()?;
+        }
+        // The last part size is repeated until the end of the input, which can result in more
+        // parts than S3 allows.
+        if self.n_checksums > S3_MAX_PARTS {
+            warn!(
+                checksum = %self,
+                parts = self.n_checksums,
+                "the checksum has {} parts, which is more than the S3 limit of {} parts, so it cannot match an S3 object, use a larger part size",
+                self.n_checksums,
+                S3_MAX_PARTS
+            );
+        }
         let concat: Vec<u8> = self
             .part_checksums
             .iter()
@@ -336,6 +454,12 @@ impl AWSETagCtx {
         self.ctx.finalize()
     }
 
+    /// Whether the string looks like an AWS checksum specification, i.e. it uses an `-aws`
+    /// suffix or the `aws-etag` alias.
+    pub fn is_aws_spec(s: &str) -> bool {
+        s.contains("-aws") || s.starts_with("aws-etag")
+    }
+
     /// Parse into a `ChecksumCtx` for values that use endianness. Parses an -aws-<n> suffix,
     /// where n represents the part size to calculate.
     pub fn parse_part_size(s: &str) -> Result<(String, PartMode)> {
@@ -343,8 +467,8 @@ impl AWSETagCtx {
         let mut s = s.replace("aws-etag", "md5-aws");
 
         // If no part size has been specified default to the first preferred part size.
-        if s == "md5-aws" {
-            s = format!("md5-aws-{}b", PREFERRED_PART_SIZES[0]);
+        if let Some(algorithm) = s.strip_suffix("-aws") {
+            s = format!("{}-aws-{}b", algorithm, PREFERRED_PART_SIZES[0]);
         }
 
         let mut iter = s.rsplitn(2, "-aws-");
@@ -361,11 +485,48 @@ impl AWSETagCtx {
             }
 
             PartMode::PartNumber(part_number)
+        } else if let Some(part_number) = part_sizes
+            .strip_prefix('p')
+            .and_then(|part_number| part_number.strip_suffix("-aligned"))
+        {
+            // Aligned part numbers use a `p<part_number>-aligned` syntax.
+            match part_number.parse::<u64>() {
+                Ok(0) => return Err(ParseError("cannot use zero part number".to_string())),
+                Ok(part_number) => PartMode::AlignedPartNumber(part_number),
+                Err(err) => {
+                    return Err(ParseError(format!(
+                        "invalid part number `{}`: {}",
+                        part_number, err
+                    )))
+                }
+            }
+        } else if let Some(part_number) = part_sizes.strip_prefix("min") {
+            // Minimum part numbers use a `min<part_number>` syntax.
+            match part_number.parse::<u64>() {
+                Ok(0) => return Err(ParseError("cannot use zero part number".to_string())),
+                Ok(part_number) if part_number > S3_MAX_PARTS => {
+                    return Err(ParseError(format!(
+                        "cannot use a minimum of `{}` parts, which is more than the S3 limit of {} parts",
+                        part_number, S3_MAX_PARTS
+                    )))
+                }
+                Ok(part_number) => PartMode::MinPartNumber(part_number),
+                Err(err) => {
+                    return Err(ParseError(format!(
+                        "invalid part number `{}`: {}",
+                        part_number, err
+                    )))
+                }
+            }
         } else {
             // Allow multiple part sizes to be specified separated with a dash.
             let part_sizes = part_sizes
                 .split("-")
-                .map(|part| parse_size::parse_size(part).map_err(|err| ParseError(err.to_string())))
+                .map(|part| match parse_size::parse_size(part) {
+                    Ok(0) => Err(ParseError("cannot use zero part size".to_string())),
+                    Ok(part_size) => Ok(part_size),
+                    Err(err) => Err(ParseError(format!("invalid part size `{}`: {}", part, err))),
+                })
                 .collect::<Result<Vec<_>>>()?;
 
             PartMode::PartSizes(part_sizes)
@@ -387,6 +548,16 @@ impl AWSETagCtx {
         )
     }
 
+    /// Get the digest formatted as an S3 multipart `ETag`, i.e. quoted and followed by the number
+    /// of parts rather than the part sizes. This must be called after `finalize`.
+    pub fn digest_to_e_tag(&self, digest: &[u8]) -> String {
+        format!(
+            "\"{}-{}\"",
+            self.ctx.digest_to_string(digest),
+            self.n_checksums
+        )
+    }
+
     /// Get the next part size.
     pub fn next_part_size(&mut self) -> Result<u64> {
         match &self.part_mode {
@@ -403,11 +574,27 @@ impl AWSETagCtx {
 
                 Ok(*part_size)
             }
-            PartMode::PartNumber(part_number) => {
+            PartMode::MinPartNumber(part_number) => {
                 let file_size = self.file_size.ok_or_else(|| {
                     ParseError("cannot use part number syntax without file size".to_string())
                 })?;
-                Ok(Self::part_number_to_size(*part_number, file_size))
+                let part_size = Self::min_part_number_to_size(*part_number, file_size);
+
+                // Every part except the last must be at least the S3 minimum part size.
+                if *part_number > 1 && part_size < S3_MIN_PART_SIZE {
+                    return Err(ParseError(format!(
+                        "file size `{}` is too small to split into at least {} parts of the S3 minimum part size",
+                        file_size, part_number
+                    )));
+                }
+
+                Ok(part_size)
+            }
+            PartMode::PartNumber(part_number) | PartMode::AlignedPartNumber(part_number) => {
+                let file_size = self.file_size.ok_or_else(|| {
+                    ParseError("cannot use part number syntax without file size".to_string())
+                })?;
+                Ok(self.part_mode_to_size(*part_number, file_size))
             }
         }
     }
@@ -421,14 +608,16 @@ impl AWSETagCtx {
     /// Get the part sizes from the part mode.
     pub fn get_part_sizes(&self) -> Vec<u64> {
         match self.part_mode {
-            PartMode::PartNumber(part_number) => {
+            PartMode::PartNumber(part_number)
+            | PartMode::AlignedPartNumber(part_number)
+            | PartMode::MinPartNumber(part_number) => {
                 if self.file_size.is_none() && self.n_checksums == 0 {
                     panic!("cannot format part number without the file size and without finalizing the checksum");
                 }
 
                 // Get the file size if it exists or default to the total bytes.
                 let file_size = self.file_size.unwrap_or(self.total_bytes);
-                let part_size = Self::part_number_to_size(part_number, file_size);
+                let part_size = self.part_mode_to_size(part_number, file_size);
 
                 vec![part_size]
             }
@@ -446,9 +635,46 @@ impl AWSETagCtx {
             .join("-")
     }
 
-    /// Convert a part number to a part size using the file size.
+    /// Convert a part number to a part size using the file size. The part size is at least one
+    /// byte so that empty files still have a valid part size.
     pub fn part_number_to_size(part_number: u64, file_size: u64) -> u64 {
-        file_size.div_ceil(part_number)
+        file_size.div_ceil(part_number).max(1)
+    }
+
+    /// Convert a part number to a part size, rounding it up to the nearest MiB. The part size
+    /// is never larger than the file size, as a single part only covers the whole file.
+    pub fn aligned_part_number_to_size(part_number: u64, file_size: u64) -> u64 {
+        Self::part_number_to_size(part_number, file_size)
+            .next_multiple_of(MIB)
+            .min(file_size.max(1))
+    }
+
+    /// Convert a minimum part number to the largest part size that splits the file into at
+    /// least that many parts. The part size is at least one byte, and at most the S3 maximum
+    /// part size, which can only result in more parts. The file is never split into more than
+    /// the S3 maximum number of parts.
+    pub fn min_part_number_to_size(part_number: u64, file_size: u64) -> u64 {
+        let part_size = match part_number {
+            1 => file_size,
+            // A part size gives at least `n` parts if `n - 1` parts are smaller than the file.
+            _ => file_size.saturating_sub(1) / (part_number - 1),
+        };
+
+        part_size
+            .min(S3_MAX_PART_SIZE)
+            .max(file_size.div_ceil(S3_MAX_PARTS))
+            .max(1)
+    }
+
+    /// Convert a part number to a part size depending on the part mode.
+    fn part_mode_to_size(&self, part_number: u64, file_size: u64) -> u64 {
+        match self.part_mode {
+            PartMode::AlignedPartNumber(_) => {
+                Self::aligned_part_number_to_size(part_number, file_size)
+            }
+            PartMode::MinPartNumber(_) => Self::min_part_number_to_size(part_number, file_size),
+            _ => Self::part_number_to_size(part_number, file_size),
+        }
     }
 
     /// Set the file size.
@@ -456,6 +682,49 @@ impl AWSETagCtx {
         self.file_size = file_size;
     }
 
+    /// Error when finalizing if the parts could not belong to an S3 object, rather than only
+    /// warning about too many parts.
+    pub fn set_strict_s3(&mut self, strict_s3: bool) {
+        self.strict_s3 = strict_s3;
+    }
+
+    /// Check that the parts are within the S3 limits. Every part except the last must be at
+    /// least the minimum part size, and no part can be larger than the maximum part size.
+    fn check_s3_parts(&self) -> Result<()> {
+        if self.n_checksums > S3_MAX_PARTS {
+            return Err(GenerateError(format!(
+                "`{}` has {} parts, which is more than the S3 limit of {} parts, use a larger part size",
+                self, self.n_checksums, S3_MAX_PARTS
+            )));
+        }
+
+        let (_, parts) = self
+            .part_checksums
+            .split_last()
+            .expect("there is always at least one part");
+        let too_small = parts.iter().any(|(size, _)| *size < S3_MIN_PART_SIZE);
+        let too_large = self
+            .part_checksums
+            .iter()
+            .any(|(size, _)| *size > S3_MAX_PART_SIZE);
+        if too_small || too_large {
+            return Err(GenerateError(format!(
+                "`{}` has parts outside the S3 part size limits of {} to {} bytes",
+                self, S3_MIN_PART_SIZE, S3_MAX_PART_SIZE
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Whether this uses the part number syntax without knowing the file size.
+    pub fn is_missing_file_size(&self) -> bool {
+        matches!(
+            self.part_mode,
+            PartMode::PartNumber(_) | PartMode::AlignedPartNumber(_) | PartMode::MinPartNumber(_)
+        ) && self.file_size.is_none()
+    }
+
     /// Get the encoded part checksums and their part sizes.
     pub fn part_checksums(&self) -> Vec<(u64, String)> {
         self.part_checksums
@@ -464,6 +733,46 @@ impl AWSETagCtx {
             .collect()
     }
 
+    /// Get a checkpoint of the completed parts. Bytes in the current part are not included, so
+    /// the checkpoint is always at a part boundary, and can be resumed for any algorithm.
+    pub fn checkpoint(&self) -> CtxCheckpoint {
+        CtxCheckpoint::new(self.total_bytes - self.current_bytes)
+            .with_part_checksums(self.part_checksums())
+    }
+
+    /// Resume from a checkpoint of completed parts. The parts must match the part sizes
+    /// of this context.
+    pub fn resume(mut self, checkpoint: &CtxCheckpoint) -> Result<Self> {
+        let name = self.ctx.to_string();
+        let mismatch = || {
+            GenerateError(format!(
+                "the checkpoint does not match the part sizes of `{}`",
+                name
+            ))
+        };
+
+        let mut offset = 0;
+        for (part_size, checksum) in &checkpoint.part_checksums {
+            if *part_size != self.next_part_size()? {
+                return Err(mismatch());
+            }
+            let digest = hex::decode(checksum)
+                .map_err(|_| ParseError(format!("invalid part checksum: {}", checksum)))?;
+
+            self.part_checksums.push((*part_size, digest));
+            offset += part_size;
+        }
+        if offset != checkpoint.offset {
+            return Err(mismatch());
+        }
+
+        self.total_bytes = offset;
+        self.current_bytes = 0;
+        self.current_part_size = 0;
+
+        Ok(self)
+    }
+
     /// Does this context represent a valid and preferred multipart checksum. All multipart
     /// checksums that AWS can use are preferred except for those with different sized part sizes.
     /// Returns the preferred part size.
@@ -480,6 +789,31 @@ impl AWSETagCtx {
     pub fn ctx(self) -> StandardCtx {
         self.ctx
     }
+
+    /// Is the underlying standard context an MD5 checksum, i.e. is this an `ETag`.
+    pub fn is_md5(&self) -> bool {
+        self.ctx.is_md5()
+    }
+
+    /// Is the underlying checksum a CRC32C.
+    pub fn is_crc32c(&self) -> bool {
+        self.ctx.is_crc32c()
+    }
+
+    /// Are values of the underlying checksum in the format that S3 uses for additional checksums.
+    pub fn is_aws_additional_value(&self) -> bool {
+        self.ctx.is_aws_additional_value()
+    }
+
+    /// Get the output rank of the underlying standard context.
+    pub fn output_rank(&self) -> u8 {
+        self.ctx.output_rank()
+    }
+
+    /// Get the strength of the underlying standard context.
+    pub fn strength(&self) -> Strength {
+        self.ctx.strength()
+    }
 }
 
 impl FromStr for AWSETagCtx {
@@ -488,6 +822,11 @@ impl FromStr for AWSETagCtx {
     fn from_str(s: &str) -> Result<Self> {
         let (s, part_mode) = Self::parse_part_size(s)?;
         let ctx = StandardCtx::from_str(&s)?;
+        if ctx.is_null() {
+            return Err(ParseError(
+                "the null checksum cannot be used with AWS ETags".to_string(),
+            ));
+        }
 
         Ok(AWSETagCtx::new(ctx, part_mode, None))
     }
@@ -501,11 +840,22 @@ impl Display for AWSETagCtx {
 
 #[cfg(test)]
 pub(crate) mod test {
-    use crate::checksum::aws_etag::{AWSETagCtx, PartMode};
+    use crate::checksum::aws_etag::{
+        predict_e_tag, AWSETagCtx, PartMode, GIB, MIB, S3_MAX_PARTS, S3_MAX_PART_SIZE,
+        S3_MIN_PART_SIZE,
+    };
+    use crate::checksum::checksum_bytes;
     use crate::checksum::standard::StandardCtx;
     use crate::checksum::test::test_checksum;
+    use crate::checksum::Ctx;
+    use crate::test::TestFileBuilder;
     use anyhow::Result;
+    use proptest::prelude::*;
+    use proptest::test_runner::TestCaseError;
+    use std::cmp::Ordering;
     use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+    use tempfile::tempdir;
 
     pub(crate) fn expected_md5_1gib() -> &'static str {
         "6c434b38867bbd608ba2f06e92ed4e43-1073741824b"
@@ -523,6 +873,28 @@ pub(crate) mod test {
         "a9ed6c4b6aadf887f90a3d483b5c5b79bc08075af2a1718e3e15c63b9904ebf7-104857600b"
     }
 
+    #[test]
+    fn test_parse_errors() {
+        let err = "md5-aws-notasize".parse::<Ctx>().unwrap_err().to_string();
+        assert!(err.contains("invalid part size `notasize`"), "{err}");
+
+        let err = "md5-aws-0".parse::<Ctx>().unwrap_err().to_string();
+        assert!(err.contains("cannot use zero part number"), "{err}");
+
+        let err = "md5-aws-8mib-0b".parse::<Ctx>().unwrap_err().to_string();
+        assert!(err.contains("cannot use zero part size"), "{err}");
+
+        let err = "md5-aws-p0-aligned".parse::<Ctx>().unwrap_err().to_string();
+        assert!(err.contains("cannot use zero part number"), "{err}");
+
+        let err = "md5-aws-px-aligned".parse::<Ctx>().unwrap_err().to_string();
+        assert!(err.contains("invalid part number `x`"), "{err}");
+
+        assert!(AWSETagCtx::is_aws_spec("aws-etag"));
+        assert!(AWSETagCtx::is_aws_spec("sha1-aws"));
+        assert!(!AWSETagCtx::is_aws_spec("sha256"));
+    }
+
     #[test]
     fn test_ordering() -> Result<()> {
         assert!(AWSETagCtx::from_str("md5-aws-8mib")? < AWSETagCtx::from_str("md5-aws-5mib")?);
@@ -606,6 +978,274 @@ pub(crate) mod test {
         Ok(())
     }
 
+    /// Compute the expected ETag by checksumming each part separately.
+    fn expected_etag(data: &[u8], part_sizes: &[usize]) -> Result<String> {
+        let mut parts = vec![];
+        let mut rest = data;
+        let mut part_sizes = part_sizes.iter().copied();
+        let mut part_size = 0;
+        while !rest.is_empty() {
+            part_size = part_sizes.next().unwrap_or(part_size);
+            let (part, next) = rest.split_at(part_size.min(rest.len()));
+            parts.push(hex::decode(checksum_bytes("md5", part)?)?);
+            rest = next;
+        }
+
+        Ok(format!(
+            "{}-{}",
+            checksum_bytes("md5", &parts.concat())?,
+            parts.len()
+        ))
+    }
+
+    /// Checksum the data by updating with the chunk sizes, repeating the last chunk size.
+    fn checksum_chunks(
+        checksum: &str,
+        data: &[u8],
+        chunk_sizes: &[usize],
+        file_size: Option<u64>,
+    ) -> Result<(String, u64)> {
+        let mut ctx = AWSETagCtx::from_str(checksum)?;
+        ctx.set_file_size(file_size);
+
+        let mut rest = data;
+        let mut chunk_sizes = chunk_sizes.iter().copied();
+        let mut chunk_size = 0;
+        while !rest.is_empty() {
+            chunk_size = chunk_sizes.next().unwrap_or(chunk_size);
+            let (chunk, next) = rest.split_at(chunk_size.min(rest.len()));
+            ctx.update(Arc::from(chunk))?;
+            rest = next;
+        }
+
+        let digest = ctx.finalize()?;
+        let checksum = ctx.digest_to_string(&digest);
+        let (checksum, _) = checksum.split_once('-').unwrap();
+        Ok((checksum.to_string(), ctx.n_checksums))
+    }
+
+    #[test]
+    fn test_aws_etag_part_boundaries() -> Result<()> {
+        let data: Vec<u8> = (0..100u8).collect();
+
+        for (checksum, part_sizes) in [
+            ("md5-aws-10b", vec![10]),
+            ("md5-aws-7b", vec![7]),
+            ("md5-aws-10b-30b", vec![10, 30]),
+            ("md5-aws-1b-100b", vec![1, 100]),
+        ] {
+            let expected = expected_etag(&data, &part_sizes)?;
+
+            for chunk_sizes in [
+                // Chunks that align exactly with part boundaries.
+                vec![10],
+                vec![10, 30],
+                vec![5],
+                vec![100],
+                // Chunks that span part boundaries.
+                vec![3],
+                vec![9, 2],
+                vec![11],
+                vec![25],
+                vec![1, 99],
+                // Chunks that span multiple parts.
+                vec![45],
+                vec![64],
+            ] {
+                for file_size in [Some(100), None] {
+                    let (sum, n_checksums) =
+                        checksum_chunks(checksum, &data, &chunk_sizes, file_size)?;
+
+                    assert_eq!(
+                        format!("{sum}-{n_checksums}"),
+                        expected,
+                        "{checksum} with chunks {chunk_sizes:?} and file size {file_size:?}"
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_predict_e_tag() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file");
+        let data: Vec<u8> = (0..=255u8).cycle().take(11 * MIB as usize).collect();
+        tokio::fs::write(&file, &data).await?;
+
+        let expected = checksum_bytes("md5-aws-5mib", &data)?;
+        let (sum, _) = expected.split_once('-').unwrap();
+        assert_eq!(
+            predict_e_tag(&file, Some(5 * MIB)).await?,
+            format!("\"{sum}-3\"")
+        );
+
+        // A single part upload still reports the number of parts.
+        let expected = checksum_bytes("md5-aws-16mib", &data)?;
+        let (sum, _) = expected.split_once('-').unwrap();
+        assert_eq!(
+            predict_e_tag(&file, Some(16 * MIB)).await?,
+            format!("\"{sum}-1\"")
+        );
+
+        // A single upload is the plain MD5, which differs from a single part multipart upload.
+        assert_eq!(
+            predict_e_tag(&file, None).await?,
+            format!("\"{}\"", checksum_bytes("md5", &data)?)
+        );
+
+        // Part sizes that S3 does not accept for the file.
+        for part_size in [0, MIB, 6 * GIB] {
+            assert!(predict_e_tag(&file, Some(part_size)).await.is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_exact_parts() -> Result<()> {
+        let data: Vec<u8> = (0..35u8).collect();
+        let part_sizes = vec![10, 5, 10, 5, 5];
+
+        let mut ctx = AWSETagCtx::from_exact_parts(StandardCtx::md5(), part_sizes.clone())?;
+        for chunk in data.chunks(7) {
+            ctx.update(Arc::from(chunk))?;
+        }
+        let digest = ctx.finalize()?;
+
+        // The parts are kept as they are, where `new` would remove the repeated part sizes.
+        assert_eq!(
+            format!("{}-{}", hex::encode(digest), ctx.n_checksums),
+            expected_etag(&data, &[10, 5, 10, 5, 5])?
+        );
+        assert_eq!(ctx.get_part_sizes(), part_sizes);
+        let mut normalized = AWSETagCtx::new(
+            StandardCtx::md5(),
+            PartMode::PartSizes(part_sizes.clone()),
+            Some(35),
+        );
+        normalized.update_part_sizes();
+        assert_ne!(normalized.get_part_sizes(), part_sizes);
+
+        // The data must cover exactly the parts.
+        let mut ctx = AWSETagCtx::from_exact_parts(StandardCtx::md5(), part_sizes)?;
+        ctx.update(Arc::from(&data[..34]))?;
+        assert!(ctx.finalize().is_err());
+
+        assert!(AWSETagCtx::from_exact_parts(StandardCtx::md5(), vec![]).is_err());
+        assert!(AWSETagCtx::from_exact_parts(StandardCtx::md5(), vec![10, 0]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_aws_etag_total_bytes() -> Result<()> {
+        // Without a file size, the final part sizes are derived from the total bytes read.
+        let data: Vec<u8> = (0..25u8).collect();
+        let mut ctx = AWSETagCtx::from_str("md5-aws-10b-20b")?;
+        for chunk in data.chunks(4) {
+            ctx.update(Arc::from(chunk))?;
+        }
+        ctx.finalize()?;
+
+        assert_eq!(ctx.total_bytes, 25);
+        assert_eq!(ctx.format_parts(), "10b-15b");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_aws_etag_more_than_max_parts() -> Result<()> {
+        // More parts than S3 allows only warns, as the checksum is still well-defined.
+        let logs = Arc::new(Mutex::new(vec![]));
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || LogWriter(writer.clone()))
+            .finish();
+        let mut ctx = AWSETagCtx::from_str("md5-aws-1b")?;
+        tracing::subscriber::with_default(subscriber, || {
+            ctx.update(Arc::from(vec![0; S3_MAX_PARTS as usize + 1]))?;
+            ctx.finalize()
+        })?;
+
+        assert_eq!(ctx.n_checksums, S3_MAX_PARTS + 1);
+        let logs = String::from_utf8(logs.lock().unwrap().clone())?;
+        assert!(logs.contains("WARN"), "{logs}");
+        assert!(logs.contains("10001 parts"), "{logs}");
+
+        // Strict mode errors instead.
+        let mut ctx = AWSETagCtx::from_str("md5-aws-1b")?;
+        ctx.set_strict_s3(true);
+        ctx.update(Arc::from(vec![0; S3_MAX_PARTS as usize + 1]))?;
+        let err = ctx.finalize().unwrap_err().to_string();
+        assert!(err.contains("10001 parts"), "{err}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_aws_etag_strict_part_sizes() -> Result<()> {
+        let finalize = |checksum: &str, size: usize, strict_s3| {
+            let mut ctx = AWSETagCtx::from_str(checksum)?;
+            ctx.set_strict_s3(strict_s3);
+            ctx.update(Arc::from(vec![0; size]))?;
+            ctx.finalize()
+        };
+
+        // Parts smaller than S3 allows are only an error in strict mode.
+        assert!(finalize("md5-aws-1kib", 2048, false).is_ok());
+        assert!(finalize("md5-aws-1kib", 2048, true).is_err());
+        // The last part can be smaller than the minimum part size.
+        assert!(finalize("md5-aws-1kib", 1024, true).is_ok());
+        assert!(finalize("md5-aws-5mib", 5 * MIB as usize + 1, true).is_ok());
+
+        Ok(())
+    }
+
+    /// Collects log output for assertions.
+    struct LogWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_aws_etag_generated_part_boundaries() -> Result<()> {
+        let tmp = tempdir()?;
+        let n_parts = |size: u64, part_size: &str| {
+            let data = std::fs::read(
+                TestFileBuilder::default()
+                    .with_directory(tmp.path().to_path_buf())
+                    .with_size(size)
+                    .generate()?,
+            )?;
+            assert_eq!(data.len() as u64, size);
+
+            let mut ctx = AWSETagCtx::from_str(&format!("md5-aws-{part_size}"))?;
+            ctx.update(Arc::from(data))?;
+            ctx.finalize()?;
+            Ok::<_, anyhow::Error>(ctx.n_checksums)
+        };
+
+        // One byte over the part size needs another part.
+        assert_eq!(n_parts(5 * MIB, "5mib")?, 1);
+        assert_eq!(n_parts(5 * MIB + 1, "5mib")?, 2);
+
+        // Exactly the maximum number of parts, and one byte over it.
+        assert_eq!(n_parts(S3_MAX_PARTS * 1024, "1kib")?, S3_MAX_PARTS);
+        assert_eq!(n_parts(S3_MAX_PARTS * 1024 + 1, "1kib")?, S3_MAX_PARTS + 1);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_aws_etag_single_part() -> Result<()> {
         test_checksum("md5-aws-1gib", expected_md5_1gib()).await?;
@@ -633,6 +1273,114 @@ pub(crate) mod test {
         test_checksum("aws-etag-10", expected_md5_10()).await
     }
 
+    #[test]
+    fn test_aligned_part_number() -> Result<()> {
+        let aligned = AWSETagCtx::aligned_part_number_to_size;
+
+        // Exactly on a MiB boundary is not rounded.
+        assert_eq!(aligned(100, 100 * MIB), MIB);
+        // One byte over rounds up to the next MiB.
+        assert_eq!(aligned(100, 100 * MIB + 1), 2 * MIB);
+        assert_eq!(aligned(100, 10 * GIB), 103 * MIB);
+        // Less than a MiB per part still uses a whole MiB.
+        assert_eq!(aligned(100, 100 * MIB - 1), MIB);
+        assert_eq!(aligned(3, 5 * MIB), 2 * MIB);
+        // The part size does not exceed small or empty files.
+        assert_eq!(aligned(100, 50), 50);
+        assert_eq!(aligned(1, MIB + 1), MIB + 1);
+        assert_eq!(aligned(100, 0), 1);
+
+        // The concrete part size is recorded, so it can be parsed as a part size again.
+        let mut ctx = AWSETagCtx::from_str("md5-aws-p100-aligned")?;
+        assert_eq!(ctx.part_mode, PartMode::AlignedPartNumber(100));
+        ctx.set_file_size(Some(100 * MIB + 1));
+        assert_eq!(ctx.to_string(), "md5-aws-2097152b");
+        assert_eq!(ctx, AWSETagCtx::from_str("md5-aws-2mib")?);
+
+        // Rounding can result in fewer parts than the part number.
+        let data = vec![1; usize::try_from(5 * MIB + 1)?];
+        let mut ctx = AWSETagCtx::from_str("md5-aws-p4-aligned")?;
+        ctx.set_file_size(Some(u64::try_from(data.len())?));
+        ctx.update(Arc::from(data.as_slice()))?;
+        let digest = ctx.finalize()?;
+        assert_eq!(
+            ctx.digest_to_e_tag(&digest),
+            format!(
+                "\"{}\"",
+                expected_etag(&data, &[usize::try_from(2 * MIB)?])?
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_part_number() -> Result<()> {
+        let min = AWSETagCtx::min_part_number_to_size;
+
+        // The largest part size that still results in at least the number of parts.
+        assert_eq!(min(3, 10), 4);
+        assert_eq!(min(3, 11), 5);
+        assert_eq!(min(2, 100 * MIB), 100 * MIB - 1);
+        assert_eq!(min(1, 100 * MIB), 100 * MIB);
+        for (part_number, file_size) in [(3, 10), (3, 11), (7, 1000), (2, 2)] {
+            let part_size = min(part_number, file_size);
+            assert!(file_size.div_ceil(part_size) >= part_number);
+            assert!(file_size.div_ceil(part_size + 1) < part_number);
+        }
+        // The part size does not exceed the S3 maximum, which only adds parts.
+        assert_eq!(min(2, 20 * GIB), S3_MAX_PART_SIZE);
+        assert_eq!(min(3, 0), 1);
+        // The part size never results in more than the S3 maximum number of parts.
+        for file_size in [
+            S3_MAX_PARTS + 1,
+            S3_MAX_PARTS * S3_MIN_PART_SIZE + 1,
+            S3_MAX_PARTS * S3_MAX_PART_SIZE / 2 + 1,
+        ] {
+            for part_number in [S3_MAX_PARTS - 1, S3_MAX_PARTS] {
+                let part_size = min(part_number, file_size);
+                assert!(file_size.div_ceil(part_size) <= S3_MAX_PARTS);
+            }
+        }
+        assert_eq!(min(S3_MAX_PARTS, S3_MAX_PARTS + 1), 2);
+
+        // The concrete part size is recorded, so it can be parsed as a part size again.
+        let mut ctx = AWSETagCtx::from_str("md5-aws-min2")?;
+        assert_eq!(ctx.part_mode, PartMode::MinPartNumber(2));
+        ctx.set_file_size(Some(10 * MIB + 1));
+        assert_eq!(ctx.to_string(), "md5-aws-10485760b");
+        assert_eq!(ctx, AWSETagCtx::from_str("md5-aws-10mib")?);
+
+        let data = vec![1; usize::try_from(5 * MIB + 1)?];
+        let mut ctx = AWSETagCtx::from_str("md5-aws-min2")?;
+        ctx.set_file_size(Some(u64::try_from(data.len())?));
+        ctx.update(Arc::from(data.as_slice()))?;
+        let digest = ctx.finalize()?;
+        assert_eq!(
+            ctx.digest_to_e_tag(&digest),
+            format!(
+                "\"{}\"",
+                expected_etag(&data, &[usize::try_from(5 * MIB)?])?
+            )
+        );
+
+        // The file is too small for the parts to be at least the S3 minimum part size.
+        let mut ctx = AWSETagCtx::from_str("md5-aws-min3")?;
+        ctx.set_file_size(Some(u64::try_from(data.len())?));
+        let err = ctx
+            .update(Arc::from(data.as_slice()))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("too small"), "{err}");
+
+        let err = "md5-aws-min0".parse::<Ctx>().unwrap_err().to_string();
+        assert!(err.contains("cannot use zero part number"), "{err}");
+        let err = "md5-aws-min10001".parse::<Ctx>().unwrap_err().to_string();
+        assert!(err.contains("S3 limit"), "{err}");
+
+        Ok(())
+    }
+
     fn assert_update_part_sizes(part_sizes: Vec<u64>, file_size: u64, expected: Vec<u64>) {
         let mut ctx = AWSETagCtx::new(
             StandardCtx::md5(),
@@ -642,4 +1390,99 @@ pub(crate) mod test {
         ctx.update_part_sizes();
         assert_eq!(ctx.part_mode, PartMode::PartSizes(expected));
     }
+
+    fn standard_spec() -> impl Strategy<Value = &'static str> {
+        prop::sample::select(vec![
+            "md5",
+            "sha1",
+            "sha256",
+            "crc32",
+            "crc32-le",
+            "crc32-be",
+            "crc32c",
+            "crc32c-le",
+            "crc32c-be",
+            "crc64nvme",
+            "crc64nvme-le",
+            "crc64nvme-be",
+        ])
+    }
+
+    fn part_size_spec() -> impl Strategy<Value = String> {
+        let unit = prop::sample::select(vec!["b", "kib", "mib"]);
+        prop::collection::vec((1..64u64, unit), 1..4).prop_map(|sizes| {
+            sizes
+                .into_iter()
+                .map(|(size, unit)| format!("{}{}", size, unit))
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+    }
+
+    fn aws_spec() -> impl Strategy<Value = (String, Option<u64>)> {
+        prop_oneof![
+            (standard_spec(), part_size_spec())
+                .prop_map(|(ctx, parts)| (format!("{}-aws-{}", ctx, parts), None)),
+            (standard_spec(), 1..10000u64, 0..u64::from(u32::MAX))
+                .prop_map(|(ctx, n, size)| (format!("{}-aws-{}", ctx, n), Some(size))),
+            (standard_spec(), 1..10000u64, 0..u64::from(u32::MAX))
+                .prop_map(|(ctx, n, size)| (format!("{}-aws-p{}-aligned", ctx, n), Some(size))),
+            standard_spec().prop_map(|ctx| (format!("{}-aws", ctx), None)),
+            prop::option::of(part_size_spec()).prop_map(|parts| match parts {
+                Some(parts) => (format!("aws-etag-{}", parts), None),
+                None => ("aws-etag".to_string(), None),
+            }),
+        ]
+    }
+
+    fn assert_round_trip(ctx: &Ctx) -> std::result::Result<(), TestCaseError> {
+        let formatted = ctx.to_string();
+        let parsed = Ctx::from_str(&formatted)
+            .map_err(|err| TestCaseError::fail(format!("`{}`: {}", formatted, err)))?;
+
+        prop_assert_eq!(&parsed, ctx);
+        prop_assert_eq!(parsed.cmp(ctx), Ordering::Equal);
+        prop_assert_eq!(parsed.to_string(), formatted);
+
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_standard(spec in standard_spec()) {
+            assert_round_trip(&Ctx::from_str(spec).unwrap())?;
+        }
+
+        #[test]
+        fn round_trip_aws((spec, file_size) in aws_spec()) {
+            let mut ctx = Ctx::from_str(&spec).unwrap();
+            ctx.set_file_size(file_size);
+
+            assert_round_trip(&ctx)?;
+        }
+
+        #[test]
+        fn ordering_consistent_with_eq(a in standard_spec(), b in standard_spec()) {
+            let (a, b) = (Ctx::from_str(a).unwrap(), Ctx::from_str(b).unwrap());
+            prop_assert_eq!(a == b, a.cmp(&b) == Ordering::Equal);
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+        }
+
+        #[test]
+        fn round_trip_finalized(
+            spec in part_size_spec(),
+            data in prop::collection::vec(any::<u8>(), 0..512),
+        ) {
+            // Use byte part sizes so that the data spans multiple parts.
+            let spec = format!("md5-aws-{}", spec.replace("mib", "b").replace("kib", "b"));
+            let mut ctx = Ctx::from_str(&spec).unwrap();
+            let digest = ctx.checksum_slice(&data).unwrap();
+
+            // The part sizes are updated with the file size, so the finalized context should
+            // produce the same checksum when it is parsed again.
+            assert_round_trip(&ctx)?;
+            let reparsed = Ctx::from_str(&ctx.to_string()).unwrap().checksum_slice(&data).unwrap();
+            prop_assert_eq!(reparsed, digest);
+        }
+    }
 }
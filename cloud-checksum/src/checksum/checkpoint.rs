@@ -0,0 +1,287 @@
+//! Checkpoints of checksum progress, so that generating checksums for large objects can be
+//! resumed later.
+//!
+
+use crate::checksum::file::ByteRange;
+use crate::checksum::Ctx;
+use crate::error::Error::GenerateError;
+use crate::error::Result;
+use futures_util::{pin_mut, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::Mutex;
+
+/// The default number of bytes processed by a checksum between writing checkpoints.
+pub const DEFAULT_CHECKPOINT_INTERVAL: u64 = 64 * 1024 * 1024;
+
+/// The progress of a single checksum.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct CtxCheckpoint {
+    /// The number of bytes of the input that the checksum has processed.
+    pub(crate) offset: u64,
+    /// The state of a checksum that can be resumed within the input, e.g. for CRC32.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) state: Option<u32>,
+    /// The part sizes and encoded checksums of completed AWS parts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) part_checksums: Vec<(u64, String)>,
+}
+
+impl CtxCheckpoint {
+    /// Create a checkpoint at an offset.
+    pub fn new(offset: u64) -> Self {
+        Self {
+            offset,
+            ..Default::default()
+        }
+    }
+
+    /// Set the resumable state of the checksum.
+    pub fn set_state(mut self, state: Option<u32>) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Set the completed part checksums.
+    pub fn with_part_checksums(mut self, part_checksums: Vec<(u64, String)>) -> Self {
+        self.part_checksums = part_checksums;
+        self
+    }
+
+    /// Get the offset.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+/// The progress of all checksums being generated for an input.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Checkpoint {
+    /// The size of the input, used to avoid resuming with a different input.
+    pub(crate) size: Option<u64>,
+    /// The byte range of the input that checksums are generated for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) range: Option<ByteRange>,
+    /// The progress of each checksum.
+    pub(crate) checksums: BTreeMap<Ctx, CtxCheckpoint>,
+}
+
+impl Checkpoint {
+    /// Create a new checkpoint.
+    pub fn new(size: Option<u64>, range: Option<ByteRange>) -> Self {
+        Self {
+            size,
+            range,
+            checksums: Default::default(),
+        }
+    }
+
+    /// Read a checkpoint, returning `None` if it does not exist.
+    pub async fn read(path: &Path) -> Result<Option<Self>> {
+        match fs::read(path).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Write the checkpoint. This writes to a temporary file first so that an interrupted
+    /// write never leaves a partial checkpoint behind.
+    pub async fn write(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, serde_json::to_vec(self)?).await?;
+        fs::rename(&tmp, path).await?;
+
+        Ok(())
+    }
+
+    /// The offset to start reading the input from so that all the checksums can be resumed.
+    /// Checksums without any progress start from the beginning.
+    pub fn resume_offset<'a>(&self, ctxs: impl IntoIterator<Item = &'a Ctx>) -> u64 {
+        ctxs.into_iter()
+            .map(|ctx| {
+                self.checksums
+                    .get(ctx)
+                    .map(|c| c.offset)
+                    .unwrap_or_default()
+            })
+            .min()
+            .unwrap_or_default()
+    }
+}
+
+/// Resumes checksums from a shared checkpoint and periodically persists their progress.
+#[derive(Debug, Clone)]
+pub struct Checkpointer {
+    path: PathBuf,
+    checkpoint: Arc<Mutex<Checkpoint>>,
+    start: u64,
+    interval: u64,
+}
+
+impl Checkpointer {
+    /// Create a checkpointer for an input that is read from the `start` offset.
+    pub fn new(path: PathBuf, checkpoint: Checkpoint, start: u64) -> Self {
+        Self {
+            path,
+            checkpoint: Arc::new(Mutex::new(checkpoint)),
+            start,
+            interval: DEFAULT_CHECKPOINT_INTERVAL,
+        }
+    }
+
+    /// Set the number of bytes processed by a checksum between writing checkpoints.
+    pub fn with_interval(mut self, interval: u64) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The offset that the input is read from.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Resume the checksum if it has a checkpoint, and generate it from a stream of the input
+    /// that begins at the start offset. Bytes that were already processed are skipped.
+    pub async fn generate(
+        &self,
+        ctx: Ctx,
+        stream: impl Stream<Item = Result<Arc<[u8]>>>,
+    ) -> Result<(Ctx, Vec<u8>)> {
+        let resumed = self.checkpoint.lock().await.checksums.get(&ctx).cloned();
+        let (mut ctx, mut offset) = match resumed {
+            Some(resumed) => (ctx.resume(&resumed)?, resumed.offset),
+            None => (ctx, 0),
+        };
+
+        let mut skip = usize::try_from(offset.checked_sub(self.start).ok_or_else(|| {
+            GenerateError(format!(
+                "the checkpoint for `{}` is before the resume offset",
+                ctx
+            ))
+        })?)?;
+        let mut written = offset;
+
+        pin_mut!(stream);
+        while let Some(chunk) = stream.next().await {
+            let mut chunk = chunk?;
+            if skip >= chunk.len() {
+                skip -= chunk.len();
+                continue;
+            } else if skip > 0 {
+                chunk = Arc::from(&chunk[skip..]);
+                skip = 0;
+            }
+
+            offset += u64::try_from(chunk.len())?;
+            ctx.update(chunk)?;
+
+            if offset - written >= self.interval {
+                self.write(&ctx, offset).await?;
+                written = offset;
+            }
+        }
+
+        let digest = ctx.finalize()?;
+        Ok((ctx, digest))
+    }
+
+    /// Record the progress of a checksum and persist the checkpoint.
+    async fn write(&self, ctx: &Ctx, offset: u64) -> Result<()> {
+        let mut checkpoint = self.checkpoint.lock().await;
+        checkpoint
+            .checksums
+            .insert(ctx.clone(), ctx.checkpoint(offset)?);
+        checkpoint.write(&self.path).await
+    }
+
+    /// Remove the checkpoint once all checksums have been generated.
+    pub async fn remove(&self) -> Result<()> {
+        match fs::remove_file(&self.path).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::checksum::checksum_bytes;
+    use anyhow::Result;
+    use futures_util::stream;
+    use tempfile::tempdir;
+
+    fn chunks(data: &[u8], size: usize) -> impl Stream<Item = crate::error::Result<Arc<[u8]>>> {
+        stream::iter(
+            data.chunks(size)
+                .map(|chunk| Ok(Arc::from(chunk)))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[tokio::test]
+    async fn resume_checksums() -> Result<()> {
+        let tmp = tempdir()?;
+        let path = tmp.path().join("checkpoint.json");
+        let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+
+        for checksum in ["crc32", "crc32c-le", "md5-aws-100b", "sha256-aws-64b-128b"] {
+            let mut ctx: Ctx = checksum.parse()?;
+            ctx.set_file_size(Some(1000));
+
+            // Write checkpoints every 150 bytes, but stop partway through the input.
+            let checkpointer =
+                Checkpointer::new(path.clone(), Checkpoint::new(Some(1000), None), 0)
+                    .with_interval(150);
+            checkpointer
+                .generate(ctx.clone(), chunks(&data[..700], 50))
+                .await?;
+
+            let checkpoint = Checkpoint::read(&path).await?.unwrap();
+            let start = checkpoint.resume_offset([&ctx]);
+            assert!(start > 0 && start < 700, "{checksum} resumed at {start}");
+
+            // Resume from the checkpoint, reading the rest of the input.
+            let checkpointer = Checkpointer::new(path.clone(), checkpoint, start);
+            let (ctx, digest) = checkpointer
+                .generate(ctx, chunks(&data[usize::try_from(start)?..], 64))
+                .await?;
+
+            assert_eq!(
+                ctx.digest_to_string(&digest),
+                checksum_bytes(checksum, &data)?,
+                "{checksum}"
+            );
+
+            checkpointer.remove().await?;
+            assert!(Checkpoint::read(&path).await?.is_none());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn not_resumable() -> Result<()> {
+        for checksum in ["md5", "sha1", "sha256", "crc64nvme"] {
+            let ctx: Ctx = checksum.parse()?;
+            let err = ctx.checkpoint(0).unwrap_err().to_string();
+            assert!(err.contains("is not resumable"), "{err}");
+        }
+
+        // AWS checksums are always resumable at part boundaries.
+        let mut ctx: Ctx = "sha256-aws-10b".parse()?;
+        ctx.update(Arc::from(&[0; 25][..]))?;
+        assert_eq!(ctx.checkpoint(25)?.offset, 20);
+
+        Ok(())
+    }
+}
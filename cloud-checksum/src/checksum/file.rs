@@ -2,15 +2,34 @@
 //!
 
 use crate::checksum::Ctx;
-use crate::error::Error::SumsFileError;
+use crate::cli::Strength;
+use crate::error::Error::{ParseError, SumsFileError};
 use crate::error::{Error, Result};
+use crate::io::sums::decompress::Decompress;
 use crate::io::sums::{ObjectSums, ObjectSumsBuilder};
-use serde::{Deserialize, Serialize};
-use serde_json::{from_slice, to_string};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use clap::ValueEnum;
+use humantime::{format_rfc3339_seconds, parse_rfc3339};
+use schemars::gen::SchemaSettings;
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+use serde::de::Error as SerdeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{from_slice, to_string, to_string_pretty, to_writer, to_writer_pretty};
+use sha2::{Digest, Sha256};
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Debug, Formatter};
 use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Write};
+use std::path::{absolute, Component, PathBuf};
+use std::result;
+use std::time::SystemTime;
+use tokio::io::AsyncWrite;
+use tokio::task::spawn_blocking;
+use tokio_util::io::SyncIoBridge;
+use tracing::warn;
 
 /// The current version of the output file.
 pub const OUTPUT_FILE_VERSION: &str = "1";
@@ -18,6 +37,153 @@ pub const OUTPUT_FILE_VERSION: &str = "1";
 /// The file ending of a sums file.
 pub const SUMS_FILE_ENDING: &str = ".sums";
 
+/// The file ending of a binary sums file.
+pub const SUMS_BIN_FILE_ENDING: &str = ".sums.bin";
+
+/// The encoding used when reading and writing sums files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SumsFormat {
+    /// JSON encoded sums files with a `.sums` ending.
+    #[default]
+    Json,
+    /// Indented and multi-line JSON encoded sums files with a `.sums` ending, which diff better
+    /// when committed to version control.
+    PrettyJson,
+    /// Compact MessagePack encoded sums files with a `.sums.bin` ending.
+    Msgpack,
+}
+
+impl SumsFormat {
+    /// Detect the format from the ending of a sums file name, if it has one.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.ends_with(SUMS_BIN_FILE_ENDING) {
+            Some(Self::Msgpack)
+        } else if name.ends_with(SUMS_FILE_ENDING) {
+            Some(Self::Json)
+        } else {
+            None
+        }
+    }
+
+    /// The file ending for this format.
+    pub fn ending(&self) -> &'static str {
+        match self {
+            Self::Json | Self::PrettyJson => SUMS_FILE_ENDING,
+            Self::Msgpack => SUMS_BIN_FILE_ENDING,
+        }
+    }
+
+    /// Format a sums file with the ending of this format.
+    pub fn format_sums_file(&self, name: &str) -> String {
+        format!("{}{}", SumsFile::format_target_file(name), self.ending())
+    }
+
+    /// Format a sums file with the ending of this format. If a destination is set, the sums
+    /// file is placed under the destination rather than next to the target file, keeping the
+    /// relative path of the target, e.g. `dir/file` with a destination of `sums` becomes
+    /// `sums/dir/file.sums`.
+    pub fn format_sums_file_at(&self, name: &str, destination: Option<&str>) -> Result<String> {
+        let sums_file = self.format_sums_file(name);
+        match destination {
+            Some(destination) => join_under(destination, &sums_file),
+            None => Ok(sums_file),
+        }
+    }
+
+    /// Format a sums file with the ending of this format under an output directory which
+    /// mirrors the absolute path of the target file, e.g. `/data/dir/file` with an output
+    /// directory of `sums` becomes `sums/data/dir/file.sums`. Relative paths are resolved
+    /// against the current directory, so each file maps to exactly one sums file and the
+    /// target can be recovered by removing the output directory from the sums file path.
+    pub fn format_sums_file_under(&self, name: &str, output_dir: &str) -> Result<String> {
+        let sums_file = absolute(self.format_sums_file(name))?;
+
+        let mut components = vec![];
+        for component in sums_file.components() {
+            match component {
+                Component::Normal(component) => components.push(component),
+                Component::ParentDir => {
+                    components.pop();
+                }
+                Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+            }
+        }
+
+        Ok(components
+            .into_iter()
+            .fold(PathBuf::from(output_dir), |path, component| {
+                path.join(component)
+            })
+            .to_string_lossy()
+            .to_string())
+    }
+
+    /// Encode the sums file using this format.
+    pub fn to_vec(&self, sums_file: &SumsFile) -> Result<Vec<u8>> {
+        let mut vec = vec![];
+        self.write(sums_file, &mut vec)?;
+        Ok(vec)
+    }
+
+    /// Encode the sums file directly to a writer using this format.
+    pub fn write<W: Write>(&self, sums_file: &SumsFile, mut writer: W) -> Result<()> {
+        match self {
+            Self::Json => to_writer(writer, sums_file)?,
+            Self::PrettyJson => to_writer_pretty(writer, sums_file)?,
+            Self::Msgpack => rmp_serde::encode::write(&mut writer, sums_file)?,
+        }
+        Ok(())
+    }
+
+    /// Decode a sums file using this format.
+    pub fn from_slice(&self, slice: &[u8]) -> Result<SumsFile> {
+        match self {
+            Self::Json | Self::PrettyJson => slice.try_into(),
+            Self::Msgpack => {
+                let SumsFileVersion { version } = rmp_serde::from_slice(slice)?;
+                SumsFile::check_version(&version)?;
+                Ok(rmp_serde::from_slice(slice)?)
+            }
+        }
+    }
+}
+
+/// Join a path under a directory, keeping the path relative to the directory. Leading, empty
+/// and `.` segments are removed, and an error is returned if the path would escape the
+/// directory using `..`.
+pub(crate) fn join_under(directory: &str, path: &str) -> Result<String> {
+    let mut segments = vec![];
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop().ok_or_else(|| {
+                    ParseError(format!("`{}` cannot be placed under `{}`", path, directory))
+                })?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    let relative = segments.join("/");
+    Ok(match directory.trim_end_matches('/') {
+        "" => relative,
+        directory => format!("{}/{}", directory, relative),
+    })
+}
+
+/// Where sums files are stored for S3 objects. Local files always use sums files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SumsStorage {
+    /// A sums file object next to the object, or at the sums destination.
+    #[default]
+    Object,
+    /// Tags on the object itself, for when sums file objects cannot be created. The sums file
+    /// must fit within the S3 limit of 10 tags with 256 characters each.
+    Tags,
+}
+
 /// Sums file state to enable writing and reading.
 pub struct State {
     pub(crate) name: String,
@@ -83,20 +249,123 @@ impl Clone for State {
     }
 }
 
+/// Metadata of the target file that can optionally be recorded in a sums file. This is not
+/// used to compare sums files.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// The modification time formatted as RFC 3339. This is `LastModified` for cloud objects.
+    pub(crate) mtime: Option<String>,
+    /// The unix permission bits of the file. This is not available for cloud objects.
+    pub(crate) mode: Option<u32>,
+}
+
+impl FileMetadata {
+    /// Create new file metadata.
+    pub fn new(mtime: Option<SystemTime>, mode: Option<u32>) -> Self {
+        Self {
+            mtime: mtime.map(|mtime| format_rfc3339_seconds(mtime).to_string()),
+            mode,
+        }
+    }
+
+    /// Get the modification time, if it is known.
+    pub fn modified(&self) -> Result<Option<SystemTime>> {
+        self.mtime
+            .as_deref()
+            .map(|mtime| {
+                parse_rfc3339(mtime)
+                    .map_err(|err| ParseError(format!("invalid modification time: {}", err)))
+            })
+            .transpose()
+    }
+}
+
 /// A file containing multiple checksums.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Ord, PartialOrd, Hash)]
+#[derive(
+    Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Ord, PartialOrd, Hash, JsonSchema,
+)]
 #[serde(rename_all = "kebab-case")]
 pub struct SumsFile {
+    /// The version of the sums file format.
     pub(crate) version: String,
+    /// The size of the target file in bytes, if it is known.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) size: Option<u64>,
+    /// The byte range of the target file that the checksums were computed over. The checksums
+    /// are for the whole file if this is not present.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) range: Option<ByteRange>,
+    /// Whether CRLF line endings were converted to LF before computing the checksums.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) normalized_newlines: bool,
+    /// The compression format that the data was decompressed from before computing the
+    /// checksums.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) decompressed: Option<Decompress>,
+    /// The modification time of the target file formatted as RFC 3339, if it was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) mtime: Option<String>,
+    /// The unix permission bits of the target file, if they were recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) mode: Option<u32>,
+    /// The part checksums of AWS checksums in part order, if they were recorded. These are used
+    /// to find the parts of a file that changed.
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        deserialize_with = "deserialize_checksums"
+    )]
+    pub(crate) parts: BTreeMap<Ctx, Vec<Checksum>>,
     // The name of the checksum is always the most canonical form.
     // E.g. no -be prefix for big-endian, and the part size as
     // the suffix for AWS checksums.
-    #[serde(flatten)]
+    #[serde(
+        flatten,
+        serialize_with = "serialize_checksums",
+        deserialize_with = "deserialize_checksums"
+    )]
     pub(crate) checksums: BTreeMap<Ctx, Checksum>,
 }
 
+/// Serialize checksums in the stable order defined by `Ctx::output_order` rather than the
+/// preference order of the map, so that output is reproducible and diffs are minimal.
+fn serialize_checksums<S>(
+    checksums: &BTreeMap<Ctx, Checksum>,
+    serializer: S,
+) -> result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut checksums: Vec<_> = checksums.iter().collect();
+    checksums.sort_by_cached_key(|(ctx, _)| ctx.output_order());
+
+    serializer.collect_map(checksums)
+}
+
+/// Deserialize a map keyed by checksum names, which may come from an untrusted sums file. AWS
+/// checksums using the part number syntax are rejected because they cannot be ordered or
+/// formatted without a file size, and sums files always record the part sizes instead.
+fn deserialize_checksums<'de, D, V>(deserializer: D) -> result::Result<BTreeMap<Ctx, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    HashMap::<String, V>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, value)| {
+            let ctx = name.parse::<Ctx>().map_err(D::Error::custom)?;
+            if matches!(&ctx, Ctx::AWSEtag(ctx) if ctx.is_missing_file_size()) {
+                return Err(D::Error::custom(format!(
+                    "`{}` must use part sizes rather than a part number in a sums file",
+                    name
+                )));
+            }
+
+            Ok((ctx, value))
+        })
+        .collect()
+}
+
 impl Default for SumsFile {
     fn default() -> Self {
         Self::new(None, BTreeMap::new())
@@ -109,13 +378,50 @@ impl SumsFile {
         Self {
             version: OUTPUT_FILE_VERSION.to_string(),
             size,
+            range: None,
+            normalized_newlines: false,
+            decompressed: None,
+            mtime: None,
+            mode: None,
+            parts: BTreeMap::new(),
             checksums,
         }
     }
 
+    /// Set the recorded part checksums of AWS checksums.
+    pub fn with_parts(mut self, parts: BTreeMap<Ctx, Vec<Checksum>>) -> Self {
+        self.parts = parts;
+        self
+    }
+
+    /// Get the recorded part checksums of an AWS checksum.
+    pub fn parts(&self, ctx: &Ctx) -> Option<&[Checksum]> {
+        self.parts.get(ctx).map(Vec::as_slice)
+    }
+
+    /// Set the recorded metadata of the target file.
+    pub fn with_metadata(mut self, metadata: FileMetadata) -> Self {
+        self.set_metadata(metadata);
+        self
+    }
+
+    /// Set the recorded metadata of the target file.
+    pub fn set_metadata(&mut self, metadata: FileMetadata) {
+        self.mtime = metadata.mtime;
+        self.mode = metadata.mode;
+    }
+
+    /// Get the recorded metadata of the target file.
+    pub fn metadata(&self) -> FileMetadata {
+        FileMetadata {
+            mtime: self.mtime.clone(),
+            mode: self.mode,
+        }
+    }
+
     /// Format a sums file with the ending.
     pub fn format_sums_file(name: &str) -> String {
-        if name.ends_with(SUMS_FILE_ENDING) {
+        if SumsFormat::from_name(name).is_some() {
             name.to_string()
         } else {
             format!("{}{}", name, SUMS_FILE_ENDING)
@@ -124,23 +430,99 @@ impl SumsFile {
 
     /// Format the target file that the sums file is for.
     pub fn format_target_file(name: &str) -> String {
-        name.strip_suffix(SUMS_FILE_ENDING)
+        name.strip_suffix(SUMS_BIN_FILE_ENDING)
+            .or_else(|| name.strip_suffix(SUMS_FILE_ENDING))
             .unwrap_or(name)
             .to_string()
     }
 
+    /// Shorten the checksums for display if a length is set. See `Checksum::shorten`.
+    pub fn shorten(mut self, len: Option<usize>) -> Self {
+        if let Some(len) = len {
+            self.checksums
+                .values_mut()
+                .for_each(|checksum| *checksum = checksum.shorten(len));
+        }
+        self
+    }
+
+    /// Format the checksums as a Subresource Integrity string, e.g. `sha256-<base64>`, where
+    /// multiple supported checksums are separated by spaces. The checksums that cannot be used
+    /// in an integrity string are returned alongside it so that they can be reported.
+    pub fn integrity(&self) -> Result<(Option<String>, Vec<&Ctx>)> {
+        let mut integrity = vec![];
+        let mut skipped = vec![];
+        for (ctx, checksum) in &self.checksums {
+            let algorithm = match ctx {
+                Ctx::Regular(ctx) => ctx.sri_algorithm(),
+                Ctx::AWSEtag(_) => None,
+            };
+            let Some(algorithm) = algorithm else {
+                skipped.push(ctx);
+                continue;
+            };
+
+            let digest = hex::decode(checksum.as_str()).map_err(|err| {
+                ParseError(format!(
+                    "invalid {} checksum `{}`: {}",
+                    ctx,
+                    checksum.as_str(),
+                    err
+                ))
+            })?;
+            integrity.push(format!("{}-{}", algorithm, BASE64_STANDARD.encode(digest)));
+        }
+
+        Ok((
+            (!integrity.is_empty()).then(|| integrity.join(" ")),
+            skipped,
+        ))
+    }
+
+    /// Get the JSON Schema of sums files.
+    pub fn json_schema() -> RootSchema {
+        let mut generator = SchemaSettings::draft07().into_generator();
+        let mut schema = generator.root_schema_for::<Self>();
+        // The checksums are flattened into the top level and keyed by their name, which is not
+        // represented by the derived schema.
+        schema.schema.object().additional_properties =
+            Some(Box::new(generator.subschema_for::<Checksum>()));
+        schema
+    }
+
     /// Convert to a JSON string.
     pub fn to_json_string(&self) -> Result<String> {
         Ok(to_string(&self)?)
     }
 
+    /// Convert to an indented and multi-line JSON string.
+    pub fn to_json_string_pretty(&self) -> Result<String> {
+        Ok(to_string_pretty(&self)?)
+    }
+
+    /// Serialize to an async writer using the format. The sums file is encoded directly into
+    /// the writer on a blocking thread and returned once the writer has been flushed.
+    pub async fn write_to<W>(self, format: SumsFormat, writer: W) -> Result<Self>
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let writer = SyncIoBridge::new(writer);
+        spawn_blocking(move || {
+            let mut writer = BufWriter::new(writer);
+            format.write(&self, &mut writer)?;
+            writer.flush()?;
+            Ok(self)
+        })
+        .await?
+    }
+
     /// Read from a slice and add the name.
     pub async fn read_from_slice(slice: &[u8]) -> Result<Self> {
         slice.try_into()
     }
 
     /// Merge with another output file, overwriting existing checksums,
-    /// taking ownership of self. Returns an error if the size of the files
+    /// taking ownership of self. Returns an error if the size or range of the files
     /// do not match, and both files are not empty.
     pub fn merge(mut self, other: Self) -> Result<Self> {
         if self.size != other.size && !self.checksums.is_empty() && !other.checksums.is_empty() {
@@ -148,6 +530,27 @@ impl SumsFile {
                 "the size of output files do not match".to_string(),
             ));
         }
+        if self.range != other.range && !self.checksums.is_empty() && !other.checksums.is_empty() {
+            return Err(SumsFileError(
+                "the byte range of output files do not match".to_string(),
+            ));
+        }
+        if self.normalized_newlines != other.normalized_newlines
+            && !self.checksums.is_empty()
+            && !other.checksums.is_empty()
+        {
+            return Err(SumsFileError(
+                "output files with and without normalized newlines cannot be merged".to_string(),
+            ));
+        }
+        if self.decompressed != other.decompressed
+            && !self.checksums.is_empty()
+            && !other.checksums.is_empty()
+        {
+            return Err(SumsFileError(
+                "output files of compressed and decompressed data cannot be merged".to_string(),
+            ));
+        }
 
         self.merge_mut(other);
         Ok(self)
@@ -157,8 +560,47 @@ impl SumsFile {
     /// check if the file name and size is the same.
     pub fn merge_mut(&mut self, other: Self) {
         for (key, checksum) in other.checksums {
+            // Recorded parts are stale if the checksum that they belong to changes.
+            if self.checksums.get(&key) != Some(&checksum) {
+                self.parts.remove(&key);
+            }
             self.checksums.insert(key, checksum);
         }
+        self.parts.extend(other.parts);
+    }
+
+    /// Merge with another output file, returning an error instead of overwriting if a checksum
+    /// exists in both files with a different value. Does not check if the file name and size
+    /// is the same.
+    pub fn merge_strict(&mut self, other: Self) -> Result<()> {
+        let conflicts = self.conflicts(&other);
+        if !conflicts.is_empty() {
+            return Err(SumsFileError(format!(
+                "conflicting checksums for the same algorithm: {}",
+                conflicts
+                    .iter()
+                    .map(|ctx| ctx.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        self.merge_mut(other);
+        Ok(())
+    }
+
+    /// Get the checksum algorithms that exist in both sums files with different values.
+    pub fn conflicts<'a>(&'a self, other: &Self) -> Vec<&'a Ctx> {
+        self.checksums
+            .iter()
+            .filter(|(ctx, checksum)| {
+                other
+                    .checksums
+                    .get(ctx)
+                    .is_some_and(|other| other != *checksum)
+            })
+            .map(|(ctx, _)| ctx)
+            .collect()
     }
 
     /// Split the sums file into multiple sums files, one for each checksum.
@@ -166,7 +608,7 @@ impl SumsFile {
         self.checksums
             .iter()
             .map(|(ctx, checksum)| {
-                let mut sums_file = Self::default().with_size(self.size);
+                let mut sums_file = Self::default().with_size(self.size).with_range(self.range);
                 sums_file.add_checksum(ctx.clone(), checksum.clone());
 
                 sums_file
@@ -177,11 +619,26 @@ impl SumsFile {
     /// Check if the sums file is the same as another according to all available checksums
     /// in the sums file. Returns the key value that resulted in equality if the sums are the same.
     pub fn is_same(&self, other: &Self) -> Option<(&Ctx, &Checksum)> {
-        if self.size != other.size {
+        self.is_same_with_strength(other, None)
+    }
+
+    /// Check if the sums files are the same using only checksums that are at least as strong
+    /// as `min_strength`. Weaker checksums are ignored, so sums files that only share weak
+    /// checksums are not proven to be the same.
+    pub fn is_same_with_strength(
+        &self,
+        other: &Self,
+        min_strength: Option<Strength>,
+    ) -> Option<(&Ctx, &Checksum)> {
+        if !self.same_input(other) {
             return None;
         }
 
         for (key, checksum) in &self.checksums {
+            if min_strength.is_some_and(|min_strength| key.strength() < min_strength) {
+                continue;
+            }
+
             if let Some(other_checksum) = other.checksums.get(key) {
                 // Two checksums are the same if they have the same top-level checksum. Since the
                 // top level checksum encodes part information for AWS sums, there is no need to
@@ -199,7 +656,7 @@ impl SumsFile {
     /// one of the same checksum type. Returns the key value that resulted in comparability if the
     /// sums are the same.
     pub fn comparable(&self, other: &Self) -> Option<(&Ctx, &Checksum)> {
-        if self.size != other.size {
+        if !self.same_input(other) {
             return None;
         }
 
@@ -212,6 +669,15 @@ impl SumsFile {
         None
     }
 
+    /// Whether the checksums of both sums files were computed over the same bytes, i.e. the
+    /// same size, byte range, newline normalization and decompression.
+    fn same_input(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self.range == other.range
+            && self.normalized_newlines == other.normalized_newlines
+            && self.decompressed == other.decompressed
+    }
+
     /// Set the size.
     pub fn with_size(mut self, size: Option<u64>) -> Self {
         self.set_size(size);
@@ -223,27 +689,235 @@ impl SumsFile {
         self.size = size;
     }
 
+    /// Set the byte range that the checksums were computed over.
+    pub fn with_range(mut self, range: Option<ByteRange>) -> Self {
+        self.set_range(range);
+        self
+    }
+
+    /// Set the byte range from a mutable reference.
+    pub fn set_range(&mut self, range: Option<ByteRange>) {
+        self.range = range;
+    }
+
+    /// Set whether CRLF line endings were converted to LF before computing the checksums.
+    pub fn with_normalized_newlines(mut self, normalized_newlines: bool) -> Self {
+        self.normalized_newlines = normalized_newlines;
+        self
+    }
+
+    /// Set the compression format that the data was decompressed from before computing the
+    /// checksums.
+    pub fn with_decompressed(mut self, decompressed: Option<Decompress>) -> Self {
+        self.decompressed = decompressed;
+        self
+    }
+
     /// Add a checksum to the sums file.
     pub fn add_checksum(&mut self, ctx: Ctx, checksum: Checksum) {
         self.checksums.insert(ctx, checksum);
     }
 
+    /// Remove the checksums, and their part checksums, which are not in the set to keep. A
+    /// regular checksum also keeps the AWS checksums of the same algorithm, e.g. `md5` keeps
+    /// `md5-aws-8mib`, whereas an AWS checksum only keeps itself. Returns the checksums that
+    /// were removed.
+    pub fn retain(&mut self, keep: &[Ctx]) -> Vec<Ctx> {
+        let kept = |ctx: &Ctx| {
+            keep.iter().any(|keep| match (keep, ctx) {
+                (Ctx::Regular(keep), Ctx::AWSEtag(ctx)) => ctx.clone().ctx() == *keep,
+                _ => keep == ctx,
+            })
+        };
+
+        let removed: Vec<_> = self
+            .checksums
+            .keys()
+            .filter(|ctx| !kept(ctx))
+            .cloned()
+            .collect();
+        self.checksums.retain(|ctx, _| kept(ctx));
+        self.parts.retain(|ctx, _| kept(ctx));
+
+        removed
+    }
+
     /// Does the sums file contain no checksums.
     pub fn is_empty(&self) -> bool {
         self.checksums.is_empty()
     }
+
+    /// Compute a single sha256 digest over a set of named sums files, which changes if any of
+    /// the checksums change. Each sums file is hashed with its name using its canonical JSON,
+    /// without metadata, and the root digest is the hash of these digests sorted by name. This
+    /// means that the digest does not depend on the order of the sums files.
+    pub fn manifest_digest(sums_files: &[(String, SumsFile)]) -> Result<String> {
+        let mut leaves = sums_files
+            .iter()
+            .map(|(name, sums_file)| {
+                let sums_file = sums_file.clone().with_metadata(Default::default());
+
+                let mut leaf = Sha256::new();
+                leaf.update(name.as_bytes());
+                leaf.update([0]);
+                leaf.update(sums_file.to_json_string()?.as_bytes());
+                Ok((name, leaf.finalize()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        leaves.sort();
+
+        let mut root = Sha256::new();
+        leaves.iter().for_each(|(_, leaf)| root.update(leaf));
+        Ok(hex::encode(root.finalize()))
+    }
+
+    /// Compute a single sha256 digest that identifies a dataset of named files. For each file in
+    /// order of its name, `<name>\0<size>\0<sha256>` is hashed, where the size is in decimal and
+    /// the sha256 is the hex checksum of the file. Unlike the manifest digest, this only depends
+    /// on the names, sizes and contents of the files, so the same files always have the same
+    /// identity regardless of which other checksums were computed.
+    pub fn dataset_id(sums_files: &[(String, SumsFile)]) -> Result<String> {
+        let sha256 = "sha256".parse::<Ctx>()?;
+        let mut entries = sums_files
+            .iter()
+            .map(|(name, sums_file)| {
+                let size = sums_file.size.ok_or_else(|| {
+                    SumsFileError(format!("missing size for `{}` in dataset", name))
+                })?;
+                let checksum = sums_file.checksums.get(&sha256).ok_or_else(|| {
+                    SumsFileError(format!("missing sha256 for `{}` in dataset", name))
+                })?;
+                Ok((name, size, checksum.as_str()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort();
+
+        if let Some(window) = entries.windows(2).find(|window| window[0].0 == window[1].0) {
+            return Err(SumsFileError(format!(
+                "duplicate name `{}` in dataset",
+                window[0].0
+            )));
+        }
+
+        let mut digest = Sha256::new();
+        for (name, size, checksum) in entries {
+            digest.update(name.as_bytes());
+            digest.update([0]);
+            digest.update(size.to_string().as_bytes());
+            digest.update([0]);
+            digest.update(checksum.as_bytes());
+        }
+        Ok(hex::encode(digest.finalize()))
+    }
+
+    /// Check that a sums file version can be read. Older versions are read with a warning,
+    /// and newer versions are an error because their fields may be misinterpreted.
+    pub fn check_version(version: &str) -> Result<()> {
+        let parse = |version: &str| {
+            version
+                .parse::<u64>()
+                .map_err(|_| SumsFileError(format!("invalid sums file version: {}", version)))
+        };
+        let supported = parse(OUTPUT_FILE_VERSION)?;
+
+        match parse(version)?.cmp(&supported) {
+            Ordering::Less => {
+                warn!(
+                    version,
+                    current = OUTPUT_FILE_VERSION,
+                    "reading a sums file version which is older than the current version"
+                );
+                Ok(())
+            }
+            Ordering::Equal => Ok(()),
+            Ordering::Greater => Err(SumsFileError(format!(
+                "sums file version {} is newer than the supported version {}, upgrade cloud-checksum to read it",
+                version, OUTPUT_FILE_VERSION
+            ))),
+        }
+    }
+}
+
+/// Only the version of a sums file, used to check it before reading other fields.
+#[derive(Deserialize)]
+struct SumsFileVersion {
+    version: String,
 }
 
 impl TryFrom<&[u8]> for SumsFile {
     type Error = Error;
 
     fn try_from(value: &[u8]) -> Result<Self> {
+        let SumsFileVersion { version } = from_slice(value)?;
+        Self::check_version(&version)?;
+
         Ok(from_slice(value)?)
     }
 }
 
-/// The output of a checksum.
-#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, Ord, PartialOrd, Hash)]
+/// A byte range of the target file that checksums were computed over. If this is not
+/// present in a sums file, the checksums are for the whole file.
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Ord, PartialOrd, Hash, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub struct ByteRange {
+    /// The offset of the first byte of the range.
+    pub(crate) offset: u64,
+    /// The number of bytes in the range.
+    pub(crate) length: u64,
+}
+
+impl ByteRange {
+    /// Create a new byte range.
+    pub fn new(offset: u64, length: u64) -> Self {
+        Self { offset, length }
+    }
+
+    /// The end of the range, exclusive.
+    pub fn end(&self) -> u64 {
+        self.offset + self.length
+    }
+
+    /// Resolve a byte range from an optional offset and length given the size of the file.
+    /// The range extends to the end of the file if the length is not set. Returns `None`
+    /// if neither the offset nor length is set.
+    pub fn from_options(
+        offset: Option<u64>,
+        length: Option<u64>,
+        file_size: Option<u64>,
+    ) -> Result<Option<Self>> {
+        if offset.is_none() && length.is_none() {
+            return Ok(None);
+        }
+
+        let file_size = file_size.ok_or_else(|| {
+            ParseError("the file size is required to read a byte range".to_string())
+        })?;
+        let offset = offset.unwrap_or_default();
+        let length = match length {
+            Some(length) => length,
+            None => file_size.checked_sub(offset).ok_or_else(|| {
+                ParseError("the offset is greater than the file size".to_string())
+            })?,
+        };
+
+        if offset.checked_add(length).is_none_or(|end| end > file_size) {
+            return Err(ParseError(
+                "the byte range exceeds the file size".to_string(),
+            ));
+        }
+
+        Ok(Some(Self::new(offset, length)))
+    }
+}
+
+/// The output of a checksum. This is always hex encoded, so checksums from other sources, such
+/// as base64 encoded S3 additional checksums, are decoded when they are read. This means that
+/// checksums can be compared directly without tracking their encoding.
+#[derive(
+    Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, Ord, PartialOrd, Hash, JsonSchema,
+)]
 #[serde(rename_all = "kebab-case")]
 pub struct Checksum(String);
 
@@ -252,14 +926,36 @@ impl Checksum {
     pub fn new(checksum: String) -> Self {
         Self(checksum)
     }
+
+    /// Get the value of the checksum.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Shorten the digest to its first `len` characters for display, like a short git hash.
+    /// The part size suffix of AWS checksums is kept.
+    pub fn shorten(&self, len: usize) -> Self {
+        let (digest, suffix) = match self.0.split_once('-') {
+            Some((digest, suffix)) => (digest, Some(suffix)),
+            None => (self.0.as_str(), None),
+        };
+        let digest = digest.get(..len).unwrap_or(digest);
+
+        match suffix {
+            Some(suffix) => Self(format!("{}-{}", digest, suffix)),
+            None => Self(digest.to_string()),
+        }
+    }
 }
 
 #[cfg(test)]
 pub(crate) mod test {
     use super::*;
     use crate::checksum::aws_etag::test::expected_md5_1gib;
+    use crate::checksum::checksum_bytes;
     use crate::checksum::standard::test::EXPECTED_MD5_SUM;
     use serde_json::{from_value, json, to_value, Value};
+    use tokio::io::AsyncReadExt;
 
     const EXPECTED_ETAG: &str = "1c3490f45b0cdc4299a128410def3a1d-b";
 
@@ -285,6 +981,41 @@ pub(crate) mod test {
         Ok(())
     }
 
+    #[test]
+    fn serialize_checksum_order() -> Result<()> {
+        let checksums = [
+            "crc32-aws-8b",
+            "md5-aws-16b",
+            "crc32c",
+            "sha256",
+            "md5-aws-8b",
+            "crc32",
+            "sha1-aws-8b",
+            "md5",
+        ]
+        .into_iter()
+        .map(|ctx| Ok((ctx.parse()?, Checksum::new("123".to_string()))))
+        .collect::<Result<BTreeMap<_, _>>>()?;
+
+        let result = SumsFile::new(None, checksums).to_json_string()?;
+        let expected = [
+            "md5",
+            "sha256",
+            "crc32",
+            "crc32c",
+            "md5-aws-8b",
+            "sha1-aws-8b",
+            "crc32-aws-8b",
+            "md5-aws-16b",
+        ]
+        .map(|ctx| format!("\"{ctx}\":\"123\""))
+        .join(",");
+
+        assert_eq!(result, format!("{{\"version\":\"1\",{expected}}}"));
+
+        Ok(())
+    }
+
     #[test]
     fn is_same() -> Result<()> {
         let file_one = expected_output_file();
@@ -358,6 +1089,489 @@ pub(crate) mod test {
         Ok(())
     }
 
+    #[test]
+    fn metadata_ignored_for_equality() -> Result<()> {
+        let file = SumsFile::new(
+            Some(1),
+            BTreeMap::from_iter(vec![("md5".parse()?, Checksum::new("123".to_string()))]),
+        );
+        let with_metadata = file.clone().with_metadata(FileMetadata {
+            mtime: Some("2025-01-01T00:00:00Z".to_string()),
+            mode: Some(0o644),
+        });
+
+        assert!(file.is_same(&with_metadata).is_some());
+        assert!(file.comparable(&with_metadata).is_some());
+
+        let json = with_metadata.to_json_string()?;
+        assert!(json.contains("\"mtime\":\"2025-01-01T00:00:00Z\",\"mode\":420"));
+        assert_eq!(SumsFile::try_from(json.as_bytes())?, with_metadata);
+        assert!(!file.to_json_string()?.contains("mtime"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn normalized_newlines_not_compared() -> Result<()> {
+        let file = SumsFile::new(
+            Some(1),
+            BTreeMap::from_iter(vec![("md5".parse()?, Checksum::new("123".to_string()))]),
+        );
+        let normalized = file.clone().with_normalized_newlines(true);
+
+        assert!(file.is_same(&normalized).is_none());
+        assert!(file.comparable(&normalized).is_none());
+        assert!(file.clone().merge(normalized.clone()).is_err());
+
+        let json = normalized.to_json_string()?;
+        assert!(json.contains("\"normalized-newlines\":true"));
+        assert_eq!(SumsFile::try_from(json.as_bytes())?, normalized);
+        assert!(!file.to_json_string()?.contains("normalized-newlines"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn decompressed_not_compared() -> Result<()> {
+        let file = SumsFile::new(
+            Some(1),
+            BTreeMap::from_iter(vec![("md5".parse()?, Checksum::new("123".to_string()))]),
+        );
+        let decompressed = file.clone().with_decompressed(Some(Decompress::Gzip));
+
+        assert!(file.is_same(&decompressed).is_none());
+        assert!(file.comparable(&decompressed).is_none());
+        assert!(file.clone().merge(decompressed.clone()).is_err());
+
+        let json = decompressed.to_json_string()?;
+        assert!(json.contains("\"decompressed\":\"gzip\""));
+        assert_eq!(SumsFile::try_from(json.as_bytes())?, decompressed);
+        assert!(!file.to_json_string()?.contains("decompressed"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_version() -> Result<()> {
+        let file = |version: &str| {
+            let mut value = expected_output_json();
+            value["version"] = json!(version);
+            value.to_string()
+        };
+
+        assert_eq!(
+            SumsFile::try_from(file(OUTPUT_FILE_VERSION).as_bytes())?,
+            expected_output_file()
+        );
+        assert_eq!(SumsFile::try_from(file("0").as_bytes())?.version, "0");
+
+        // A newer version is an error even if it has fields that cannot be read.
+        let mut newer = expected_output_json();
+        newer["version"] = json!("2");
+        newer["new-field"] = json!({ "value": 1 });
+        let err = SumsFile::try_from(newer.to_string().as_bytes()).unwrap_err();
+        assert!(matches!(err, SumsFileError(_)));
+        assert!(err.to_string().contains("upgrade"));
+
+        let err = SumsFile::try_from(file("invalid").as_bytes()).unwrap_err();
+        assert!(matches!(err, SumsFileError(_)));
+
+        let mut newer = expected_output_file();
+        newer.version = "2".to_string();
+        let encoded = SumsFormat::Msgpack.to_vec(&newer)?;
+        assert!(matches!(
+            SumsFormat::Msgpack.from_slice(&encoded),
+            Err(SumsFileError(_))
+        ));
+
+        // The version of MessagePack sums files is also checked before reading other fields.
+        let mut newer = expected_output_json();
+        newer["version"] = json!("2");
+        newer["size"] = json!({ "value": 1 });
+        let encoded = rmp_serde::to_vec_named(&newer)?;
+        let err = SumsFormat::Msgpack.from_slice(&encoded).unwrap_err();
+        assert!(err.to_string().contains("upgrade"), "{err}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn untrusted_checksum_names() -> Result<()> {
+        let file = |mut value: Value| {
+            value["version"] = json!(OUTPUT_FILE_VERSION);
+            SumsFile::try_from(value.to_string().as_bytes())
+        };
+
+        // Part numbers cannot be ordered without a file size, so these are errors rather than
+        // panics.
+        assert!(file(json!({ "md5-aws-2": "a", "sha1-aws-3": "b" })).is_err());
+        assert!(file(json!({ "md5-aws-p2-aligned": "a", "md5": "b" })).is_err());
+        assert!(file(json!({ "parts": { "md5-aws-2": ["a"], "md5-aws-3": ["b"] } })).is_err());
+        assert!(file(json!({ "md5-aws-1000000000000000000000gib": "a" })).is_err());
+
+        let sums_file = file(json!({ "md5-aws-1b-2b": "a", "parts": { "md5-aws-1b-2b": ["b"] } }))?;
+        assert_eq!(
+            sums_file.parts(&"md5-aws-1b-2b".parse()?),
+            Some([Checksum::new("b".to_string())].as_slice())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_digest() -> Result<()> {
+        let file = |md5: &str| -> Result<SumsFile> {
+            Ok(SumsFile::new(
+                Some(1),
+                BTreeMap::from_iter(vec![("md5".parse()?, Checksum::new(md5.to_string()))]),
+            ))
+        };
+        let sums_files = vec![
+            ("a".to_string(), file("123")?),
+            ("b".to_string(), file("456")?),
+        ];
+
+        let digest = SumsFile::manifest_digest(&sums_files)?;
+        assert_eq!(digest.len(), 64);
+
+        let reversed = sums_files.iter().cloned().rev().collect::<Vec<_>>();
+        assert_eq!(SumsFile::manifest_digest(&reversed)?, digest);
+
+        let mut with_metadata = sums_files.clone();
+        with_metadata[0].1.set_metadata(FileMetadata {
+            mtime: Some("2025-01-01T00:00:00Z".to_string()),
+            mode: Some(0o644),
+        });
+        assert_eq!(SumsFile::manifest_digest(&with_metadata)?, digest);
+
+        let mut changed = sums_files.clone();
+        changed[1].1 = file("789")?;
+        assert_ne!(SumsFile::manifest_digest(&changed)?, digest);
+
+        let mut renamed = sums_files.clone();
+        renamed[1].0 = "c".to_string();
+        assert_ne!(SumsFile::manifest_digest(&renamed)?, digest);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dataset_id() -> Result<()> {
+        let file = |size: u64, data: &[u8]| -> Result<SumsFile> {
+            Ok(SumsFile::new(
+                Some(size),
+                BTreeMap::from_iter(vec![(
+                    "sha256".parse()?,
+                    Checksum::new(checksum_bytes("sha256", data)?),
+                )]),
+            ))
+        };
+        let sums_files = vec![
+            ("b".to_string(), file(3, b"bbb")?),
+            ("a".to_string(), file(2, b"aa")?),
+        ];
+
+        // The records are hashed in order of the names.
+        let mut expected = Sha256::new();
+        expected.update(format!("a\x002\x00{}", checksum_bytes("sha256", b"aa")?));
+        expected.update(format!("b\x003\x00{}", checksum_bytes("sha256", b"bbb")?));
+        let id = SumsFile::dataset_id(&sums_files)?;
+        assert_eq!(id, hex::encode(expected.finalize()));
+
+        // Other checksums do not change the identity, but names do.
+        let mut with_md5 = sums_files.clone();
+        with_md5[0]
+            .1
+            .add_checksum("md5".parse()?, Checksum::new("123".to_string()));
+        assert_eq!(SumsFile::dataset_id(&with_md5)?, id);
+
+        let mut renamed = sums_files.clone();
+        renamed[0].0 = "c".to_string();
+        assert_ne!(SumsFile::dataset_id(&renamed)?, id);
+
+        let mut duplicate = sums_files.clone();
+        duplicate[0].0 = "a".to_string();
+        assert!(SumsFile::dataset_id(&duplicate).is_err());
+        let mut missing = sums_files.clone();
+        missing[0].1 = SumsFile::default().with_size(Some(3));
+        assert!(SumsFile::dataset_id(&missing).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_strict() -> Result<()> {
+        let file = |md5: &str| -> Result<SumsFile> {
+            Ok(SumsFile::new(
+                Some(1),
+                BTreeMap::from_iter(vec![("md5".parse()?, Checksum::new(md5.to_string()))]),
+            ))
+        };
+
+        let mut file_one = file("123")?;
+        let mut file_two = file("123")?;
+        file_two.add_checksum("sha1".parse()?, Checksum::new("456".to_string()));
+
+        file_one.merge_strict(file_two.clone())?;
+        assert_eq!(file_one, file_two);
+
+        let err = file_one.merge_strict(file("789")?).unwrap_err();
+        assert!(matches!(err, SumsFileError(_)));
+        assert!(err.to_string().contains("md5"));
+        assert_eq!(file_one, file_two);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parts() -> Result<()> {
+        let aws: Ctx = "md5-aws-1b".parse()?;
+        let file = |checksum: &str, parts: &[&str]| {
+            SumsFile::new(
+                Some(2),
+                BTreeMap::from_iter([(aws.clone(), Checksum::new(checksum.to_string()))]),
+            )
+            .with_parts(BTreeMap::from_iter([(
+                aws.clone(),
+                parts
+                    .iter()
+                    .map(|part| Checksum::new(part.to_string()))
+                    .collect(),
+            )]))
+        };
+
+        let sums = file("123-1b", &["1", "2"]);
+        let json = serde_json::to_string(&sums)?;
+        assert!(json.contains(r#""parts":{"md5-aws-1b":["1","2"]}"#));
+        for format in [SumsFormat::Json, SumsFormat::Msgpack] {
+            assert_eq!(format.from_slice(&format.to_vec(&sums)?)?, sums);
+        }
+
+        // Parts are dropped if their checksum changes without new parts.
+        let merged = sums.clone().merge(SumsFile::new(
+            Some(2),
+            BTreeMap::from_iter([(aws.clone(), Checksum::new("456-1b".to_string()))]),
+        ))?;
+        assert!(merged.parts(&aws).is_none());
+        let merged = sums.merge(file("456-1b", &["3", "4"]))?;
+        assert_eq!(merged.parts(&aws).map(<[_]>::len), Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn shorten() -> Result<()> {
+        let checksum = Checksum::new("1c3490f45b0cdc4299a128410def3a1d".to_string());
+        assert_eq!(checksum.shorten(7).as_str(), "1c3490f");
+        assert_eq!(checksum.shorten(100), checksum);
+
+        let aws = Checksum::new(EXPECTED_ETAG.to_string());
+        assert_eq!(aws.shorten(7).as_str(), "1c3490f-b");
+
+        let file = expected_output_file().shorten(Some(4));
+        assert!(file.checksums.values().all(|checksum| checksum
+            .as_str()
+            .split('-')
+            .next()
+            .unwrap()
+            .len()
+            == 4));
+        assert_eq!(file.size, expected_output_file().size);
+
+        Ok(())
+    }
+
+    #[test]
+    fn integrity() -> Result<()> {
+        let sha256 = "sha256".parse::<Ctx>()?;
+        let md5 = "md5".parse::<Ctx>()?;
+        let file = SumsFile::new(
+            None,
+            BTreeMap::from([
+                (
+                    sha256,
+                    Checksum::new(
+                        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+                            .to_string(),
+                    ),
+                ),
+                (md5.clone(), Checksum::new(EXPECTED_MD5_SUM.to_string())),
+            ]),
+        );
+
+        let (integrity, skipped) = file.integrity()?;
+        assert_eq!(
+            integrity.as_deref(),
+            Some("sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=")
+        );
+        assert_eq!(skipped, vec![&md5]);
+
+        let file = expected_output_file();
+        let (integrity, skipped) = file.integrity()?;
+        assert!(integrity.is_none());
+        assert_eq!(skipped.len(), file.checksums.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn range() -> Result<()> {
+        let file_one = expected_output_file().with_range(Some(ByteRange::new(1, 122)));
+        let file_two = expected_output_file();
+
+        assert!(file_one.is_same(&file_two).is_none());
+        assert!(file_one.comparable(&file_two).is_none());
+        assert!(file_one.clone().merge(file_two).is_err());
+
+        let value = to_value(&file_one)?;
+        assert_eq!(value["range"], json!({ "offset": 1, "length": 122 }));
+        assert_eq!(from_value::<SumsFile>(value)?, file_one);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_to() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let path = tmp.path().join("file.sums");
+        let value = expected_output_file().with_range(Some(ByteRange::new(0, 123)));
+
+        for format in [
+            SumsFormat::Json,
+            SumsFormat::PrettyJson,
+            SumsFormat::Msgpack,
+        ] {
+            let written = value
+                .clone()
+                .write_to(format, tokio::fs::File::create(&path).await?)
+                .await?;
+            assert_eq!(written, value);
+
+            let written = tokio::fs::read(&path).await?;
+            assert_eq!(written, format.to_vec(&value)?);
+            assert_eq!(format.from_slice(&written)?, value);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_to_small_writer() -> Result<()> {
+        let value = expected_output_file().with_range(Some(ByteRange::new(0, 123)));
+
+        for format in [
+            SumsFormat::Json,
+            SumsFormat::PrettyJson,
+            SumsFormat::Msgpack,
+        ] {
+            // The duplex buffer is smaller than the encoded sums file, so the writer only
+            // completes if the encoded bytes are streamed while they are being read.
+            let (writer, mut reader) = tokio::io::duplex(16);
+            let read = tokio::spawn(async move {
+                let mut written = vec![];
+                reader.read_to_end(&mut written).await?;
+                Ok::<_, Error>(written)
+            });
+
+            value.clone().write_to(format, writer).await?;
+
+            let written = read.await??;
+            assert_eq!(written, format.to_vec(&value)?);
+            assert_eq!(format.from_slice(&written)?, value);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn msgpack_format() -> Result<()> {
+        let value = expected_output_file().with_range(Some(ByteRange::new(0, 123)));
+        let format = SumsFormat::Msgpack;
+
+        let encoded = format.to_vec(&value)?;
+        assert!(encoded.len() < value.to_json_string()?.len());
+        assert_eq!(format.from_slice(&encoded)?, value);
+
+        Ok(())
+    }
+
+    #[test]
+    fn pretty_json_format() -> Result<()> {
+        let value = expected_output_file().with_range(Some(ByteRange::new(0, 123)));
+        let format = SumsFormat::PrettyJson;
+
+        let encoded = String::from_utf8(format.to_vec(&value)?).unwrap();
+        assert_eq!(encoded, value.to_json_string_pretty()?);
+        assert!(encoded.lines().count() > 1);
+        assert_eq!(format.from_slice(encoded.as_bytes())?, value);
+        assert_eq!(SumsFormat::Json.from_slice(encoded.as_bytes())?, value);
+        assert_eq!(format.ending(), SumsFormat::Json.ending());
+
+        Ok(())
+    }
+
+    #[test]
+    fn format_names() -> Result<()> {
+        assert_eq!(SumsFormat::from_name("file.sums"), Some(SumsFormat::Json));
+        assert_eq!(
+            SumsFormat::from_name("file.sums.bin"),
+            Some(SumsFormat::Msgpack)
+        );
+        assert_eq!(SumsFormat::from_name("file"), None);
+
+        assert_eq!(SumsFile::format_target_file("file.sums.bin"), "file");
+        assert_eq!(SumsFile::format_sums_file("file.sums.bin"), "file.sums.bin");
+        assert_eq!(
+            SumsFormat::Msgpack.format_sums_file("file.sums"),
+            "file.sums.bin"
+        );
+
+        let format = SumsFormat::Json;
+        assert_eq!(
+            format.format_sums_file_at("dir/file", None)?,
+            "dir/file.sums"
+        );
+        assert_eq!(
+            format.format_sums_file_at("dir/file", Some("sums/"))?,
+            "sums/dir/file.sums"
+        );
+        assert_eq!(
+            format.format_sums_file_at("dir/file", Some(""))?,
+            "dir/file.sums"
+        );
+        assert_eq!(
+            format.format_sums_file_at("/dir//./a/../file", Some("sums"))?,
+            "sums/dir/file.sums"
+        );
+        assert!(format.format_sums_file_at("../file", Some("sums")).is_err());
+
+        // Inputs with the same name in different directories do not collide.
+        assert_ne!(
+            format.format_sums_file_at("a/x.bam", Some("sums"))?,
+            format.format_sums_file_at("b/x.bam", Some("sums"))?
+        );
+
+        assert_eq!(
+            format.format_sums_file_under("/data/dir/file", "sums/")?,
+            "sums/data/dir/file.sums"
+        );
+        assert_eq!(
+            SumsFormat::Msgpack.format_sums_file_under("/data/../dir/./file", "sums")?,
+            "sums/dir/file.sums.bin"
+        );
+        let relative = format.format_sums_file_under("dir/file", "sums")?;
+        let cwd = std::env::current_dir()?;
+        assert_eq!(
+            relative,
+            format!(
+                "sums/{}/dir/file.sums",
+                cwd.to_string_lossy().trim_start_matches('/')
+            )
+        );
+
+        Ok(())
+    }
+
     fn set_checksums(file_two: &mut SumsFile, aws: Ctx) {
         file_two.checksums =
             BTreeMap::from_iter(vec![(aws, Checksum::new(expected_md5_1gib().to_string()))]);
@@ -370,6 +1584,34 @@ pub(crate) mod test {
         SumsFile::new(Some(123), BTreeMap::from_iter(checksums))
     }
 
+    #[test]
+    fn json_schema() -> Result<()> {
+        let schema = to_value(SumsFile::json_schema())?;
+        let properties = schema["properties"].as_object().unwrap();
+
+        let mut file = expected_output_file()
+            .with_range(Some(ByteRange::new(0, 123)))
+            .with_decompressed(Some(Decompress::Gzip));
+        file.set_metadata(FileMetadata::new(Some(SystemTime::UNIX_EPOCH), Some(0o644)));
+        // Fields are described by the properties, and the checksums by the additional
+        // properties.
+        for (key, value) in to_value(&file)?.as_object().unwrap() {
+            if key.parse::<Ctx>().is_ok() {
+                assert!(value.is_string());
+            } else {
+                assert!(properties.contains_key(key), "{key}");
+            }
+        }
+        assert_eq!(schema["required"], json!(["version"]));
+        assert_eq!(
+            schema["additionalProperties"],
+            json!({ "$ref": "#/definitions/Checksum" })
+        );
+        assert_eq!(schema["definitions"]["Checksum"]["type"], "string");
+
+        Ok(())
+    }
+
     fn expected_output_json() -> Value {
         json!({
             "version": OUTPUT_FILE_VERSION,
@@ -2,13 +2,19 @@
 //!
 
 pub mod aws_etag;
+pub mod checkpoint;
 pub mod file;
+pub mod rolling;
+pub mod self_test;
 pub mod standard;
 
 use crate::checksum::aws_etag::AWSETagCtx;
+use crate::checksum::checkpoint::CtxCheckpoint;
 use crate::checksum::standard::StandardCtx;
+use crate::cli::Strength;
 use crate::error::{Error, Result};
 use crate::io::Provider;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::types::ChecksumAlgorithm;
 use futures_util::{pin_mut, Stream, StreamExt};
 use serde::de::Error as SerdeError;
@@ -56,6 +62,25 @@ impl Serialize for Ctx {
     }
 }
 
+/// The result of generating a checksum, including any part checksums.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerateOutput {
+    /// The top-level digest.
+    pub digest: Vec<u8>,
+    /// The formatted checksum of the digest.
+    pub checksum: String,
+    /// The resolved part sizes if this is an AWS checksum.
+    pub part_sizes: Option<Vec<u64>>,
+    /// The encoded part checksums and their part sizes if this is an AWS checksum.
+    pub part_checksums: Option<Vec<(u64, String)>>,
+}
+
+/// Compute a checksum over an in-memory buffer, e.g. `checksum_bytes("sha256", &data)`.
+/// The checksum uses the same syntax as the `--checksum` option of the CLI.
+pub fn checksum_bytes(checksum: &str, data: &[u8]) -> Result<String> {
+    Ctx::from_str(checksum)?.checksum_slice(data)
+}
+
 impl Ctx {
     /// Update a checksum with some data.
     pub fn update(&mut self, data: Arc<[u8]>) -> Result<()> {
@@ -87,6 +112,33 @@ impl Ctx {
         self.finalize()
     }
 
+    /// Generate a checksum from a stream of bytes, returning the formatted checksum alongside
+    /// the resolved part sizes and part checksums of AWS contexts.
+    pub async fn generate_output(
+        &mut self,
+        stream: impl Stream<Item = Result<Arc<[u8]>>>,
+    ) -> Result<GenerateOutput> {
+        let digest = self.generate(stream).await?;
+
+        Ok(GenerateOutput {
+            checksum: self.digest_to_string(&digest),
+            digest,
+            part_sizes: self.part_sizes(),
+            part_checksums: self.part_checksums(),
+        })
+    }
+
+    /// Compute the checksum of an in-memory buffer and format the digest. This sets the
+    /// file size to the length of the buffer, and finalizes the context so it cannot be
+    /// updated afterwards.
+    pub fn checksum_slice(&mut self, data: &[u8]) -> Result<String> {
+        self.set_file_size(Some(u64::try_from(data.len())?));
+        self.update(Arc::from(data))?;
+
+        let digest = self.finalize()?;
+        Ok(self.digest_to_string(&digest))
+    }
+
     /// Get the digest output.
     pub fn digest_to_string(&self, digest: &[u8]) -> String {
         match self {
@@ -102,6 +154,46 @@ impl Ctx {
         }
     }
 
+    /// Set whether an AWS context errors if its parts could not belong to an S3 object.
+    pub fn set_strict_s3(&mut self, strict_s3: bool) {
+        if let Ctx::AWSEtag(ctx) = self {
+            ctx.set_strict_s3(strict_s3);
+        }
+    }
+
+    /// Whether this is an AWS context using the part number syntax without knowing the file
+    /// size.
+    pub fn is_missing_file_size(&self) -> bool {
+        matches!(self, Ctx::AWSEtag(ctx) if ctx.is_missing_file_size())
+    }
+
+    /// Get a checkpoint of the checksum after it has processed `offset` bytes. AWS checksums
+    /// are checkpointed at the last completed part. This errors if the checksum cannot be
+    /// resumed.
+    pub fn checkpoint(&self, offset: u64) -> Result<CtxCheckpoint> {
+        match self {
+            Ctx::Regular(ctx) => Ok(CtxCheckpoint::new(offset).set_state(ctx.state()?)),
+            Ctx::AWSEtag(ctx) => Ok(ctx.checkpoint()),
+        }
+    }
+
+    /// Resume the checksum from a checkpoint.
+    pub fn resume(self, checkpoint: &CtxCheckpoint) -> Result<Self> {
+        match self {
+            Ctx::Regular(ctx) => Ok(Ctx::Regular(ctx.resume(checkpoint.state)?)),
+            Ctx::AWSEtag(ctx) => Ok(Ctx::AWSEtag(ctx.resume(checkpoint)?)),
+        }
+    }
+
+    /// Get the part sizes if this is an AWS checksum context. For contexts using the part
+    /// number syntax, this requires either the file size or that the checksum is finalized.
+    pub fn part_sizes(&self) -> Option<Vec<u64>> {
+        match self {
+            Ctx::Regular(_) => None,
+            Ctx::AWSEtag(ctx) => Some(ctx.get_part_sizes()),
+        }
+    }
+
     /// Get the encoded part checksums and their part sizes if this is an AWS checksum context.
     pub fn part_checksums(&self) -> Option<Vec<(u64, String)>> {
         match self {
@@ -121,6 +213,61 @@ impl Ctx {
         }
     }
 
+    /// The key used to order checksums when writing sums files. Standard checksums come first
+    /// ordered by `StandardCtx::output_rank`, followed by AWS checksums ordered by their part
+    /// sizes and then by the output rank. The formatted name breaks any remaining ties, such as
+    /// different endianness.
+    pub fn output_order(&self) -> (bool, Vec<u64>, u8, String) {
+        match self {
+            Ctx::Regular(ctx) => (false, vec![], ctx.output_rank(), ctx.to_string()),
+            Ctx::AWSEtag(ctx) => (
+                true,
+                ctx.get_part_sizes(),
+                ctx.output_rank(),
+                ctx.to_string(),
+            ),
+        }
+    }
+
+    /// Is this the no-op `null` checksum, which should never be written to sums files.
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Regular(regular) if regular.is_null())
+    }
+
+    /// The strength of the checksum algorithm against collisions. AWS checksums have the
+    /// strength of their underlying algorithm.
+    pub fn strength(&self) -> Strength {
+        match self {
+            Ctx::Regular(ctx) => ctx.strength(),
+            Ctx::AWSEtag(ctx) => ctx.strength(),
+        }
+    }
+
+    /// Is this an MD5 checksum, including AWS `ETag`s.
+    pub fn is_md5(&self) -> bool {
+        match self {
+            Ctx::Regular(ctx) => ctx.is_md5(),
+            Ctx::AWSEtag(ctx) => ctx.is_md5(),
+        }
+    }
+
+    /// Is this a CRC32C checksum, including AWS additional checksums.
+    pub fn is_crc32c(&self) -> bool {
+        match self {
+            Ctx::Regular(ctx) => ctx.is_crc32c(),
+            Ctx::AWSEtag(ctx) => ctx.is_crc32c(),
+        }
+    }
+
+    /// Are values of this checksum in the format that S3 uses for additional checksums, so that
+    /// they can be sent with uploads.
+    pub fn is_aws_additional_value(&self) -> bool {
+        match self {
+            Ctx::Regular(ctx) => ctx.is_aws_additional_value(),
+            Ctx::AWSEtag(ctx) => ctx.is_aws_additional_value(),
+        }
+    }
+
     /// Does this context represent an AWS-compatible single part checksum, i.e. is it a regular
     /// checksum that AWS supports directly or as an additional checksum.
     pub fn is_preferred_single_part(&self, provider: &Provider) -> bool {
@@ -128,6 +275,7 @@ impl Ctx {
     }
 }
 
+#[cfg(feature = "cloud")]
 impl From<Ctx> for ChecksumAlgorithm {
     fn from(ctx: Ctx) -> Self {
         let ctx = match ctx {
@@ -160,11 +308,12 @@ impl FromStr for Ctx {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let aws_etag = AWSETagCtx::from_str(s);
-        if aws_etag.is_err() {
-            Ok(Self::Regular(StandardCtx::from_str(s)?))
+        // Only parse standard checksums if this is not meant to be an AWS checksum, so that
+        // AWS-specific errors are not hidden by a standard checksum error.
+        if AWSETagCtx::is_aws_spec(s) {
+            Ok(Self::AWSEtag(AWSETagCtx::from_str(s)?))
         } else {
-            Ok(Self::AWSEtag(aws_etag?))
+            Ok(Self::Regular(StandardCtx::from_str(s)?))
         }
     }
 }
@@ -179,6 +328,104 @@ pub(crate) mod test {
     use tokio::fs::File;
     use tokio::join;
 
+    #[test]
+    fn test_checksum_bytes() -> Result<()> {
+        assert_eq!(
+            checksum_bytes("sha256", b"hello world")?,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9" // pragma: allowlist secret
+        );
+        assert_eq!(
+            checksum_bytes("md5-aws-4b", b"hello world")?,
+            "177e85e8bb233bd57a6aabda201a0c2c-4b" // pragma: allowlist secret
+        );
+        assert!(checksum_bytes("sha257", b"hello world").is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_output() -> Result<()> {
+        let data = b"hello world";
+        let stream =
+            || futures_util::stream::iter(data.chunks(4).map(|chunk| Ok(Arc::from(chunk))));
+
+        let mut ctx = Ctx::from_str("md5-aws-4b")?;
+        let output = ctx.generate_output(stream()).await?;
+        assert_eq!(output.checksum, checksum_bytes("md5-aws-4b", data)?);
+        assert_eq!(output.part_sizes, Some(vec![4]));
+        assert_eq!(
+            output.part_checksums,
+            Some(vec![
+                (4, checksum_bytes("md5", b"hell")?),
+                (4, checksum_bytes("md5", b"o wo")?),
+                (3, checksum_bytes("md5", b"rld")?),
+            ])
+        );
+
+        let mut ctx = Ctx::from_str("md5")?;
+        let output = ctx.generate_output(stream()).await?;
+        assert_eq!(output.checksum, checksum_bytes("md5", data)?);
+        assert_eq!(output.part_sizes, None);
+        assert_eq!(output.part_checksums, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_checksum_empty() -> Result<()> {
+        for (checksum, expected) in [
+            ("md5", "d41d8cd98f00b204e9800998ecf8427e"), // pragma: allowlist secret
+            ("sha1", "da39a3ee5e6b4b0d3255bfef95601890afd80709"), // pragma: allowlist secret
+            (
+                "sha256",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", // pragma: allowlist secret
+            ),
+            ("crc32", "00000000"),
+            ("crc32-le", "00000000"),
+            ("crc32c", "00000000"),
+            ("crc32c-le", "00000000"),
+            ("crc64nvme", "0000000000000000"),
+            ("crc64nvme-le", "0000000000000000"),
+            ("null", ""),
+        ] {
+            assert_eq!(checksum_bytes(checksum, b"")?, expected, "{checksum}");
+        }
+
+        // Empty files have a single empty part with the minimum part size for any part mode.
+        for part_mode in ["1", "10", "1b", "8mib", "1mib-2mib"] {
+            for (checksum, expected) in [
+                ("md5", "59adb24ef3cdbe0297f05b395827453f-1b"), // pragma: allowlist secret
+                ("aws-etag", "59adb24ef3cdbe0297f05b395827453f-1b"), // pragma: allowlist secret
+                ("sha1", "be1bdec0aa74b4dcb079943e70528096cca985f8-1b"), // pragma: allowlist secret
+                (
+                    "sha256",
+                    "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456-1b", // pragma: allowlist secret
+                ),
+                ("crc32", "2144df1c-1b"),
+                ("crc32c", "48674bc7-1b"),
+                ("crc64nvme", "0cefcfc4d49091bd-1b"),
+            ] {
+                let checksum = if checksum == "aws-etag" {
+                    format!("aws-etag-{}", part_mode)
+                } else {
+                    format!("{}-aws-{}", checksum, part_mode)
+                };
+                assert_eq!(checksum_bytes(&checksum, b"")?, expected, "{checksum}");
+            }
+        }
+
+        let mut ctx = Ctx::from_str("md5-aws-2")?;
+        ctx.set_file_size(Some(0));
+        let output = ctx.generate_output(futures_util::stream::empty()).await?;
+        assert_eq!(output.part_sizes, Some(vec![1]));
+        assert_eq!(
+            output.part_checksums,
+            Some(vec![(0, checksum_bytes("md5", b"")?)])
+        );
+
+        Ok(())
+    }
+
     pub(crate) async fn test_checksum(checksum: &str, expected: &str) -> Result<()> {
         let test_file = TestFileBuilder::default().generate_test_defaults()?;
         let mut reader = channel_reader(File::open(test_file).await?).await;
@@ -0,0 +1,207 @@
+//! Rolling checksums of fixed-size blocks, for rsync-style delta detection.
+//!
+
+use crate::checksum::file::Checksum;
+use crate::error::Error::ParseError;
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// The version of the block sums format. This is versioned separately from sums files.
+pub const BLOCKS_FILE_VERSION: &str = "1";
+
+/// An adler32-style weak checksum, as used by rsync. Unlike other checksums, the window that it
+/// covers can be moved forward by one byte in constant time, so the blocks of a known file can
+/// be found at any offset of another file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RollingChecksum {
+    a: u16,
+    b: u16,
+    len: u16,
+}
+
+impl RollingChecksum {
+    /// Compute the checksum of the data.
+    pub fn new(data: &[u8]) -> Self {
+        let mut checksum = Self::default();
+        checksum.update(data);
+        checksum
+    }
+
+    /// Extend the window with more data. All arithmetic is modulo 2^16.
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.a = self.a.wrapping_add(u16::from(byte));
+            self.b = self.b.wrapping_add(self.a);
+            self.len = self.len.wrapping_add(1);
+        }
+    }
+
+    /// Move the window forward by one byte, removing the first byte of the window and adding
+    /// the next byte.
+    pub fn roll(&mut self, out: u8, next: u8) {
+        self.a = self
+            .a
+            .wrapping_sub(u16::from(out))
+            .wrapping_add(u16::from(next));
+        self.b = self
+            .b
+            .wrapping_sub(self.len.wrapping_mul(u16::from(out)))
+            .wrapping_add(self.a);
+    }
+
+    /// Get the checksum of the current window.
+    pub fn digest(&self) -> u32 {
+        u32::from(self.a) | (u32::from(self.b) << 16)
+    }
+}
+
+/// The checksums of a single block.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BlockSum {
+    /// The hex encoded `RollingChecksum` of the block.
+    pub(crate) weak: Checksum,
+    /// The hex encoded sha256 of the block.
+    pub(crate) strong: Checksum,
+}
+
+impl BlockSum {
+    /// Compute the checksums of a block.
+    pub fn new(block: &[u8]) -> Self {
+        Self {
+            weak: Self::format_weak(RollingChecksum::new(block).digest()),
+            strong: Checksum::new(hex::encode(Sha256::digest(block))),
+        }
+    }
+
+    /// Format a weak checksum in the same way as it is stored.
+    pub fn format_weak(digest: u32) -> Checksum {
+        Checksum::new(format!("{:08x}", digest))
+    }
+}
+
+/// A weak rolling checksum and a strong checksum for each fixed-size block of a file. This is
+/// the signature that an rsync-style delta algorithm needs to find the unchanged blocks of a
+/// file. The last block is shorter if the size is not a multiple of the block size.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct BlockSums {
+    /// The version of the block sums format.
+    pub(crate) version: String,
+    /// The size of the file in bytes.
+    pub(crate) size: u64,
+    /// The size of each block in bytes.
+    pub(crate) block_size: u64,
+    /// The checksums of each block in order.
+    pub(crate) blocks: Vec<BlockSum>,
+}
+
+impl BlockSums {
+    /// Create empty block sums.
+    pub fn new(block_size: u64) -> Self {
+        Self {
+            version: BLOCKS_FILE_VERSION.to_string(),
+            size: 0,
+            block_size,
+            blocks: vec![],
+        }
+    }
+
+    /// Add the next block.
+    pub fn push(&mut self, block: &[u8]) {
+        self.size += block.len() as u64;
+        self.blocks.push(BlockSum::new(block));
+    }
+
+    /// Index the blocks by their weak checksum so that they can be found in constant time.
+    pub fn index(&self) -> Result<BlockIndex<'_>> {
+        let mut blocks: HashMap<_, Vec<_>> = HashMap::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            let weak = u32::from_str_radix(block.weak.as_str(), 16).map_err(|_| {
+                ParseError(format!("invalid weak checksum: {}", block.weak.as_str()))
+            })?;
+            blocks.entry(weak).or_default().push(i);
+        }
+
+        Ok(BlockIndex { sums: self, blocks })
+    }
+}
+
+/// Block sums indexed by the weak checksum of each block.
+#[derive(Debug)]
+pub struct BlockIndex<'a> {
+    sums: &'a BlockSums,
+    blocks: HashMap<u32, Vec<usize>>,
+}
+
+impl BlockIndex<'_> {
+    /// Find a block that matches a window of data with the rolling checksum. The strong
+    /// checksum is only computed if the weak checksum of a block matches.
+    pub fn find(&self, checksum: &RollingChecksum, window: &[u8]) -> Option<usize> {
+        let candidates = self.blocks.get(&checksum.digest())?;
+        let strong = Checksum::new(hex::encode(Sha256::digest(window)));
+        candidates
+            .iter()
+            .copied()
+            .find(|&i| self.sums.blocks[i].strong == strong)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    #[test]
+    fn rolling_checksum() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(5000).collect();
+        let window = 1000;
+
+        let mut rolling = RollingChecksum::new(&data[..window]);
+        for start in 1..data.len() - window {
+            rolling.roll(data[start - 1], data[start + window - 1]);
+            assert_eq!(rolling, RollingChecksum::new(&data[start..start + window]));
+        }
+
+        // The same as rsync, with `a` in the low and `b` in the high 16 bits.
+        assert_eq!(RollingChecksum::new(b"abc").digest(), 0x024a0126);
+    }
+
+    #[test]
+    fn block_sums_find() -> Result<()> {
+        // Data that does not repeat, so that each block is only found where it was placed.
+        let mut state = 1u32;
+        let data: Vec<u8> = (0..10000)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let mut sums = BlockSums::new(1000);
+        data.chunks(1000).for_each(|block| sums.push(block));
+        assert_eq!(sums.size, 10000);
+        assert_eq!(sums.blocks.len(), 10);
+
+        // Inserting bytes shifts the blocks, which are found again at their new offsets.
+        let mut changed = data.clone();
+        changed.splice(1500..1500, [1, 2, 3]);
+        let index = sums.index()?;
+        let mut rolling = RollingChecksum::new(&changed[..1000]);
+        let mut found = vec![];
+        for start in 0..changed.len() - 1000 {
+            if start > 0 {
+                rolling.roll(changed[start - 1], changed[start + 999]);
+            }
+            if let Some(block) = index.find(&rolling, &changed[start..start + 1000]) {
+                found.push((start, block));
+            }
+        }
+
+        assert!(found.contains(&(0, 0)));
+        assert!(!found.iter().any(|(_, block)| *block == 1));
+        assert!(found.contains(&(2003, 2)));
+        assert!(found.contains(&(8003, 8)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+//! Known-answer tests for every checksum algorithm, used to validate a build.
+//!
+
+use crate::checksum::aws_etag::MIB;
+use crate::checksum::Ctx;
+use crate::error::Result;
+use crate::stats::{AlgorithmResult, SelfTestStats, VectorFailure};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The size of chunks used to compute the checksums a second time. This is deliberately not
+/// a power of two so that chunks do not line up with part boundaries or SIMD block sizes.
+const CHUNK_SIZE: usize = 4093;
+
+/// An embedded input of a known-answer vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// No data.
+    Empty,
+    /// The bytes `abc`.
+    Abc,
+    /// 1 MiB where each byte is its offset modulo 251.
+    Pattern,
+}
+
+impl Input {
+    /// The name of the input in the output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Input::Empty => "empty",
+            Input::Abc => "abc",
+            Input::Pattern => "1mib-pattern",
+        }
+    }
+
+    /// The data of the input.
+    pub fn data(&self) -> Vec<u8> {
+        match self {
+            Input::Empty => vec![],
+            Input::Abc => b"abc".to_vec(),
+            Input::Pattern => (0..MIB).map(|i| (i % 251) as u8).collect(),
+        }
+    }
+}
+
+/// A checksum with a known expected value for an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownAnswer {
+    checksum: &'static str,
+    input: Input,
+    expected: &'static str,
+}
+
+impl KnownAnswer {
+    /// Create a new known-answer vector.
+    pub const fn new(checksum: &'static str, input: Input, expected: &'static str) -> Self {
+        Self {
+            checksum,
+            input,
+            expected,
+        }
+    }
+
+    /// Compute the checksum over the data, updating the checksum with chunks of the chunk size.
+    fn compute(&self, data: &[u8], chunk_size: usize) -> Result<String> {
+        let mut ctx = Ctx::from_str(self.checksum)?;
+        ctx.set_file_size(Some(u64::try_from(data.len())?));
+        for chunk in data.chunks(chunk_size) {
+            ctx.update(Arc::from(chunk))?;
+        }
+
+        let digest = ctx.finalize()?;
+        Ok(ctx.digest_to_string(&digest))
+    }
+
+    /// Run the vector, computing the checksum both in a single update and in chunks. Returns
+    /// a failure if either result does not match the expected value.
+    pub fn run(&self, data: &[u8]) -> Option<VectorFailure> {
+        let failure = |actual, error| VectorFailure {
+            input: self.input.name().to_string(),
+            expected: self.expected.to_string(),
+            actual,
+            error,
+        };
+
+        for chunk_size in [data.len().max(1), CHUNK_SIZE] {
+            match self.compute(data, chunk_size) {
+                Ok(actual) if actual == self.expected => {}
+                Ok(actual) => return Some(failure(Some(actual), None)),
+                Err(err) => return Some(failure(None, Some(err.to_string()))),
+            }
+        }
+
+        None
+    }
+}
+
+/// The known-answer vectors. The expected values come from the reference implementations of
+/// each algorithm, and the AWS values combine the part checksums in the same way as S3.
+pub const KNOWN_ANSWERS: &[KnownAnswer] = &[
+    KnownAnswer::new("md5", Input::Empty, "d41d8cd98f00b204e9800998ecf8427e"),
+    KnownAnswer::new("md5", Input::Abc, "900150983cd24fb0d6963f7d28e17f72"),
+    KnownAnswer::new("md5", Input::Pattern, "8f293a2f6c19b345152f7a49bb4c643c"),
+    KnownAnswer::new(
+        "sha1",
+        Input::Empty,
+        "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+    ),
+    KnownAnswer::new(
+        "sha1",
+        Input::Abc,
+        "a9993e364706816aba3e25717850c26c9cd0d89d",
+    ),
+    KnownAnswer::new(
+        "sha1",
+        Input::Pattern,
+        "c2fc4cb20f1301a6b0dd211c19e69a13925dbe40",
+    ),
+    KnownAnswer::new(
+        "sha256",
+        Input::Empty,
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+    ),
+    KnownAnswer::new(
+        "sha256",
+        Input::Abc,
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+    ),
+    KnownAnswer::new(
+        "sha256",
+        Input::Pattern,
+        "631b84027d6b9e52b539c4e8373622d23032dfadc64d60af87339c9037e4f769",
+    ),
+    KnownAnswer::new("crc32", Input::Empty, "00000000"),
+    KnownAnswer::new("crc32", Input::Abc, "352441c2"),
+    KnownAnswer::new("crc32", Input::Pattern, "ef0e6054"),
+    KnownAnswer::new("crc32c", Input::Empty, "00000000"),
+    KnownAnswer::new("crc32c", Input::Abc, "364b3fb7"),
+    KnownAnswer::new("crc32c", Input::Pattern, "dc3e0071"),
+    KnownAnswer::new("crc32c-le", Input::Abc, "b73f4b36"),
+    KnownAnswer::new("crc64nvme", Input::Empty, "0000000000000000"),
+    KnownAnswer::new("crc64nvme", Input::Abc, "05e5cabb3fc1faeb"),
+    KnownAnswer::new("crc64nvme", Input::Pattern, "8821d9f150fec9fc"),
+    // An empty input is a single empty part, so the part size is clamped to one byte.
+    KnownAnswer::new(
+        "md5-aws",
+        Input::Empty,
+        "59adb24ef3cdbe0297f05b395827453f-1b",
+    ),
+    KnownAnswer::new(
+        "md5-aws-256kib",
+        Input::Pattern,
+        "4b8abdf3d85fcc6e4f24697a3eb95a90-262144b",
+    ),
+    KnownAnswer::new(
+        "sha256-aws-4",
+        Input::Pattern,
+        "a75548e3a65d37683dd3ec5d62bbc249636b2deb2c70986ef8ce4601bfaa74bf-262144b",
+    ),
+    KnownAnswer::new("crc32c-aws-300kib", Input::Pattern, "2de16600-307200b"),
+    KnownAnswer::new(
+        "crc64nvme-aws-512kib",
+        Input::Pattern,
+        "b3c6805759539ce5-524288b",
+    ),
+];
+
+/// Run known-answer vectors and report the results of each checksum algorithm in the order
+/// that they first appear.
+pub fn run(known_answers: &[KnownAnswer]) -> SelfTestStats {
+    let inputs = [Input::Empty, Input::Abc, Input::Pattern].map(|input| (input, input.data()));
+
+    let mut results: Vec<AlgorithmResult> = vec![];
+    for known_answer in known_answers {
+        let (_, data) = inputs
+            .iter()
+            .find(|(input, _)| *input == known_answer.input)
+            .expect("all inputs are embedded");
+
+        let position = match results
+            .iter()
+            .position(|result| result.checksum == known_answer.checksum)
+        {
+            Some(position) => position,
+            None => {
+                results.push(AlgorithmResult::new(known_answer.checksum.to_string()));
+                results.len() - 1
+            }
+        };
+        results[position].add(known_answer.run(data));
+    }
+
+    SelfTestStats::new(results)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    #[test]
+    fn known_answers() {
+        let stats = run(KNOWN_ANSWERS);
+        assert_eq!(stats.n_failed(), 0, "{:?}", stats);
+        assert!(stats.passed);
+        assert!(stats.results.iter().any(|result| result.checksum == "md5"));
+        assert!(stats
+            .results
+            .iter()
+            .any(|result| result.checksum == "md5-aws-256kib"));
+    }
+
+    #[test]
+    fn known_answer_failure() {
+        let stats = run(&[
+            KnownAnswer::new("md5", Input::Abc, "900150983cd24fb0d6963f7d28e17f72"),
+            KnownAnswer::new("md5", Input::Empty, "00"),
+            KnownAnswer::new("crc32", Input::Abc, "352441c2"),
+            KnownAnswer::new("unknown", Input::Abc, "00"),
+        ]);
+        assert!(!stats.passed);
+        assert_eq!(stats.n_failed(), 2);
+
+        let md5 = &stats.results[0];
+        assert!(!md5.passed);
+        assert_eq!(md5.failures.len(), 1);
+        assert_eq!(md5.failures[0].input, "empty");
+        assert_eq!(
+            md5.failures[0].actual.as_deref(),
+            Some("d41d8cd98f00b204e9800998ecf8427e")
+        );
+        assert!(stats.results[1].passed);
+        assert!(stats.results[2].failures[0].error.is_some());
+    }
+}
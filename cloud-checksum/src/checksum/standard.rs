@@ -1,11 +1,11 @@
 //! Standard checksum algorithms
 //!
 
-use crate::cli::{Checksum, Endianness};
-use crate::error::Error::ParseError;
+use crate::cli::{Checksum, Endianness, Strength};
+use crate::error::Error::{GenerateError, ParseError};
 use crate::error::{Error, Result};
 use crate::io::Provider;
-use crc32c::crc32c_append;
+use crc32c::{crc32c_append, crc32c_combine};
 use md5::Digest;
 use std::cmp::Ordering;
 use std::fmt;
@@ -32,6 +32,9 @@ pub enum StandardCtx {
     SHA256(Option<sha2::Sha256>),
     /// Calculate the QuickXor checksum.
     QuickXor,
+    /// A no-op checksum that consumes data and produces an empty digest. This is used for
+    /// benchmarking IO without the cost of hashing.
+    Null,
 }
 
 impl Debug for StandardCtx {
@@ -42,7 +45,7 @@ impl Debug for StandardCtx {
 
 impl Ord for StandardCtx {
     fn cmp(&self, other: &Self) -> Ordering {
-        (self.to_u8(), self.endianness()).cmp(&(other.to_u8(), self.endianness()))
+        (self.to_u8(), self.endianness()).cmp(&(other.to_u8(), other.endianness()))
     }
 }
 
@@ -90,7 +93,8 @@ impl FromStr for StandardCtx {
             Checksum::CRC32 => Self::crc32(),
             Checksum::CRC32C => Self::crc32c(),
             Checksum::CRC64NVME => Self::crc64nvme(),
-            _ => return Err(ParseError("unsupported checksum algorithm".to_string())),
+            Checksum::Null => Self::Null,
+            _ => return Err(Checksum::unsupported_error(s)),
         };
         Ok(ctx)
     }
@@ -106,6 +110,7 @@ impl From<&StandardCtx> for Checksum {
             StandardCtx::CRC32(_, _) => Self::CRC32,
             StandardCtx::CRC32C(_, _) => Self::CRC32C,
             StandardCtx::QuickXor => Self::QuickXor,
+            StandardCtx::Null => Self::Null,
         }
     }
 }
@@ -130,6 +135,7 @@ impl Display for StandardCtx {
                 Endianness::BigEndian => write!(f, "crc64nvme"),
             },
             StandardCtx::QuickXor => todo!(),
+            StandardCtx::Null => write!(f, "null"),
         }
     }
 }
@@ -209,6 +215,7 @@ impl StandardCtx {
             StandardCtx::CRC32C(ctx, _) => *ctx = crc32c_append(*ctx, &data),
             StandardCtx::CRC64NVME(Some(ctx), _) => ctx.write(&data),
             StandardCtx::QuickXor => todo!(),
+            StandardCtx::Null => {}
             _ => panic!("cannot call update with empty context"),
         };
 
@@ -237,6 +244,7 @@ impl StandardCtx {
                 Endianness::BigEndian => ctx.take().expect(msg).finish().to_be_bytes().to_vec(),
             },
             StandardCtx::QuickXor => todo!(),
+            StandardCtx::Null => vec![],
         };
 
         Ok(digest)
@@ -252,6 +260,7 @@ impl StandardCtx {
             StandardCtx::CRC32C(_, endianness) => Self::crc32c().with_endianness(*endianness),
             StandardCtx::CRC64NVME(_, endianness) => Self::crc64nvme().with_endianness(*endianness),
             StandardCtx::QuickXor => todo!(),
+            StandardCtx::Null => Self::Null,
         }
     }
 
@@ -260,6 +269,49 @@ impl StandardCtx {
         hex::encode(digest)
     }
 
+    /// The name of the hash algorithm in a Subresource Integrity string, if the checksum can
+    /// be used in one. SRI only supports the SHA-2 family of hashes, of which only SHA256 is
+    /// supported here.
+    pub fn sri_algorithm(&self) -> Option<&'static str> {
+        match self {
+            StandardCtx::SHA256(_) => Some("sha256"),
+            _ => None,
+        }
+    }
+
+    /// Get the state of the checksum so that it can be resumed. Only checksums where the
+    /// state is the value of the checksum itself, i.e. CRC32 and CRC32C, are resumable.
+    pub fn state(&self) -> Result<Option<u32>> {
+        match self {
+            StandardCtx::CRC32(Some(ctx), _) => Ok(Some(ctx.clone().finalize())),
+            StandardCtx::CRC32C(ctx, _) => Ok(Some(*ctx)),
+            StandardCtx::Null => Ok(None),
+            ctx => Err(Self::not_resumable(ctx)),
+        }
+    }
+
+    /// Resume the checksum from a state returned by `state`.
+    pub fn resume(self, state: Option<u32>) -> Result<Self> {
+        match (self, state) {
+            (StandardCtx::CRC32(_, endianness), Some(state)) => Ok(StandardCtx::CRC32(
+                Some(crc32fast::Hasher::new_with_initial(state)),
+                endianness,
+            )),
+            (StandardCtx::CRC32C(_, endianness), Some(state)) => {
+                Ok(StandardCtx::CRC32C(state, endianness))
+            }
+            (StandardCtx::Null, _) => Ok(StandardCtx::Null),
+            (ctx, _) => Err(Self::not_resumable(&ctx)),
+        }
+    }
+
+    fn not_resumable(ctx: &Self) -> Error {
+        GenerateError(format!(
+            "`{}` is not resumable, use an AWS checksum such as `{}-aws` to resume at part boundaries",
+            ctx, ctx
+        ))
+    }
+
     /// Extract the endianness if this is a CRC variant.
     pub fn endianness(&self) -> Option<Endianness> {
         match self {
@@ -280,9 +332,55 @@ impl StandardCtx {
             StandardCtx::SHA1(_) => 5,
             StandardCtx::SHA256(_) => 6,
             StandardCtx::QuickXor => 7,
+            StandardCtx::Null => 8,
+        }
+    }
+
+    /// The position of this checksum when writing sums files. Unlike `to_u8`, which defines
+    /// the preferred order for copying, this is a fixed order used to keep output stable:
+    /// `md5`, `sha1`, `sha256`, `crc32`, `crc32c`, `crc64nvme`, `quickxor` and then `null`.
+    pub fn output_rank(&self) -> u8 {
+        match self {
+            StandardCtx::MD5(_) => 1,
+            StandardCtx::SHA1(_) => 2,
+            StandardCtx::SHA256(_) => 3,
+            StandardCtx::CRC32(_, _) => 4,
+            StandardCtx::CRC32C(_, _) => 5,
+            StandardCtx::CRC64NVME(_, _) => 6,
+            StandardCtx::QuickXor => 7,
+            StandardCtx::Null => 8,
         }
     }
 
+    /// The strength of the checksum algorithm against collisions.
+    pub fn strength(&self) -> Strength {
+        match self {
+            StandardCtx::MD5(_) => Strength::Md5,
+            StandardCtx::SHA1(_) => Strength::Sha1,
+            StandardCtx::SHA256(_) => Strength::Sha256,
+            StandardCtx::CRC32(_, _)
+            | StandardCtx::CRC32C(_, _)
+            | StandardCtx::CRC64NVME(_, _)
+            | StandardCtx::QuickXor
+            | StandardCtx::Null => Strength::Crc,
+        }
+    }
+
+    /// Is this the no-op `null` checksum.
+    pub fn is_null(&self) -> bool {
+        matches!(self, StandardCtx::Null)
+    }
+
+    /// Is this an MD5 checksum.
+    pub fn is_md5(&self) -> bool {
+        matches!(self, StandardCtx::MD5(_))
+    }
+
+    /// Is this a CRC32C checksum.
+    pub fn is_crc32c(&self) -> bool {
+        matches!(self, StandardCtx::CRC32C(_, _))
+    }
+
     /// Is this a preferred cloud checksum for copying files.
     pub fn is_preferred_cloud_ctx(&self, provider: &Provider) -> bool {
         if provider.is_s3() {
@@ -294,18 +392,49 @@ impl StandardCtx {
 
     /// Is this an AWS-compatible checksum context.
     pub fn is_aws_ctx(&self) -> bool {
-        !matches!(self, StandardCtx::QuickXor)
+        !matches!(self, StandardCtx::QuickXor | StandardCtx::Null)
     }
 
     /// Is this an AWS additional checksum that can be specified.
     pub fn is_aws_additional_ctx(&self) -> bool {
-        !matches!(self, StandardCtx::QuickXor | StandardCtx::MD5(_))
+        !matches!(
+            self,
+            StandardCtx::QuickXor | StandardCtx::MD5(_) | StandardCtx::Null
+        )
+    }
+
+    /// Are values of this checksum in the format that S3 uses for additional checksums, i.e.
+    /// an additional checksum that is not a little-endian CRC.
+    pub fn is_aws_additional_value(&self) -> bool {
+        self.is_aws_additional_ctx() && self.endianness() != Some(Endianness::LittleEndian)
     }
 }
 
+/// Combine the CRC32 values of consecutive parts into the CRC32 of their concatenation. Each
+/// part is given as its length in bytes and its CRC32, so the parts do not have to be read again.
+pub fn combine_crc32(parts: Vec<(u64, u32)>) -> u32 {
+    parts
+        .into_iter()
+        .fold(crc32fast::Hasher::new(), |mut hasher, (len, crc)| {
+            hasher.combine(&crc32fast::Hasher::new_with_initial_len(crc, len));
+            hasher
+        })
+        .finalize()
+}
+
+/// Combine the CRC32C values of consecutive parts into the CRC32C of their concatenation. Each
+/// part is given as its length in bytes and its CRC32C, so the parts do not have to be read again.
+pub fn combine_crc32c(parts: Vec<(u64, u32)>) -> Result<u32> {
+    parts.into_iter().try_fold(0, |combined, (len, crc)| {
+        Ok(crc32c_combine(combined, crc, usize::try_from(len)?))
+    })
+}
+
 #[cfg(test)]
 pub(crate) mod test {
+    use super::*;
     use crate::checksum::test::test_checksum;
+    use crate::checksum::Ctx;
     use anyhow::Result;
 
     pub(crate) const EXPECTED_MD5_SUM: &str = "d93e71879054f205ede90d35c8081ca5"; // pragma: allowlist secret
@@ -317,6 +446,57 @@ pub(crate) mod test {
     pub(crate) const EXPECTED_CRC32C_BE_SUM: &str = "4920106a";
     pub(crate) const EXPECTED_CRC32C_LE_SUM: &str = "6a102049";
 
+    #[test]
+    fn test_combine_crcs() -> Result<()> {
+        let data: Vec<u8> = (0..=255u8).cycle().take(10000).collect();
+        let parts = |crc: fn(&[u8]) -> u32| {
+            [0, 1, 4096, 0, 5000, 903]
+                .into_iter()
+                .scan(0, |start, len| {
+                    let part = &data[*start..*start + len];
+                    *start += len;
+                    Some((len as u64, crc(part)))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            combine_crc32(parts(crc32fast::hash)),
+            crc32fast::hash(&data)
+        );
+        assert_eq!(
+            combine_crc32c(parts(crc32c::crc32c))?,
+            crc32c::crc32c(&data)
+        );
+        assert_eq!(combine_crc32(vec![]), crc32fast::hash(&[]));
+        assert_eq!(combine_crc32c(vec![])?, crc32c::crc32c(&[]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported() {
+        for checksum in ["sha257", "quick-xor", "sha257-le"] {
+            let err = checksum.parse::<Ctx>().unwrap_err().to_string();
+            assert!(err.contains("unsupported checksum algorithm"), "{err}");
+            assert!(
+                err.contains("md5, sha1, sha256, crc32, crc32c, crc64nvme"),
+                "{err}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_null() -> Result<()> {
+        test_checksum("null", "").await?;
+
+        assert!("null".parse::<Ctx>()?.is_null());
+        assert!("null-aws-1".parse::<Ctx>().is_err());
+        assert!(!"md5".parse::<Ctx>()?.is_null());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_md5() -> Result<()> {
         test_checksum("md5", EXPECTED_MD5_SUM).await
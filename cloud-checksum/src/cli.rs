@@ -1,31 +1,65 @@
 //! Cli commands and code.
 //!
 
-use crate::checksum::file::SumsFile;
+use crate::checksum::aws_etag::predict_e_tag;
+use crate::checksum::file::{SumsFile, SumsFormat, SumsStorage};
+use crate::checksum::rolling::BlockSums;
+use crate::checksum::self_test;
+use crate::checksum::standard::StandardCtx;
 use crate::checksum::Ctx;
 use crate::error::Error;
-use crate::error::Error::{CheckError, ParseError};
+use crate::error::Error::{CheckError, CopyError, GenerateError, NotFound, ParseError, Timeout};
 use crate::error::Result;
-use crate::io::sums::channel::ChannelReader;
+#[cfg(not(feature = "cloud"))]
+use crate::io::s3_unsupported;
+#[cfg(feature = "cloud")]
+use crate::io::sums::aws::{S3Builder, S3};
+use crate::io::sums::cache::DownloadCache;
+use crate::io::sums::channel::{ChannelMetrics, ChannelReader};
+use crate::io::sums::decompress::Decompress;
+use crate::io::sums::normalize::NormalizeNewlines;
+use crate::io::sums::tee::TeeReader;
 use crate::io::sums::ObjectSumsBuilder;
+use crate::io::Client;
 use crate::io::{create_s3_client, default_s3_client, Provider};
-use crate::stats::{CheckStats, ChecksumPair, CopyStats, GenerateFileStats, GenerateStats};
-use crate::task::check::{CheckTask, CheckTaskBuilder, GroupBy};
+use crate::profile::Profile;
+use crate::stats::{
+    BisectStats, CheckStats, ChecksumPair, CopyStats, DatasetIdStats, DiffStats, GenerateFileStats,
+    GenerateStats, ObjectInfo, PruneStats, SelfTestStats, SumsDiff, TouchStats,
+};
+use crate::task::bisect::BisectTaskBuilder;
+use crate::task::blocks::BlocksTaskBuilder;
+use crate::task::cat::CatTaskBuilder;
+use crate::task::check::{CheckTask, CheckTaskBuilder, GroupBy, SumsKey};
 use crate::task::copy::CopyTaskBuilder;
+use crate::task::dedup::DedupCache;
+use crate::task::diff::DiffTaskBuilder;
 use crate::task::generate::{GenerateTaskBuilder, SumCtxPairs};
-use aws_sdk_s3::Client;
-use clap::{Args, Parser, Subcommand, ValueEnum};
-use humantime::Duration;
+use crate::task::prune::PruneTaskBuilder;
+use crate::task::repair::RepairTaskBuilder;
+use crate::task::touch::TouchTaskBuilder;
+use clap::builder::RangedU64ValueParser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use futures_util::{stream, StreamExt};
+use humantime::{parse_rfc3339_weak, Duration};
 use parse_size::parse_size;
+use schemars::schema::RootSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::{to_string, to_string_pretty};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::ffi::OsString;
 use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::io::stdin;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{stdin, stdout, AsyncRead};
+use tracing::level_filters::LevelFilter;
+use tracing::{info, info_span, warn, Instrument};
+use tracing_subscriber::EnvFilter;
 
 /// Args for the checksum-cloud CLI.
 #[derive(Parser, Debug)]
@@ -35,6 +69,16 @@ pub struct Command {
     /// checksum will be saved to the partial checksum file.
     #[arg(global = true, short, long, env)]
     pub timeout: Option<Duration>,
+    /// Use the default options from a named profile in the profiles file, e.g. to always
+    /// generate a team's standard set of checksums. A profile can set the `checksum`,
+    /// `part-size`, `concurrency` and `format` options. Options set on the command line or
+    /// using environment variables take precedence over the profile.
+    #[arg(global = true, long, env)]
+    pub checksum_profile: Option<String>,
+    /// The profiles file to read the `--checksum-profile` from. Defaults to
+    /// `~/.config/cloud-checksum/profiles.toml`, or under `$XDG_CONFIG_HOME` if it is set.
+    #[arg(global = true, long, env, requires = "checksum_profile")]
+    pub profiles_file: Option<PathBuf>,
     /// The subcommands for cloud-checksum.
     #[command(subcommand)]
     pub commands: Subcommands,
@@ -47,14 +91,15 @@ pub struct Command {
     /// Options related to credentials.
     #[command(flatten)]
     pub credentials: Credentials,
+    /// Options related to logging.
+    #[command(flatten)]
+    pub logging: Logging,
 }
 
 impl Command {
     /// Parse args and set default values.
     pub fn parse_args() -> Result<Self> {
-        let args = Self::parse();
-        Self::validate(&args)?;
-        Ok(args)
+        Self::parse_from_iter(std::env::args_os())
     }
 
     /// Parse the command from an iterator.
@@ -63,26 +108,194 @@ impl Command {
         I: IntoIterator<Item = T>,
         T: Into<OsString> + Clone,
     {
-        let args = Self::parse_from(iter);
+        let matches = Self::command().get_matches_from(iter);
+        let mut args = Self::from_arg_matches(&matches)?;
+        args.apply_profile(&matches)?;
+        if args.output.ndjson {
+            args.output.output_mode = OutputMode::Ndjson;
+        }
+        args.read_input_files()?;
         Self::validate(&args)?;
         Ok(args)
     }
 
+    /// Set any options from the `--checksum-profile` which were not explicitly set on the
+    /// command line or using environment variables.
+    pub fn apply_profile(&mut self, matches: &ArgMatches) -> Result<()> {
+        let Some(name) = &self.checksum_profile else {
+            return Ok(());
+        };
+        let file = match &self.profiles_file {
+            Some(file) => file.clone(),
+            None => Profile::default_file().ok_or_else(|| {
+                ParseError("could not determine the location of the profiles file".to_string())
+            })?,
+        };
+        let profile = Profile::load(&file, name)?;
+
+        let is_unset = |matches: &ArgMatches, id: &str| {
+            !matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+        let sub_matches = matches
+            .subcommand()
+            .map(|(_, sub_matches)| sub_matches)
+            .unwrap_or(matches);
+
+        if let Some(format) = profile.format {
+            if is_unset(sub_matches, "format") {
+                self.output.format = format;
+            }
+        }
+
+        match &mut self.commands {
+            Subcommands::Generate(generate) => {
+                if let Some(checksum) = profile.checksum {
+                    if is_unset(sub_matches, "checksum") {
+                        generate.checksum = checksum;
+                    }
+                }
+                if let Some(concurrency) = profile.concurrency {
+                    if is_unset(sub_matches, "concurrency") {
+                        generate.concurrency = concurrency.max(1);
+                    }
+                }
+            }
+            Subcommands::Copy(copy) => {
+                if let Some(part_size) = profile.part_size {
+                    if is_unset(sub_matches, "part_size") {
+                        copy.part_size = Some(part_size);
+                    }
+                }
+                if let Some(concurrency) = profile.concurrency {
+                    if is_unset(sub_matches, "concurrency") {
+                        copy.concurrency = usize::try_from(concurrency)?.max(1);
+                    }
+                }
+            }
+            Subcommands::Cas(cas) => {
+                if let Some(concurrency) = profile.concurrency {
+                    if is_unset(sub_matches, "concurrency") {
+                        cas.concurrency = usize::try_from(concurrency)?.max(1);
+                    }
+                }
+            }
+            Subcommands::Repair(repair) => {
+                if let Some(concurrency) = profile.concurrency {
+                    if is_unset(sub_matches, "concurrency") {
+                        repair.concurrency = concurrency.max(1);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Read newline-delimited inputs from an `--input-file` and append them to the inputs.
+    /// Empty lines and lines starting with `#` are ignored. Use `-` to read from stdin.
+    pub fn read_input_files(&mut self) -> Result<()> {
+        let (input, input_file) = match &mut self.commands {
+            Subcommands::Generate(generate) => (&mut generate.input, &generate.input_file),
+            Subcommands::Check(check) => (&mut check.input, &check.input_file),
+            _ => return Ok(()),
+        };
+
+        if let Some(input_file) = input_file {
+            if input_file == "-" && input.iter().any(|input| input == "-") {
+                return Err(ParseError(
+                    "stdin cannot be used for both the input file and an input".to_string(),
+                ));
+            }
+
+            let inputs = if input_file == "-" {
+                Self::parse_input_list(std::io::stdin().lock())?
+            } else {
+                Self::parse_input_list(BufReader::new(std::fs::File::open(input_file)?))?
+            };
+            input.extend(inputs);
+        }
+
+        Ok(())
+    }
+
+    /// Parse a newline-delimited list of inputs, ignoring empty lines and `#` comments.
+    pub fn parse_input_list(reader: impl BufRead) -> Result<Vec<String>> {
+        reader
+            .lines()
+            .filter_map(|line| {
+                line.map(|line| {
+                    let line = line.trim();
+                    (!line.is_empty() && !line.starts_with('#')).then(|| line.to_string())
+                })
+                .map_err(Error::from)
+                .transpose()
+            })
+            .collect()
+    }
+
     /// Validate commands.
     pub fn validate(args: &Self) -> Result<()> {
+        if let Subcommands::Check(check) = &args.commands {
+            if check.input.len() < 2 {
+                return Err(ParseError(
+                    "at least two inputs are required to check".to_string(),
+                ));
+            }
+        }
+
         if let Subcommands::Generate(generate) = &args.commands {
+            if generate.input.is_empty() {
+                return Err(ParseError("at least one input is required".to_string()));
+            }
+
+            if generate.compare_to.is_some() && generate.input.len() != 1 {
+                return Err(ParseError(
+                    "only a single input can be compared to a baseline".to_string(),
+                ));
+            }
+
             // For S3 objects, passing no checksums is valid as metadata can be used, otherwise
-            // it's an error if not verifying the data.
+            // it's an error if not verifying the data or using the checksums of a baseline.
             if generate.checksum.is_empty()
                 && !generate.verify
+                && generate.compare_to.is_none()
+                && generate.expect.is_none()
                 && !generate.input.iter().all(|input| {
                     Provider::try_from(input.as_str()).is_ok_and(|provider| provider.is_s3())
+                        || (generate.recursive && input.starts_with("s3://"))
                 })
             {
                 return Err(ParseError(
                     "some checksums must be specified if using file based objects and not verify existing sums".to_string(),
                 ));
             }
+
+            if (generate.offset.is_some() || generate.length.is_some())
+                && generate.input.iter().any(|input| input == "-")
+            {
+                return Err(ParseError(
+                    "a byte range cannot be used when reading from stdin".to_string(),
+                ));
+            }
+
+            if generate.checkpoint.is_some()
+                && (generate.input.len() != 1 || generate.input[0] == "-" || generate.recursive)
+            {
+                return Err(ParseError(
+                    "a checkpoint can only be used with a single input that is not stdin"
+                        .to_string(),
+                ));
+            }
+
+            if generate.tee.is_some() && generate.input.len() != 1 {
+                return Err(ParseError(
+                    "a copy of the input can only be written with a single input".to_string(),
+                ));
+            }
         }
 
         let credentials = &args.credentials;
@@ -99,33 +312,127 @@ impl Command {
         Ok(())
     }
 
+    /// Await a future, returning a timeout error if it does not complete within the timeout.
+    pub async fn with_timeout<T>(
+        timeout: Option<Duration>,
+        future: impl Future<Output = Result<T>>,
+        message: impl FnOnce(Duration) -> String,
+    ) -> Result<T> {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(*timeout, future)
+                .await
+                .map_err(|_| Timeout(message(timeout)))?,
+            None => future.await,
+        }
+    }
+
     /// Execute the command from the args.
     pub async fn execute(self) -> Result<()> {
+        let pretty_json = self.output.pretty_json;
+        Self::with_timeout(
+            self.optimization.total_timeout,
+            self.execute_command(),
+            |timeout| format!("the command did not complete within {}", timeout),
+        )
+        .await
+        .inspect_err(|err| {
+            if matches!(err, Timeout(_)) {
+                Self::print_stats(err, pretty_json).ok();
+            }
+        })
+    }
+
+    /// Execute the command without the total timeout.
+    async fn execute_command(self) -> Result<()> {
         let client = Arc::new(self.credentials.source_client().await?);
 
         let pretty_json = self.output.pretty_json;
-        let write_sums_file = self.output.write_sums_file;
         match self.commands {
+            Subcommands::Generate(generate_args) if generate_args.compare_to.is_some() => {
+                let diff = generate_args
+                    .compare(
+                        self.optimization,
+                        &self.credentials,
+                        vec![client],
+                        &self.output,
+                    )
+                    .await
+                    .inspect_err(|err| {
+                        Self::print_stats(err, pretty_json).ok();
+                    })?;
+
+                Self::print_stats(&diff, pretty_json)?;
+                if diff.is_different() {
+                    return Err(CheckError(format!(
+                        "the sums of `{}` are different to the baseline `{}`",
+                        diff.input, diff.baseline
+                    )));
+                }
+            }
             Subcommands::Generate(generate_args) => {
+                let short = generate_args.short;
                 let (sums, stats) = generate_args
-                    .generate(self.optimization, &self.credentials, vec![client], true)
+                    .generate(
+                        self.optimization,
+                        &self.credentials,
+                        vec![client],
+                        true,
+                        &self.output,
+                    )
                     .await
                     .inspect_err(|err| {
                         Self::print_stats(err, pretty_json).ok();
                     })?;
-                if let Some(stats) = stats {
+                let n_timed_out = stats
+                    .as_ref()
+                    .map(GenerateStats::n_timed_out)
+                    .unwrap_or_default();
+                let n_failed = stats
+                    .as_ref()
+                    .map(GenerateStats::n_failed)
+                    .unwrap_or_default();
+                if self.output.output_mode != OutputMode::Stats {
+                    // Sums files have already been printed as they completed, so only the
+                    // statistics are printed at the end on stderr.
+                    if let Some(stats) = stats {
+                        Self::eprint_stats(&stats, pretty_json)?;
+                    }
+                } else if let Some(stats) = stats {
                     Self::print_stats(&stats, pretty_json)?;
                 } else {
-                    sums.iter()
-                        .try_for_each(|sums| Self::print_stats(&sums, pretty_json))?;
+                    // A single stdin input is printed as one JSON object, in the same shape as
+                    // the records of `--output ndjson`.
+                    sums.into_iter().try_for_each(|(input, sums)| {
+                        Self::print_stats(
+                            &NamedSumsFile {
+                                name: &input,
+                                sums_file: &sums.shorten(short),
+                            },
+                            pretty_json,
+                        )
+                    })?;
+                }
+
+                if n_failed > 0 {
+                    return Err(GenerateError(format!(
+                        "{} inputs failed to generate checksums",
+                        n_failed
+                    )));
+                }
+                if n_timed_out > 0 {
+                    return Err(Timeout(format!(
+                        "{} inputs did not complete within the operation timeout",
+                        n_timed_out
+                    )));
                 }
             }
             Subcommands::Check(check_args) => {
+                let quiet = check_args.quiet;
                 let output = check_args
                     .check(
                         self.optimization,
                         &self.credentials,
-                        write_sums_file,
+                        &self.output,
                         false,
                         vec![client],
                     )
@@ -134,7 +441,23 @@ impl Command {
                         Self::print_stats(err, pretty_json).ok();
                     })?;
 
-                Self::print_stats(&output, pretty_json)?;
+                if !quiet {
+                    Self::print_stats(&output, pretty_json)?;
+                } else if output.is_mismatch() {
+                    Self::print_stats(&output, pretty_json)?;
+                    return Err(CheckError(format!(
+                        "inputs are not the same, found {} groups",
+                        output.groups.len()
+                    )));
+                }
+
+                let n_failed = output.n_failed();
+                if n_failed > 0 {
+                    if quiet && !output.is_mismatch() {
+                        Self::print_stats(&output, pretty_json)?;
+                    }
+                    return Err(CheckError(format!("{} inputs could not be read", n_failed)));
+                }
             }
             Subcommands::Copy(copy_args) => {
                 let destination_client = Arc::new(self.credentials.destination_client().await?);
@@ -145,7 +468,123 @@ impl Command {
                         destination_client,
                         self.credentials,
                         self.optimization,
-                        write_sums_file,
+                        &self.output,
+                    )
+                    .await
+                    .inspect_err(|err| {
+                        Self::print_stats(err, pretty_json).ok();
+                    })?;
+
+                Self::print_stats(&output, pretty_json)?;
+            }
+            Subcommands::Cas(cas_args) => {
+                let destination_client = Arc::new(self.credentials.destination_client().await?);
+
+                let output = cas_args
+                    .cas(
+                        client,
+                        destination_client,
+                        self.credentials,
+                        self.optimization,
+                        &self.output,
+                    )
+                    .await
+                    .inspect_err(|err| {
+                        Self::print_stats(err, pretty_json).ok();
+                    })?;
+
+                Self::print_stats(&output, pretty_json)?;
+            }
+            Subcommands::PredictEtag(predict_etag_args) => {
+                let e_tag = predict_etag_args.predict_etag().await.inspect_err(|err| {
+                    Self::print_stats(err, pretty_json).ok();
+                })?;
+
+                println!("{}", e_tag);
+            }
+            Subcommands::Info(info_args) => {
+                let info = info_args
+                    .info(client, &self.credentials)
+                    .await
+                    .inspect_err(|err| {
+                        Self::print_stats(err, pretty_json).ok();
+                    })?;
+
+                Self::print_stats(&info, pretty_json)?;
+            }
+            Subcommands::Bisect(bisect_args) => {
+                let output = bisect_args
+                    .bisect(self.optimization, client)
+                    .await
+                    .inspect_err(|err| {
+                        Self::print_stats(err, pretty_json).ok();
+                    })?;
+
+                Self::print_stats(&output, pretty_json)?;
+                if output.is_mismatch() {
+                    return Err(CheckError(format!(
+                        "{} parts of `{}` differ from the sums file",
+                        output.mismatched.len(),
+                        output.input
+                    )));
+                }
+            }
+            Subcommands::Diff(diff_args) => {
+                let output = diff_args.diff(client).await.inspect_err(|err| {
+                    Self::print_stats(err, pretty_json).ok();
+                })?;
+
+                Self::print_stats(&output, pretty_json)?;
+                if let Some(offset) = output.offset {
+                    return Err(CheckError(format!(
+                        "`{}` and `{}` differ at byte {}",
+                        output.first, output.second, offset
+                    )));
+                }
+            }
+            Subcommands::Blocks(blocks_args) => {
+                let output = blocks_args.blocks(client).await.inspect_err(|err| {
+                    Self::print_stats(err, pretty_json).ok();
+                })?;
+
+                Self::print_stats(&output, pretty_json)?;
+            }
+            Subcommands::DatasetId(dataset_id_args) => {
+                let output = dataset_id_args
+                    .dataset_id(
+                        self.optimization,
+                        &self.credentials,
+                        vec![client],
+                        &self.output,
+                    )
+                    .await
+                    .inspect_err(|err| {
+                        Self::print_stats(err, pretty_json).ok();
+                    })?;
+
+                Self::print_stats(&output, pretty_json)?;
+            }
+            Subcommands::SelfTest(self_test_args) => {
+                let output = self_test_args.self_test();
+
+                Self::print_stats(&output, pretty_json)?;
+                if !output.passed {
+                    return Err(CheckError(format!(
+                        "{} algorithms did not match their known answers",
+                        output.n_failed()
+                    )));
+                }
+            }
+            Subcommands::Schema(schema_args) => {
+                Self::print_stats(&schema_args.schema(), pretty_json)?;
+            }
+            Subcommands::Repair(repair_args) => {
+                let output = repair_args
+                    .repair(
+                        self.optimization,
+                        &self.credentials,
+                        vec![client],
+                        &self.output,
                     )
                     .await
                     .inspect_err(|err| {
@@ -154,6 +593,38 @@ impl Command {
 
                 Self::print_stats(&output, pretty_json)?;
             }
+            Subcommands::Prune(prune_args) => {
+                let output = prune_args
+                    .prune(vec![client], &self.output)
+                    .await
+                    .inspect_err(|err| {
+                        Self::print_stats(err, pretty_json).ok();
+                    })?;
+
+                Self::print_stats(&output, pretty_json)?;
+            }
+            Subcommands::Touch(touch_args) => {
+                let output = touch_args
+                    .touch(&self.credentials, vec![client], &self.output)
+                    .await
+                    .inspect_err(|err| {
+                        Self::print_stats(err, pretty_json).ok();
+                    })?;
+
+                Self::print_stats(&output, pretty_json)?;
+            }
+            Subcommands::Cat(cat_args) => {
+                let output = cat_args
+                    .cat(self.optimization, client)
+                    .await
+                    .inspect_err(|err| {
+                        Self::eprint_stats(err, pretty_json).ok();
+                    })?;
+
+                if let Some(output) = output {
+                    Self::eprint_stats(&output, pretty_json)?;
+                }
+            }
         }
 
         Ok(())
@@ -172,6 +643,20 @@ impl Command {
 
         Ok(())
     }
+
+    /// Print output statistics to stderr. This is used when stdout contains data.
+    pub fn eprint_stats<T>(stats: &T, pretty_json: bool) -> Result<()>
+    where
+        T: Serialize,
+    {
+        if pretty_json {
+            eprintln!("{}", to_string_pretty(stats)?);
+        } else {
+            eprintln!("{}", to_string(stats)?);
+        }
+
+        Ok(())
+    }
 }
 
 /// The generate subcommand components.provenance: false
@@ -180,8 +665,21 @@ pub struct Generate {
     /// The input file to calculate the checksum for. By default, accepts a file name.
     /// use - to accept input from stdin. If using stdin, the output will be written to stdout.
     /// Multiple files can be specified.
-    #[arg(value_delimiter = ',', required = true)]
+    ///
+    /// Members of uncompressed tar archives can be read without extracting them using the
+    /// `archive.tar!path/inside` syntax. The sums file of a member is written next to the
+    /// archive with any `/` in the member path encoded as `%2F`.
+    ///
+    /// Files can be joined with `+` to compute checksums over their concatenation without
+    /// joining them, e.g. `dir/part1+part2+part3`. Parts after the first are relative to the
+    /// directory of the first part, and the sums file is named after the whole input.
+    #[arg(value_delimiter = ',', required_unless_present = "input_file")]
     pub input: Vec<String>,
+    /// Read additional inputs from a file containing one input per line. Empty lines and lines
+    /// starting with `#` are ignored. Use - to read the list from stdin. This is appended to
+    /// any other inputs.
+    #[arg(long, env)]
+    pub input_file: Option<String>,
     /// Checksums to use. Can be specified multiple times or comma-separated.
     ///
     /// Use an `aws-<part_size>` suffix to create AWS ETag-style checksums, e.g. `md5-aws-8mib`.
@@ -189,7 +687,13 @@ pub struct Generate {
     /// this is interpreted as a `<part-number>` where the input file is split evenly into the
     /// number of parts (where the last part can be smaller). For example `md5-aws-10` splits
     /// the file into 10 parts. `<part-number>` is not supported when the file size is not
-    /// known, such as when taking input from stdin.
+    /// known, such as when taking input from stdin or a named pipe, unless `--input-size` is
+    /// specified. When the part size is omitted, e.g. `sha256-aws`, the default part size of
+    /// 8 MiB is used. Use `p<part-number>-aligned`, e.g. `md5-aws-p100-aligned`, to round the
+    /// part size of a `<part-number>` up to the nearest MiB, which matches upload tools that
+    /// round their part sizes. Use `min<part-number>`, e.g. `md5-aws-min4`, for the largest part
+    /// size that results in at least `<part-number>` parts. This is an error if the parts would
+    /// be smaller than the S3 minimum part size.
     ///
     /// It is possible to specify different part sizes by appending additional parts separated
     /// by a `-`. In this case, if the file is bigger than the number of parts, the last part
@@ -197,6 +701,11 @@ pub struct Generate {
     /// example, `md5-aws-8mib-16mib` will create one 8 MiB part and the rest will be 16 MiB
     /// parts.
     ///
+    /// Checksums with an `aws` suffix correspond to S3 `COMPOSITE` checksums, which are a checksum
+    /// of the part checksums. Checksums without a suffix, e.g. `crc64nvme`, correspond to S3
+    /// `FULL_OBJECT` checksums, which cover the whole object even if it was uploaded in parts.
+    /// The two types are never compared with each other.
+    ///
     /// This option supports file-based objects and objects in S3 by using the
     /// `S3://bucket/object` syntax. This option must be specified for file-based objects. It
     /// does not need to be specified for S3 objects as it will use metadata by default. This
@@ -209,12 +718,27 @@ pub struct Generate {
     /// `--checksum` will also be generated.
     #[arg(short, long, env)]
     pub missing: bool,
+    /// Only generate missing checksums that are at least this strong, e.g. `sha256`. By
+    /// default, the checksum that the most inputs already have is generated, preferring
+    /// `crc32c` as it is the cheapest to compute and S3 can provide it.
+    #[arg(long, env, requires = "missing")]
+    pub min_strength: Option<Strength>,
     /// Overwrite the sums file. By default, only checksums that are missing are computed and
     /// added to an existing sums file. Any existing checksums are preserved (even if not
     /// specified in --checksums). This option allows overwriting any existing sums file. This
     /// will recompute all checksums specified.
     #[arg(short, long, env, conflicts_with = "verify")]
     pub force_overwrite: bool,
+    /// When to overwrite an existing sums file. `missing` adds missing checksums to it, `never`
+    /// leaves it unchanged, `size-changed` replaces it if the input size changed and otherwise
+    /// adds missing checksums, and `always` replaces it like `--force-overwrite`.
+    #[arg(
+        long,
+        env,
+        default_value = "missing",
+        conflicts_with_all = ["force_overwrite", "verify"]
+    )]
+    pub overwrite_policy: OverwritePolicy,
     /// Verify the contents of existing sums files when generating checksums. By default,
     /// existing checksum files are assumed to contain checksums that have correct values. This
     /// option allows computing existing sums file checksums and updating the file to ensure
@@ -222,40 +746,444 @@ pub struct Generate {
     /// if the metadata for that checksum exists.
     #[arg(short, long, env, conflicts_with = "force_overwrite")]
     pub verify: bool,
+    /// Only compute checksums starting at this byte offset of the input. This can be specified
+    /// with a size unit, e.g. 1mib. The range is recorded in the sums file so that it is not
+    /// confused with a checksum of the whole file. Replacing an existing sums file that covers
+    /// a different range requires `--force-overwrite`.
+    #[arg(long, env, conflicts_with = "missing", value_parser = |s: &str| parse_size(s))]
+    pub offset: Option<u64>,
+    /// Only compute checksums over this many bytes of the input. This can be specified with a
+    /// size unit, e.g. 64kib. By default, the checksum extends to the end of the input.
+    #[arg(long, env, conflicts_with = "missing", value_parser = |s: &str| parse_size(s))]
+    pub length: Option<u64>,
+    /// The size of an input that cannot be determined before reading it, such as stdin or a
+    /// named pipe. This can be specified with a size unit, e.g. 10gib. It is required to use
+    /// `<part-number>` AWS checksums with these inputs, and it is an error if the input is a
+    /// different size. It is ignored for inputs with a known size.
+    #[arg(long, env, conflicts_with_all = ["normalize_newlines", "decompress"], value_parser = |s: &str| parse_size(s))]
+    pub input_size: Option<u64>,
+    /// Record the modification time and permissions of the input in the sums file. For S3
+    /// objects, the `LastModified` time is recorded instead. This metadata is not used when
+    /// comparing sums files, and is removed if generating again without this option.
+    #[arg(long, env)]
+    pub record_metadata: bool,
+    /// Record the checksum of every part of AWS checksums in the sums file, e.g. for
+    /// `md5-aws-8mib`. These are not used when comparing sums files, but allow `bisect` to find
+    /// the byte ranges of an input that changed.
+    #[arg(long, env)]
+    pub record_parts: bool,
+    /// Fail if an AWS checksum has parts that could not belong to an S3 object, i.e. more than
+    /// 10000 parts, or parts outside the 5 MiB to 5 GiB part size limits. By default, only
+    /// checksums with too many parts are reported as a warning.
+    #[arg(long, env)]
+    pub strict_s3: bool,
+    /// Write sums files to this location instead of next to the inputs. This is useful for
+    /// read-only inputs. The location must use the same storage as the inputs, e.g. a directory
+    /// for files or `s3://bucket/prefix/` for S3 objects. The sums file keeps the path of the
+    /// input under the location, e.g. `s3://bucket/prefix/dir/key.sums` for the key `dir/key`.
+    #[arg(long, env, conflicts_with = "missing")]
+    pub sums_destination: Option<String>,
+    /// Compute checksums only once for local files with identical contents. Inputs are first
+    /// matched using their size and a hash of their first 64 KiB, and then compared in full
+    /// before the checksums of an earlier input are reused.
+    #[arg(long, env, conflicts_with = "verify")]
+    pub dedup_compute: bool,
+    /// Only generate checksums for inputs modified at or after this time, skipping any that
+    /// are older. This is useful for refreshing sums over a large number of inputs where only
+    /// a few have changed. The time is either RFC 3339, e.g. `2025-01-01T00:00:00Z`, or a unix
+    /// timestamp in seconds prefixed with `@`, e.g. `@1735689600`. For S3 objects, the
+    /// `LastModified` time is used.
+    #[arg(long, env, value_parser = |s: &str| Generate::parse_since(s))]
+    pub since: Option<SystemTime>,
+    /// Output a single sha256 digest over the sums files of all inputs in the statistics. This
+    /// changes if any checksum changes, so it can be used to pin a whole dataset. The digest
+    /// does not depend on the order of the inputs, and does not include recorded metadata.
+    #[arg(long, env)]
+    pub manifest_digest: bool,
+    /// Treat S3 inputs as prefixes and generate checksums for every object under them, e.g.
+    /// `s3://bucket/prefix/`. Existing sums files under the prefix are not included as inputs.
+    #[arg(short, long, env)]
+    pub recursive: bool,
+    /// The number of inputs to generate checksums for at the same time. This is useful when
+    /// generating checksums for many objects, such as with `--recursive`.
+    #[arg(long, env, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub concurrency: u64,
+    /// Periodically save the progress of generating checksums to this file, and resume from it
+    /// if it exists. This is useful for very large inputs that may be interrupted, e.g. on spot
+    /// instances. The file is removed once all checksums are generated. AWS checksums resume
+    /// from the last completed part, and other checksums are only resumable if their state can
+    /// be saved, i.e. `crc32` and `crc32c`. Only a single input can be used.
+    #[arg(long, env)]
+    pub checkpoint: Option<PathBuf>,
+    /// Print checksums shortened to the first `N` characters of their digest, like a short git
+    /// hash. This is useful for human-friendly labels. Only the printed output is affected, and
+    /// sums files always contain the full checksums.
+    #[arg(long, env, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub short: Option<usize>,
+    /// Convert CRLF line endings to LF before computing checksums, so that text files checked
+    /// out on different platforms have the same checksums. This is recorded in the sums file,
+    /// and normalized sums are never compared with sums of the raw data. Because the size of
+    /// the normalized data is not known in advance, AWS checksums must use a part size rather
+    /// than a number of parts. This cannot be used with `--checkpoint`.
+    #[arg(long, env, conflicts_with = "checkpoint")]
+    pub normalize_newlines: bool,
+    /// Decompress the input before computing checksums, so that the checksums are of the
+    /// decompressed content. This is recorded in the sums file, and decompressed sums are never
+    /// compared with sums of the compressed data. Truncated or corrupt input is an error. Like
+    /// `--normalize-newlines`, AWS checksums must use a part size rather than a number of parts,
+    /// and this cannot be used with `--checkpoint`.
+    #[arg(long, env, value_name = "FORMAT", conflicts_with = "checkpoint")]
+    pub decompress: Option<Decompress>,
+    /// Compare the generated sums to a baseline sums file, e.g. one committed to version
+    /// control, and exit with an error if any checksums were added, removed or changed. All
+    /// requested checksums are computed again rather than taken from an existing sums file, and
+    /// the sums of the baseline are used if `--checksum` is not specified. The generated sums
+    /// are not written unless `--update` is also used. Only a single input can be used.
+    #[arg(long, env, conflicts_with_all = ["missing", "verify", "recursive", "manifest_digest"])]
+    pub compare_to: Option<String>,
+    /// Write the generated sums file when using `--compare-to`, even if it differs from the
+    /// baseline.
+    #[arg(short, long, env, requires = "compare_to")]
+    pub update: bool,
+    /// Keep processing the other inputs if generating checksums for an input fails. Failed
+    /// inputs and their errors are reported in the output statistics, the sums of successful
+    /// inputs are still written, and the command exits with an error at the end.
+    #[arg(long, env)]
+    pub continue_on_error: bool,
+    /// The maximum amount of time to spend generating checksums for each input, e.g. `10m`.
+    /// Inputs that time out are stopped and reported in the output statistics without
+    /// stopping other inputs, and the command fails once all inputs have finished.
+    #[arg(long, env)]
+    pub operation_timeout: Option<Duration>,
+    /// Write a copy of the input to this file while computing its checksums, e.g. to download,
+    /// verify and save an object from a single read. The specified checksums are always
+    /// computed again, but an existing sums file is not modified with `--overwrite-policy
+    /// never`. Only a single input can be used, which can be stdin.
+    #[arg(long, env, conflicts_with_all = ["missing", "dedup_compute", "recursive", "checkpoint"])]
+    pub tee: Option<PathBuf>,
+    /// Exit with an error if a generated checksum is not this value, given as
+    /// `<checksum>:<value>`, e.g. `sha256:<hex>`. The checksum is generated even if it is not
+    /// specified with `--checksum`. If it does not match, the file written by `--tee` is removed.
+    #[arg(long, env, value_parser = |s: &str| Generate::parse_expect(s).map(|_| s.to_string()))]
+    pub expect: Option<String>,
 }
 
 impl Generate {
-    /// Perform the generate sub command from the args.
-    pub async fn generate(
-        self,
-        optimization: Optimization,
-        credentials: &Credentials,
-        mut clients: Vec<Arc<Client>>,
-        write_sums_file: bool,
-    ) -> Result<(Vec<(String, SumsFile)>, Option<GenerateStats>)> {
-        if self.input[0] == "-" {
-            let reader = ChannelReader::new(stdin(), optimization.channel_capacity);
+    /// Parse an `--expect` checksum of the form `<checksum>:<value>`.
+    pub fn parse_expect(s: &str) -> Result<(Ctx, String)> {
+        let (ctx, value) = s.split_once(':').ok_or_else(|| {
+            ParseError(format!(
+                "invalid expected checksum `{}`, use `<checksum>:<value>`",
+                s
+            ))
+        })?;
+        Ok((ctx.parse()?, value.to_string()))
+    }
 
-            let output = GenerateTaskBuilder::default()
+    /// The overwrite policy, where `--force-overwrite` always overwrites.
+    pub fn overwrite_policy(&self) -> OverwritePolicy {
+        if self.force_overwrite {
+            OverwritePolicy::Always
+        } else {
+            self.overwrite_policy
+        }
+    }
+
+    /// Parse a `--since` time from RFC 3339 or a unix timestamp prefixed with `@`.
+    pub fn parse_since(s: &str) -> Result<SystemTime> {
+        match s.strip_prefix('@') {
+            Some(seconds) => {
+                let seconds = seconds.parse::<u64>().map_err(|err| {
+                    ParseError(format!("invalid unix timestamp `{}`: {}", s, err))
+                })?;
+                Ok(UNIX_EPOCH + std::time::Duration::from_secs(seconds))
+            }
+            None => parse_rfc3339_weak(s)
+                .map_err(|err| ParseError(format!("invalid time `{}`: {}", s, err))),
+        }
+    }
+
+    /// Keep only the inputs that were modified at or after a time. Inputs without a known
+    /// modification time are kept.
+    pub async fn modified_since(
+        input: Vec<String>,
+        since: SystemTime,
+        clients: &[Arc<Client>],
+        credentials: &Credentials,
+    ) -> Result<Vec<String>> {
+        let mut modified = vec![];
+        for (input, client) in input.into_iter().zip(clients.iter().cycle()) {
+            let mtime = ObjectSumsBuilder::default()
+                .set_client(Some(client.clone()))
                 .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
-                .with_overwrite(self.force_overwrite)
-                .with_verify(self.verify)
-                .with_context(self.checksum)
-                .with_reader(reader)
-                .set_client(clients.first().cloned())
-                .build()
+                .build(input.to_string())
                 .await?
-                .run()
+                .file_metadata()
                 .await?
-                .into_inner()
-                .0;
+                .modified()?;
 
-            Ok((vec![(self.input[0].to_string(), output)], None))
-        } else {
-            let now = Instant::now();
-            let mut check_stats = None;
-            let mut generate_stats = vec![];
-            let mut sums_files = vec![];
+            if mtime.is_none_or(|mtime| mtime >= since) {
+                modified.push(input);
+            }
+        }
+
+        Ok(modified)
+    }
+
+    /// Expand S3 inputs into the objects under them when used as prefixes. Other inputs are
+    /// kept as is.
+    #[cfg(feature = "cloud")]
+    pub async fn list_prefixes(input: Vec<String>, clients: &[Arc<Client>]) -> Result<Vec<String>> {
+        let mut objects = vec![];
+        for (input, client) in input.into_iter().zip(clients.iter().cycle()) {
+            if !input.starts_with("s3://") {
+                objects.push(input);
+                continue;
+            }
+
+            let (bucket, prefix) = Provider::parse_s3_prefix(&input)?;
+            objects.extend(
+                S3::list_objects(client, &bucket, &prefix)
+                    .await?
+                    .into_iter()
+                    .map(|key| Provider::format_s3(&bucket, &key)),
+            );
+        }
+
+        Ok(objects)
+    }
+
+    /// Without the `cloud` feature inputs are kept as is, and S3 inputs fail when they are read.
+    #[cfg(not(feature = "cloud"))]
+    pub async fn list_prefixes(
+        input: Vec<String>,
+        _clients: &[Arc<Client>],
+    ) -> Result<Vec<String>> {
+        Ok(input)
+    }
+
+    /// Print a sums file as soon as it is complete if the output mode streams sums files.
+    pub fn print_sums(
+        output_mode: OutputMode,
+        input: &str,
+        sums_file: &SumsFile,
+        short: Option<usize>,
+    ) -> Result<()> {
+        match output_mode {
+            OutputMode::Stats => Ok(()),
+            OutputMode::Ndjson => Self::print_ndjson(input, &sums_file.clone().shorten(short)),
+            OutputMode::Sri => Self::print_sri(input, sums_file),
+        }
+    }
+
+    /// Print the Subresource Integrity string of a sums file followed by the name of its input.
+    /// Checksums that cannot be used in an integrity string are skipped with a warning, and an
+    /// error is returned if there are no checksums left.
+    pub fn print_sri(input: &str, sums_file: &SumsFile) -> Result<()> {
+        let (integrity, skipped) = sums_file.integrity()?;
+        for ctx in skipped {
+            warn!(
+                input,
+                checksum = %ctx,
+                "skipping a checksum which is not supported by SRI"
+            );
+        }
+
+        match integrity {
+            Some(integrity) => Self::write_line(
+                std::io::stdout().lock(),
+                &format!("{} {}", integrity, input),
+            ),
+            None => Err(ParseError(format!(
+                "`{}` has no checksums that can be used in an SRI string, only sha256 is supported",
+                input
+            ))),
+        }
+    }
+
+    /// Print a sums file annotated with the name of its input as a single JSON line.
+    pub fn print_ndjson(input: &str, sums_file: &SumsFile) -> Result<()> {
+        Self::write_line(
+            std::io::stdout().lock(),
+            &Self::ndjson_line(input, sums_file)?,
+        )
+    }
+
+    /// Format a sums file annotated with the name of its input as a single JSON line.
+    pub fn ndjson_line(input: &str, sums_file: &SumsFile) -> Result<String> {
+        Ok(to_string(&NamedSumsFile {
+            name: input,
+            sums_file,
+        })?)
+    }
+
+    /// Write a line and flush the writer, so that a downstream reader in a pipeline receives
+    /// each record as soon as it is complete. Unlike `println!`, a closed pipe is an error
+    /// rather than a panic.
+    pub fn write_line(mut writer: impl Write, line: &str) -> Result<()> {
+        writeln!(writer, "{}", line)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Generate sums for the input and compare them to the baseline sums file from
+    /// `--compare-to`.
+    pub async fn compare(
+        mut self,
+        optimization: Optimization,
+        credentials: &Credentials,
+        clients: Vec<Arc<Client>>,
+        output: &Output,
+    ) -> Result<SumsDiff> {
+        let now = Instant::now();
+        let baseline = self
+            .compare_to
+            .take()
+            .ok_or_else(|| ParseError("a baseline sums file is required".to_string()))?;
+        let (baseline, baseline_sums) =
+            Check::read_sums_files(&[baseline], &clients, output.sums_format())
+                .await?
+                .remove(0);
+
+        if self.checksum.is_empty() {
+            self.checksum = baseline_sums.checksums.keys().cloned().collect();
+        }
+        // Existing checksums are computed again so that they can be compared.
+        self.force_overwrite = true;
+
+        let update = self.update;
+        let (mut sums, stats) = self
+            .generate(optimization, credentials, clients, update, output)
+            .await?;
+        let updated = stats.is_some_and(|stats| stats.stats.iter().any(|stat| stat.updated));
+        let (input, generated) = sums.remove(0);
+
+        Ok(SumsDiff::new(
+            now.elapsed(),
+            (input, &generated),
+            (baseline, &baseline_sums),
+            updated,
+        ))
+    }
+
+    /// Perform the generate sub command from the args. If a copy of the input is written with
+    /// `--tee`, it is removed if generating fails or the `--expect` checksum does not match.
+    pub async fn generate(
+        mut self,
+        optimization: Optimization,
+        credentials: &Credentials,
+        clients: Vec<Arc<Client>>,
+        write_sums_file: bool,
+        output: &Output,
+    ) -> Result<(Vec<(String, SumsFile)>, Option<GenerateStats>)> {
+        let tee = self.tee.clone();
+        let expect = self.expect.as_deref().map(Self::parse_expect).transpose()?;
+        if let Some((ctx, _)) = &expect {
+            if !self.checksum.contains(ctx) {
+                self.checksum.push(ctx.clone());
+            }
+        }
+
+        let result = self
+            .generate_inputs(optimization, credentials, clients, write_sums_file, output)
+            .await
+            .and_then(|(sums, stats)| {
+                if let Some((ctx, expected)) = &expect {
+                    for (input, sums) in &sums {
+                        // Checksums are always hex encoded, which is case-insensitive.
+                        let checksum = sums.checksums.get(ctx).map(|checksum| checksum.as_str());
+                        if !checksum.is_some_and(|checksum| checksum.eq_ignore_ascii_case(expected))
+                        {
+                            return Err(CheckError(format!(
+                                "the `{}` checksum of `{}` is `{}`, but `{}` was expected",
+                                ctx,
+                                input,
+                                checksum.unwrap_or("missing"),
+                                expected
+                            )));
+                        }
+                    }
+                }
+                Ok((sums, stats))
+            });
+
+        if let (Err(_), Some(tee)) = (&result, &tee) {
+            match tokio::fs::remove_file(tee).await {
+                Ok(()) => warn!(tee = %tee.display(), "removed the copy of the input"),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    warn!(tee = %tee.display(), %err, "failed to remove the copy of the input")
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Generate the sums of all inputs.
+    async fn generate_inputs(
+        mut self,
+        optimization: Optimization,
+        credentials: &Credentials,
+        mut clients: Vec<Arc<Client>>,
+        write_sums_file: bool,
+        output: &Output,
+    ) -> Result<(Vec<(String, SumsFile)>, Option<GenerateStats>)> {
+        let format = output.sums_format();
+        let storage = output.sums_storage;
+        let output_mode = output.output_mode;
+        let overwrite_policy = self.overwrite_policy();
+        let channel_metrics = optimization
+            .channel_metrics
+            .then(|| Arc::new(ChannelMetrics::default()));
+
+        if self.input[0] == "-" {
+            let mut reader: Box<dyn AsyncRead + Unpin + Send> = Box::new(stdin());
+            if let Some(tee) = &self.tee {
+                reader = Box::new(TeeReader::new(reader, tokio::fs::File::create(tee).await?));
+            }
+            if let Some(decompress) = &self.decompress {
+                reader = decompress.reader(reader);
+            }
+            if self.normalize_newlines {
+                reader = Box::new(NormalizeNewlines::new(reader));
+            }
+            let reader = ChannelReader::new(reader, optimization.channel_capacity)
+                .set_metrics(channel_metrics);
+
+            let output = GenerateTaskBuilder::default()
+                .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
+                .with_overwrite_policy(overwrite_policy)
+                .with_verify(self.verify)
+                .with_format(format)
+                .with_storage(storage)
+                .with_context(self.checksum)
+                .with_normalize_newlines(self.normalize_newlines)
+                .set_decompress(self.decompress)
+                .set_input_size(self.input_size)
+                .with_reader(reader)
+                .set_client(clients.first().cloned())
+                .build()
+                .await?
+                .run()
+                .await?
+                .into_inner()
+                .0;
+            Self::print_sums(output_mode, &self.input[0], &output, self.short)?;
+
+            Ok((vec![(self.input[0].to_string(), output)], None))
+        } else {
+            let now = Instant::now();
+            if self.recursive {
+                self.input = Self::list_prefixes(self.input, &clients).await?;
+            }
+            if let Some(since) = self.since {
+                self.input = Self::modified_since(self.input, since, &clients, credentials).await?;
+            }
+
+            let mut check_stats = None;
+            let mut generate_stats = vec![];
+            let mut sums_files = vec![];
             let mut errors = HashSet::new();
 
             if self.missing {
@@ -264,8 +1192,17 @@ impl Generate {
                     self.input.clone(),
                     clients.clone(),
                     credentials.avoid_get_object_attributes,
+                    output,
+                    self.continue_on_error,
                 )
                 .await?;
+                // Inputs that could not be read are reported as failed generate stats.
+                for (input, err) in ctxs.failed() {
+                    generate_stats.push(GenerateFileStats::failed(
+                        input.to_string(),
+                        err.to_string(),
+                    ));
+                }
                 let (objects, compared, updated, api_errors) = ctxs.into_inner();
                 check_stats = Some(CheckStats::new(
                     now.elapsed().as_secs_f64(),
@@ -281,7 +1218,7 @@ impl Generate {
                     clients = vec![Arc::new(default_s3_client().await?)];
                 }
 
-                let ctxs = SumCtxPairs::from_comparable(objects)?;
+                let ctxs = SumCtxPairs::from_comparable(objects, self.min_strength)?;
                 if let Some(ctxs) = ctxs {
                     for (ctx, client) in ctxs
                         .into_inner()
@@ -293,64 +1230,172 @@ impl Generate {
                             .with_avoid_get_object_attributes(
                                 credentials.avoid_get_object_attributes,
                             )
-                            .with_overwrite(self.force_overwrite)
+                            .with_overwrite_policy(overwrite_policy)
                             .with_verify(self.verify)
                             .with_input_file_name(input.to_string())
                             .with_context(vec![ctx])
                             .with_capacity(optimization.channel_capacity)
+                            .set_channel_metrics(channel_metrics.clone())
+                            .with_timings(optimization.timings)
+                            .with_sparse(optimization.sparse_files)
+                            .set_download_cache(optimization.download_cache())
+                            .set_tee(self.tee.clone())
                             .with_client(client)
+                            .with_format(format)
+                            .with_storage(storage)
+                            .set_sums_source(output.sums_source.clone())
+                            .set_output_dir(output.output_dir.clone())
                             .set_write(write_sums_file)
                             .build()
-                            .await?
-                            .run()
-                            .await?;
+                            .await;
+                        let task = match task {
+                            Ok(task) => task.run().await,
+                            Err(err) => Err(err),
+                        };
+                        let task = match task {
+                            Err(err) if self.continue_on_error => {
+                                warn!(input, %err, "failed to generate checksums");
+                                generate_stats
+                                    .push(GenerateFileStats::failed(input, err.to_string()));
+                                continue;
+                            }
+                            task => task?,
+                        };
 
+                        Self::print_sums(output_mode, &input, task.sums_file(), self.short)?;
                         sums_files.push((input, task.sums_file().clone()));
                         errors.extend(task.api_errors());
-                        generate_stats.push(GenerateFileStats::from_task(task));
+                        generate_stats.push(GenerateFileStats::from_task(task).shorten(self.short));
                     }
                 }
 
                 if self.checksum.is_empty() {
+                    let manifest_digest = self
+                        .manifest_digest
+                        .then(|| SumsFile::manifest_digest(&sums_files))
+                        .transpose()?;
                     return Ok((
                         sums_files,
-                        Some(GenerateStats::new(
-                            now.elapsed().as_secs_f64(),
-                            generate_stats,
-                            check_stats,
-                            errors,
-                        )),
+                        Some(
+                            GenerateStats::new(
+                                now.elapsed().as_secs_f64(),
+                                generate_stats,
+                                check_stats,
+                                errors,
+                            )
+                            .set_manifest_digest(manifest_digest)
+                            .set_channel_metrics(
+                                channel_metrics.as_deref().map(ChannelMetrics::stats),
+                            ),
+                        ),
                     ));
                 }
             };
 
-            for (input, client) in self.input.into_iter().zip(clients.into_iter().cycle()) {
-                let task = GenerateTaskBuilder::default()
-                    .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
-                    .with_overwrite(self.force_overwrite)
-                    .with_verify(self.verify)
-                    .with_input_file_name(input.to_string())
-                    .with_context(self.checksum.clone())
-                    .with_capacity(optimization.channel_capacity)
-                    .with_client(client)
-                    .set_write(write_sums_file)
-                    .build()
-                    .await?
-                    .run()
-                    .await?;
+            let dedup = self.dedup_compute.then(DedupCache::default);
+            let inputs = self.input.into_iter().zip(clients.into_iter().cycle());
+            let mut tasks = stream::iter(inputs)
+                .map(|(input, client)| {
+                    let dedup = dedup.as_ref();
+                    let builder = GenerateTaskBuilder::default()
+                        .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
+                        .with_overwrite_policy(overwrite_policy)
+                        .with_verify(self.verify)
+                        .with_input_file_name(input.to_string())
+                        .with_context(self.checksum.clone())
+                        .with_capacity(optimization.channel_capacity)
+                        .set_channel_metrics(channel_metrics.clone())
+                        .with_timings(optimization.timings)
+                        .with_sparse(optimization.sparse_files)
+                        .set_download_cache(optimization.download_cache())
+                        .set_tee(self.tee.clone())
+                        .with_client(client)
+                        .set_offset(self.offset)
+                        .set_length(self.length)
+                        .set_input_size(self.input_size)
+                        .with_record_metadata(self.record_metadata)
+                        .with_record_parts(self.record_parts)
+                        .with_strict_s3(self.strict_s3)
+                        .set_sums_destination(self.sums_destination.clone())
+                        .set_checkpoint(self.checkpoint.clone())
+                        .with_normalize_newlines(self.normalize_newlines)
+                        .set_decompress(self.decompress)
+                        .with_format(format)
+                        .with_storage(storage)
+                        .set_sums_source(output.sums_source.clone())
+                        .set_output_dir(output.output_dir.clone())
+                        .set_write(write_sums_file);
+
+                    let span = info_span!("generate", input = %input);
+                    let operation_timeout = self.operation_timeout;
+                    async move {
+                        let generate = async {
+                            let precomputed = match dedup {
+                                Some(dedup) => dedup.get(&input).await?,
+                                None => None,
+                            };
+                            let is_precomputed = precomputed.is_some();
+
+                            let task = builder
+                                .set_precomputed(precomputed)
+                                .build()
+                                .await?
+                                .run()
+                                .await?;
+                            if let Some(dedup) = dedup.filter(|_| !is_precomputed) {
+                                dedup.insert(&input, task.sums_file().clone()).await?;
+                            }
+
+                            Ok(task)
+                        };
+
+                        let task = Command::with_timeout(operation_timeout, generate, |timeout| {
+                            format!("`{}` did not complete within {}", input, timeout)
+                        })
+                        .await;
+                        (input, task)
+                    }
+                    .instrument(span)
+                })
+                .buffered(usize::try_from(self.concurrency)?);
+
+            while let Some((input, task)) = tasks.next().await {
+                // A timed out input is reported without stopping the other inputs.
+                let task = match task {
+                    Err(Timeout(err)) => {
+                        warn!(input, err, "timed out");
+                        generate_stats.push(GenerateFileStats::timed_out(input));
+                        continue;
+                    }
+                    Err(err) if self.continue_on_error => {
+                        warn!(input, %err, "failed to generate checksums");
+                        generate_stats.push(GenerateFileStats::failed(input, err.to_string()));
+                        continue;
+                    }
+                    task => task?,
+                };
+                Self::print_sums(output_mode, &input, task.sums_file(), self.short)?;
                 sums_files.push((input, task.sums_file().clone()));
                 errors.extend(task.api_errors());
-                generate_stats.push(GenerateFileStats::from_task(task));
+                generate_stats.push(GenerateFileStats::from_task(task).shorten(self.short));
             }
 
+            let manifest_digest = self
+                .manifest_digest
+                .then(|| SumsFile::manifest_digest(&sums_files))
+                .transpose()?;
             Ok((
                 sums_files,
-                Some(GenerateStats::new(
-                    now.elapsed().as_secs_f64(),
-                    generate_stats,
-                    check_stats,
-                    errors,
-                )),
+                Some(
+                    GenerateStats::new(
+                        now.elapsed().as_secs_f64(),
+                        generate_stats,
+                        check_stats,
+                        errors,
+                    )
+                    .set_manifest_digest(manifest_digest)
+                    .set_channel_metrics(channel_metrics.as_deref().map(ChannelMetrics::stats)),
+                ),
             ))
         }
     }
@@ -359,9 +1404,15 @@ impl Generate {
 /// The check subcommand components.
 #[derive(Debug, Args)]
 pub struct Check {
-    /// The input file to check a checksum. Requires at least two files.
-    #[arg(value_delimiter = ',', required = true, num_args = 2..)]
+    /// The input file to check a checksum. Requires at least two files, including any
+    /// read from `--input-file`.
+    #[arg(value_delimiter = ',', required_unless_present = "input_file")]
     pub input: Vec<String>,
+    /// Read additional inputs from a file containing one input per line. Empty lines and lines
+    /// starting with `#` are ignored. Use - to read the list from stdin. This is appended to
+    /// any other inputs.
+    #[arg(long, env)]
+    pub input_file: Option<String>,
     /// Update existing sums files when running the `check` subcommand. This will add checksums to
     /// any sums files that are confirmed to be identical through other sums files.
     #[arg(short, long, env)]
@@ -373,9 +1424,37 @@ pub struct Check {
     pub group_by: GroupBy,
     /// Generate missing sums for the check. This is equivalent to `--missing` on the `generate`
     /// command except that it does not write .sums to the input location unless `--write-sums-file`
-    /// is also specified.
+    /// is also specified. This is enabled automatically when checking local files that do not
+    /// have a .sums file against S3 objects.
     #[arg(short, long, env)]
     pub missing: bool,
+    /// Fail if sums files that are identical contain different values for the same checksum
+    /// algorithm. By default, conflicting values are overwritten when merging sums files,
+    /// which can hide data integrity problems.
+    #[arg(long, env)]
+    pub strict_merge: bool,
+    /// Only print output when the inputs are not all in the same group, and exit with an
+    /// error in that case. Nothing is printed if all inputs are the same. Sums files are still
+    /// updated when using `--update`.
+    #[arg(short, long, env)]
+    pub quiet: bool,
+    /// Treat the inputs as sums files and compare their recorded checksums directly, without
+    /// accessing the files that they describe, e.g. `a.sums b.sums`. The format of each sums
+    /// file is detected from its ending.
+    #[arg(long, env, conflicts_with_all = ["missing", "update"])]
+    pub sums_only: bool,
+    /// Leave out inputs that cannot be read rather than stopping the check. The other inputs
+    /// are still compared, failed inputs and their errors are reported in the output, and the
+    /// command exits with an error at the end.
+    #[arg(long, env)]
+    pub continue_on_error: bool,
+    /// Only consider inputs to be identical if they share a checksum that is at least this
+    /// strong, e.g. `sha256`. Inputs that only share weaker checksums, such as a CRC or MD5,
+    /// are not grouped together and are reported as weakly matched instead. When generating
+    /// missing sums, only checksums that are at least this strong are computed, rather than
+    /// preferring `crc32c`.
+    #[arg(long, env)]
+    pub min_strength: Option<Strength>,
 }
 
 impl Check {
@@ -384,12 +1463,19 @@ impl Check {
         input: Vec<String>,
         clients: Vec<Arc<Client>>,
         avoid_get_object_attributes: bool,
+        output: &Output,
+        continue_on_error: bool,
     ) -> Result<(CheckTask, GroupBy)> {
         Ok((
             CheckTaskBuilder::default()
                 .with_input_files(input)
                 .with_group_by(GroupBy::Comparability)
                 .with_avoid_get_object_attributes(avoid_get_object_attributes)
+                .with_format(output.sums_format())
+                .with_storage(output.sums_storage)
+                .set_sums_source(output.sums_source.clone())
+                .set_output_dir(output.output_dir.clone())
+                .with_continue_on_error(continue_on_error)
                 .with_clients(clients)
                 .build()
                 .await?
@@ -399,10 +1485,77 @@ impl Check {
         ))
     }
 
-    /// Determine sums to generate based on a comparability check
-    fn generate_sums(ctxs: CheckTask) -> Vec<Ctx> {
-        if ctxs.is_empty() {
-            vec![Ctx::default()]
+    /// Whether any local or HTTP inputs do not have a sums file while S3 inputs are present. In this
+    /// case, the local checksums can be computed to match the S3 object metadata.
+    pub async fn local_sums_missing(
+        input: &[String],
+        format: SumsFormat,
+        sums_source: Option<String>,
+        output_dir: Option<String>,
+    ) -> Result<bool> {
+        let providers = input
+            .iter()
+            .map(|input| Provider::try_from(input.as_str()))
+            .collect::<Result<Vec<_>>>()?;
+        // Reading the sums of HTTP inputs requires a request, so avoid it if there is no S3 input.
+        if !providers.iter().any(Provider::is_s3) {
+            return Ok(false);
+        }
+
+        for (input, provider) in input.iter().zip(providers) {
+            // HTTP locations are read-only so only have sums files in a sums source.
+            if !provider.is_s3()
+                && ObjectSumsBuilder::default()
+                    .with_format(format)
+                    .set_sums_source(sums_source.clone())
+                    .set_output_dir(output_dir.clone())
+                    .build(input.to_string())
+                    .await?
+                    .sums_file()
+                    .await?
+                    .is_none()
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Read the inputs as sums files without accessing the files that they describe. The format
+    /// of the file ending is used if there is one.
+    pub async fn read_sums_files(
+        input: &[String],
+        clients: &[Arc<Client>],
+        format: SumsFormat,
+    ) -> Result<Vec<(String, SumsFile)>> {
+        let mut sums_files = vec![];
+        for (input, client) in input.iter().zip(clients.iter().cycle()) {
+            let sums_file = ObjectSumsBuilder::default()
+                .set_client(Some(client.clone()))
+                .with_format(format)
+                .build(input.to_string())
+                .await?
+                .sums_file()
+                .await?
+                .ok_or_else(|| NotFound(format!("sums file `{}`", input)))?;
+
+            sums_files.push((input.to_string(), sums_file));
+        }
+
+        Ok(sums_files)
+    }
+
+    /// Determine sums to generate based on a comparability check. If no input has a checksum
+    /// with at least the minimum strength, the default checksum for that strength is generated.
+    fn generate_sums(ctxs: CheckTask, min_strength: Option<Strength>) -> Vec<Ctx> {
+        let has_strong = ctxs.state_objects().keys().any(|SumsKey((sums, _))| {
+            sums.checksums
+                .keys()
+                .any(|ctx| min_strength.is_none_or(|min_strength| ctx.strength() >= min_strength))
+        });
+        if ctxs.is_empty() || !has_strong {
+            vec![min_strength.map(Strength::default_ctx).unwrap_or_default()]
         } else {
             vec![]
         }
@@ -413,37 +1566,94 @@ impl Check {
         self,
         optimization: Optimization,
         credentials: &Credentials,
-        write_sums_file: bool,
+        output: &Output,
         verify: bool,
         clients: Vec<Arc<Client>>,
     ) -> Result<CheckStats> {
         let now = Instant::now();
+        let format = output.sums_format();
+        let storage = output.sums_storage;
         let group_by = self.group_by;
 
         let mut builder = CheckTaskBuilder::default()
             .with_group_by(group_by)
             .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
+            .with_format(format)
+            .with_storage(storage)
+            .set_sums_source(output.sums_source.clone())
+            .set_output_dir(output.output_dir.clone())
             .with_input_files(self.input.clone())
             .with_update(self.update)
+            .with_strict_merge(self.strict_merge)
+            .with_continue_on_error(self.continue_on_error)
+            .set_min_strength(self.min_strength)
             .with_clients(clients.clone());
         let mut generate_stats = None;
-        if self.missing {
+        if self.sums_only {
+            builder = builder
+                .with_sums_files(Check::read_sums_files(&self.input, &clients, format).await?);
+        } else if self.missing
+            || Check::local_sums_missing(
+                &self.input,
+                format,
+                output.sums_source.clone(),
+                output.output_dir.clone(),
+            )
+            .await?
+        {
             let (ctxs, _) = Check::comparable_check(
                 self.input.clone(),
                 clients.clone(),
                 credentials.avoid_get_object_attributes,
+                output,
+                self.continue_on_error,
             )
             .await?;
-            let checksum = Check::generate_sums(ctxs);
+            let checksum = Check::generate_sums(ctxs, self.min_strength);
 
             let (sums, stats) = Generate {
                 input: self.input.clone(),
+                input_file: None,
                 checksum,
                 missing: true,
+                min_strength: self.min_strength,
                 force_overwrite: false,
+                overwrite_policy: OverwritePolicy::Missing,
                 verify,
+                offset: None,
+                length: None,
+                input_size: None,
+                record_metadata: false,
+                record_parts: false,
+                strict_s3: false,
+                sums_destination: None,
+                dedup_compute: false,
+                since: None,
+                manifest_digest: false,
+                recursive: false,
+                concurrency: 1,
+                operation_timeout: None,
+                checkpoint: None,
+                short: None,
+                normalize_newlines: false,
+                decompress: None,
+                compare_to: None,
+                update: false,
+                continue_on_error: self.continue_on_error,
+                tee: None,
+                expect: None,
             }
-            .generate(optimization, credentials, clients.clone(), write_sums_file)
+            .generate(
+                optimization,
+                credentials,
+                clients.clone(),
+                output.write_sums_file,
+                // Sums generated for a check are part of the check output rather than streamed.
+                &Output {
+                    output_mode: OutputMode::Stats,
+                    ..output.clone()
+                },
+            )
             .await?;
             generate_stats = stats;
 
@@ -490,6 +1700,22 @@ impl MetadataCopy {
     }
 }
 
+/// When an existing sums file is overwritten by `generate`.
+#[derive(Debug, Clone, ValueEnum, Copy, Default, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Never modify an existing sums file, even if it is missing checksums.
+    Never,
+    /// Compute only the checksums that are missing from an existing sums file and merge them
+    /// into it, keeping the existing checksums.
+    #[default]
+    Missing,
+    /// Replace an existing sums file if the size of the input changed, otherwise merge missing
+    /// checksums into it. The sums file is replaced if the size of the input is not known.
+    SizeChanged,
+    /// Always replace an existing sums file by recomputing the specified checksums.
+    Always,
+}
+
 /// Mode to execute copy task in.
 #[derive(Debug, Clone, ValueEnum, Copy, Default, Deserialize, Serialize)]
 pub enum CopyMode {
@@ -587,6 +1813,31 @@ pub struct Copy {
     /// match.
     #[arg(long, env)]
     pub no_skip: bool,
+    /// Verify the copy by computing a checksum over the data as it is uploaded, and comparing it
+    /// to the sums at the source. This avoids reading the copied data again after the copy. It
+    /// only applies to download-upload copies where the source has existing sums, otherwise
+    /// the copy is checked afterwards as usual.
+    #[arg(long, env, conflicts_with = "no_check")]
+    pub verify_during_copy: bool,
+    /// Verify the destination after the copy by comparing its checksum to the sums at the
+    /// source, and fail the copy if they differ. Native checksums of the destination, such as
+    /// S3 additional checksums, are used where possible so that the data is not read again.
+    /// Otherwise, the destination is read to compute a checksum. This replaces the check that
+    /// is performed after the copy by default.
+    #[arg(long, env, conflicts_with = "no_check")]
+    pub verify_after_write: bool,
+    /// When verifying after the copy, use the checksum algorithm that the destination reports,
+    /// such as the default checksum of an S3 bucket, and compute it at the source if the source
+    /// sums do not contain it. This avoids reading the destination again when the destination
+    /// chose a different algorithm than the source sums.
+    #[arg(long, env, requires = "verify_after_write")]
+    pub algorithm_from_destination: bool,
+    /// Send checksums that were already computed for the source, e.g. the sha256 in its sums
+    /// file, with S3 uploads. S3 then validates and stores them as additional checksums rather
+    /// than computing them separately. Part checksums are sent with multipart uploads if the
+    /// source sums contain them for the same part size.
+    #[arg(long, env)]
+    pub send_checksums: bool,
 }
 
 impl Copy {
@@ -597,20 +1848,26 @@ impl Copy {
         optimization: Optimization,
         credentials: &Credentials,
         verify: bool,
-        write_sums_file: bool,
+        output: &Output,
     ) -> Result<CheckStats> {
         let input = vec![self.source.to_string(), self.destination.to_string()];
 
         let result = Check {
             input,
-            update: write_sums_file,
+            input_file: None,
+            update: output.write_sums_file,
             group_by: GroupBy::Equality,
             missing: true,
+            strict_merge: false,
+            quiet: false,
+            sums_only: false,
+            continue_on_error: false,
+            min_strength: None,
         }
         .check(
             optimization,
             credentials,
-            write_sums_file,
+            output,
             verify,
             vec![source_client, destination_client],
         )
@@ -626,7 +1883,7 @@ impl Copy {
         destination_client: Arc<Client>,
         credentials: Credentials,
         optimization: Optimization,
-        write_sums_file: bool,
+        output: &Output,
     ) -> Result<CopyStats> {
         let now = Instant::now();
 
@@ -636,6 +1893,7 @@ impl Copy {
             let file_size = ObjectSumsBuilder::default()
                 .set_client(Some(source_client.clone()))
                 .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
+                .with_format(output.sums_format())
                 .build(self.destination.to_string())
                 .await?
                 .file_size()
@@ -653,11 +1911,16 @@ impl Copy {
                         optimization.clone(),
                         &credentials,
                         false,
-                        write_sums_file,
+                        output,
                     )
                     .await?;
 
                 if check_stats.groups.len() == 1 {
+                    info!(
+                        source = self.source,
+                        destination = self.destination,
+                        "skipping copy because the sums match"
+                    );
                     let copy_stats = CopyStats {
                         elapsed_seconds: now.elapsed().as_secs_f64(),
                         source: self.source,
@@ -665,6 +1928,8 @@ impl Copy {
                         bytes_transferred: 0,
                         copy_mode: self.copy_mode,
                         reason: Option::<ChecksumPair>::from(&check_stats),
+                        verified_during_copy: false,
+                        verified_after_write: None,
                         skipped: true,
                         sums_mismatch: false,
                         n_retries: 0,
@@ -690,9 +1955,15 @@ impl Copy {
             .with_tag_mode(self.tag_mode)
             .with_multipart_threshold(self.multipart_threshold)
             .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
+            .with_format(output.sums_format())
             .with_concurrency(self.concurrency)
             .with_part_size(self.part_size)
             .with_copy_mode(copy_mode)
+            .with_verify_during_copy(self.verify_during_copy)
+            .with_verify_after_write(self.verify_after_write)
+            .with_algorithm_from_destination(self.algorithm_from_destination)
+            .with_send_checksums(self.send_checksums)
+            .with_capacity(optimization.channel_capacity)
             .with_source_client(source_client.clone())
             .with_destination_client(destination_client.clone())
             .build()
@@ -702,7 +1973,10 @@ impl Copy {
 
         // If the file existed at the start there must be a sums mismatch.
         let sums_mismatch = exists;
-        let copy_stats = if !self.no_check {
+        let copy_stats = if !self.no_check
+            && result.verified().is_none()
+            && result.verified_after_write().is_none()
+        {
             let check_stats = self
                 .copy_check(
                     source_client,
@@ -710,7 +1984,7 @@ impl Copy {
                     optimization,
                     &credentials,
                     sums_mismatch,
-                    write_sums_file,
+                    output,
                 )
                 .await?;
             CopyStats::from_task(
@@ -728,96 +2002,994 @@ impl Copy {
     }
 }
 
-/// The subcommands for cloud-checksum.
-#[derive(Subcommand, Debug)]
-pub enum Subcommands {
-    /// Generate a checksum.
-    Generate(#[arg(flatten)] Generate),
-    /// Confirm a set of files is identical. This returns sets of files that are identical.
-    /// Which means that more than two files can be checked at the same time.
-    Check(#[arg(flatten)] Check),
-    /// Copy a file to a location. This command can also simultaneously generate checksums, and
-    /// supports all options for generate.
-    Copy(#[arg(flatten)] Copy),
+/// The cat subcommand components.
+#[derive(Debug, Args)]
+pub struct Cat {
+    /// The object to stream to stdout. Accepts a file name or an S3 object using the
+    /// `s3://bucket/object` syntax.
+    #[arg(required = true)]
+    pub input: String,
+    /// Checksums to compute while streaming the object. Can be specified multiple times or
+    /// comma-separated. The computed checksums are printed to stderr once the object has
+    /// been written to stdout. This supports the same checksums as `generate`.
+    #[arg(value_delimiter = ',', short, long)]
+    pub checksum: Vec<Ctx>,
+    /// Only stream the object starting at this byte offset. This can be specified with a size
+    /// unit, e.g. 1mib.
+    #[arg(long, env, value_parser = |s: &str| parse_size(s))]
+    pub offset: Option<u64>,
+    /// Only stream this many bytes of the object. This can be specified with a size unit, e.g.
+    /// 64kib. By default, the object is streamed to the end.
+    #[arg(long, env, value_parser = |s: &str| parse_size(s))]
+    pub length: Option<u64>,
 }
 
-/// The checksum to use.
-#[derive(Debug, Clone, ValueEnum, PartialEq, Eq, PartialOrd, Ord, Copy)]
-pub enum Checksum {
-    /// Calculate the MD5 checksum.
-    MD5,
-    /// Calculate the SHA1 checksum.
-    SHA1,
-    /// Calculate the SHA256 checksum.
-    SHA256,
-    /// Calculate a CRC32.
-    CRC32,
-    /// Calculate a CRC32C.
-    CRC32C,
-    /// Calculate a CRC64NVME.
-    CRC64NVME,
-    /// Calculate the QuickXor checksum.
-    QuickXor,
+impl Cat {
+    /// Perform the cat sub command from the args. Returns the computed checksums if any
+    /// were requested.
+    pub async fn cat(
+        self,
+        optimization: Optimization,
+        client: Arc<Client>,
+    ) -> Result<Option<SumsFile>> {
+        let compute = !self.checksum.is_empty();
+        let sums = CatTaskBuilder::default()
+            .with_source(self.input)
+            .set_client(Some(client))
+            .with_context(self.checksum)
+            .with_capacity(optimization.channel_capacity)
+            .set_offset(self.offset)
+            .set_length(self.length)
+            .build()
+            .await?
+            .run(stdout())
+            .await?;
+
+        Ok(compute.then_some(sums))
+    }
 }
 
-impl FromStr for Checksum {
-    type Err = Error;
+/// The predict-etag subcommand components.
+#[derive(Debug, Args)]
+pub struct PredictEtag {
+    /// The local file to predict the ETag of.
+    #[arg(required = true)]
+    pub input: String,
+    /// The part size of the planned multipart upload. This can be specified with a size unit,
+    /// e.g. 16mib. Without a part size, this predicts the `ETag` of a single `PutObject`
+    /// upload, which is the plain MD5 of the file, the same as the `md5` checksum.
+    #[arg(short, long, env, value_parser = |s: &str| parse_size(s))]
+    pub part_size: Option<u64>,
+}
 
-    fn from_str(s: &str) -> Result<Self> {
-        <Checksum as ValueEnum>::from_str(s, true).map_err(ParseError)
+impl PredictEtag {
+    /// Perform the predict-etag sub command from the args. Returns the ETag exactly as S3
+    /// reports it.
+    pub async fn predict_etag(self) -> Result<String> {
+        let file = Provider::try_from(self.input.as_str())?
+            .into_file()
+            .map_err(|_| ParseError(format!("`{}` is not a local file", self.input)))?;
+
+        predict_e_tag(Path::new(&file), self.part_size).await
     }
 }
 
-/// The endianness to use for CRC-based checksums.
-#[derive(Debug, Clone, ValueEnum, PartialEq, Eq, PartialOrd, Ord, Copy, Hash)]
-pub enum Endianness {
-    /// Use little-endian representation.
-    LittleEndian,
-    /// Use big-endian representation.
-    BigEndian,
+/// The info subcommand components.
+#[derive(Debug, Args)]
+pub struct Info {
+    /// The S3 object to get information about, e.g. `s3://bucket/key`. A specific version can
+    /// be selected using `?versionId=`.
+    pub input: String,
 }
 
-impl Display for Endianness {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Endianness::LittleEndian => f.write_str("le"),
-            Endianness::BigEndian => f.write_str("be"),
+impl Info {
+    /// Perform the info sub command from the args. This only reads the object metadata.
+    #[cfg(feature = "cloud")]
+    pub async fn info(self, client: Arc<Client>, credentials: &Credentials) -> Result<ObjectInfo> {
+        let Provider::S3 {
+            bucket,
+            key,
+            version_id,
+        } = Provider::try_from(self.input.as_str())?
+        else {
+            return Err(ParseError(format!("`{}` is not an S3 object", self.input)));
+        };
+
+        S3Builder::default()
+            .with_client(client)
+            .with_bucket(bucket)
+            .with_key(key)
+            .set_version_id(version_id)
+            .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
+            .build()?
+            .object_info()
+            .await
+    }
+
+    /// Without the `cloud` feature there is no object metadata to read.
+    #[cfg(not(feature = "cloud"))]
+    pub async fn info(
+        self,
+        _client: Arc<Client>,
+        _credentials: &Credentials,
+    ) -> Result<ObjectInfo> {
+        if !Provider::try_from(self.input.as_str())?.is_s3() {
+            return Err(ParseError(format!("`{}` is not an S3 object", self.input)));
         }
+
+        Err(s3_unsupported(&self.input))
     }
 }
 
-/// Commands related to optimizing IO and CPU tasks.
-#[derive(Args, Debug, Clone)]
-#[group(required = false)]
-pub struct Optimization {
-    /// The capacity of the sender channel for the channel reader. This controls the
-    /// number of elements that can be stored in the reader channel for waiting for checksum
-    /// processes to catch up.
-    #[arg(global = true, long, env, default_value_t = 100)]
-    pub channel_capacity: usize,
-    /// The chunk size of the channel reader in bytes. This controls how many bytes are read
-    /// by the reader before they are passed into the channel.
-    #[arg(global = true, long, env, default_value_t = 1048576)]
-    pub reader_chunk_size: usize,
+/// The bisect subcommand components.
+#[derive(Debug, Args)]
+pub struct Bisect {
+    /// The input to find the changed byte ranges of. Accepts a file name or an S3 object using
+    /// the `s3://bucket/object` syntax.
+    pub input: String,
+    /// A known-good sums file with part checksums, generated using `--record-parts`. By default,
+    /// the sums file of the input is used.
+    #[arg(long, env)]
+    pub sums_file: Option<String>,
+    /// The AWS checksum whose parts are compared, e.g. `md5-aws-8mib`. By default, the recorded
+    /// checksum with the most parts is used.
+    #[arg(short, long, env)]
+    pub checksum: Option<Ctx>,
 }
 
-/// Options related to outputting information from the CLI.
-#[derive(Args, Debug)]
-#[group(required = false)]
-pub struct Output {
-    /// Print the output statistics using indented and multi-line json rather than on a single line.
-    #[arg(global = true, long, env)]
-    pub pretty_json: bool,
-    /// Write sums files at the location when copying or checking. By default, `copy` operations and
-    /// `check` operations with `--missing` will not write any .sums files at the source or
-    /// destination.
-    #[arg(global = true, long, env)]
-    pub write_sums_file: bool,
+impl Bisect {
+    /// Perform the bisect sub command from the args. This reads the whole input.
+    pub async fn bisect(
+        self,
+        optimization: Optimization,
+        client: Arc<Client>,
+    ) -> Result<BisectStats> {
+        BisectTaskBuilder::default()
+            .with_input(self.input)
+            .set_sums_file(self.sums_file)
+            .set_checksum(self.checksum)
+            .set_client(Some(client))
+            .with_capacity(optimization.channel_capacity)
+            .build()
+            .await?
+            .run()
+            .await
+    }
 }
 
-/// Options related to credentials. Options prefixed with `source_` affect `check`, `generate` and
-/// the source of a `copy` command. These options also have an alias without the prefix as they are
-/// used in all commands. Options prefixed with `destination_` only affect the destination of a
+/// The diff subcommand components.
+#[derive(Debug, Args)]
+pub struct Diff {
+    /// The first input to compare. Accepts a file name or an S3 object using the
+    /// `s3://bucket/object` syntax.
+    pub first: String,
+    /// The second input to compare, using the same syntax as the first.
+    pub second: String,
+}
+
+impl Diff {
+    /// Perform the diff sub command from the args. This reads both inputs up to the first
+    /// difference.
+    pub async fn diff(self, client: Arc<Client>) -> Result<DiffStats> {
+        DiffTaskBuilder::default()
+            .with_first(self.first)
+            .with_second(self.second)
+            .set_client(Some(client))
+            .build()
+            .await?
+            .run()
+            .await
+    }
+}
+
+/// The blocks subcommand components.
+#[derive(Debug, Args)]
+pub struct Blocks {
+    /// The input to compute block checksums for. Accepts a file name or an S3 object using the
+    /// `s3://bucket/object` syntax.
+    pub input: String,
+    /// The size of each block. This can be specified with a size unit, e.g. 64kib. The last
+    /// block is shorter if the size of the input is not a multiple of the block size. The block
+    /// size can be at most 64mib.
+    #[arg(short, long, env, default_value = "1mib", value_parser = |s: &str| parse_size(s))]
+    pub block_size: u64,
+}
+
+impl Blocks {
+    /// Perform the blocks sub command from the args. This reads the whole input.
+    pub async fn blocks(self, client: Arc<Client>) -> Result<BlockSums> {
+        BlocksTaskBuilder::default()
+            .with_input(self.input)
+            .with_block_size(self.block_size)
+            .set_client(Some(client))
+            .build()
+            .await?
+            .run()
+            .await
+    }
+}
+
+/// The dataset-id subcommand components.
+#[derive(Debug, Args)]
+pub struct DatasetId {
+    /// The files in the dataset, using the same syntax as `generate`. The inputs are used as
+    /// the names of the files, so relative paths should be used for an identity that does not
+    /// depend on where the dataset is located.
+    #[arg(value_delimiter = ',', required = true)]
+    pub input: Vec<String>,
+    /// The number of inputs to compute checksums for at the same time.
+    #[arg(long, env, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub concurrency: u64,
+}
+
+impl DatasetId {
+    /// Perform the dataset-id sub command from the args. The sha256 of each input is read from
+    /// its sums file if it has one, and computed otherwise.
+    pub async fn dataset_id(
+        self,
+        optimization: Optimization,
+        credentials: &Credentials,
+        clients: Vec<Arc<Client>>,
+        output: &Output,
+    ) -> Result<DatasetIdStats> {
+        let now = Instant::now();
+        let sha256 = Ctx::from_str("sha256")?;
+
+        let inputs = self.input.into_iter().zip(clients.into_iter().cycle());
+        let mut tasks = stream::iter(inputs)
+            .map(|(input, client)| {
+                let builder = GenerateTaskBuilder::default()
+                    .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
+                    .with_input_file_name(input.to_string())
+                    .with_context(vec![sha256.clone()])
+                    .with_capacity(optimization.channel_capacity)
+                    .with_client(client)
+                    .with_format(output.sums_format())
+                    .with_storage(output.sums_storage)
+                    .set_sums_source(output.sums_source.clone())
+                    .set_output_dir(output.output_dir.clone())
+                    .set_write(output.write_sums_file);
+
+                let name = input.to_string();
+                async move {
+                    let sums = builder.build().await?.run().await?.into_inner().0;
+                    Ok::<_, Error>((input, sums))
+                }
+                .instrument(info_span!("dataset_id", input = %name))
+            })
+            .buffered(usize::try_from(self.concurrency)?);
+
+        let mut sums_files = vec![];
+        while let Some(task) = tasks.next().await {
+            sums_files.push(task?);
+        }
+
+        Ok(DatasetIdStats {
+            elapsed_seconds: now.elapsed().as_secs_f64(),
+            dataset_id: SumsFile::dataset_id(&sums_files)?,
+            n_files: sums_files.len(),
+            size: sums_files.iter().filter_map(|(_, sums)| sums.size).sum(),
+        })
+    }
+}
+
+/// The self-test subcommand components.
+#[derive(Debug, Args)]
+pub struct SelfTest {}
+
+impl SelfTest {
+    /// Perform the self-test sub command. This runs every checksum algorithm against embedded
+    /// known-answer vectors.
+    pub fn self_test(self) -> SelfTestStats {
+        self_test::run(self_test::KNOWN_ANSWERS)
+    }
+}
+
+/// The schema subcommand components.
+#[derive(Debug, Args)]
+pub struct Schema {}
+
+impl Schema {
+    /// Perform the schema sub command. This returns the JSON Schema of sums files.
+    pub fn schema(self) -> RootSchema {
+        SumsFile::json_schema()
+    }
+}
+
+/// The repair subcommand components.
+#[derive(Debug, Args)]
+pub struct Repair {
+    /// The sums files to repair. This accepts either the sums file or the object it describes,
+    /// using the same syntax as `generate`. Inputs without a sums file are skipped.
+    #[arg(value_delimiter = ',', required = true)]
+    pub input: Vec<String>,
+    /// Checksums that the sums files must contain. Only the checksums that are missing from a
+    /// sums file are computed, and sums files that already contain all of them are not changed.
+    /// Can be specified multiple times or comma-separated.
+    #[arg(value_delimiter = ',', short, long, required = true)]
+    pub ensure: Vec<Ctx>,
+    /// The number of inputs to repair at the same time.
+    #[arg(long, env, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub concurrency: u64,
+}
+
+impl Repair {
+    /// Perform the repair sub command from the args.
+    pub async fn repair(
+        self,
+        optimization: Optimization,
+        credentials: &Credentials,
+        clients: Vec<Arc<Client>>,
+        output: &Output,
+    ) -> Result<GenerateStats> {
+        let now = Instant::now();
+
+        let inputs = self.input.into_iter().zip(clients.into_iter().cycle());
+        let mut tasks = stream::iter(inputs)
+            .map(|(input, client)| {
+                let builder = RepairTaskBuilder::default()
+                    .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
+                    .with_input_file_name(input.to_string())
+                    .with_ensure(self.ensure.clone())
+                    .with_capacity(optimization.channel_capacity)
+                    .set_client(Some(client))
+                    .with_format(output.sums_format())
+                    .with_storage(output.sums_storage)
+                    .set_sums_source(output.sums_source.clone())
+                    .set_output_dir(output.output_dir.clone());
+
+                async move { builder.build().await?.run().await }
+                    .instrument(info_span!("repair", input = %input))
+            })
+            .buffered(usize::try_from(self.concurrency)?);
+
+        let mut stats = vec![];
+        let mut errors = HashSet::new();
+        while let Some(task) = tasks.next().await {
+            let task = task?;
+            errors.extend(task.api_errors());
+            stats.push(GenerateFileStats::from_repair_task(task));
+        }
+
+        Ok(GenerateStats::new(
+            now.elapsed().as_secs_f64(),
+            stats,
+            None,
+            errors,
+        ))
+    }
+}
+
+/// The prune subcommand components.
+#[derive(Debug, Args)]
+pub struct Prune {
+    /// The sums files to prune. This accepts either the sums file or the object it describes,
+    /// using the same syntax as `generate`. Inputs without a sums file are skipped.
+    #[arg(value_delimiter = ',', required = true)]
+    pub input: Vec<String>,
+    /// Checksums to keep in the sums files. All other checksums, and their part checksums, are
+    /// removed. A regular checksum also keeps the AWS checksums of the same algorithm, e.g. `md5`
+    /// keeps `md5-aws-8mib`. Can be specified multiple times or comma-separated.
+    #[arg(value_delimiter = ',', short, long, required = true)]
+    pub keep: Vec<Ctx>,
+    /// Allow pruning to remove every checksum from a sums file. By default, this is an error and
+    /// no sums files are changed.
+    #[arg(long, env)]
+    pub allow_empty: bool,
+    /// The number of sums files to read at the same time.
+    #[arg(long, env, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub concurrency: u64,
+}
+
+impl Prune {
+    /// Perform the prune sub command from the args. All sums files are read and pruned before
+    /// any are written, so that an error does not leave some of them pruned.
+    pub async fn prune(self, clients: Vec<Arc<Client>>, output: &Output) -> Result<PruneStats> {
+        let now = Instant::now();
+
+        let inputs = self.input.into_iter().zip(clients.into_iter().cycle());
+        let mut tasks = stream::iter(inputs)
+            .map(|(input, client)| {
+                let builder = PruneTaskBuilder::default()
+                    .with_input_file_name(input.to_string())
+                    .with_keep(self.keep.clone())
+                    .with_allow_empty(self.allow_empty)
+                    .set_client(Some(client))
+                    .with_format(output.sums_format())
+                    .with_storage(output.sums_storage)
+                    .set_sums_source(output.sums_source.clone())
+                    .set_output_dir(output.output_dir.clone());
+
+                async move { builder.build().await }.instrument(info_span!("prune", input = %input))
+            })
+            .buffered(usize::try_from(self.concurrency)?);
+
+        let mut prune_tasks = vec![];
+        while let Some(task) = tasks.next().await {
+            prune_tasks.push(task?);
+        }
+
+        let mut pruned = BTreeMap::new();
+        let mut errors = HashSet::new();
+        for task in prune_tasks {
+            let task = task.run().await?;
+            errors.extend(task.api_errors());
+            let (location, removed) = task.into_inner();
+            pruned.insert(location, removed);
+        }
+
+        Ok(PruneStats {
+            elapsed_seconds: now.elapsed().as_secs_f64(),
+            pruned,
+            api_errors: errors,
+        })
+    }
+}
+
+/// The touch subcommand components.
+#[derive(Debug, Args)]
+pub struct Touch {
+    /// The sums files to touch. This accepts either the sums file or the object it describes,
+    /// using the same syntax as `generate`. Inputs without a sums file are skipped.
+    #[arg(value_delimiter = ',', required = true)]
+    pub input: Vec<String>,
+    /// Also update the recorded metadata of the objects, such as the modification time.
+    #[arg(long, env)]
+    pub record_metadata: bool,
+    /// Update the sums files even though their checksums are not re-verified. By default, it is
+    /// an error if any sums file would be updated, and no sums files are changed.
+    #[arg(long, env)]
+    pub force: bool,
+    /// The number of sums files to read at the same time.
+    #[arg(long, env, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub concurrency: u64,
+}
+
+impl Touch {
+    /// Perform the touch sub command from the args. All sums files are read before any are
+    /// written, so that an error does not leave some of them touched.
+    pub async fn touch(
+        self,
+        credentials: &Credentials,
+        clients: Vec<Arc<Client>>,
+        output: &Output,
+    ) -> Result<TouchStats> {
+        let now = Instant::now();
+        warn!("touch updates sums files without re-verifying their checksums");
+
+        let inputs = self.input.into_iter().zip(clients.into_iter().cycle());
+        let mut tasks = stream::iter(inputs)
+            .map(|(input, client)| {
+                let builder = TouchTaskBuilder::default()
+                    .with_input_file_name(input.to_string())
+                    .with_record_metadata(self.record_metadata)
+                    .with_force(self.force)
+                    .set_client(Some(client))
+                    .with_avoid_get_object_attributes(credentials.avoid_get_object_attributes)
+                    .with_format(output.sums_format())
+                    .with_storage(output.sums_storage)
+                    .set_sums_source(output.sums_source.clone())
+                    .set_output_dir(output.output_dir.clone());
+
+                async move { builder.build().await }.instrument(info_span!("touch", input = %input))
+            })
+            .buffered(usize::try_from(self.concurrency)?);
+
+        let mut touch_tasks = vec![];
+        while let Some(task) = tasks.next().await {
+            touch_tasks.push(task?);
+        }
+
+        let mut touched = BTreeMap::new();
+        let mut errors = HashSet::new();
+        for task in touch_tasks {
+            let task = task.run().await?;
+            errors.extend(task.api_errors());
+            if let (location, Some(update)) = task.into_inner() {
+                touched.insert(location, update);
+            }
+        }
+
+        Ok(TouchStats {
+            elapsed_seconds: now.elapsed().as_secs_f64(),
+            touched,
+            api_errors: errors,
+        })
+    }
+}
+
+/// The cas subcommand components.
+#[derive(Debug, Args)]
+pub struct Cas {
+    /// The object to store. Accepts a file name or an S3 object using the `s3://bucket/object`
+    /// syntax.
+    #[arg(required = true)]
+    pub input: String,
+    /// The checksum used to name the object at the destination. AWS ETag-style checksums are
+    /// not supported as they depend on the part size.
+    #[arg(short, long, env, default_value = "sha256")]
+    pub algorithm: Ctx,
+    /// The location to store the object under, e.g. a directory or `s3://bucket/prefix/`. The
+    /// object is copied to `<prefix>/<checksum>` and a .sums file is written next to it.
+    #[arg(short, long, env)]
+    pub prefix: String,
+    /// The number of simultaneous copy tasks to run when using multipart copies. This controls
+    /// how many simultaneous connections are made to copy files.
+    #[arg(long, env, default_value_t = 10)]
+    pub concurrency: usize,
+}
+
+impl Cas {
+    /// Perform the cas sub command from the args.
+    pub async fn cas(
+        self,
+        source_client: Arc<Client>,
+        destination_client: Arc<Client>,
+        credentials: Credentials,
+        optimization: Optimization,
+        output: &Output,
+    ) -> Result<CopyStats> {
+        if matches!(self.algorithm, Ctx::AWSEtag(_)) || self.algorithm.is_null() {
+            return Err(ParseError(format!(
+                "cannot use `{}` for a content-addressable location",
+                self.algorithm
+            )));
+        }
+
+        let avoid_get_object_attributes = credentials.avoid_get_object_attributes;
+        let sums = GenerateTaskBuilder::default()
+            .with_avoid_get_object_attributes(avoid_get_object_attributes)
+            .with_input_file_name(self.input.to_string())
+            .with_context(vec![self.algorithm.clone()])
+            .with_capacity(optimization.channel_capacity)
+            .with_client(source_client.clone())
+            .with_format(output.sums_format())
+            .with_storage(output.sums_storage)
+            .set_write(output.write_sums_file)
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        let checksum = sums.checksums.get(&self.algorithm).ok_or_else(|| {
+            CopyError(format!(
+                "failed to compute `{}` for `{}`",
+                self.algorithm, self.input
+            ))
+        })?;
+        let destination = format!(
+            "{}/{}",
+            self.prefix.trim_end_matches('/'),
+            checksum.as_str()
+        );
+
+        let stats = Copy {
+            source: self.input,
+            destination: destination.to_string(),
+            tag_mode: Default::default(),
+            metadata_mode: Default::default(),
+            copy_mode: Default::default(),
+            multipart_threshold: None,
+            part_size: None,
+            concurrency: self.concurrency,
+            no_check: false,
+            no_skip: false,
+            verify_during_copy: false,
+            verify_after_write: false,
+            send_checksums: false,
+            algorithm_from_destination: false,
+        }
+        .copy(
+            source_client,
+            destination_client.clone(),
+            credentials,
+            optimization,
+            output,
+        )
+        .await?;
+
+        // The copy is checked, so the source sums also apply to the destination.
+        let mut object = ObjectSumsBuilder::default()
+            .set_client(Some(destination_client))
+            .with_avoid_get_object_attributes(avoid_get_object_attributes)
+            .with_format(output.sums_format())
+            .with_storage(output.sums_storage)
+            .build(destination)
+            .await?;
+        let sums = match object.sums_file().await? {
+            Some(existing) => existing.merge(sums)?,
+            None => sums,
+        };
+        object.write_sums_file(&sums).await?;
+
+        Ok(stats)
+    }
+}
+
+/// The subcommands for cloud-checksum.
+#[derive(Subcommand, Debug)]
+pub enum Subcommands {
+    /// Generate a checksum.
+    Generate(#[arg(flatten)] Generate),
+    /// Confirm a set of files is identical. This returns sets of files that are identical.
+    /// Which means that more than two files can be checked at the same time.
+    Check(#[arg(flatten)] Check),
+    /// Copy a file to a location. This command can also simultaneously generate checksums, and
+    /// supports all options for generate.
+    Copy(#[arg(flatten)] Copy),
+    /// Stream an object to stdout. This can optionally compute checksums while the object
+    /// is read, which are printed to stderr.
+    Cat(#[arg(flatten)] Cat),
+    /// Copy an object to a content-addressable location named after its checksum. The copy
+    /// is skipped if an object with matching sums already exists at the location.
+    Cas(#[arg(flatten)] Cas),
+    /// Predict the ETag that S3 assigns to a local file when it is uploaded using a multipart
+    /// upload with a part size.
+    PredictEtag(#[arg(flatten)] PredictEtag),
+    /// Show the size, storage class, `ETag`, part size and the checksums that S3 already has
+    /// for an object, without reading the object.
+    Info(#[arg(flatten)] Info),
+    /// Add missing checksums to existing sums files. Only the missing checksums are computed,
+    /// and everything else in the sums files is kept.
+    Repair(#[arg(flatten)] Repair),
+    /// Remove the checksums that are not in a set of algorithms from existing sums files, e.g.
+    /// for a downstream system which only accepts some algorithms. The objects are not read.
+    Prune(#[arg(flatten)] Prune),
+    /// Update the recorded size of existing sums files to the current size of the objects,
+    /// e.g. to fix manifests copied from a template. The objects are not read, so the
+    /// checksums are not re-verified and may not match the objects.
+    Touch(#[arg(flatten)] Touch),
+    /// Find the byte ranges of an input that changed by comparing each part of an AWS checksum
+    /// with the part checksums recorded in a known-good sums file. Exits with an error if any
+    /// part differs.
+    Bisect(#[arg(flatten)] Bisect),
+    /// Compare two inputs byte by byte and report the offset of the first byte that differs.
+    /// Both inputs are read at the same time and reading stops at the first difference, so this
+    /// does not need sums files. Exits with an error if the inputs differ.
+    Diff(#[arg(flatten)] Diff),
+    /// Compute a weak rolling checksum and a sha256 of each fixed-size block of an input. This
+    /// is the signature that an rsync-style delta algorithm needs to find the blocks that are
+    /// unchanged in another file, even if they moved. The block checksums are printed as JSON
+    /// rather than written to a sums file.
+    Blocks(#[arg(flatten)] Blocks),
+    /// Compute a single digest that identifies a set of files, which depends on the name, size
+    /// and sha256 of each file but not on the order of the inputs. Existing sums files are used
+    /// for the sha256 if they have it.
+    DatasetId(#[arg(flatten)] DatasetId),
+    /// Run every checksum algorithm, including AWS ETag style checksums, against embedded
+    /// known-answer vectors. This validates a build, e.g. that hardware-accelerated CRCs are
+    /// detected and compiled correctly. Exits with an error if any algorithm fails.
+    SelfTest(#[arg(flatten)] SelfTest),
+    /// Print the JSON Schema of the sums file format, which can be used to validate sums files
+    /// in other languages.
+    Schema(#[arg(flatten)] Schema),
+}
+
+/// The checksum to use.
+#[derive(Debug, Clone, ValueEnum, PartialEq, Eq, PartialOrd, Ord, Copy)]
+pub enum Checksum {
+    /// Calculate the MD5 checksum.
+    MD5,
+    /// Calculate the SHA1 checksum.
+    SHA1,
+    /// Calculate the SHA256 checksum.
+    SHA256,
+    /// Calculate a CRC32.
+    CRC32,
+    /// Calculate a CRC32C.
+    CRC32C,
+    /// Calculate a CRC64NVME.
+    CRC64NVME,
+    /// Calculate the QuickXor checksum.
+    QuickXor,
+    /// A no-op checksum which reads data without hashing it. This is only useful for
+    /// benchmarking IO, and is never written to sums files.
+    Null,
+}
+
+impl Checksum {
+    /// The names of the checksum algorithms that can be computed. This does not include
+    /// the `null` checksum.
+    pub fn supported() -> Vec<String> {
+        Self::value_variants()
+            .iter()
+            .filter(|checksum| !matches!(checksum, Self::QuickXor | Self::Null))
+            .filter_map(|checksum| checksum.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .collect()
+    }
+
+    /// Create an error for an unsupported checksum algorithm.
+    pub fn unsupported_error(s: &str) -> Error {
+        ParseError(format!(
+            "unsupported checksum algorithm `{}`, expected one of: {}",
+            s,
+            Self::supported().join(", ")
+        ))
+    }
+}
+
+impl FromStr for Checksum {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        <Checksum as ValueEnum>::from_str(s, true).map_err(|_| Self::unsupported_error(s))
+    }
+}
+
+/// The strength of a checksum algorithm against collisions, from weakest to strongest.
+#[derive(Debug, Clone, ValueEnum, PartialEq, Eq, PartialOrd, Ord, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strength {
+    /// CRC-based checksums, which only detect accidental corruption.
+    Crc,
+    /// MD5 checksums, including AWS `ETag`s.
+    Md5,
+    /// SHA1 checksums.
+    Sha1,
+    /// SHA256 checksums.
+    Sha256,
+}
+
+impl Strength {
+    /// The checksum to generate when no existing checksum is at least this strong. This is the
+    /// default checksum if it is strong enough, and otherwise the algorithm of this strength.
+    pub fn default_ctx(self) -> Ctx {
+        match self {
+            Strength::Crc => Ctx::default(),
+            Strength::Md5 => Ctx::Regular(StandardCtx::md5()),
+            Strength::Sha1 => Ctx::Regular(StandardCtx::sha1()),
+            Strength::Sha256 => Ctx::Regular(StandardCtx::sha256()),
+        }
+    }
+}
+
+/// The endianness to use for CRC-based checksums.
+#[derive(Debug, Clone, ValueEnum, PartialEq, Eq, PartialOrd, Ord, Copy, Hash)]
+pub enum Endianness {
+    /// Use little-endian representation.
+    LittleEndian,
+    /// Use big-endian representation.
+    BigEndian,
+}
+
+impl Display for Endianness {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endianness::LittleEndian => f.write_str("le"),
+            Endianness::BigEndian => f.write_str("be"),
+        }
+    }
+}
+
+/// Commands related to optimizing IO and CPU tasks.
+#[derive(Args, Debug, Clone)]
+#[group(required = false)]
+pub struct Optimization {
+    /// The capacity of the sender channel for the channel reader. This controls the
+    /// number of elements that can be stored in the reader channel for waiting for checksum
+    /// processes to catch up.
+    #[arg(global = true, long, env, default_value_t = 100)]
+    pub channel_capacity: usize,
+    /// Report how often the reader channels were full or empty in the `generate` output
+    /// statistics. Full channels mean that the reader waited for the checksum processes, and
+    /// empty channels mean that the checksum processes waited for the reader, which helps
+    /// with tuning `--channel-capacity`.
+    #[arg(global = true, long, env)]
+    pub channel_metrics: bool,
+    /// Report how long the reader and the checksum processes of each input took in the
+    /// `generate` output statistics, both in total and while busy rather than waiting, and how
+    /// long the reader waited for reads of the input. A reader that waits for reads for most of
+    /// its time is limited by I/O, which can help with choosing `--concurrency`. Busy checksum
+    /// processes are limited by CPU, which can help with choosing the number of worker threads
+    /// using `TOKIO_WORKER_THREADS`. If both wait for each other, increasing
+    /// `--channel-capacity` can help.
+    #[arg(global = true, long, env)]
+    pub timings: bool,
+    /// The chunk size of the channel reader in bytes. This controls how many bytes are read
+    /// by the reader before they are passed into the channel.
+    #[arg(global = true, long, env, default_value_t = 1048576)]
+    pub reader_chunk_size: usize,
+    /// Skip reading the holes of sparse local files, such as VM images, by producing their
+    /// zeros in memory instead. The checksums are identical to reading the files normally.
+    /// Holes are only detected on Linux filesystems that support `SEEK_HOLE`, and other files
+    /// are read normally.
+    #[arg(global = true, long, env)]
+    pub sparse_files: bool,
+    /// The maximum amount of time for the whole command to run, e.g. `1h`.
+    #[arg(global = true, long, env)]
+    pub total_timeout: Option<Duration>,
+    /// Cache whole S3 objects that are read in this directory, so that reading them again,
+    /// e.g. in repeated checks, does not download them again. Cached objects are keyed by
+    /// their `ETag` and are only reused if the object has not changed. Downloads that stop
+    /// early are resumed from where they stopped.
+    #[arg(global = true, long, env)]
+    pub cache_dir: Option<PathBuf>,
+    /// The maximum size of the `--cache-dir`, e.g. 100gib. The least recently used objects are
+    /// removed when it becomes larger than this. By default, the cache is not limited.
+    #[arg(global = true, long, env, requires = "cache_dir", value_parser = |s: &str| parse_size(s))]
+    pub cache_size: Option<u64>,
+}
+
+impl Optimization {
+    /// Get the download cache if a `--cache-dir` is set.
+    pub fn download_cache(&self) -> Option<DownloadCache> {
+        self.cache_dir
+            .clone()
+            .map(|dir| DownloadCache::new(dir).set_max_size(self.cache_size))
+    }
+}
+
+/// A sums file annotated with the name of its input.
+#[derive(Debug, Serialize)]
+pub struct NamedSumsFile<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    sums_file: &'a SumsFile,
+}
+
+/// How the results of a command are output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputMode {
+    /// Print the output statistics once the command completes.
+    #[default]
+    Stats,
+    /// Print each sums file from `generate` as a single JSON line as soon as it is complete,
+    /// annotated with the name of the input. The output statistics are printed to stderr.
+    Ndjson,
+    /// Print the Subresource Integrity string of each input from `generate`, e.g.
+    /// `sha256-<base64> <input>`, as soon as it is complete. Only `sha256` can be used, as
+    /// `sha384` and `sha512` checksums are not supported. Other checksums are skipped with a
+    /// warning, and inputs without a `sha256` checksum are an error. The output statistics are
+    /// printed to stderr.
+    Sri,
+}
+
+/// Options related to outputting information from the CLI.
+#[derive(Args, Debug, Clone)]
+#[group(required = false)]
+pub struct Output {
+    /// Print the output statistics using indented and multi-line json rather than on a single line.
+    #[arg(global = true, long, env, visible_alias = "pretty")]
+    pub pretty_json: bool,
+    /// Write sums files at the location when copying or checking. By default, `copy` operations and
+    /// `check` operations with `--missing` will not write any .sums files at the source or
+    /// destination.
+    #[arg(global = true, long, env)]
+    pub write_sums_file: bool,
+    /// The format of sums files to read and write. `pretty-json` writes indented `.sums` files,
+    /// which diff better when committed to version control. `msgpack` writes compact binary sums
+    /// files with a `.sums.bin` ending, which are faster to parse for large numbers of files.
+    /// Inputs that explicitly end in `.sums` or `.sums.bin` are always read using that format.
+    #[arg(global = true, long, env, default_value = "json")]
+    pub format: SumsFormat,
+    /// Where to read and write sums for S3 objects. `tags` stores the sums file in the tags of
+    /// the object itself, which is useful when `.sums` objects cannot be created. Any existing
+    /// tags are kept, and it is an error if the sums file does not fit in the remaining tags.
+    /// Local files always use `.sums` files.
+    #[arg(global = true, long, env, default_value = "object")]
+    pub sums_storage: SumsStorage,
+    /// Read and write sums files in this local directory rather than next to the inputs, e.g.
+    /// `file://manifests/`. This decouples where the data is stored from where its sums files
+    /// are kept, such as S3 objects with sums files in a git repository. The sums file of an S3
+    /// object is keyed by its bucket and key rather than only its key, so that objects with the
+    /// same key in different buckets do not share sums, e.g. `manifests/bucket/path/to/key.sums`
+    /// for `s3://bucket/path/to/key`. The version of versioned objects is also included. HTTP
+    /// URLs are keyed by their host and path, and local files by their path. Keys that would
+    /// escape the directory using `..` are rejected.
+    #[arg(global = true, long, env)]
+    pub sums_source: Option<String>,
+    /// Read and write the sums files of local files in this directory rather than next to them,
+    /// which is useful when the inputs are read-only. The directory mirrors the absolute paths of
+    /// the inputs, e.g. `sums/data/file.sums` for `/data/file`, with relative inputs resolved
+    /// against the current directory, so that a later `check` with the same `--output-dir`
+    /// finds the sums files again. S3 objects and HTTP URLs are not affected.
+    #[arg(global = true, long, env, conflicts_with = "sums_source")]
+    pub output_dir: Option<String>,
+    /// How to output results. `ndjson` streams each sums file from `generate` as a line of
+    /// JSON as soon as it is complete, rather than only printing statistics at the end. `sri`
+    /// streams the Subresource Integrity string of each input instead.
+    #[arg(
+        global = true,
+        long = "output",
+        env = "OUTPUT_MODE",
+        default_value = "stats"
+    )]
+    pub output_mode: OutputMode,
+    /// Shorthand for `--output ndjson`. Each record is a JSON object with the `name` of the
+    /// input and the fields of its sums file, written on its own line and flushed immediately.
+    #[arg(global = true, long, env, conflicts_with = "output_mode")]
+    pub ndjson: bool,
+}
+
+impl Output {
+    /// The format used to write sums files.
+    pub fn sums_format(&self) -> SumsFormat {
+        self.format
+    }
+}
+
+/// The level of log messages to output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    /// Do not output any log messages.
+    Off,
+    Error,
+    #[default]
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// The format of log messages.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable log lines.
+    #[default]
+    Text,
+    /// A single line of JSON per log message.
+    Json,
+}
+
+/// Options related to logging. Logs are always written to stderr so that they do not mix with
+/// the output of commands.
+#[derive(Args, Debug, Clone)]
+#[group(required = false)]
+pub struct Logging {
+    /// The level of log messages to output. Warnings are output by default, and `off` disables
+    /// all log messages. `debug` shows each S3 call that is made, and `info` shows decisions
+    /// such as skipped, matched or retried objects. Dependencies such as the AWS SDK only output
+    /// warnings and errors.
+    #[arg(global = true, long, env, default_value = "warn")]
+    pub log_level: LogLevel,
+    /// The format of log messages. `json` outputs each message as a single line of JSON,
+    /// including the fields of the message and its spans, which can be ingested by log
+    /// pipelines.
+    #[arg(global = true, long, env, default_value = "text")]
+    pub log_format: LogFormat,
+}
+
+impl Logging {
+    /// Initialize the global subscriber which outputs log messages.
+    pub fn init(&self) -> Result<()> {
+        let level = LevelFilter::from(self.log_level);
+        let filter = EnvFilter::builder().parse_lossy(format!(
+            "{},{}={}",
+            level.min(LevelFilter::WARN),
+            env!("CARGO_CRATE_NAME"),
+            level
+        ));
+
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_writer(std::io::stderr);
+        match self.log_format {
+            LogFormat::Text => subscriber.try_init(),
+            LogFormat::Json => subscriber.json().try_init(),
+        }
+        .map_err(|err| ParseError(format!("failed to initialize logging: {}", err)))
+    }
+}
+
+/// Options related to credentials. Options prefixed with `source_` affect `check`, `generate` and
+/// the source of a `copy` command. These options also have an alias without the prefix as they are
+/// used in all commands. Options prefixed with `destination_` only affect the destination of a
 /// `copy` command.
 #[derive(Args, Debug)]
 #[group(required = false)]
@@ -836,18 +3008,21 @@ pub struct Credentials {
     /// destination of a `copy` operation.
     #[arg(global = true, long, env, default_value = "default-environment")]
     pub destination_credential_provider: CredentialProvider,
-    /// The source profile to use if the source credential provider is `aws-profile`.
-    /// This must be specified if using `aws-profile`.
+    /// The source profile to use. This must be specified if the source credential provider is
+    /// `aws-profile`. Otherwise, it overrides the profile of the default environment, e.g. the
+    /// `AWS_PROFILE` environment variable.
     #[arg(global = true, long, env, alias = "profile")]
     pub source_profile: Option<String>,
-    /// The destination profile to use if the destination credential provider is `aws-profile`.
-    /// This must be specified if using `aws-profile`.
+    /// The destination profile to use. This must be specified if the destination credential
+    /// provider is `aws-profile`. Otherwise, it overrides the profile of the default
+    /// environment.
     #[arg(global = true, long, env)]
     pub destination_profile: Option<String>,
-    /// Set the region for the source credential provider.
+    /// Set the region for the source credential provider. This overrides the region of the
+    /// environment or profile, e.g. the `AWS_REGION` environment variable.
     #[arg(global = true, long, env, alias = "region")]
     pub source_region: Option<String>,
-    /// Set the region for the source credential provider.
+    /// Set the region for the destination credential provider.
     #[arg(global = true, long, env)]
     pub destination_region: Option<String>,
     /// Set the source endpoint URL for AWS calls. This allows using a different endpoint that
@@ -858,6 +3033,14 @@ pub struct Credentials {
     /// that has an S3-compatible storage API.
     #[arg(global = true, long, env)]
     pub destination_endpoint_url: Option<String>,
+    /// Force path-style addressing for the source endpoint, i.e. `endpoint/bucket/key` rather
+    /// than `bucket.endpoint/key`. This is required by some S3-compatible servers such as
+    /// MinIO or LocalStack.
+    #[arg(global = true, long, env, alias = "force-path-style")]
+    pub source_force_path_style: bool,
+    /// Force path-style addressing for the destination endpoint.
+    #[arg(global = true, long, env)]
+    pub destination_force_path_style: bool,
     /// Avoid `GetObjectAttributes` calls when determining sums. `HeadObject` will be used as a
     /// fallback. `GetObjectAttributes` is preferred over `HeadObject` because it only requires
     /// a single call rather than a call for each part.
@@ -865,33 +3048,1570 @@ pub struct Credentials {
     pub avoid_get_object_attributes: bool,
 }
 
-impl Credentials {
-    /// Construct the source client from the credentials.
-    pub async fn source_client(&self) -> Result<Client> {
-        create_s3_client(
-            &self.source_credential_provider,
-            self.source_profile.as_deref(),
-            self.source_region.as_deref(),
-            self.source_endpoint_url.as_deref(),
+impl Credentials {
+    /// Construct the source client from the credentials.
+    pub async fn source_client(&self) -> Result<Client> {
+        create_s3_client(
+            &self.source_credential_provider,
+            self.source_profile.as_deref(),
+            self.source_region.as_deref(),
+            self.source_endpoint_url.as_deref(),
+            self.source_force_path_style,
+        )
+        .await
+    }
+
+    /// Construct the destination client from the credentials.
+    pub async fn destination_client(&self) -> Result<Client> {
+        create_s3_client(
+            &self.destination_credential_provider,
+            self.destination_profile.as_deref(),
+            self.destination_region.as_deref(),
+            self.destination_endpoint_url.as_deref(),
+            self.destination_force_path_style,
+        )
+        .await
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.source_credential_provider.is_default()
+            && self.destination_credential_provider.is_default()
+            && self.source_profile.is_none()
+            && self.destination_profile.is_none()
+            && self.source_endpoint_url.is_none()
+            && self.destination_endpoint_url.is_none()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::checksum::checksum_bytes;
+    use crate::checksum::file::Checksum;
+    #[cfg(feature = "cloud")]
+    use crate::io::sums::aws::test::mock_list_objects_rules;
+    use crate::io::sums::file::FileBuilder;
+    #[cfg(feature = "cloud")]
+    use aws_sdk_s3::operation::get_object_attributes::GetObjectAttributesOutput;
+    #[cfg(feature = "cloud")]
+    use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingOutput;
+    #[cfg(feature = "cloud")]
+    use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+    #[cfg(feature = "cloud")]
+    use aws_smithy_mocks::{mock, mock_client, RuleMode};
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    /// An S3 client for tests that do not make any requests.
+    #[cfg(feature = "cloud")]
+    fn unused_client() -> Arc<Client> {
+        Arc::new(mock_client!(aws_sdk_s3, &[]))
+    }
+
+    /// An S3 client for tests that do not make any requests.
+    #[cfg(not(feature = "cloud"))]
+    fn unused_client() -> Arc<Client> {
+        Arc::new(Client)
+    }
+
+    #[tokio::test]
+    async fn local_sums_missing() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&file, b"data").await?;
+        let input = vec![file.to_string(), "s3://bucket/key".to_string()];
+
+        assert!(Check::local_sums_missing(&input, SumsFormat::default(), None, None).await?);
+        assert!(!Check::local_sums_missing(&input[..1], SumsFormat::default(), None, None).await?);
+
+        FileBuilder::default()
+            .with_file(file)
+            .build()?
+            .write_sums(&SumsFile::default())
+            .await?;
+        assert!(!Check::local_sums_missing(&input, SumsFormat::default(), None, None).await?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_since() -> anyhow::Result<()> {
+        let since = UNIX_EPOCH + std::time::Duration::from_secs(1735689600);
+        assert_eq!(Generate::parse_since("2025-01-01T00:00:00Z")?, since);
+        assert_eq!(Generate::parse_since("@1735689600")?, since);
+        assert!(Generate::parse_since("@yesterday").is_err());
+        assert!(Generate::parse_since("yesterday").is_err());
+
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "generate",
+            "-c",
+            "md5",
+            "--since",
+            "@1735689600",
+            "file",
+        ])?;
+        let Subcommands::Generate(generate) = command.commands else {
+            panic!("expected generate subcommand");
+        };
+        assert_eq!(generate.since, Some(since));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn modified_since() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let path = |name| tmp.path().join(name).to_string_lossy().to_string();
+        let (old, new) = (path("old"), path("new"));
+        tokio::fs::write(&old, b"old").await?;
+        tokio::fs::write(&new, b"new").await?;
+
+        let since = Generate::parse_since("2025-01-01T00:00:00Z")?;
+        std::fs::File::options()
+            .write(true)
+            .open(&old)?
+            .set_modified(since - std::time::Duration::from_secs(1))?;
+
+        let client = unused_client();
+        let credentials =
+            Command::parse_from_iter(["cloud-checksum", "generate", "-c", "md5", "file"])?;
+        let input = Generate::modified_since(
+            vec![old.to_string(), new.to_string(), format!("{}+{}", old, new)],
+            since,
+            &[client],
+            &credentials.credentials,
+        )
+        .await?;
+
+        // Concatenated inputs do not have a modification time so they are always kept.
+        assert_eq!(input, vec![new.to_string(), format!("{}+{}", old, new)]);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cloud")]
+    #[tokio::test]
+    async fn recursive_generate() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let local = (0..5)
+            .map(|i| tmp.path().join(i.to_string()).to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        for (i, file) in local.iter().enumerate() {
+            tokio::fs::write(file, vec![0; i]).await?;
+        }
+
+        let client = Arc::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &mock_list_objects_rules()
+        ));
+        let input = Generate::list_prefixes(
+            vec!["s3://bucket/prefix/".to_string(), local[0].to_string()],
+            &[client],
+        )
+        .await?;
+        assert_eq!(
+            input,
+            vec![
+                "s3://bucket/prefix/a".to_string(),
+                "s3://bucket/prefix/b".to_string(),
+                "s3://bucket/prefix/c".to_string(),
+                local[0].to_string()
+            ]
+        );
+
+        let command = Command::parse_from_iter(
+            [
+                "cloud-checksum",
+                "generate",
+                "-c",
+                "md5",
+                "--concurrency",
+                "3",
+            ]
+            .into_iter()
+            .chain(local.iter().map(String::as_str)),
+        )?;
+        let Subcommands::Generate(generate) = command.commands else {
+            unreachable!();
+        };
+        let (sums, _) = generate
+            .generate(
+                command.optimization,
+                &command.credentials,
+                vec![unused_client()],
+                false,
+                &command.output,
+            )
+            .await?;
+
+        // Results are in the order of the inputs even when generated concurrently.
+        assert_eq!(
+            sums.iter().map(|(input, _)| input).collect::<Vec<_>>(),
+            local.iter().collect::<Vec<_>>()
+        );
+        assert!(sums
+            .iter()
+            .enumerate()
+            .all(|(i, (_, sums))| sums.size == Some(i as u64)));
+
+        assert!(Command::try_parse_from([
+            "cloud-checksum",
+            "generate",
+            "--concurrency",
+            "0",
+            "file"
+        ])
+        .is_err());
+        assert!(
+            Command::parse_from_iter(["cloud-checksum", "generate", "-r", "s3://bucket"]).is_ok()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn operation_timeout() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&file, b"hello world").await?;
+
+        // A server that accepts connections but never responds.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/object", listener.local_addr()?);
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "generate",
+            "--operation-timeout",
+            "200ms",
+            "-c",
+            "md5",
+            "--concurrency",
+            "2",
+            &url,
+            &file,
+        ])?;
+        let Subcommands::Generate(generate) = command.commands else {
+            unreachable!();
+        };
+        let (sums, stats) = generate
+            .generate(
+                command.optimization,
+                &command.credentials,
+                vec![unused_client()],
+                false,
+                &command.output,
+            )
+            .await?;
+
+        // The other input still completes.
+        assert_eq!(sums.len(), 1);
+        assert_eq!(sums[0].0, file);
+
+        let stats = stats.unwrap();
+        assert_eq!(stats.n_timed_out(), 1);
+        let timed_out = stats.stats.iter().find(|stat| stat.timed_out).unwrap();
+        assert_eq!(timed_out.input, url);
+        assert!(to_string(&stats)?.contains(r#""timed_out":true"#));
+
+        let err = Command::with_timeout(
+            Some("10ms".parse()?),
+            async {
+                tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                Ok(())
+            },
+            |timeout| format!("after {}", timeout),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Timeout(message) if message == "after 10ms"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn operation_timeout_aborts_tasks() -> anyhow::Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A server that starts sending the body and then stalls until the connection is closed.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/object", listener.local_addr()?);
+        let stalled = Arc::new(AtomicUsize::new(0));
+        let started = Arc::new(AtomicUsize::new(0));
+        let (open, opened) = (stalled.clone(), started.clone());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (open, opened) = (open.clone(), opened.clone());
+                tokio::spawn(async move {
+                    let mut buf = vec![0; 4096];
+                    let n = stream.read(&mut buf).await?;
+                    let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                    let response = if request.starts_with("head") {
+                        "HTTP/1.1 200 OK\r\ncontent-length: 1000\r\nconnection: close\r\n\r\n"
+                            .to_string()
+                    } else if request.contains("range: bytes=0-0") {
+                        "HTTP/1.1 206 Partial Content\r\ncontent-range: bytes 0-0/1000\r\ncontent-length: 1\r\nconnection: close\r\n\r\n0".to_string()
+                    } else {
+                        format!(
+                            "HTTP/1.1 200 OK\r\ncontent-length: 1000\r\n\r\n{}",
+                            "0".repeat(10)
+                        )
+                    };
+                    stream.write_all(response.as_bytes()).await?;
+                    if response.contains("connection: close") {
+                        return Ok::<_, std::io::Error>(());
+                    }
+
+                    opened.fetch_add(1, Ordering::SeqCst);
+                    open.fetch_add(1, Ordering::SeqCst);
+                    while stream.read(&mut buf).await? != 0 {}
+                    open.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                });
+            }
+        });
+
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "generate",
+            "--operation-timeout",
+            "500ms",
+            "-c",
+            "md5",
+            &url,
+        ])?;
+        let Subcommands::Generate(generate) = command.commands else {
+            unreachable!();
+        };
+        let (_, stats) = generate
+            .generate(
+                command.optimization,
+                &command.credentials,
+                vec![unused_client()],
+                false,
+                &command.output,
+            )
+            .await?;
+        assert_eq!(stats.unwrap().n_timed_out(), 1);
+        assert!(started.load(Ordering::SeqCst) > 0);
+
+        // The reader of the timed out input is stopped, which closes its connection.
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while stalled.load(Ordering::SeqCst) != 0 {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn continue_on_error() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let path = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        let (a, missing, b) = (path("a"), path("missing"), path("b"));
+        tokio::fs::write(&a, b"hello world").await?;
+        tokio::fs::write(&b, b"hello world").await?;
+
+        let generate = |args: Vec<&str>| {
+            let command = Command::parse_from_iter(
+                ["cloud-checksum", "generate", "-c", "md5"]
+                    .into_iter()
+                    .chain(args),
+            );
+            async move {
+                let command = command?;
+                let Subcommands::Generate(generate) = command.commands else {
+                    unreachable!();
+                };
+                generate
+                    .generate(
+                        command.optimization,
+                        &command.credentials,
+                        vec![unused_client()],
+                        true,
+                        &command.output,
+                    )
+                    .await
+            }
+        };
+
+        assert!(generate(vec![&a, &missing, &b]).await.is_err());
+
+        // The other inputs are still written and the failure is reported.
+        let (sums, stats) = generate(vec!["--continue-on-error", &a, &missing, &b]).await?;
+        assert_eq!(
+            sums.iter().map(|(input, _)| input).collect::<Vec<_>>(),
+            [&a, &b]
+        );
+        assert!(tokio::fs::try_exists(format!("{}.sums", b)).await?);
+
+        let stats = stats.unwrap();
+        assert_eq!(stats.n_failed(), 1);
+        let failed = stats
+            .stats
+            .iter()
+            .find(|stat| stat.error.is_some())
+            .unwrap();
+        assert_eq!(failed.input, missing);
+
+        // A missing file has no sums to compare, so use a location that cannot be reached.
+        let unreachable = "http://127.0.0.1:1/object";
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "check",
+            "--continue-on-error",
+            &a,
+            unreachable,
+            &b,
+        ])?;
+        let Subcommands::Check(check) = command.commands else {
+            unreachable!();
+        };
+        let stats = check
+            .check(
+                command.optimization,
+                &command.credentials,
+                &command.output,
+                false,
+                vec![unused_client()],
+            )
+            .await?;
+        assert_eq!(stats.groups, [[a, b]]);
+        assert_eq!(stats.n_failed(), 1);
+        assert!(stats.failed.contains_key(unreachable));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn check_missing_min_strength() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let path = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        let (a, b, c, d) = (path("a"), path("b"), path("c"), path("d"));
+        for file in [&a, &b, &c, &d] {
+            tokio::fs::write(file, b"hello world").await?;
+        }
+
+        // Only `c` and `d` have existing sums files, which are weaker than the minimum.
+        let command =
+            Command::parse_from_iter(["cloud-checksum", "generate", "-c", "md5", &c, &d])?;
+        let Subcommands::Generate(generate) = command.commands else {
+            unreachable!();
+        };
+        generate
+            .generate(
+                command.optimization,
+                &command.credentials,
+                vec![unused_client()],
+                true,
+                &command.output,
+            )
+            .await?;
+
+        for (first, second) in [(&a, &b), (&c, &d)] {
+            let command = Command::parse_from_iter([
+                "cloud-checksum",
+                "check",
+                "--missing",
+                "--min-strength",
+                "sha256",
+                first,
+                second,
+            ])?;
+            let Subcommands::Check(check) = command.commands else {
+                unreachable!();
+            };
+            let stats = check
+                .check(
+                    command.optimization,
+                    &command.credentials,
+                    &command.output,
+                    false,
+                    vec![unused_client()],
+                )
+                .await?;
+            assert_eq!(stats.groups, [[first.clone(), second.clone()]]);
+        }
+
+        assert_eq!(Strength::Crc.default_ctx(), Ctx::default());
+        assert_eq!(
+            Strength::Sha256.default_ctx(),
+            Ctx::Regular(StandardCtx::sha256())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn output_dir() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let path = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        let (a, b, output_dir) = (path("data/a"), path("data/b"), path("sums"));
+        tokio::fs::create_dir(path("data")).await?;
+        tokio::fs::write(&a, b"hello world").await?;
+        tokio::fs::write(&b, b"hello world").await?;
+
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "generate",
+            "-c",
+            "md5",
+            "--output-dir",
+            &output_dir,
+            &a,
+            &b,
+        ])?;
+        let Subcommands::Generate(generate) = command.commands else {
+            unreachable!();
+        };
+        generate
+            .generate(
+                command.optimization,
+                &command.credentials,
+                vec![unused_client()],
+                true,
+                &command.output,
+            )
+            .await?;
+
+        // The sums files mirror the absolute paths of the inputs under the output directory.
+        let mirrored = |file: &str| format!("{}{}.sums", output_dir, file);
+        assert!(tokio::fs::try_exists(mirrored(&a)).await?);
+        assert!(tokio::fs::try_exists(mirrored(&b)).await?);
+        assert!(!tokio::fs::try_exists(format!("{}.sums", a)).await?);
+
+        // A later check with the same output directory finds the sums files.
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "check",
+            "--output-dir",
+            &output_dir,
+            &a,
+            &b,
+        ])?;
+        let Subcommands::Check(check) = command.commands else {
+            unreachable!();
+        };
+        let stats = check
+            .check(
+                command.optimization,
+                &command.credentials,
+                &command.output,
+                false,
+                vec![unused_client()],
+            )
+            .await?;
+        assert_eq!(stats.groups, [[a.clone(), b]]);
+
+        assert!(Command::try_parse_from([
+            "cloud-checksum",
+            "check",
+            "--output-dir",
+            &output_dir,
+            "--sums-source",
+            &output_dir,
+            &a,
+        ])
+        .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn channel_metrics() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let a = tmp.path().join("a").to_string_lossy().to_string();
+        tokio::fs::write(&a, vec![0; 5000]).await?;
+
+        let generate = |args: &[&str]| -> anyhow::Result<_> {
+            let command = Command::parse_from_iter(
+                [
+                    "cloud-checksum",
+                    "generate",
+                    "-c",
+                    "md5,sha1",
+                    "--force-overwrite",
+                    &a,
+                ]
+                .into_iter()
+                .chain(args.iter().copied()),
+            )?;
+            let Subcommands::Generate(generate) = command.commands else {
+                unreachable!();
+            };
+            Ok(async move {
+                generate
+                    .generate(
+                        command.optimization,
+                        &command.credentials,
+                        vec![unused_client()],
+                        false,
+                        &command.output,
+                    )
+                    .await
+            })
+        };
+
+        let (_, stats) = generate(&[])?.await?;
+        assert!(stats.unwrap().channel_metrics.is_none());
+
+        let (_, stats) = generate(&["--channel-metrics"])?.await?;
+        let metrics = stats.unwrap().channel_metrics.unwrap();
+        // Each chunk is sent to both checksums.
+        assert_eq!(metrics.n_sent, 10);
+        assert_eq!(metrics.n_received, 10);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn timings() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let a = tmp.path().join("a").to_string_lossy().to_string();
+        tokio::fs::write(&a, vec![0; 5000]).await?;
+
+        let generate = |args: &[&str]| -> anyhow::Result<_> {
+            let command = Command::parse_from_iter(
+                [
+                    "cloud-checksum",
+                    "generate",
+                    "-c",
+                    "md5,sha1",
+                    "--force-overwrite",
+                    &a,
+                ]
+                .into_iter()
+                .chain(args.iter().copied()),
+            )?;
+            let Subcommands::Generate(generate) = command.commands else {
+                unreachable!();
+            };
+            Ok(async move {
+                generate
+                    .generate(
+                        command.optimization,
+                        &command.credentials,
+                        vec![unused_client()],
+                        false,
+                        &command.output,
+                    )
+                    .await
+            })
+        };
+
+        let (_, stats) = generate(&[])?.await?;
+        assert!(stats.unwrap().stats[0].timings.is_none());
+
+        let (_, stats) = generate(&["--timings"])?.await?;
+        let timings = stats.unwrap().stats[0].timings.unwrap();
+        assert!(timings.reader.busy_seconds <= timings.reader.wall_seconds);
+        assert!(timings.reader.busy_seconds > 0.0);
+        assert!(timings.read_seconds > 0.0);
+        assert!(timings.read_seconds <= timings.reader.wall_seconds);
+        assert!(timings.checksums.busy_seconds > 0.0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn generate_tee() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let a = tmp.path().join("a").to_string_lossy().to_string();
+        let out = tmp.path().join("out").to_string_lossy().to_string();
+        tokio::fs::write(&a, "hello").await?;
+
+        let generate = |expect: &str| -> anyhow::Result<_> {
+            let command = Command::parse_from_iter([
+                "cloud-checksum",
+                "generate",
+                "-c",
+                "sha1",
+                "--tee",
+                &out,
+                "--expect",
+                expect,
+                &a,
+            ])?;
+            let Subcommands::Generate(generate) = command.commands else {
+                unreachable!();
+            };
+            Ok(async move {
+                generate
+                    .generate(
+                        command.optimization,
+                        &command.credentials,
+                        vec![unused_client()],
+                        false,
+                        &command.output,
+                    )
+                    .await
+            })
+        };
+
+        // The expected checksum is generated in addition to the specified checksums.
+        let (sums, _) = generate("md5:5D41402ABC4B2A76B9719D911017C592")?.await?; // pragma: allowlist secret
+        assert_eq!(sums[0].1.checksums.len(), 2);
+        assert_eq!(tokio::fs::read_to_string(&out).await?, "hello");
+
+        let err = generate("md5:00000000000000000000000000000000")?
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CheckError(_)), "{err}");
+        assert!(!Path::new(&out).exists());
+
+        assert!(Generate::parse_expect("md5").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn ndjson_output() -> anyhow::Result<()> {
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "generate",
+            "-c",
+            "md5",
+            "--output",
+            "ndjson",
+            "file",
+        ])?;
+        assert_eq!(command.output.output_mode, OutputMode::Ndjson);
+
+        let sums_file = SumsFile::new(
+            Some(1),
+            [("md5".parse()?, Checksum::new("123".to_string()))].into(),
+        );
+        let mut out = vec![];
+        Generate::write_line(&mut out, &Generate::ndjson_line("file", &sums_file)?)?;
+        Generate::write_line(&mut out, &Generate::ndjson_line("-", &sums_file)?)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            concat!(
+                r#"{"name":"file","version":"1","size":1,"md5":"123"}"#,
+                "\n",
+                r#"{"name":"-","version":"1","size":1,"md5":"123"}"#,
+                "\n"
+            )
+        );
+
+        let command =
+            Command::parse_from_iter(["cloud-checksum", "generate", "-c", "md5", "--ndjson", "-"])?;
+        assert_eq!(command.output.output_mode, OutputMode::Ndjson);
+        assert!(Command::try_parse_from([
+            "cloud-checksum",
+            "generate",
+            "-c",
+            "md5",
+            "--ndjson",
+            "--output",
+            "sri",
+            "-"
+        ])
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn checksum_profile() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let profiles = tmp.path().join("profiles.toml");
+        std::fs::write(
+            &profiles,
+            "[standard]\nchecksum = [\"sha256\", \"md5-aws-16mib\"]\npart-size = \"16mib\"\nconcurrency = 4\nformat = \"msgpack\"\n",
+        )?;
+        let profiles = profiles.to_string_lossy().to_string();
+        let parse = |args: &[&str]| {
+            Command::parse_from_iter(
+                [
+                    "cloud-checksum",
+                    "--checksum-profile",
+                    "standard",
+                    "--profiles-file",
+                    &profiles,
+                ]
+                .iter()
+                .chain(args),
+            )
+        };
+
+        let command = parse(&["generate", "file"])?;
+        let Subcommands::Generate(generate) = &command.commands else {
+            panic!("expected generate");
+        };
+        assert_eq!(
+            generate.checksum,
+            vec!["sha256".parse()?, "md5-aws-16mib".parse()?]
+        );
+        assert_eq!(generate.concurrency, 4);
+        assert_eq!(command.output.format, SumsFormat::Msgpack);
+
+        let command = parse(&[
+            "generate",
+            "-c",
+            "md5",
+            "--concurrency",
+            "2",
+            "--format",
+            "json",
+            "file",
+        ])?;
+        let Subcommands::Generate(generate) = &command.commands else {
+            panic!("expected generate");
+        };
+        assert_eq!(generate.checksum, vec!["md5".parse()?]);
+        assert_eq!(generate.concurrency, 2);
+        assert_eq!(command.output.format, SumsFormat::Json);
+
+        let command = parse(&["copy", "a", "b"])?;
+        let Subcommands::Copy(copy) = &command.commands else {
+            panic!("expected copy");
+        };
+        assert_eq!(copy.part_size, Some(16 * 1024 * 1024));
+        assert_eq!(copy.concurrency, 4);
+
+        assert!(Command::parse_from_iter([
+            "cloud-checksum",
+            "--checksum-profile",
+            "missing",
+            "--profiles-file",
+            &profiles,
+            "generate",
+            "file",
+        ])
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn sri_output() -> anyhow::Result<()> {
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "generate",
+            "-c",
+            "sha256",
+            "--output",
+            "sri",
+            "file",
+        ])?;
+        assert_eq!(command.output.output_mode, OutputMode::Sri);
+
+        // Sums files without a checksum that can be used in an SRI string are an error.
+        let mut sums_file = SumsFile::default();
+        sums_file.add_checksum(
+            "md5".parse()?,
+            Checksum::new("5eb63bbbe01eeed093cb22bb8f5acdc3".to_string()),
+        );
+        assert!(Generate::print_sri("file", &sums_file).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn pretty_output() -> anyhow::Result<()> {
+        let parse = |args: &[&str]| {
+            Command::parse_from_iter(
+                ["cloud-checksum", "generate", "-c", "md5"]
+                    .iter()
+                    .chain(args)
+                    .chain(&["file"])
+                    .copied(),
+            )
+        };
+
+        assert_eq!(parse(&[])?.output.sums_format(), SumsFormat::Json);
+        // Pretty output statistics do not change the format of sums files.
+        for pretty in ["--pretty-json", "--pretty"] {
+            let command = parse(&[pretty])?;
+            assert!(command.output.pretty_json);
+            assert_eq!(command.output.sums_format(), SumsFormat::Json);
+        }
+        assert_eq!(
+            parse(&["--format", "pretty-json"])?.output.sums_format(),
+            SumsFormat::PrettyJson
+        );
+        assert_eq!(
+            parse(&["--pretty", "--format", "msgpack"])?
+                .output
+                .sums_format(),
+            SumsFormat::Msgpack
+        );
+
+        let Subcommands::Generate(generate) = parse(&["--short", "7"])?.commands else {
+            panic!("expected generate subcommand");
+        };
+        assert_eq!(generate.short, Some(7));
+        assert!(
+            Command::try_parse_from(["cloud-checksum", "generate", "--short", "0", "file"])
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sums_only_check() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let path = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        let sums_file = |checksums: &[(&str, &str)]| -> anyhow::Result<SumsFile> {
+            let checksums = checksums
+                .iter()
+                .map(|(ctx, checksum)| Ok((ctx.parse()?, Checksum::new(checksum.to_string()))))
+                .collect::<anyhow::Result<_>>()?;
+            Ok(SumsFile::new(Some(11), checksums))
+        };
+
+        // Only the sums files exist, not the files that they describe.
+        let (a, b, c) = (path("a.sums"), path("b.sums.bin"), path("c.sums"));
+        tokio::fs::write(&a, SumsFormat::Json.to_vec(&sums_file(&[("md5", "1")])?)?).await?;
+        tokio::fs::write(
+            &b,
+            SumsFormat::Msgpack.to_vec(&sums_file(&[("md5", "1"), ("sha1", "2")])?)?,
         )
-        .await
+        .await?;
+        tokio::fs::write(&c, SumsFormat::Json.to_vec(&sums_file(&[("md5", "3")])?)?).await?;
+
+        let command =
+            Command::parse_from_iter(["cloud-checksum", "check", "--sums-only", &a, &b, &c])?;
+        let Subcommands::Check(check) = command.commands else {
+            panic!("expected check subcommand");
+        };
+        let client = unused_client();
+        let mut groups = check
+            .check(
+                command.optimization,
+                &command.credentials,
+                &command.output,
+                false,
+                vec![client],
+            )
+            .await?
+            .groups;
+        groups.iter_mut().for_each(|group| group.sort());
+        groups.sort();
+
+        assert_eq!(groups, vec![vec![a, b], vec![c]]);
+        assert!(Command::try_parse_from([
+            "cloud-checksum",
+            "check",
+            "--sums-only",
+            "-u",
+            "a",
+            "b"
+        ])
+        .is_err());
+
+        Ok(())
     }
 
-    /// Construct the destination client from the credentials.
-    pub async fn destination_client(&self) -> Result<Client> {
-        create_s3_client(
-            &self.destination_credential_provider,
-            self.destination_profile.as_deref(),
-            self.destination_region.as_deref(),
-            self.destination_endpoint_url.as_deref(),
+    #[test]
+    fn parse_unsupported_checksum() {
+        let err = Command::try_parse_from(["cloud-checksum", "generate", "-c", "bogus", "file"])
+            .unwrap_err()
+            .to_string();
+
+        assert!(
+            err.contains("unsupported checksum algorithm `bogus`"),
+            "{err}"
+        );
+        assert!(err.contains("expected one of: md5, sha1, sha256"), "{err}");
+    }
+
+    #[test]
+    fn parse_comma_separated_checksums() -> anyhow::Result<()> {
+        let checksums = |args: &[&str]| -> anyhow::Result<HashSet<Ctx>> {
+            let command = Command::parse_from_iter(
+                ["cloud-checksum", "generate"]
+                    .into_iter()
+                    .chain(args.iter().copied())
+                    .chain(["file"]),
+            )?;
+            let Subcommands::Generate(generate) = command.commands else {
+                unreachable!();
+            };
+            Ok(HashSet::from_iter(generate.checksum))
+        };
+
+        let expected = HashSet::from_iter([
+            Ctx::from_str("sha256")?,
+            Ctx::from_str("crc32c")?,
+            Ctx::from_str("md5-aws-16mib")?,
+        ]);
+        assert_eq!(
+            checksums(&["--checksum", "sha256,crc32c,md5-aws-16mib"])?,
+            expected
+        );
+        assert_eq!(
+            checksums(&["-c", "sha256", "-c", "crc32c", "-c", "md5-aws-16mib"])?,
+            expected
+        );
+        assert_eq!(
+            checksums(&["-c", "sha256,crc32c", "--checksum", "md5-aws-16mib,sha256"])?,
+            expected
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_input_file() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let input_file = tmp.path().join("inputs").to_string_lossy().to_string();
+        std::fs::write(
+            &input_file,
+            "# objects\ns3://bucket/key1\n\n  s3://bucket/key2  \n",
+        )?;
+
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "check",
+            "s3://bucket/key0",
+            "--input-file",
+            &input_file,
+        ])?;
+        let Subcommands::Check(check) = command.commands else {
+            panic!("expected check subcommand");
+        };
+        assert_eq!(
+            check.input,
+            vec!["s3://bucket/key0", "s3://bucket/key1", "s3://bucket/key2"]
+        );
+
+        std::fs::write(&input_file, "s3://bucket/key1\n")?;
+        assert!(
+            Command::parse_from_iter(["cloud-checksum", "check", "--input-file", &input_file])
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_force_path_style() -> anyhow::Result<()> {
+        let command = Command::try_parse_from([
+            "cloud-checksum",
+            "check",
+            "--endpoint-url",
+            "http://localhost:9000",
+            "--force-path-style",
+            "s3://bucket/key1",
+            "s3://bucket/key2",
+        ])?;
+
+        assert!(command.credentials.source_force_path_style);
+        assert!(!command.credentials.destination_force_path_style);
+        assert_eq!(
+            command.credentials.source_endpoint_url.as_deref(),
+            Some("http://localhost:9000")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn parse_profile_and_region() -> anyhow::Result<()> {
+        let command = Command::try_parse_from([
+            "cloud-checksum",
+            "generate",
+            "--profile",
+            "other-account",
+            "--region",
+            "ap-southeast-2",
+            "s3://bucket/key",
+        ])?;
+
+        let credentials = &command.credentials;
+        assert_eq!(credentials.source_profile.as_deref(), Some("other-account"));
+        assert_eq!(credentials.source_region.as_deref(), Some("ap-southeast-2"));
+        // A different profile cannot use server-side copies with the destination.
+        assert!(!credentials.is_default());
+
+        #[cfg(feature = "cloud")]
+        assert_eq!(
+            credentials
+                .source_client()
+                .await?
+                .config()
+                .region()
+                .map(|region| region.as_ref()),
+            Some("ap-southeast-2")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_quiet() -> anyhow::Result<()> {
+        let command =
+            Command::try_parse_from(["cloud-checksum", "check", "--quiet", "--update", "a", "b"])?;
+
+        let Subcommands::Check(check) = command.commands else {
+            panic!("expected check subcommand");
+        };
+        assert!(check.quiet);
+        assert!(check.update);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_logging() -> anyhow::Result<()> {
+        let command = Command::try_parse_from(["cloud-checksum", "check", "a", "b"])?;
+        assert_eq!(command.logging.log_level, LogLevel::Warn);
+        assert_eq!(command.logging.log_format, LogFormat::Text);
+
+        let command =
+            Command::try_parse_from(["cloud-checksum", "--log-level", "off", "check", "a", "b"])?;
+        assert_eq!(
+            LevelFilter::from(command.logging.log_level),
+            LevelFilter::OFF
+        );
+
+        let command = Command::try_parse_from([
+            "cloud-checksum",
+            "check",
+            "--log-level",
+            "debug",
+            "--log-format",
+            "json",
+            "a",
+            "b",
+        ])?;
+        assert_eq!(command.logging.log_level, LogLevel::Debug);
+        assert_eq!(command.logging.log_format, LogFormat::Json);
+        assert_eq!(
+            LevelFilter::from(command.logging.log_level),
+            LevelFilter::DEBUG
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compare_to() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        let baseline = tmp
+            .path()
+            .join("baseline.sums")
+            .to_string_lossy()
+            .to_string();
+        tokio::fs::write(&file, b"hello world").await?;
+        tokio::fs::write(
+            &baseline,
+            SumsFormat::Json.to_vec(&SumsFile::new(
+                Some(11),
+                BTreeMap::from_iter([
+                    (
+                        "md5".parse()?,
+                        Checksum::new(checksum_bytes("md5", b"hello world")?),
+                    ),
+                    (
+                        "sha1".parse()?,
+                        Checksum::new(checksum_bytes("sha1", b"hello world")?),
+                    ),
+                ]),
+            ))?,
         )
-        .await
+        .await?;
+
+        let compare = |args: &[&str]| {
+            let command = Command::parse_from_iter(
+                ["cloud-checksum", "generate", "--compare-to", &baseline]
+                    .iter()
+                    .chain(args)
+                    .chain([&file.as_str()]),
+            );
+            async move {
+                let command = command?;
+                let Subcommands::Generate(generate) = command.commands else {
+                    panic!("expected generate subcommand");
+                };
+                let client = unused_client();
+                anyhow::Ok(
+                    generate
+                        .compare(
+                            command.optimization,
+                            &command.credentials,
+                            vec![client],
+                            &command.output,
+                        )
+                        .await?,
+                )
+            }
+        };
+
+        // The checksums of the baseline are used by default, and nothing is written.
+        let diff = compare(&[]).await?;
+        assert!(!diff.is_different());
+        assert!(!diff.updated);
+        assert!(!Path::new(&format!("{file}.sums")).exists());
+
+        let diff = compare(&["-c", "md5,sha256"]).await?;
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.removed.len(), 1);
+        assert!(diff.changed.is_empty());
+        assert!(diff.is_different());
+
+        // Changes are reported and the generated sums are written with `--update`.
+        tokio::fs::write(&file, b"hello there").await?;
+        let diff = compare(&["--update"]).await?;
+        assert_eq!(diff.changed.len(), 2);
+        assert!(diff.updated);
+        assert!(Path::new(&format!("{file}.sums")).exists());
+
+        assert!(
+            Command::try_parse_from(["cloud-checksum", "generate", "-u", "-c", "md5", "a"])
+                .is_err()
+        );
+
+        Ok(())
     }
 
-    pub fn is_default(&self) -> bool {
-        self.source_credential_provider.is_default()
-            && self.destination_credential_provider.is_default()
-            && self.source_endpoint_url.is_none()
-            && self.destination_endpoint_url.is_none()
+    #[tokio::test]
+    async fn predict_etag() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&file, b"hello world").await?;
+
+        let parse = |input: &str| -> anyhow::Result<PredictEtag> {
+            let command = Command::try_parse_from([
+                "cloud-checksum",
+                "predict-etag",
+                "--part-size",
+                "16mib",
+                input,
+            ])?;
+            let Subcommands::PredictEtag(predict_etag) = command.commands else {
+                panic!("expected predict-etag subcommand");
+            };
+            Ok(predict_etag)
+        };
+
+        let predict_etag = parse(&file)?;
+        assert_eq!(predict_etag.part_size, Some(16 * 1024 * 1024));
+        assert_eq!(
+            predict_etag.predict_etag().await?,
+            "\"241d8a27c836427bd7f04461b60e7359-1\""
+        );
+
+        // Only local files are supported.
+        assert!(parse("s3://bucket/key")?.predict_etag().await.is_err());
+
+        // A single upload has no part count.
+        let command = Command::try_parse_from(["cloud-checksum", "predict-etag", &file])?;
+        let Subcommands::PredictEtag(predict_etag) = command.commands else {
+            panic!("expected predict-etag subcommand");
+        };
+        assert_eq!(
+            predict_etag.predict_etag().await?,
+            "\"5eb63bbbe01eeed093cb22bb8f5acdc3\"" // pragma: allowlist secret
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cloud")]
+    #[tokio::test]
+    async fn check_single_put_e_tag() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&file, b"hello world").await?;
+
+        // An object uploaded with a single `PutObject` has the plain MD5 as its `ETag`.
+        let e_tag = "\"5eb63bbbe01eeed093cb22bb8f5acdc3\""; // pragma: allowlist secret
+        let is_object = |bucket: Option<&str>, key: Option<&str>| {
+            bucket == Some("bucket") && key == Some("key")
+        };
+        let client = Arc::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::MatchAny,
+            &[
+                &mock!(aws_sdk_s3::Client::head_object)
+                    .match_requests(move |req| is_object(req.bucket(), req.key()))
+                    .then_output(move || {
+                        HeadObjectOutput::builder()
+                            .e_tag(e_tag)
+                            .content_length(11)
+                            .build()
+                    }),
+                &mock!(aws_sdk_s3::Client::get_object_attributes)
+                    .match_requests(move |req| is_object(req.bucket(), req.key()))
+                    .then_output(move || {
+                        GetObjectAttributesOutput::builder()
+                            .e_tag(e_tag)
+                            .object_size(11)
+                            .build()
+                    }),
+                // There are no existing sums in the object tags.
+                &mock!(aws_sdk_s3::Client::get_object_tagging)
+                    .match_requests(move |req| is_object(req.bucket(), req.key()))
+                    .then_output(|| {
+                        GetObjectTaggingOutput::builder()
+                            .set_tag_set(Some(vec![]))
+                            .build()
+                            .unwrap()
+                    }),
+            ]
+        ));
+
+        let command = Command::parse_from_iter([
+            "cloud-checksum",
+            "check",
+            "--sums-storage",
+            "tags",
+            &file,
+            "s3://bucket/key",
+        ])?;
+        let Subcommands::Check(check) = command.commands else {
+            unreachable!();
+        };
+        let stats = check
+            .check(
+                command.optimization,
+                &command.credentials,
+                &command.output,
+                false,
+                vec![client],
+            )
+            .await?;
+        assert_eq!(stats.groups, [[file, "s3://bucket/key".to_string()]]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dataset_id() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let path = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        let (a, b) = (path("a"), path("b"));
+        tokio::fs::write(&a, b"hello").await?;
+        tokio::fs::write(&b, b"world").await?;
+
+        let dataset_id = |input: Vec<&str>| {
+            let command =
+                Command::parse_from_iter(["cloud-checksum", "dataset-id"].into_iter().chain(input));
+            async move {
+                let command = command?;
+                let Subcommands::DatasetId(dataset_id) = command.commands else {
+                    unreachable!();
+                };
+                Ok::<_, anyhow::Error>(
+                    dataset_id
+                        .dataset_id(
+                            command.optimization,
+                            &command.credentials,
+                            vec![unused_client()],
+                            &command.output,
+                        )
+                        .await?,
+                )
+            }
+        };
+
+        let stats = dataset_id(vec![&a, &b]).await?;
+        assert_eq!(stats.n_files, 2);
+        assert_eq!(stats.size, 10);
+
+        let mut expected = Sha256::new();
+        expected.update(format!(
+            "{a}\x005\x00{}",
+            checksum_bytes("sha256", b"hello")?
+        ));
+        expected.update(format!(
+            "{b}\x005\x00{}",
+            checksum_bytes("sha256", b"world")?
+        ));
+        assert_eq!(stats.dataset_id, hex::encode(expected.finalize()));
+
+        // The order of the inputs does not matter.
+        assert_eq!(dataset_id(vec![&b, &a]).await?.dataset_id, stats.dataset_id);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn prune() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let path = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        let (a, b) = (path("a"), path("b"));
+        let write = |name: &str, checksums: &[&str]| {
+            let sums_file = SumsFile::new(
+                Some(1),
+                checksums
+                    .iter()
+                    .map(|ctx| Ok((ctx.parse()?, Checksum::new("0".to_string()))))
+                    .collect::<Result<_>>()?,
+            );
+            std::fs::write(name, "a")?;
+            std::fs::write(format!("{}.sums", name), sums_file.to_json_string()?)?;
+            Ok::<_, anyhow::Error>(sums_file)
+        };
+        let read = |name: &str| {
+            Ok::<_, anyhow::Error>(
+                SumsFormat::Json.from_slice(&std::fs::read(format!("{}.sums", name))?)?,
+            )
+        };
+        let a_sums = write(&a, &["md5", "sha256"])?;
+        write(&b, &["md5"])?;
+
+        let prune = |args: Vec<&str>| {
+            let command =
+                Command::parse_from_iter(["cloud-checksum", "prune"].into_iter().chain(args));
+            async move {
+                let command = command?;
+                let Subcommands::Prune(prune) = command.commands else {
+                    unreachable!();
+                };
+                Ok::<_, anyhow::Error>(prune.prune(vec![unused_client()], &command.output).await?)
+            }
+        };
+
+        // No sums files are changed if any would be left without checksums.
+        assert!(prune(vec!["--keep", "sha256", &a, &b]).await.is_err());
+        assert_eq!(read(&a)?, a_sums);
+
+        let stats = prune(vec!["--keep", "sha256", "--allow-empty", &a, &b]).await?;
+        assert_eq!(stats.pruned[&a], vec!["md5".parse()?]);
+        assert_eq!(stats.pruned[&b], vec!["md5".parse()?]);
+        assert_eq!(
+            read(&a)?.checksums.into_keys().collect::<Vec<Ctx>>(),
+            vec!["sha256".parse()?]
+        );
+        assert!(read(&b)?.checksums.is_empty());
+
+        // AWS checksums are kept with their algorithm, or on their own.
+        write(&a, &["md5", "md5-aws-8mib", "sha256-aws-8mib"])?;
+        let stats = prune(vec!["--keep", "md5", &a]).await?;
+        assert_eq!(stats.pruned[&a], vec!["sha256-aws-8mib".parse()?]);
+        assert_eq!(
+            read(&a)?.checksums.into_keys().collect::<HashSet<Ctx>>(),
+            HashSet::from(["md5".parse()?, "md5-aws-8mib".parse()?])
+        );
+        let stats = prune(vec!["--keep", "md5-aws-8mib", &a]).await?;
+        assert_eq!(stats.pruned[&a], vec!["md5".parse()?]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn touch() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let path = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        let (a, b) = (path("a"), path("b"));
+        let sums_file = SumsFile::new(
+            Some(1),
+            [("md5".parse()?, Checksum::new("0".to_string()))].into(),
+        );
+        for (name, contents) in [(&a, "a"), (&b, "bb")] {
+            std::fs::write(name, contents)?;
+            std::fs::write(format!("{}.sums", name), sums_file.to_json_string()?)?;
+        }
+        let read = |name: &str| {
+            Ok::<_, anyhow::Error>(
+                SumsFormat::Json.from_slice(&std::fs::read(format!("{}.sums", name))?)?,
+            )
+        };
+
+        let touch = |args: Vec<&str>| {
+            let command =
+                Command::parse_from_iter(["cloud-checksum", "touch"].into_iter().chain(args));
+            async move {
+                let command = command?;
+                let Subcommands::Touch(touch) = command.commands else {
+                    unreachable!();
+                };
+                Ok::<_, anyhow::Error>(
+                    touch
+                        .touch(&command.credentials, vec![unused_client()], &command.output)
+                        .await?,
+                )
+            }
+        };
+
+        // No sums files are changed unless forced, as the checksums are not re-verified.
+        let err = touch(vec![&a, &b]).await.unwrap_err();
+        assert!(err.to_string().contains("--force"), "{err}");
+        assert_eq!(read(&a)?, sums_file);
+        assert_eq!(read(&b)?, sums_file);
+
+        let stats = touch(vec!["--force", &a, &b]).await?;
+        assert!(!stats.touched.contains_key(&a));
+        assert_eq!(stats.touched[&b].size, 2);
+        assert_eq!(read(&b)?.size, Some(2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cas() -> anyhow::Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        let store = tmp.path().join("store").to_string_lossy().to_string();
+        tokio::fs::write(&file, b"hello world").await?;
+        tokio::fs::create_dir(&store).await?;
+
+        let cas = || async {
+            let command = Command::try_parse_from(["cloud-checksum", "cas", "-p", &store, &file])?;
+            let Subcommands::Cas(cas) = command.commands else {
+                panic!("expected cas subcommand");
+            };
+            let client = unused_client();
+
+            cas.cas(
+                client.clone(),
+                client,
+                command.credentials,
+                command.optimization,
+                &command.output,
+            )
+            .await
+        };
+
+        let sha256 = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"; // pragma: allowlist secret
+        let destination = format!("{}/{}", store, sha256);
+
+        let stats = cas().await?;
+        assert!(!stats.skipped);
+        assert_eq!(tokio::fs::read(&destination).await?, b"hello world");
+        let sums = FileBuilder::default()
+            .with_file(destination.to_string())
+            .build()?
+            .get_existing_sums()
+            .await?
+            .unwrap();
+        assert_eq!(sums.checksums[&"sha256".parse()?].as_str(), sha256);
+
+        let stats = cas().await?;
+        assert!(stats.skipped);
+
+        let command = Command::try_parse_from([
+            "cloud-checksum",
+            "cas",
+            "-p",
+            &store,
+            "--concurrency",
+            "4",
+            &file,
+        ])?;
+        let Subcommands::Cas(cas) = command.commands else {
+            panic!("expected cas subcommand");
+        };
+        assert_eq!(cas.concurrency, 4);
+
+        Ok(())
     }
 }
@@ -2,26 +2,48 @@
 //!
 
 use crate::error::Error::AwsError;
+#[cfg(feature = "cloud")]
+use aws_credential_types::provider::error::CredentialsError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::error::SdkError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::complete_multipart_upload::CompleteMultipartUploadError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::copy_object::CopyObjectError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::create_multipart_upload::CreateMultipartUploadError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::get_object::GetObjectError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::get_object_attributes::GetObjectAttributesError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::head_object::HeadObjectError;
+#[cfg(feature = "cloud")]
+use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::put_object::PutObjectError;
+#[cfg(feature = "cloud")]
+use aws_sdk_s3::operation::put_object_tagging::PutObjectTaggingError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::upload_part::UploadPartError;
+#[cfg(feature = "cloud")]
 use aws_sdk_s3::operation::upload_part_copy::UploadPartCopyError;
+#[cfg(feature = "cloud")]
 use aws_smithy_runtime_api::client::orchestrator::HttpResponse;
+#[cfg(feature = "cloud")]
 use aws_smithy_runtime_api::client::result::CreateUnhandledError;
 use aws_smithy_types::byte_stream;
 use aws_smithy_types::error::display::DisplayErrorContext;
+#[cfg(feature = "cloud")]
 use aws_smithy_types::error::metadata::ProvideErrorMetadata;
 use serde::{Deserialize, Serialize, Serializer};
+#[cfg(feature = "cloud")]
+use std::error;
 use std::fmt::{Debug, Display, Formatter};
 use std::num::TryFromIntError;
-use std::{error, fmt, io, result};
+use std::{fmt, io, result};
 use thiserror::Error;
 use tokio::sync::mpsc;
 use tokio::task::JoinError;
@@ -38,7 +60,13 @@ pub enum Error {
     MemoryError(String),
     #[serde(serialize_with = "serialize_io", skip_deserializing)]
     #[error("performing IO: {0}")]
-    IOError(#[from] io::Error),
+    IOError(io::Error),
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("access denied: {0}, check that `AWS_PROFILE` and the region are correct")]
+    AccessDenied(String),
+    #[error("loading credentials: {0}, check that `AWS_PROFILE` and the region are correct")]
+    Credentials(String),
     #[error("parsing: {0}")]
     ParseError(String),
     #[serde(serialize_with = "serialize_try_from_int", skip_deserializing)]
@@ -54,6 +82,12 @@ pub enum Error {
     CheckError(String),
     #[error("copy command error: {0}")]
     CopyError(String),
+    #[error("http error: {0}")]
+    HttpError(String),
+    #[error("timed out: {0}")]
+    Timeout(String),
+    #[error("size mismatch: {0}")]
+    SizeMismatch(String),
     #[serde(serialize_with = "serialize_aws_error")]
     #[error("aws error: {message}")]
     AwsError {
@@ -76,6 +110,20 @@ impl Error {
             api_error: None,
         }
     }
+
+    /// Check if any error in the source chain failed to load credentials.
+    #[cfg(feature = "cloud")]
+    fn is_credentials_error(err: &(dyn error::Error + 'static)) -> bool {
+        let mut source = Some(err);
+        while let Some(err) = source {
+            if err.is::<CredentialsError>() {
+                return true;
+            }
+            source = err.source();
+        }
+
+        false
+    }
 }
 
 fn serialize_aws_error<S>(
@@ -110,6 +158,26 @@ where
     err.to_string().serialize(serializer)
 }
 
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        let message = DisplayErrorContext(&err).to_string();
+        match err.status().map(|status| status.as_u16()) {
+            Some(404) => Self::NotFound(message),
+            Some(401) | Some(403) => Self::AccessDenied(message),
+            _ => Self::HttpError(message),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NotFound(err.to_string()),
+            _ => Self::IOError(err),
+        }
+    }
+}
+
 impl From<JoinError> for Error {
     fn from(err: JoinError) -> Self {
         Self::ConcurrencyError(err.to_string())
@@ -134,6 +202,18 @@ impl From<serde_json::Error> for Error {
     }
 }
 
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(err: rmp_serde::encode::Error) -> Self {
+        Self::SerdeError(err.to_string())
+    }
+}
+
+impl From<rmp_serde::decode::Error> for Error {
+    fn from(err: rmp_serde::decode::Error) -> Self {
+        Self::SerdeError(err.to_string())
+    }
+}
+
 impl From<byte_stream::error::Error> for Error {
     fn from(err: byte_stream::error::Error) -> Self {
         Self::IOError(io::Error::other(err))
@@ -165,8 +245,14 @@ impl ApiError {
     pub fn is_access_denied(&self) -> bool {
         self.code == "AccessDenied"
     }
+
+    /// Check if the error is a missing object error.
+    pub fn is_not_found(&self) -> bool {
+        self.code == "NoSuchKey" || self.code == "NotFound"
+    }
 }
 
+#[cfg(feature = "cloud")]
 impl<T> From<(&SdkError<T, HttpResponse>, String)> for ApiError
 where
     T: ProvideErrorMetadata + CreateUnhandledError + error::Error + Send + Sync + 'static,
@@ -192,6 +278,7 @@ impl Display for ApiError {
 /// Generate an impl for an AWS error type with the context of the API call.
 macro_rules! generate_aws_error_impl {
     ($t:ty) => {
+        #[cfg(feature = "cloud")]
         impl From<&SdkError<$t>> for ApiError {
             fn from(err: &SdkError<$t>) -> Self {
                 let api_call = stringify!($t);
@@ -206,9 +293,25 @@ macro_rules! generate_aws_error_impl {
             }
         }
 
+        #[cfg(feature = "cloud")]
         impl From<SdkError<$t>> for Error {
             fn from(err: SdkError<$t>) -> Self {
+                if Error::is_credentials_error(&err) {
+                    return Self::Credentials(DisplayErrorContext(&err).to_string());
+                }
+
+                let status = err
+                    .raw_response()
+                    .map(|response| response.status().as_u16());
                 let err = ApiError::from(&err);
+
+                if status == Some(404) || err.is_not_found() {
+                    return Self::NotFound(err.to_string());
+                }
+                if status == Some(403) || err.is_access_denied() {
+                    return Self::AccessDenied(err.to_string());
+                }
+
                 Self::AwsError {
                     message: err.to_string(),
                     api_error: Some(err),
@@ -219,12 +322,91 @@ macro_rules! generate_aws_error_impl {
 }
 
 generate_aws_error_impl!(HeadObjectError);
+generate_aws_error_impl!(ListObjectsV2Error);
 generate_aws_error_impl!(GetObjectAttributesError);
 generate_aws_error_impl!(PutObjectError);
 generate_aws_error_impl!(GetObjectTaggingError);
+generate_aws_error_impl!(PutObjectTaggingError);
 generate_aws_error_impl!(CreateMultipartUploadError);
 generate_aws_error_impl!(CompleteMultipartUploadError);
 generate_aws_error_impl!(CopyObjectError);
 generate_aws_error_impl!(UploadPartCopyError);
 generate_aws_error_impl!(GetObjectError);
 generate_aws_error_impl!(UploadPartError);
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    #[cfg(feature = "cloud")]
+    use aws_sdk_s3::types::error::{NoSuchKey, NotFound};
+    #[cfg(feature = "cloud")]
+    use aws_smithy_runtime_api::http::StatusCode;
+    #[cfg(feature = "cloud")]
+    use aws_smithy_types::body::SdkBody;
+
+    #[cfg(feature = "cloud")]
+    fn response(status: u16) -> anyhow::Result<HttpResponse> {
+        Ok(HttpResponse::new(
+            StatusCode::try_from(status)?,
+            SdkBody::empty(),
+        ))
+    }
+
+    #[test]
+    fn io_not_found() {
+        let err = Error::from(io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(err, Error::NotFound(_)));
+
+        let err = Error::from(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert!(matches!(err, Error::IOError(_)));
+    }
+
+    #[cfg(feature = "cloud")]
+    #[test]
+    fn aws_not_found() -> anyhow::Result<()> {
+        let err = Error::from(SdkError::service_error(
+            HeadObjectError::NotFound(NotFound::builder().build()),
+            response(404)?,
+        ));
+        assert!(matches!(err, Error::NotFound(_)));
+
+        let err = Error::from(SdkError::service_error(
+            GetObjectError::NoSuchKey(NoSuchKey::builder().build()),
+            response(404)?,
+        ));
+        assert!(matches!(err, Error::NotFound(_)));
+
+        let err = Error::from(SdkError::service_error(
+            HeadObjectError::unhandled("error"),
+            response(500)?,
+        ));
+        assert!(matches!(err, Error::AwsError { .. }));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cloud")]
+    #[test]
+    fn aws_access_denied() -> anyhow::Result<()> {
+        let err = Error::from(SdkError::service_error(
+            GetObjectError::unhandled("error"),
+            response(403)?,
+        ));
+        assert!(matches!(err, Error::AccessDenied(_)));
+        assert!(err.to_string().contains("AWS_PROFILE"));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cloud")]
+    #[test]
+    fn aws_credentials() {
+        let err = Error::from(
+            SdkError::<HeadObjectError, HttpResponse>::construction_failure(
+                CredentialsError::not_loaded("no providers in chain provided credentials"),
+            ),
+        );
+        assert!(matches!(err, Error::Credentials(_)));
+        assert!(err.to_string().contains("AWS_PROFILE"));
+    }
+}
@@ -1,7 +1,8 @@
 //! AWS checksums and functionality.
 //!
 
-use crate::checksum::file::SumsFile;
+use crate::checksum::aws_etag::{S3_MAX_PARTS, S3_MAX_PART_SIZE, S3_MIN_PART_SIZE};
+use crate::checksum::file::{Checksum, SumsFile};
 use crate::cli::MetadataCopy;
 use crate::error::Error::{CopyError, ParseError};
 use crate::error::{ApiError, Error, Result};
@@ -17,10 +18,13 @@ use aws_sdk_s3::Client;
 use aws_smithy_runtime_api::client::orchestrator::HttpResponse;
 use aws_smithy_runtime_api::client::result::SdkError;
 use aws_smithy_types::byte_stream::ByteStream;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
 use std::collections::HashMap;
 use std::result;
 use std::sync::Arc;
 use tokio::io::AsyncReadExt;
+use tracing::{debug, warn};
 
 /// Build an S3 sums object.
 #[derive(Debug, Default)]
@@ -162,6 +166,45 @@ impl TryFrom<Part> for CompletedPart {
     }
 }
 
+/// Precomputed checksums to send with an upload, base64 encoded as S3 expects. Only the value
+/// of the algorithm that is uploaded with is set.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChecksumValues {
+    crc32: Option<String>,
+    crc32_c: Option<String>,
+    sha1: Option<String>,
+    sha256: Option<String>,
+    crc64_nvme: Option<String>,
+}
+
+impl ChecksumValues {
+    /// Create the values from a hex encoded checksum for the algorithm, if there is one.
+    pub fn new(algorithm: Option<&ChecksumAlgorithm>, checksum: Option<&Checksum>) -> Result<Self> {
+        let mut values = Self::default();
+        let (Some(algorithm), Some(checksum)) = (algorithm, checksum) else {
+            return Ok(values);
+        };
+
+        let value = hex::decode(checksum.as_str()).map_err(|_| {
+            ParseError(format!(
+                "failed to decode hex checksum: {}",
+                checksum.as_str()
+            ))
+        })?;
+        let value = Some(BASE64_STANDARD.encode(value));
+        match algorithm {
+            ChecksumAlgorithm::Crc32 => values.crc32 = value,
+            ChecksumAlgorithm::Crc32C => values.crc32_c = value,
+            ChecksumAlgorithm::Sha1 => values.sha1 = value,
+            ChecksumAlgorithm::Sha256 => values.sha256 = value,
+            ChecksumAlgorithm::Crc64Nvme => values.crc64_nvme = value,
+            _ => {}
+        }
+
+        Ok(values)
+    }
+}
+
 /// Represents an S3 bucket and key.
 #[derive(Debug, Clone)]
 pub struct BucketKey {
@@ -215,6 +258,7 @@ impl S3 {
         key: &str,
         bucket: &str,
     ) -> result::Result<HeadObjectOutput, SdkError<HeadObjectError, HttpResponse>> {
+        debug!(bucket, key, "HeadObject");
         self.client
             .head_object()
             .bucket(bucket)
@@ -229,6 +273,7 @@ impl S3 {
         key: &str,
         bucket: &str,
     ) -> result::Result<GetObjectTaggingOutput, SdkError<GetObjectTaggingError, HttpResponse>> {
+        debug!(bucket, key, "GetObjectTagging");
         self.client
             .get_object_tagging()
             .bucket(bucket)
@@ -264,6 +309,7 @@ impl S3 {
         additional_checksum: Option<ChecksumAlgorithm>,
     ) -> Result<(String, Vec<ApiError>)> {
         let do_upload = |tagging, metadata, additional_checksum| async {
+            debug!(bucket, key, "CreateMultipartUpload");
             self.client
                 .create_multipart_upload()
                 .set_tagging(tagging)
@@ -286,6 +332,7 @@ impl S3 {
         let (upload, err) = if let Err(ref err) = result {
             let err = ApiError::from(err);
             if self.tag_mode.is_best_effort() && err.is_access_denied() {
+                warn!(bucket, key, "retrying CreateMultipartUpload without tags");
                 (
                     do_upload(None, metadata, additional_checksum).await?,
                     vec![err],
@@ -329,6 +376,13 @@ impl S3 {
 
         let additional_checksum = state.additional_ctx().map(ChecksumAlgorithm::from);
         let do_copy = |tagging, tagging_set, metadata, metadata_set, additional_checksum| async {
+            debug!(
+                source_bucket = %source.bucket,
+                source_key = %source.key,
+                bucket = %destination.bucket,
+                key = %destination.key,
+                "CopyObject"
+            );
             self.client
                 .copy_object()
                 .tagging_directive(tagging)
@@ -356,6 +410,11 @@ impl S3 {
         let (_, err) = if let Err(ref err) = result {
             let err = ApiError::from(err);
             if self.tag_mode.is_best_effort() && err.is_access_denied() {
+                warn!(
+                    bucket = %destination.bucket,
+                    key = %destination.key,
+                    "retrying CopyObject without tags"
+                );
                 let result = do_copy(
                     TaggingDirective::Replace,
                     Some("".to_string()),
@@ -430,6 +489,15 @@ impl S3 {
         };
 
         if let Some(part_number) = multi_part.part_number {
+            multi_part.check_part_number()?;
+            debug!(
+                source_bucket = %source.bucket,
+                source_key = %source.key,
+                bucket = %destination.bucket,
+                key = %destination.key,
+                part_number,
+                "UploadPartCopy"
+            );
             let part = self
                 .client
                 .upload_part_copy()
@@ -458,7 +526,7 @@ impl S3 {
                 &destination.key,
                 &destination.bucket,
                 upload_id.to_string(),
-                multi_part.parts,
+                multi_part.completed_parts()?,
             )
             .await?;
 
@@ -476,6 +544,12 @@ impl S3 {
             }
         }
 
+        debug!(
+            bucket = %source.bucket,
+            key = %source.key,
+            range = ?multi_part.as_ref().and_then(|multi_part| multi_part.format_range()),
+            "GetObject"
+        );
         let result = self
             .client
             .get_object()
@@ -502,12 +576,24 @@ impl S3 {
         let buf = Self::read_content(&mut content, None).await?;
 
         let additional_checksum = state.additional_ctx().map(ChecksumAlgorithm::from);
+        let values = ChecksumValues::new(additional_checksum.as_ref(), state.precomputed(None))?;
         let do_put = |tags, metadata, additional_checksum, buf| async {
+            debug!(
+                bucket = %destination.bucket,
+                key = %destination.key,
+                precomputed = values != Default::default(),
+                "PutObject"
+            );
             self.client
                 .put_object()
                 .set_tagging(tags)
                 .set_metadata(metadata)
                 .set_checksum_algorithm(additional_checksum)
+                .set_checksum_crc32(values.crc32.clone())
+                .set_checksum_crc32_c(values.crc32_c.clone())
+                .set_checksum_sha1(values.sha1.clone())
+                .set_checksum_sha256(values.sha256.clone())
+                .set_checksum_crc64_nvme(values.crc64_nvme.clone())
                 .bucket(&destination.bucket)
                 .key(&destination.key)
                 .body(ByteStream::from(buf))
@@ -527,6 +613,11 @@ impl S3 {
         let (_, err) = if let Err(ref err) = result {
             let err = ApiError::from(err);
             if self.tag_mode.is_best_effort() && err.is_access_denied() {
+                warn!(
+                    bucket = %destination.bucket,
+                    key = %destination.key,
+                    "retrying PutObject without tags"
+                );
                 let result = do_put(None, state.metadata(), additional_checksum, buf).await?;
 
                 (result, vec![err])
@@ -588,11 +679,28 @@ impl S3 {
         };
 
         if let Some(part_number) = multi_part.part_number {
+            multi_part.check_part_number()?;
+            let values = ChecksumValues::new(
+                additional_checksum.as_ref(),
+                state.precomputed(Some(part_number)),
+            )?;
+            debug!(
+                bucket = %destination.bucket,
+                key = %destination.key,
+                part_number,
+                precomputed = values != Default::default(),
+                "UploadPart"
+            );
             let part = self
                 .client
                 .upload_part()
                 .upload_id(&upload_id)
                 .set_checksum_algorithm(additional_checksum)
+                .set_checksum_crc32(values.crc32)
+                .set_checksum_crc32_c(values.crc32_c)
+                .set_checksum_sha1(values.sha1)
+                .set_checksum_sha256(values.sha256)
+                .set_checksum_crc64_nvme(values.crc64_nvme)
                 .part_number(i32::try_from(part_number)?)
                 .key(&destination.key)
                 .bucket(&destination.bucket)
@@ -610,7 +718,7 @@ impl S3 {
                 &destination.key,
                 &destination.bucket,
                 upload_id.to_string(),
-                multi_part.parts,
+                multi_part.completed_parts()?,
             )
             .await?;
 
@@ -618,17 +726,20 @@ impl S3 {
         }
     }
 
-    /// Complete a multipart upload.
+    /// Complete a multipart upload. The parts must be ordered by part number.
     async fn complete_multipart_upload(
         &self,
         key: &str,
         bucket: &str,
         upload_id: String,
-        mut parts: Vec<Part>,
+        parts: Vec<Part>,
     ) -> Result<()> {
-        // Parts must be ordered.
-        parts.sort_by(|a, b| a.part_number.cmp(&b.part_number));
-
+        debug!(
+            bucket,
+            key,
+            n_parts = parts.len(),
+            "CompleteMultipartUpload"
+        );
         self.client
             .complete_multipart_upload()
             .bucket(bucket)
@@ -683,15 +794,15 @@ impl ObjectCopy for S3 {
     }
 
     fn max_part_size(&self) -> u64 {
-        5368709120
+        S3_MAX_PART_SIZE
     }
 
     fn max_parts(&self) -> u64 {
-        10000
+        S3_MAX_PARTS
     }
 
     fn min_part_size(&self) -> u64 {
-        5242880
+        S3_MIN_PART_SIZE
     }
 
     async fn initialize_state(&self) -> Result<CopyState> {
@@ -182,8 +182,8 @@ impl ObjectCopy for File {
         multipart: Option<MultiPartOptions>,
         _state: &CopyState,
     ) -> Result<CopyResult> {
-        // It doesn't matter what the part number is for filesystem operations, just append to the
-        // end of the file as we assume correct ordering of parts.
+        // The part number and total parts are ignored for filesystem operations, just append to
+        // the end of the file as we assume correct ordering of parts.
         let bytes = self.write(data, multipart).await?;
 
         CopyResult::new(None, None, bytes, vec![])
@@ -0,0 +1,82 @@
+//! Copying from read-only HTTP(S) objects.
+//!
+
+use crate::error::Error::CopyError;
+use crate::error::Result;
+use crate::io::copy::{CopyContent, CopyResult, CopyState, MultiPartOptions, ObjectCopy};
+use crate::io::sums::http::Http as HttpObject;
+
+/// An HTTP(S) object that can only be used as a copy source.
+#[derive(Debug, Clone)]
+pub struct Http {
+    source: HttpObject,
+}
+
+impl Http {
+    /// Create a new HTTP copy source.
+    pub fn new(url: String) -> Self {
+        Self {
+            source: HttpObject::new(url),
+        }
+    }
+
+    fn read_only_error() -> crate::error::Error {
+        CopyError("http locations can only be used as a copy source".to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectCopy for Http {
+    async fn copy(
+        &self,
+        _multipart: Option<MultiPartOptions>,
+        _state: &CopyState,
+    ) -> Result<CopyResult> {
+        Err(Self::read_only_error())
+    }
+
+    async fn download(&self, multipart: Option<MultiPartOptions>) -> Result<CopyContent> {
+        // The final multipart call only completes the upload so there is nothing to read.
+        if let Some(multipart) = &multipart {
+            if multipart.part_number.is_none() {
+                return Ok(Default::default());
+            }
+        }
+
+        Ok(CopyContent::new(
+            self.source.object_reader(multipart).await?,
+        ))
+    }
+
+    async fn upload(
+        &self,
+        _data: CopyContent,
+        _multipart: Option<MultiPartOptions>,
+        _state: &CopyState,
+    ) -> Result<CopyResult> {
+        Err(Self::read_only_error())
+    }
+
+    fn max_part_size(&self) -> u64 {
+        u64::MAX
+    }
+
+    fn max_parts(&self) -> u64 {
+        u64::MAX
+    }
+
+    fn min_part_size(&self) -> u64 {
+        u64::MIN
+    }
+
+    async fn initialize_state(&self) -> Result<CopyState> {
+        let size = self.source.size().await?.ok_or_else(|| {
+            CopyError(format!(
+                "could not determine the size of `{}`",
+                self.source.url()
+            ))
+        })?;
+
+        Ok(CopyState::new(size, None, None))
+    }
+}
@@ -1,21 +1,30 @@
 //! Functionality related to copying.
 //!
 
+use crate::checksum::file::{ByteRange, Checksum};
 use crate::checksum::Ctx;
 use crate::cli::MetadataCopy;
 use crate::error::Error::CopyError;
 use crate::error::{ApiError, Result};
+#[cfg(feature = "cloud")]
 use crate::io::copy::aws::S3Builder;
 use crate::io::copy::file::FileBuilder;
-use crate::io::{default_s3_client, Provider};
-use aws_sdk_s3::Client;
+use crate::io::copy::http::Http;
+#[cfg(feature = "cloud")]
+use crate::io::default_s3_client;
+#[cfg(not(feature = "cloud"))]
+use crate::io::s3_unsupported;
+use crate::io::{Client, Provider};
 use dyn_clone::DynClone;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::io::{empty, AsyncRead};
 
+#[cfg(feature = "cloud")]
 pub mod aws;
 pub mod file;
+pub mod http;
+pub mod verify;
 
 /// Content to download/upload with optional tags.
 pub struct CopyContent {
@@ -35,11 +44,20 @@ impl CopyContent {
     pub fn new(data: Box<dyn AsyncRead + Sync + Send + Unpin>) -> Self {
         Self { data }
     }
+
+    /// Get the inner data.
+    pub fn into_inner(self) -> Box<dyn AsyncRead + Sync + Send + Unpin> {
+        self.data
+    }
 }
 
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(not(feature = "cloud"), allow(dead_code))]
 pub struct MultiPartOptions {
+    /// The 1-based number of this part, or `None` for the final call which completes the upload.
     pub(crate) part_number: Option<u64>,
+    /// The total number of parts of the upload, if known.
+    pub(crate) total_parts: Option<u64>,
     pub(crate) start: u64,
     pub(crate) end: u64,
     pub(crate) parts: Vec<Part>,
@@ -56,10 +74,55 @@ impl MultiPartOptions {
     pub fn bytes_transferred(&self) -> u64 {
         self.end - self.start
     }
+
+    /// Check that the part number is within the total number of parts, if both are known.
+    pub fn check_part_number(&self) -> Result<()> {
+        match (self.part_number, self.total_parts) {
+            (Some(part_number), Some(total_parts))
+                if part_number == 0 || part_number > total_parts =>
+            {
+                Err(CopyError(format!(
+                    "part number {} is outside of the {} parts of the upload",
+                    part_number, total_parts
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the uploaded parts ordered by part number for completing the upload. If the total
+    /// number of parts is known, every part from 1 to the total must be present exactly once.
+    pub fn completed_parts(&self) -> Result<Vec<Part>> {
+        let mut parts = self.parts.clone();
+        parts.sort_by_key(|part| part.part_number);
+
+        if let Some(total_parts) = self.total_parts {
+            let part_numbers: Vec<_> = parts.iter().map(|part| part.part_number).collect();
+            if !part_numbers.iter().copied().eq(1..=total_parts) {
+                return Err(CopyError(format!(
+                    "expected parts 1 to {} to complete the upload, got {:?}",
+                    total_parts, part_numbers
+                )));
+            }
+        }
+
+        Ok(parts)
+    }
+}
+
+impl From<ByteRange> for MultiPartOptions {
+    fn from(range: ByteRange) -> Self {
+        Self {
+            start: range.offset,
+            end: range.end(),
+            ..Default::default()
+        }
+    }
 }
 
 /// Represents a part for a multipart copy.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(not(feature = "cloud"), allow(dead_code))]
 pub struct Part {
     pub(crate) crc32: Option<String>,
     pub(crate) crc32_c: Option<String>,
@@ -123,6 +186,8 @@ pub struct CopyState {
     tags: Option<String>,
     metadata: Option<HashMap<String, String>>,
     additional_ctx: Option<Ctx>,
+    precomputed: Option<Checksum>,
+    precomputed_parts: Vec<Checksum>,
 }
 
 impl CopyState {
@@ -153,6 +218,8 @@ impl CopyState {
             tags,
             metadata,
             additional_ctx: None,
+            precomputed: None,
+            precomputed_parts: vec![],
         }
     }
 
@@ -160,6 +227,25 @@ impl CopyState {
     pub fn set_additional_ctx(&mut self, additional_ctx: Ctx) {
         self.additional_ctx = Some(additional_ctx);
     }
+
+    /// Set the checksums of the additional context that were already computed for the source,
+    /// either for the whole object or for each part in order. These are sent with uploads so
+    /// that the destination validates and stores them rather than computing them again.
+    pub fn set_precomputed(&mut self, precomputed: Option<Checksum>, parts: Vec<Checksum>) {
+        self.precomputed = precomputed;
+        self.precomputed_parts = parts;
+    }
+
+    /// Get the precomputed checksum of the additional context for the whole object, or for a
+    /// part if a part number is specified.
+    pub fn precomputed(&self, part_number: Option<u64>) -> Option<&Checksum> {
+        match part_number {
+            None => self.precomputed.as_ref(),
+            Some(part_number) => self
+                .precomputed_parts
+                .get(usize::try_from(part_number.checked_sub(1)?).ok()?),
+        }
+    }
 }
 
 /// Write operations on file based or cloud files.
@@ -210,7 +296,25 @@ pub struct ObjectCopyBuilder {
 
 impl ObjectCopyBuilder {
     /// Build the object copy. Both the source and destination need to be of the same type.
+    /// HTTP(S) locations can only be used as a source.
     pub async fn build(self) -> Result<Box<dyn ObjectCopy + Send + Sync>> {
+        if let Some(Provider::Http { url }) = &self.destination {
+            return Err(CopyError(format!(
+                "cannot copy to `{}` because http locations are read-only",
+                url
+            )));
+        }
+        if let Some(Provider::Http { url }) = self.source {
+            if self.destination.is_some() {
+                return Err(CopyError(format!(
+                    "cannot copy from `{}` to a destination using server-side copy",
+                    url
+                )));
+            }
+
+            return Ok(Box::new(Http::new(url)));
+        }
+
         let is_s3 = match (&self.source, &self.destination) {
             (Some(source), _) => source.is_s3(),
             (_, Some(destination)) => destination.is_s3(),
@@ -218,29 +322,7 @@ impl ObjectCopyBuilder {
         };
 
         if is_s3 {
-            let client = match self.client {
-                Some(client) => client,
-                None => Arc::new(default_s3_client().await?),
-            };
-            let source = self.source.map(|source| source.into_s3()).transpose()?;
-            let destination = self
-                .destination
-                .map(|destination| destination.into_s3())
-                .transpose()?;
-
-            let mut builder = S3Builder::default()
-                .with_copy_metadata(self.metadata_mode)
-                .with_copy_tags(self.tag_mode)
-                .with_client(client);
-
-            if let Some((bucket, key)) = source {
-                builder = builder.with_source(&bucket, &key);
-            }
-            if let Some((bucket, key)) = destination {
-                builder = builder.with_destination(&bucket, &key);
-            }
-
-            Ok(Box::new(builder.build()?))
+            self.build_s3().await
         } else {
             let source = self.source.map(|source| source.into_file()).transpose()?;
             let destination = self
@@ -260,6 +342,45 @@ impl ObjectCopyBuilder {
         }
     }
 
+    /// Build an S3 object copy.
+    #[cfg(feature = "cloud")]
+    async fn build_s3(self) -> Result<Box<dyn ObjectCopy + Send + Sync>> {
+        let client = match self.client {
+            Some(client) => client,
+            None => Arc::new(default_s3_client().await?),
+        };
+        let source = self.source.map(|source| source.into_s3()).transpose()?;
+        let destination = self
+            .destination
+            .map(|destination| destination.into_s3())
+            .transpose()?;
+
+        let mut builder = S3Builder::default()
+            .with_copy_metadata(self.metadata_mode)
+            .with_copy_tags(self.tag_mode)
+            .with_client(client);
+
+        if let Some((bucket, key)) = source {
+            builder = builder.with_source(&bucket, &key);
+        }
+        if let Some((bucket, key)) = destination {
+            builder = builder.with_destination(&bucket, &key);
+        }
+
+        Ok(Box::new(builder.build()?))
+    }
+
+    /// S3 copies are not available without the `cloud` feature.
+    #[cfg(not(feature = "cloud"))]
+    async fn build_s3(self) -> Result<Box<dyn ObjectCopy + Send + Sync>> {
+        let url = self
+            .source
+            .or(self.destination)
+            .map(|provider| provider.format())
+            .unwrap_or_default();
+        Err(s3_unsupported(&url))
+    }
+
     /// Set the destination.
     pub fn set_destination(mut self, destination: Option<Provider>) -> Self {
         self.destination = destination;
@@ -290,3 +411,56 @@ impl ObjectCopyBuilder {
         self
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    fn part(part_number: u64) -> Part {
+        Part {
+            part_number,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn multipart_part_numbers() {
+        let options = |part_number, parts| MultiPartOptions {
+            part_number,
+            total_parts: Some(3),
+            parts,
+            ..Default::default()
+        };
+
+        assert!(options(Some(1), vec![]).check_part_number().is_ok());
+        assert!(options(Some(3), vec![]).check_part_number().is_ok());
+        assert!(options(Some(0), vec![]).check_part_number().is_err());
+        assert!(options(Some(4), vec![]).check_part_number().is_err());
+
+        // Parts that completed out of order are placed by their part number.
+        let parts = options(None, vec![part(3), part(1), part(2)])
+            .completed_parts()
+            .unwrap();
+        assert_eq!(
+            parts
+                .iter()
+                .map(|part| part.part_number)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        assert!(options(None, vec![part(3), part(1)])
+            .completed_parts()
+            .is_err());
+        assert!(options(None, vec![part(1), part(2), part(2), part(3)])
+            .completed_parts()
+            .is_err());
+
+        // Without a total, the parts are only ordered.
+        let options = MultiPartOptions {
+            parts: vec![part(2), part(1)],
+            ..Default::default()
+        };
+        assert_eq!(options.completed_parts().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,135 @@
+//! Compute checksums over the data of a copy as it is transferred.
+//!
+
+use crate::checksum::file::Checksum;
+use crate::checksum::Ctx;
+use crate::error::Error::CopyError;
+use crate::error::Result;
+use crate::io::copy::CopyContent;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// A running checksum which is updated by the data of each part as it is uploaded. Parts must
+/// be read in order, so that the checksum is the same as reading the whole object.
+#[derive(Debug, Clone)]
+pub struct CopyVerifier {
+    ctx: Arc<Mutex<Ctx>>,
+    expected: Checksum,
+}
+
+impl CopyVerifier {
+    /// Create a verifier which computes the checksum and compares it to the expected value.
+    pub fn new(ctx: Ctx, expected: Checksum) -> Self {
+        Self {
+            ctx: Arc::new(Mutex::new(ctx)),
+            expected,
+        }
+    }
+
+    /// Wrap the content of a part so that the checksum is updated as it is read.
+    pub fn wrap(&self, content: CopyContent) -> CopyContent {
+        CopyContent::new(Box::new(VerifyReader {
+            inner: content.into_inner(),
+            ctx: self.ctx.clone(),
+        }))
+    }
+
+    /// Finalize the checksum, returning an error if it does not match the expected value.
+    pub fn finalize(self) -> Result<(Ctx, Checksum)> {
+        let mut ctx = self
+            .ctx
+            .lock()
+            .map_err(|err| CopyError(err.to_string()))?
+            .clone();
+        let digest = ctx.finalize()?;
+        let checksum = Checksum::new(ctx.digest_to_string(&digest));
+
+        if checksum != self.expected {
+            return Err(CopyError(format!(
+                "the `{}` checksum of the copied data `{}` does not match the source `{}`",
+                ctx,
+                checksum.as_str(),
+                self.expected.as_str()
+            )));
+        }
+
+        Ok((ctx, checksum))
+    }
+}
+
+/// A reader which updates a checksum with the data that is read.
+struct VerifyReader {
+    inner: Box<dyn AsyncRead + Sync + Send + Unpin>,
+    ctx: Arc<Mutex<Ctx>>,
+}
+
+impl AsyncRead for VerifyReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        let data = &buf.filled()[filled..];
+        if !data.is_empty() {
+            this.ctx
+                .lock()
+                .map_err(|err| io::Error::other(err.to_string()))?
+                .update(Arc::from(data))
+                .map_err(io::Error::other)?;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::checksum::checksum_bytes;
+    use anyhow::Result;
+    use tokio::io::AsyncReadExt;
+
+    async fn read_parts(verifier: &CopyVerifier, parts: &[&'static [u8]]) -> Result<()> {
+        for part in parts {
+            let mut data = vec![];
+            verifier
+                .wrap(CopyContent::new(Box::new(*part)))
+                .into_inner()
+                .read_to_end(&mut data)
+                .await?;
+            assert_eq!(data, *part);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn verify_parts() -> Result<()> {
+        let parts: [&[u8]; 3] = [b"hello", b" ", b"world"];
+
+        for checksum in ["md5", "sha256-aws-2b"] {
+            let mut ctx: Ctx = checksum.parse()?;
+            ctx.set_file_size(Some(11));
+            let expected = Checksum::new(checksum_bytes(checksum, b"hello world")?);
+
+            let verifier = CopyVerifier::new(ctx.clone(), expected.clone());
+            read_parts(&verifier, &parts).await?;
+            assert_eq!(verifier.finalize()?.1, expected);
+
+            // Parts read out of order do not match.
+            let verifier = CopyVerifier::new(ctx, expected);
+            read_parts(&verifier, &[parts[2], parts[1], parts[0]]).await?;
+            assert!(verifier.finalize().is_err());
+        }
+
+        Ok(())
+    }
+}
@@ -4,18 +4,47 @@
 use crate::cli::CredentialProvider;
 use crate::error::Error::ParseError;
 use crate::error::{Error, Result};
+#[cfg(feature = "cloud")]
 use aws_config::Region;
-use aws_sdk_s3::{config, Client};
+#[cfg(feature = "cloud")]
+use aws_sdk_s3::config;
+#[cfg(feature = "cloud")]
+pub use aws_sdk_s3::Client;
+#[cfg(feature = "cloud")]
 use aws_smithy_runtime_api::client::behavior_version::BehaviorVersion;
 
 pub mod copy;
 pub mod sums;
 
+/// A placeholder for the S3 client when built without the `cloud` feature. It cannot be
+/// used to make any requests, and S3 urls are rejected before a client is needed.
+#[cfg(not(feature = "cloud"))]
+#[derive(Debug, Clone)]
+pub struct Client;
+
+/// The error returned when an S3 url is used without the `cloud` feature.
+#[cfg(not(feature = "cloud"))]
+pub(crate) fn s3_unsupported(url: &str) -> Error {
+    ParseError(format!(
+        "{} is an S3 url, but S3 support was not compiled in, rebuild with the `cloud` feature",
+        url
+    ))
+}
+
 /// The type of provider for the object.
 #[derive(Debug, Clone)]
 pub enum Provider {
-    File { file: String },
-    S3 { bucket: String, key: String },
+    File {
+        file: String,
+    },
+    S3 {
+        bucket: String,
+        key: String,
+        version_id: Option<String>,
+    },
+    Http {
+        url: String,
+    },
 }
 
 impl Provider {
@@ -24,6 +53,17 @@ impl Provider {
         format!("s3://{}/{}", bucket, key)
     }
 
+    /// Format an S3 url for a specific version of an object if there is one, e.g.
+    /// `s3://bucket/key?versionId=version`.
+    pub fn format_s3_version(bucket: &str, key: &str, version_id: Option<&str>) -> String {
+        match version_id {
+            Some(version_id) => {
+                format!("{}?versionId={}", Self::format_s3(bucket, key), version_id)
+            }
+            None => Self::format_s3(bucket, key),
+        }
+    }
+
     /// Format a file url.
     pub fn format_file(file: &str) -> String {
         format!("file://{}", file)
@@ -33,16 +73,28 @@ impl Provider {
     pub fn format(&self) -> String {
         match self {
             Provider::File { file } => Self::format_file(file),
-            Provider::S3 { bucket, key } => Self::format_s3(bucket, key),
+            Provider::S3 {
+                bucket,
+                key,
+                version_id,
+            } => Self::format_s3_version(bucket, key, version_id.as_deref()),
+            Provider::Http { url } => url.to_string(),
         }
     }
 
-    /// Parse from an S3 url, e.g.`s3://bucket/key`.
+    /// Parse from an S3 url, e.g.`s3://bucket/key`. A specific version of the object can be
+    /// selected with a `?versionId=` suffix, e.g. `s3://bucket/key?versionId=version`.
     pub fn parse_s3_url(s: &str) -> Result<Self> {
         let Some(s) = s.strip_prefix("s3://") else {
             return Err(ParseError(format!("{} is not an S3 url", s)));
         };
 
+        let (s, version_id) = match s.rsplit_once("?versionId=") {
+            Some((_, "")) => return Err(ParseError(format!("{} is missing a version id", s))),
+            Some((s, version_id)) => (s, Some(version_id.to_string())),
+            None => (s, None),
+        };
+
         let split = s.split_once("/");
         let Some((bucket, key)) = split else {
             return Err(ParseError(format!("failed to parse {}", s)));
@@ -58,13 +110,36 @@ impl Provider {
         Ok(Self::S3 {
             bucket: bucket.to_string(),
             key: key.to_string(),
+            version_id,
         })
     }
 
+    /// Parse an S3 url where the key is an optional prefix, e.g. `s3://bucket` or
+    /// `s3://bucket/prefix/`. Returns the bucket and prefix.
+    pub fn parse_s3_prefix(s: &str) -> Result<(String, String)> {
+        let Some(s) = s.strip_prefix("s3://") else {
+            return Err(ParseError(format!("{} is not an S3 url", s)));
+        };
+
+        let (bucket, prefix) = s.split_once("/").unwrap_or((s, ""));
+        if bucket.is_empty() {
+            return Err(ParseError(format!("{} is missing a bucket", s)));
+        }
+
+        Ok((bucket.to_string(), prefix.to_string()))
+    }
+
     /// Convert the provider into an S3 bucket and key.
     pub fn into_s3(self) -> Result<(String, String)> {
         match self {
-            Provider::S3 { bucket, key } => Ok((bucket, key)),
+            Provider::S3 {
+                bucket,
+                key,
+                version_id: None,
+            } => Ok((bucket, key)),
+            Provider::S3 { .. } => Err(ParseError(
+                "a specific object version is not supported here".to_string(),
+            )),
             _ => Err(ParseError("not an S3 provider".to_string())),
         }
     }
@@ -89,10 +164,23 @@ impl Provider {
         matches!(self, Provider::File { .. })
     }
 
+    /// Get the version id if this is a specific version of an S3 object.
+    pub fn version_id(&self) -> Option<&str> {
+        match self {
+            Provider::S3 { version_id, .. } => version_id.as_deref(),
+            _ => None,
+        }
+    }
+
     /// Check if the provider is an S3 provider.
     pub fn is_s3(&self) -> bool {
         matches!(self, Provider::S3 { .. })
     }
+
+    /// Check if the provider is a read-only HTTP(S) provider.
+    pub fn is_http(&self) -> bool {
+        matches!(self, Provider::Http { .. })
+    }
 }
 
 impl TryFrom<&str> for Provider {
@@ -101,6 +189,10 @@ impl TryFrom<&str> for Provider {
     fn try_from(url: &str) -> Result<Self> {
         if url.starts_with("s3://") {
             Self::parse_s3_url(url)
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            Ok(Self::Http {
+                url: url.to_string(),
+            })
         } else {
             Ok(Self::parse_file_url(url))
         }
@@ -108,11 +200,16 @@ impl TryFrom<&str> for Provider {
 }
 
 /// Create an S3 client from the credentials provider, profile, region and endpoint url.
+/// The profile and region override those of the environment, so that a single invocation can
+/// target a different account or region. Path-style addressing can be forced for S3-compatible
+/// servers that do not support virtual-hosted-style requests, such as MinIO or LocalStack.
+#[cfg(feature = "cloud")]
 pub async fn create_s3_client(
     provider: &CredentialProvider,
     profile: Option<&str>,
     region: Option<&str>,
     endpoint_url: Option<&str>,
+    force_path_style: bool,
 ) -> Result<Client> {
     let mut loader = aws_config::defaults(BehaviorVersion::latest());
 
@@ -124,7 +221,8 @@ pub async fn create_s3_client(
     }
 
     let loader = match (provider, profile) {
-        (CredentialProvider::DefaultEnvironment, _) => loader,
+        (CredentialProvider::DefaultEnvironment, Some(profile)) => loader.profile_name(profile),
+        (CredentialProvider::DefaultEnvironment, None) => loader,
         (CredentialProvider::NoCredentials, _) => loader.no_credentials(),
         (CredentialProvider::AwsProfile, Some(profile)) => loader.profile_name(profile),
         _ => {
@@ -134,14 +232,35 @@ pub async fn create_s3_client(
         }
     };
 
-    let config = config::Builder::from(&loader.load().await).build();
+    let config = config::Builder::from(&loader.load().await)
+        .force_path_style(force_path_style)
+        .build();
 
     Ok(Client::from_conf(config))
 }
 
+/// Create a placeholder client when built without the `cloud` feature.
+#[cfg(not(feature = "cloud"))]
+pub async fn create_s3_client(
+    _provider: &CredentialProvider,
+    _profile: Option<&str>,
+    _region: Option<&str>,
+    _endpoint_url: Option<&str>,
+    _force_path_style: bool,
+) -> Result<Client> {
+    Ok(Client)
+}
+
 /// Create the default S3 client.
 pub async fn default_s3_client() -> Result<Client> {
-    create_s3_client(&CredentialProvider::DefaultEnvironment, None, None, None).await
+    create_s3_client(
+        &CredentialProvider::DefaultEnvironment,
+        None,
+        None,
+        None,
+        false,
+    )
+    .await
 }
 
 #[cfg(test)]
@@ -176,6 +295,25 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_s3_version() -> Result<()> {
+        let url = "s3://bucket/key?versionId=version";
+        let s3 = Provider::try_from(url)?;
+        assert_eq!(s3.version_id(), Some("version"));
+        assert_eq!(s3.format(), url);
+        // Copies use the latest object so a version is rejected.
+        assert!(s3.into_s3().is_err());
+
+        let s3 = Provider::try_from("s3://bucket/key?v")?;
+        assert_eq!(s3.version_id(), None);
+        assert_eq!(s3.format(), "s3://bucket/key?v");
+
+        assert!(Provider::try_from("s3://bucket/key?versionId=").is_err());
+        assert!(Provider::try_from("s3://bucket/?versionId=version").is_err());
+
+        Ok(())
+    }
+
     fn provider_s3(url: &str) -> Result<(String, String)> {
         Ok(Provider::try_from(url)?.into_s3()?)
     }
@@ -3,27 +3,67 @@
 
 use crate::checksum::aws_etag::{AWSETagCtx, PartMode};
 use crate::checksum::file::Checksum;
-use crate::checksum::file::SumsFile;
+use crate::checksum::file::{FileMetadata, SumsFile, SumsFormat, SumsStorage};
 use crate::checksum::standard::StandardCtx;
 use crate::checksum::Ctx;
-use crate::error::Error::ParseError;
+use crate::error::Error::{ParseError, SumsFileError};
 use crate::error::{ApiError, Error, Result};
+use crate::io::copy::MultiPartOptions;
+use crate::io::sums::cache::DownloadCache;
 use crate::io::sums::ObjectSums;
 use crate::io::Provider;
+use crate::stats::{ChecksumStats, ObjectInfo};
+use aws_sdk_s3::error::SdkError;
 use aws_sdk_s3::operation::get_object::GetObjectError;
 use aws_sdk_s3::operation::get_object_attributes::GetObjectAttributesOutput;
 use aws_sdk_s3::operation::head_object::HeadObjectOutput;
+use aws_sdk_s3::operation::put_object::PutObjectError;
+use aws_sdk_s3::types;
 use aws_sdk_s3::types::{
-    ChecksumAlgorithm, ChecksumMode, ChecksumType, ObjectAttributes, ObjectPart,
+    ChecksumAlgorithm, ChecksumMode, ChecksumType, ObjectAttributes, ObjectPart, StorageClass, Tag,
+    Tagging,
 };
 use aws_sdk_s3::Client;
+use aws_smithy_runtime_api::client::orchestrator::HttpResponse;
 use aws_smithy_types::byte_stream::ByteStream;
+use aws_smithy_types::error::metadata::ProvideErrorMetadata;
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::io::AsyncRead;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// The prefix of object tag keys that store a sums file, followed by the index of the tag.
+pub const SUMS_TAG_PREFIX: &str = "cloud-checksum-sums-";
+
+/// The maximum number of tags on an S3 object.
+pub const MAX_TAGS: usize = 10;
+
+/// The maximum length of an S3 object tag value.
+pub const MAX_TAG_VALUE_LENGTH: usize = 256;
+
+/// The maximum number of attempts to write a sums object that is being modified concurrently.
+pub const MAX_CONDITIONAL_WRITE_ATTEMPTS: usize = 5;
+
+/// The delay before the first retry of a conditional write, which doubles on every retry.
+pub const CONDITIONAL_WRITE_BACKOFF: Duration = Duration::from_millis(100);
+
+/// The state of the sums object when it was last read or written. This is used to make
+/// conditional writes that detect a concurrent modification of the sums object.
+#[derive(Debug, Clone, Default)]
+enum SumsObjectState {
+    /// The sums object has not been read.
+    #[default]
+    Unknown,
+    /// The sums object did not exist.
+    Missing,
+    /// The sums object existed with this `ETag`.
+    ETag(String),
+}
 
 /// Build an S3 sums object.
 #[derive(Debug, Default)]
@@ -31,7 +71,12 @@ pub struct S3Builder {
     client: Option<Arc<Client>>,
     bucket: Option<String>,
     key: Option<String>,
+    version_id: Option<String>,
     avoid_get_object_attributes: bool,
+    format: SumsFormat,
+    storage: SumsStorage,
+    sums_destination: Option<(String, String)>,
+    download_cache: Option<DownloadCache>,
 }
 
 impl S3Builder {
@@ -53,12 +98,43 @@ impl S3Builder {
         self
     }
 
+    /// Set a specific version of the object to use rather than the latest.
+    pub fn set_version_id(mut self, version_id: Option<String>) -> Self {
+        self.version_id = version_id;
+        self
+    }
+
     /// Avoid `GetObjectAttributes` calls.
     pub fn with_avoid_get_object_attributes(mut self, avoid_get_object_attributes: bool) -> Self {
         self.avoid_get_object_attributes = avoid_get_object_attributes;
         self
     }
 
+    /// Set the format of the sums file.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set where the sums file is stored.
+    pub fn with_storage(mut self, storage: SumsStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Set a bucket and prefix to read and write the sums file in, rather than next to the
+    /// object.
+    pub fn set_sums_destination(mut self, sums_destination: Option<(String, String)>) -> Self {
+        self.sums_destination = sums_destination;
+        self
+    }
+
+    /// Set a local cache to download the object into.
+    pub fn set_download_cache(mut self, download_cache: Option<DownloadCache>) -> Self {
+        self.download_cache = download_cache;
+        self
+    }
+
     fn get_components(self) -> Result<(Arc<Client>, String, String, bool)> {
         let error_fn =
             || ParseError("client, bucket and key are required in `S3Builder`".to_string());
@@ -73,7 +149,17 @@ impl S3Builder {
 
     /// Build using the client, bucket and key.
     pub fn build(self) -> Result<S3> {
-        Ok(self.get_components()?.into())
+        let format = self.format;
+        let storage = self.storage;
+        let sums_destination = self.sums_destination.clone();
+        let version_id = self.version_id.clone();
+        let download_cache = self.download_cache.clone();
+        Ok(S3::from(self.get_components()?)
+            .set_version_id(version_id)
+            .with_format(format)
+            .with_storage(storage)
+            .set_sums_destination(sums_destination)
+            .set_download_cache(download_cache))
     }
 }
 
@@ -91,10 +177,16 @@ pub struct S3 {
     client: Arc<Client>,
     bucket: String,
     key: String,
+    version_id: Option<String>,
     get_object_attributes: Option<GetObjectAttributesOutput>,
     head_object: HashMap<Option<u64>, HeadObjectOutput>,
     api_errors: HashSet<ApiError>,
     avoid_get_object_attributes: bool,
+    format: SumsFormat,
+    storage: SumsStorage,
+    sums_destination: Option<(String, String)>,
+    sums_object_state: Arc<Mutex<SumsObjectState>>,
+    download_cache: Option<DownloadCache>,
 }
 
 impl S3 {
@@ -109,35 +201,237 @@ impl S3 {
             client,
             bucket,
             key,
+            version_id: None,
             get_object_attributes: None,
             head_object: HashMap::new(),
             api_errors: HashSet::new(),
             avoid_get_object_attributes,
+            format: Default::default(),
+            storage: Default::default(),
+            sums_destination: None,
+            sums_object_state: Default::default(),
+            download_cache: None,
+        }
+    }
+
+    /// Set a specific version of the object to use rather than the latest.
+    pub fn set_version_id(mut self, version_id: Option<String>) -> Self {
+        self.version_id = version_id;
+        self
+    }
+
+    /// Set the format of the sums file.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set where the sums file is stored.
+    pub fn with_storage(mut self, storage: SumsStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Set a bucket and prefix to read and write the sums file in, rather than next to the
+    /// object.
+    pub fn set_sums_destination(mut self, sums_destination: Option<(String, String)>) -> Self {
+        self.sums_destination = sums_destination;
+        self
+    }
+
+    /// Set a local cache to download the object into, so that reading it again reuses the
+    /// downloaded bytes if the `ETag` of the object has not changed.
+    pub fn set_download_cache(mut self, download_cache: Option<DownloadCache>) -> Self {
+        self.download_cache = download_cache;
+        self
+    }
+
+    /// Format the key that the sums file is named after. Sums files of a specific version
+    /// include the version, e.g. `key.version.sums`, so that they do not collide with the
+    /// sums of other versions.
+    pub fn format_versioned_key(key: &str, version_id: Option<&str>) -> String {
+        match version_id {
+            Some(version_id) => format!("{}.{}", SumsFile::format_target_file(key), version_id),
+            None => key.to_string(),
+        }
+    }
+
+    /// Get the bucket and key of the sums file.
+    fn sums_location(&self) -> Result<(&str, String)> {
+        let key = Self::format_versioned_key(&self.key, self.version_id.as_deref());
+        Ok(match &self.sums_destination {
+            Some((bucket, prefix)) => {
+                (bucket, self.format.format_sums_file_at(&key, Some(prefix))?)
+            }
+            None => (&self.bucket, self.format.format_sums_file(&key)),
+        })
+    }
+
+    /// List the keys of all objects under a prefix. Listings of more than 1000 keys are
+    /// followed using continuation tokens. Sums files and directory markers are skipped.
+    pub async fn list_objects(client: &Client, bucket: &str, prefix: &str) -> Result<Vec<String>> {
+        let mut keys = vec![];
+        let mut continuation_token = None;
+        loop {
+            debug!(bucket, prefix, "ListObjectsV2");
+            let output = client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token.take())
+                .send()
+                .await?;
+
+            keys.extend(
+                output
+                    .contents()
+                    .iter()
+                    .filter_map(|object| object.key())
+                    .filter(|key| !key.ends_with('/') && SumsFormat::from_name(key).is_none())
+                    .map(|key| key.to_string()),
+            );
+
+            match output.next_continuation_token() {
+                Some(token) if output.is_truncated().unwrap_or_default() => {
+                    continuation_token = Some(token.to_string())
+                }
+                _ => break,
+            }
         }
+
+        Ok(keys)
+    }
+
+    /// Get the tags of the object.
+    pub async fn get_tags(&self) -> Result<Vec<Tag>> {
+        debug!(bucket = %self.bucket, key = %self.key, "GetObjectTagging");
+        Ok(self
+            .client
+            .get_object_tagging()
+            .bucket(&self.bucket)
+            .key(SumsFile::format_target_file(&self.key))
+            .set_version_id(self.version_id.clone())
+            .send()
+            .await?
+            .tag_set)
+    }
+
+    /// Read a sums file from the base64 encoded chunks stored in object tags, if there is one.
+    pub fn sums_from_tags(&self, tags: &[Tag]) -> Result<Option<SumsFile>> {
+        let mut chunks = tags
+            .iter()
+            .filter_map(|tag| {
+                let index = tag
+                    .key()
+                    .strip_prefix(SUMS_TAG_PREFIX)?
+                    .parse::<usize>()
+                    .ok()?;
+                Some((index, tag.value()))
+            })
+            .collect::<Vec<_>>();
+        if chunks.is_empty() {
+            return Ok(None);
+        }
+
+        chunks.sort();
+        let encoded = chunks
+            .into_iter()
+            .map(|(_, chunk)| chunk)
+            .collect::<String>();
+        let data = BASE64_STANDARD.decode(encoded).map_err(|_| {
+            ParseError(format!(
+                "failed to decode sums file from the tags of `{}`",
+                self.location()
+            ))
+        })?;
+
+        Ok(Some(self.format.from_slice(&data)?))
+    }
+
+    /// Replace the sums file stored in a set of object tags, keeping any other tags. This
+    /// returns an error if the sums file does not fit within the S3 tag limits.
+    pub fn sums_to_tags(&self, sums_file: &SumsFile, tags: Vec<Tag>) -> Result<Vec<Tag>> {
+        let mut tags = tags
+            .into_iter()
+            .filter(|tag| !tag.key().starts_with(SUMS_TAG_PREFIX))
+            .collect::<Vec<_>>();
+
+        let encoded = BASE64_STANDARD.encode(self.format.to_vec(sums_file)?);
+        let chunks = encoded
+            .as_bytes()
+            .chunks(MAX_TAG_VALUE_LENGTH)
+            .collect::<Vec<_>>();
+        let available = MAX_TAGS.saturating_sub(tags.len());
+        if chunks.len() > available {
+            return Err(SumsFileError(format!(
+                "the sums file for `{}` is too large to store in object tags, it needs {} tags of {} characters but only {} are available",
+                self.location(),
+                chunks.len(),
+                MAX_TAG_VALUE_LENGTH,
+                available
+            )));
+        }
+
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let tag = Tag::builder()
+                .key(format!("{}{}", SUMS_TAG_PREFIX, index))
+                .value(String::from_utf8_lossy(chunk))
+                .build()
+                .map_err(|err| ParseError(err.to_string()))?;
+            tags.push(tag);
+        }
+
+        Ok(tags)
     }
 
     /// Get an existing sums file if it exists.
     pub async fn get_existing_sums(&self) -> Result<Option<SumsFile>> {
+        if self.storage == SumsStorage::Tags {
+            return match self.get_tags().await {
+                Ok(tags) => self.sums_from_tags(&tags),
+                Err(Error::NotFound(_)) => Ok(None),
+                Err(err) => Err(err),
+            };
+        }
+
+        let (bucket, key) = self.sums_location()?;
+        debug!(bucket, key, "GetObject for existing sums");
         match self
             .client
             .get_object()
-            .bucket(&self.bucket)
-            .key(SumsFile::format_sums_file(&self.key))
+            .bucket(bucket)
+            .key(&key)
             .send()
             .await
         {
             Ok(sums) => {
+                if let Some(e_tag) = sums.e_tag() {
+                    self.record_state(SumsObjectState::ETag(e_tag.to_string()))
+                        .await;
+                }
+
                 let data = sums.body.collect().await?.to_vec();
-                let sums = SumsFile::read_from_slice(data.as_slice()).await?;
+                let sums = self.format.from_slice(data.as_slice())?;
                 Ok(Some(sums))
             }
             Err(err) if matches!(err.as_service_error(), Some(GetObjectError::NoSuchKey(_))) => {
+                debug!(bucket, key, "no existing sums");
+                self.record_state(SumsObjectState::Missing).await;
                 Ok(None)
             }
             Err(err) => Err(err.into()),
         }
     }
 
+    /// Record the state of the sums object the first time that it is read, so that a later
+    /// write fails if the sums object was modified at any point after that.
+    async fn record_state(&self, state: SumsObjectState) {
+        let mut current = self.sums_object_state.lock().await;
+        if matches!(*current, SumsObjectState::Unknown) {
+            *current = state;
+        }
+    }
+
     /// Get the `GetObjectAttributes` output for the target file. This caches the result in
     /// memory so that subsequent calls do not repeat the query.
     pub async fn get_object_attributes(&mut self) -> Option<&GetObjectAttributesOutput> {
@@ -149,11 +443,18 @@ impl S3 {
             return Some(attributes);
         }
 
+        debug!(
+            bucket = %self.bucket,
+            key = %self.key,
+            version_id = ?self.version_id,
+            "GetObjectAttributes"
+        );
         let attributes = self
             .client
             .get_object_attributes()
             .bucket(&self.bucket)
             .key(SumsFile::format_target_file(&self.key))
+            .set_version_id(self.version_id.clone())
             .object_attributes(ObjectAttributes::Etag)
             .object_attributes(ObjectAttributes::Checksum)
             .object_attributes(ObjectAttributes::ObjectSize)
@@ -164,6 +465,12 @@ impl S3 {
         match attributes {
             Ok(attributes) => Some(self.get_object_attributes.insert(attributes)),
             Err(ref err) => {
+                warn!(
+                    bucket = %self.bucket,
+                    key = %self.key,
+                    error = %err,
+                    "GetObjectAttributes failed, falling back to HeadObject"
+                );
                 self.api_errors.insert(ApiError::from(err));
                 None
             }
@@ -177,11 +484,19 @@ impl S3 {
             return Ok(&self.head_object[&part_number]);
         }
 
+        debug!(
+            bucket = %self.bucket,
+            key = %self.key,
+            version_id = ?self.version_id,
+            part_number,
+            "HeadObject"
+        );
         let head_object = self
             .client
             .head_object()
             .bucket(&self.bucket)
             .key(SumsFile::format_target_file(&self.key))
+            .set_version_id(self.version_id.clone())
             .set_part_number(part_number.map(i32::try_from).transpose()?)
             .checksum_mode(ChecksumMode::Enabled)
             .send()
@@ -217,8 +532,18 @@ impl S3 {
         }
     }
 
-    /// Get the AWS checksum value from `HeadObject`.
+    /// Get the AWS checksum value, preferring the `Checksum` from `GetObjectAttributes` and
+    /// falling back to `HeadObject`.
     pub async fn aws_sums_from_ctx(&mut self, ctx: &StandardCtx) -> Result<Option<String>> {
+        let sum = self
+            .get_object_attributes()
+            .await
+            .and_then(|attributes| attributes.checksum())
+            .and_then(|checksum| Self::aws_sums_from_checksum(ctx, checksum));
+        if sum.is_some() {
+            return Ok(sum);
+        }
+
         let head = self.head_object(None).await?;
 
         let sum = match ctx {
@@ -236,6 +561,53 @@ impl S3 {
         Ok(sum.map(|sum| sum.to_string()))
     }
 
+    /// Get the AWS checksum value from the `GetObjectAttributes` `Checksum`.
+    pub fn aws_sums_from_checksum(ctx: &StandardCtx, checksum: &types::Checksum) -> Option<String> {
+        let sum = match ctx {
+            StandardCtx::SHA1(_) => checksum.checksum_sha1(),
+            StandardCtx::SHA256(_) => checksum.checksum_sha256(),
+            StandardCtx::CRC32(_, _) => checksum.checksum_crc32(),
+            StandardCtx::CRC32C(_, _) => checksum.checksum_crc32_c(),
+            StandardCtx::CRC64NVME(_, _) => checksum.checksum_crc64_nvme(),
+            // The `ETag` is not part of the `Checksum`.
+            _ => None,
+        };
+
+        sum.map(|sum| sum.to_string())
+    }
+
+    /// Get the total part count and checksum type of additional checksums from
+    /// `GetObjectAttributes`. Composite checksums are not always suffixed with the part count,
+    /// so this uses the total part count of the object parts instead.
+    pub async fn aws_parts_and_type_from_attributes(
+        &mut self,
+    ) -> Result<Option<(Option<u64>, ChecksumType)>> {
+        let Some(attributes) = self.get_object_attributes().await else {
+            return Ok(None);
+        };
+        let Some(checksum_type) = attributes
+            .checksum()
+            .and_then(|checksum| checksum.checksum_type())
+            .cloned()
+        else {
+            return Ok(None);
+        };
+
+        let total_parts = attributes
+            .object_parts()
+            .and_then(|parts| parts.total_parts_count())
+            .map(u64::try_from)
+            .transpose()?;
+
+        match (checksum_type, total_parts) {
+            (ChecksumType::Composite, None) => Ok(None),
+            (ChecksumType::Composite, total_parts) => {
+                Ok(Some((total_parts, ChecksumType::Composite)))
+            }
+            (checksum_type, _) => Ok(Some((None, checksum_type))),
+        }
+    }
+
     /// Get the AWS checksum part from `ObjectPart`.
     pub fn aws_parts_from_ctx(ctx: &StandardCtx, part: &ObjectPart) -> Option<String> {
         let sum = match ctx {
@@ -316,6 +688,17 @@ impl S3 {
             return Ok(());
         };
 
+        // An `ETag` without a part count comes from a single upload, so it is the plain MD5 of
+        // the object rather than an AWS composite checksum.
+        if !Self::is_additional_checksum(&ctx) && !sum.trim_matches('"').contains('-') {
+            let sum = Self::decode_sum(&ctx, sum)?;
+            let ctx = Ctx::Regular(ctx);
+            let checksum = Checksum::new(ctx.digest_to_string(&sum));
+            sums_file.add_checksum(ctx, checksum);
+
+            return Ok(());
+        }
+
         // Get the file size, total part count and checksum type from the head.
         let file_size = self
             .head_object(None)
@@ -323,7 +706,15 @@ impl S3 {
             .content_length()
             .map(u64::try_from)
             .transpose()?;
-        let (total_parts, checksum_type) = Self::parse_parts_and_type(sum.as_str())?;
+        let attributes_parts_and_type = if Self::is_additional_checksum(&ctx) {
+            self.aws_parts_and_type_from_attributes().await?
+        } else {
+            None
+        };
+        let (total_parts, checksum_type) = match attributes_parts_and_type {
+            Some(parts_and_type) => parts_and_type,
+            None => Self::parse_parts_and_type(sum.as_str())?,
+        };
 
         // Determine the parts if they exist.
         let parts = self.aws_parts_from_attributes().await?;
@@ -369,7 +760,8 @@ impl S3 {
     ///
     /// 1. `GetObjectAttributes` contains `Checksum`s, `ETag`s and parts:
     ///     - For `ETag`s, there are no parts, however there is a `TotalPartsCount`
-    ///     - For the other checksums, parts are included in the response.
+    ///     - For the other checksums, parts are included in the response, and the `ChecksumType`
+    ///       determines whether a checksum is composite. These are preferred over `HeadObject`.
     ///     - If other checksums are present, then the `ETag` will have the same part sizes.
     /// 2. `HeadObject` contains the above information, but no part checksums:
     ///     - For `ETag`s, the `ContentLength` header determines the part size of a part if
@@ -409,6 +801,46 @@ impl S3 {
         Ok(sums_file)
     }
 
+    /// Get information about the object and the checksums that are already available from its
+    /// metadata, without reading the object. The part size is inferred from the part sizes of
+    /// the multipart checksums.
+    pub async fn object_info(&mut self) -> Result<ObjectInfo> {
+        let sums_file = self.sums_from_metadata().await?;
+
+        let head = self.head_object(None).await?;
+        let storage_class = head
+            .storage_class()
+            .unwrap_or(&StorageClass::Standard)
+            .as_str()
+            .to_string();
+        let e_tag = head
+            .e_tag()
+            .map(|e_tag| e_tag.trim_matches('"').to_string());
+
+        let part_count = match &e_tag {
+            Some(e_tag) => Self::parse_parts_and_type(e_tag)?.0,
+            None => None,
+        };
+        let part_size = sums_file.checksums.keys().find_map(|ctx| match ctx {
+            Ctx::AWSEtag(ctx) => match ctx.get_part_sizes()[..] {
+                [part_size] => Some(part_size),
+                _ => None,
+            },
+            Ctx::Regular(_) => None,
+        });
+
+        Ok(ObjectInfo {
+            location: self.location(),
+            size: sums_file.size,
+            storage_class,
+            e_tag,
+            multipart: part_count.is_some(),
+            part_count,
+            part_size,
+            checksums: ChecksumStats::from(sums_file.checksums),
+        })
+    }
+
     /// Parse the number of parts and the checksum type from a string.
     pub fn parse_parts_and_type(s: &str) -> Result<(Option<u64>, ChecksumType)> {
         let split = s.trim_matches('\"').rsplit_once("-");
@@ -427,13 +859,24 @@ impl S3 {
         (self.bucket, self.key)
     }
 
-    /// Get the object and convert it into an `AsyncRead`.
-    pub async fn object_reader(&self) -> Result<impl AsyncRead> {
+    /// Get the object and convert it into an `AsyncRead`, optionally only reading the
+    /// specified range.
+    pub async fn object_reader(&self, range: Option<MultiPartOptions>) -> Result<impl AsyncRead> {
+        let range = range.and_then(|range| range.format_range());
+        debug!(
+            bucket = %self.bucket,
+            key = %self.key,
+            version_id = ?self.version_id,
+            ?range,
+            "GetObject"
+        );
         Ok(Box::new(
             self.client
                 .get_object()
                 .bucket(&self.bucket)
                 .key(SumsFile::format_target_file(&self.key))
+                .set_version_id(self.version_id.clone())
+                .set_range(range)
                 .send()
                 .await?
                 .body
@@ -441,6 +884,53 @@ impl S3 {
         ))
     }
 
+    /// Read the whole object through the download cache. The cache entry is keyed by the
+    /// `ETag`, and the object is only downloaded if it still has that `ETag`, so a cached entry
+    /// always has the current contents of the object. The object is written to the cache while
+    /// it is read, and a previous download that stopped early is resumed with a range request.
+    async fn cached_reader(
+        &mut self,
+        cache: &DownloadCache,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        let head = self.head_object(None).await?;
+        let (Some(e_tag), Some(size)) = (head.e_tag().map(str::to_string), head.content_length())
+        else {
+            return Ok(Box::new(self.object_reader(None).await?));
+        };
+
+        let key = format!("{}/{}", self.location(), e_tag.trim_matches('"'));
+        let size = u64::try_from(size)?;
+        if let Some(file) = cache.get(&key, size).await? {
+            return Ok(Box::new(file));
+        }
+        let Some(partial) = cache.partial(&key, size).await? else {
+            return Ok(Box::new(self.object_reader(None).await?));
+        };
+
+        let range = (partial.offset() > 0).then(|| format!("bytes={}-", partial.offset()));
+        debug!(
+            bucket = %self.bucket,
+            key = %self.key,
+            version_id = ?self.version_id,
+            e_tag,
+            range,
+            "GetObject"
+        );
+        let body = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(SumsFile::format_target_file(&self.key))
+            .set_version_id(self.version_id.clone())
+            .if_match(e_tag)
+            .set_range(range)
+            .send()
+            .await?
+            .body
+            .into_async_read();
+        partial.reader(body).await
+    }
+
     /// Get the object file size.
     async fn size(&mut self) -> Result<Option<u64>> {
         Ok(self
@@ -451,19 +941,117 @@ impl S3 {
             .transpose()?)
     }
 
-    /// Write the sums file to the configured location using `PutObject`.
-    pub async fn put_sums(&self, sums_file: &SumsFile) -> Result<()> {
-        let key = SumsFile::format_sums_file(&self.key);
+    /// Write the sums file into the object tags using `PutObjectTagging`.
+    pub async fn put_sums_tags(&self, sums_file: &SumsFile) -> Result<()> {
+        let tags = self.sums_to_tags(sums_file, self.get_tags().await?)?;
+        let tagging = Tagging::builder()
+            .set_tag_set(Some(tags))
+            .build()
+            .map_err(|err| ParseError(err.to_string()))?;
+
+        debug!(bucket = %self.bucket, key = %self.key, "PutObjectTagging");
         self.client
-            .put_object()
-            .checksum_algorithm(ChecksumAlgorithm::Crc64Nvme)
+            .put_object_tagging()
             .bucket(&self.bucket)
-            .key(&key)
-            .body(ByteStream::from(sums_file.to_json_string()?.into_bytes()))
+            .key(SumsFile::format_target_file(&self.key))
+            .set_version_id(self.version_id.clone())
+            .tagging(tagging)
             .send()
             .await?;
         Ok(())
     }
+
+    /// Write the sums file to the configured location using `PutObject`, or into the
+    /// object tags. If the sums object was read before, the write is conditional on it not
+    /// having been modified since, so that a concurrent writer is not silently overwritten.
+    /// When it was modified, the sums object is read again and merged with the sums file
+    /// before retrying with an exponential backoff, up to `MAX_CONDITIONAL_WRITE_ATTEMPTS` times.
+    pub async fn put_sums(&self, sums_file: &SumsFile) -> Result<()> {
+        if self.storage == SumsStorage::Tags {
+            return self.put_sums_tags(sums_file).await;
+        }
+
+        let mut sums_file = sums_file.clone();
+        for attempt in 1..=MAX_CONDITIONAL_WRITE_ATTEMPTS {
+            if self.put_sums_object(&sums_file).await? {
+                return Ok(());
+            }
+            if attempt == MAX_CONDITIONAL_WRITE_ATTEMPTS {
+                break;
+            }
+
+            let (bucket, key) = self.sums_location()?;
+            warn!(
+                bucket,
+                key, attempt, "sums object was modified by another writer, merging and retrying"
+            );
+            tokio::time::sleep(CONDITIONAL_WRITE_BACKOFF * 2u32.pow(attempt as u32 - 1)).await;
+
+            // Keep the checksums of the other writer that were not generated here. The rest of
+            // the sums file, such as its metadata, is kept from this write.
+            *self.sums_object_state.lock().await = SumsObjectState::Unknown;
+            if let Some(current) = self.get_existing_sums().await? {
+                let metadata = sums_file.metadata();
+                sums_file = current.merge(sums_file)?.with_metadata(metadata);
+            }
+        }
+
+        let (bucket, key) = self.sums_location()?;
+        Err(SumsFileError(format!(
+            "`{}` was modified by another writer on every attempt to write it after {} attempts",
+            Provider::format_s3(bucket, &key),
+            MAX_CONDITIONAL_WRITE_ATTEMPTS
+        )))
+    }
+
+    /// Write the sums object if it has not been modified since it was read, returning whether
+    /// it was written.
+    async fn put_sums_object(&self, sums_file: &SumsFile) -> Result<bool> {
+        let (bucket, key) = self.sums_location()?;
+        let state = self.sums_object_state.lock().await.clone();
+        let (if_match, if_none_match) = match state {
+            SumsObjectState::Unknown => (None, None),
+            SumsObjectState::Missing => (None, Some("*".to_string())),
+            SumsObjectState::ETag(e_tag) => (Some(e_tag), None),
+        };
+
+        debug!(bucket, key, ?if_match, ?if_none_match, "PutObject for sums");
+        let result = self
+            .client
+            .put_object()
+            .checksum_algorithm(ChecksumAlgorithm::Crc64Nvme)
+            .bucket(bucket)
+            .key(&key)
+            .set_if_match(if_match)
+            .set_if_none_match(if_none_match)
+            .body(ByteStream::from(self.format.to_vec(sums_file)?))
+            .send()
+            .await;
+
+        match result {
+            Ok(output) => {
+                *self.sums_object_state.lock().await = match output.e_tag() {
+                    Some(e_tag) => SumsObjectState::ETag(e_tag.to_string()),
+                    None => SumsObjectState::Unknown,
+                };
+                Ok(true)
+            }
+            Err(err) if Self::is_precondition_failed(&err) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Whether a conditional write failed because the object was modified concurrently.
+    fn is_precondition_failed(err: &SdkError<PutObjectError, HttpResponse>) -> bool {
+        let status = err
+            .raw_response()
+            .map(|response| response.status().as_u16());
+        matches!(status, Some(409) | Some(412))
+            || matches!(
+                err.code(),
+                Some("PreconditionFailed") | Some("ConditionalRequestConflict")
+            )
+    }
 }
 
 #[async_trait::async_trait]
@@ -477,8 +1065,20 @@ impl ObjectSums for S3 {
         }
     }
 
-    async fn reader(&mut self) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
-        Ok(Box::new(self.object_reader().await?))
+    async fn metadata_sums(&mut self) -> Result<Option<SumsFile>> {
+        Ok(Some(self.sums_from_metadata().await?))
+    }
+
+    async fn reader(
+        &mut self,
+        range: Option<MultiPartOptions>,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        // Only whole objects are cached.
+        if let (Some(cache), None) = (self.download_cache.clone(), &range) {
+            return self.cached_reader(&cache).await;
+        }
+
+        Ok(Box::new(self.object_reader(range).await?))
     }
 
     async fn file_size(&mut self) -> Result<Option<u64>> {
@@ -490,7 +1090,19 @@ impl ObjectSums for S3 {
     }
 
     fn location(&self) -> String {
-        Provider::format_s3(&self.bucket, &self.key)
+        Provider::format_s3_version(&self.bucket, &self.key, self.version_id.as_deref())
+    }
+
+    async fn file_metadata(&mut self) -> Result<FileMetadata> {
+        let last_modified = self
+            .head_object(None)
+            .await?
+            .last_modified()
+            .map(|last_modified| SystemTime::try_from(*last_modified))
+            .transpose()
+            .map_err(|err| ParseError(format!("invalid last modified time: {}", err)))?;
+
+        Ok(FileMetadata::new(last_modified, None))
     }
 
     fn api_errors(&self) -> HashSet<ApiError> {
@@ -504,10 +1116,19 @@ pub(crate) mod test {
     use crate::checksum::standard::test::EXPECTED_MD5_SUM;
     use crate::task::generate::test::generate_for;
     use crate::test::{TEST_FILE_NAME, TEST_FILE_SIZE};
+    use aws_sdk_s3::operation::get_object::GetObjectOutput;
+    use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingOutput;
     use aws_sdk_s3::operation::head_object::builders::HeadObjectOutputBuilder;
+    use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Output;
+    use aws_sdk_s3::operation::put_object::PutObjectOutput;
+    use aws_sdk_s3::operation::put_object_tagging::PutObjectTaggingOutput;
     use aws_sdk_s3::types;
     use aws_sdk_s3::types::GetObjectAttributesParts;
-    use aws_smithy_mocks_experimental::{mock, mock_client, Rule, RuleMode};
+    use aws_smithy_mocks::{mock, mock_client, Rule, RuleMode};
+    use aws_smithy_runtime_api::http::StatusCode;
+    use aws_smithy_types::body::SdkBody;
+    use std::collections::BTreeMap;
+    use std::result;
 
     const EXPECTED_SHA256_SUM: &str = "Kf+9U8vkMXmrL6YtvZWMDsMLNAq1DOfHheinpLR3Hjk="; // pragma: allowlist secret
 
@@ -592,6 +1213,491 @@ pub(crate) mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    pub async fn test_object_info() -> anyhow::Result<()> {
+        let mut s3 = S3Builder::default()
+            .with_client(Arc::new(mock_multi_part_with_sha256()))
+            .with_bucket("bucket".to_string())
+            .with_key("key".to_string())
+            .build()?;
+
+        let info = s3.object_info().await?;
+        assert_eq!(info.location, "s3://bucket/key");
+        assert_eq!(info.size, Some(TEST_FILE_SIZE));
+        assert_eq!(info.storage_class, "STANDARD");
+        assert_eq!(info.e_tag.as_deref(), Some(EXPECTED_MD5_SUM_5));
+        assert!(info.multipart);
+        assert_eq!(info.part_count, Some(5));
+        assert_eq!(info.part_size, Some(214748365));
+
+        let mut checksums: Vec<_> = serde_json::to_value(&info.checksums)?
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|pair| pair["kind"].as_str().map(str::to_string))
+            .collect();
+        checksums.sort();
+        assert_eq!(checksums, ["md5-aws-214748365b", "sha256-aws-214748365b"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_multi_part_with_sha256_from_attributes() -> anyhow::Result<()> {
+        // The composite checksum is only in `GetObjectAttributes` and has no part count suffix,
+        // so the checksum type and total part count are used.
+        let sha256 = EXPECTED_SHA256_SUM_5.trim_end_matches("-5");
+        let mut s3 = S3Builder::default()
+            .with_client(Arc::new(mock_multi_part_with_sha256_from(None, sha256)))
+            .with_bucket("bucket".to_string())
+            .with_key("key".to_string())
+            .build()?;
+
+        let sums = s3.sums_from_metadata().await?;
+        assert!(sums
+            .checksums
+            .keys()
+            .any(|ctx| matches!(ctx, Ctx::AWSEtag(_)) && !ctx.is_md5()));
+
+        let expected = generate_for("key", vec!["md5-aws-5", "sha256-aws-5"], true, false)
+            .await?
+            .split();
+
+        assert_all_same(sums.split(), expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_multi_part_full_object_checksum() -> anyhow::Result<()> {
+        // Multipart uploads can have a full-object checksum over the whole object rather than a
+        // composite checksum of the part checksums. This is recorded as a regular checksum.
+        let mut s3 = S3Builder::default()
+            .with_client(Arc::new(mock_multi_part_full_object()))
+            .with_bucket("bucket".to_string())
+            .with_key("key".to_string())
+            .build()?;
+
+        let sums = s3.sums_from_metadata().await?;
+        assert_eq!(
+            sums.checksums.get(&"crc32c".parse()?),
+            Some(&Checksum::new("00000001".to_string()))
+        );
+        assert!(sums
+            .checksums
+            .keys()
+            .all(|ctx| !matches!(ctx, Ctx::AWSEtag(_)) || ctx.is_md5()));
+
+        // A composite checksum of the same object is not comparable to the full-object checksum.
+        let mut composite = SumsFile::default().with_size(Some(TEST_FILE_SIZE));
+        let mut ctx: Ctx = "crc32c-aws-5".parse()?;
+        ctx.set_file_size(Some(TEST_FILE_SIZE));
+        composite.add_checksum(ctx, Checksum::new("00000001-214748365b".to_string()));
+        assert!(sums.comparable(&composite).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_sums_tags() -> anyhow::Result<()> {
+        let user_tag = || Tag::builder().key("user").value("value").build().unwrap();
+        let sums = tags_sums_file(30)?;
+        let tags = tags_s3(mock_client!(aws_sdk_s3, &[])).sums_to_tags(&sums, vec![user_tag()])?;
+
+        let tags_output = tags.clone();
+        let get_object_tagging = mock!(Client::get_object_tagging)
+            .match_requests(|req| req.bucket() == Some("bucket") && req.key() == Some("key"))
+            .then_output(move || {
+                GetObjectTaggingOutput::builder()
+                    .set_tag_set(Some(tags_output.clone()))
+                    .build()
+                    .unwrap()
+            });
+        let s3 = tags_s3(mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[&get_object_tagging]
+        ));
+
+        assert_eq!(tags[0], user_tag());
+        assert!(tags.len() > 2);
+        assert!(tags
+            .iter()
+            .all(|tag| tag.value().len() <= MAX_TAG_VALUE_LENGTH));
+        assert_eq!(s3.get_existing_sums().await?, Some(sums));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_put_sums_tags() -> anyhow::Result<()> {
+        let get_object_tagging = mock!(Client::get_object_tagging)
+            .match_requests(|req| req.bucket() == Some("bucket") && req.key() == Some("key"))
+            .then_output(|| {
+                GetObjectTaggingOutput::builder()
+                    .tag_set(Tag::builder().key("user").value("value").build().unwrap())
+                    .tag_set(
+                        Tag::builder()
+                            .key(format!("{}{}", SUMS_TAG_PREFIX, 1))
+                            .value("stale")
+                            .build()
+                            .unwrap(),
+                    )
+                    .build()
+                    .unwrap()
+            });
+        let put_object_tagging = mock!(Client::put_object_tagging)
+            .match_requests(|req| {
+                let tags = req
+                    .tagging()
+                    .map(|tagging| tagging.tag_set())
+                    .unwrap_or_default();
+                req.bucket() == Some("bucket")
+                    && req.key() == Some("key")
+                    && tags.len() == 2
+                    && tags[0].key() == "user"
+                    && tags[1].key() == format!("{}{}", SUMS_TAG_PREFIX, 0)
+            })
+            .then_output(|| PutObjectTaggingOutput::builder().build());
+        let s3 = tags_s3(mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[&get_object_tagging, &put_object_tagging]
+        ));
+
+        s3.put_sums(&tags_sums_file(1)?).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_put_sums_conditional() -> anyhow::Result<()> {
+        let sums = tags_sums_file(1)?;
+        let is_sums = |bucket: Option<&str>, key: Option<&str>| {
+            bucket == Some("bucket") && key == Some("key.sums")
+        };
+
+        // A sums object that did not exist must still not exist when writing.
+        let get_missing = mock!(Client::get_object)
+            .match_requests(move |req| is_sums(req.bucket(), req.key()))
+            .then_http_response(|| error_response(404, "NoSuchKey"));
+        let put_missing = mock!(Client::put_object)
+            .match_requests(move |req| {
+                is_sums(req.bucket(), req.key()) && req.if_none_match() == Some("*")
+            })
+            .then_output(|| PutObjectOutput::builder().e_tag("\"new\"").build());
+        // Later writes are conditional on the written sums object.
+        let put_existing = mock!(Client::put_object)
+            .match_requests(move |req| {
+                is_sums(req.bucket(), req.key()) && req.if_match() == Some("\"new\"")
+            })
+            .then_output(|| PutObjectOutput::builder().build());
+
+        let s3 = sums_s3(mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[&get_missing, &put_missing, &put_existing]
+        ));
+        assert_eq!(s3.get_existing_sums().await?, None);
+        s3.put_sums(&sums).await?;
+        s3.put_sums(&sums).await?;
+
+        // A sums object modified by another writer after it was read is read again and merged.
+        let get_sums = |sums: &SumsFile, e_tag: &'static str| -> anyhow::Result<Rule> {
+            let data = SumsFormat::Json.to_vec(sums)?;
+            Ok(mock!(Client::get_object)
+                .match_requests(move |req| is_sums(req.bucket(), req.key()))
+                .then_output(move || {
+                    GetObjectOutput::builder()
+                        .e_tag(e_tag)
+                        .body(ByteStream::from(data.clone()))
+                        .build()
+                }))
+        };
+        let put_modified = |e_tag: &'static str| {
+            mock!(Client::put_object)
+                .match_requests(move |req| {
+                    is_sums(req.bucket(), req.key()) && req.if_match() == Some(e_tag)
+                })
+                .then_http_response(|| error_response(412, "PreconditionFailed"))
+        };
+        let mut concurrent = sums.clone();
+        concurrent
+            .checksums
+            .insert("sha1".parse()?, Checksum::new("c".to_string()));
+        let written = SumsFormat::Json.to_vec(&concurrent)?;
+        let put_merged = mock!(Client::put_object)
+            .match_requests(move |req| {
+                is_sums(req.bucket(), req.key())
+                    && req.if_match() == Some("\"concurrent\"")
+                    && req.body().bytes() == Some(written.as_slice())
+            })
+            .then_output(|| PutObjectOutput::builder().build());
+
+        let s3 = sums_s3(mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[
+                &get_sums(&sums, "\"old\"")?,
+                &put_modified("\"old\""),
+                &get_sums(&concurrent, "\"concurrent\"")?,
+                &put_merged
+            ]
+        ));
+        assert_eq!(s3.get_existing_sums().await?, Some(sums.clone()));
+        s3.put_sums(&sums).await?;
+
+        // Writing fails if the sums object keeps being modified, without reading it again after
+        // the last attempt.
+        let mut rules = vec![get_sums(&sums, "\"old\"")?];
+        for _ in 1..MAX_CONDITIONAL_WRITE_ATTEMPTS {
+            rules.push(put_modified("\"old\""));
+            rules.push(get_sums(&sums, "\"old\"")?);
+        }
+        let last_put = put_modified("\"old\"");
+        rules.push(last_put.clone());
+        let s3 = sums_s3(mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            rules.as_slice()
+        ));
+        s3.get_existing_sums().await?;
+        let err = s3.put_sums(&sums).await.unwrap_err();
+        assert!(matches!(err, SumsFileError(_)));
+        assert!(err.to_string().contains("modified by another writer"));
+        assert_eq!(last_put.num_calls(), 1);
+
+        Ok(())
+    }
+
+    /// An S3 error response with the status and error code.
+    pub(crate) fn error_response(status: u16, code: &str) -> HttpResponse {
+        HttpResponse::new(
+            StatusCode::try_from(status).unwrap(),
+            SdkBody::from(format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>{code}</Code></Error>"
+            )),
+        )
+    }
+
+    fn sums_s3(client: Client) -> S3 {
+        S3Builder::default()
+            .with_client(Arc::new(client))
+            .with_bucket("bucket".to_string())
+            .with_key("key".to_string())
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn test_download_cache() -> anyhow::Result<()> {
+        use crate::io::sums::cache::test::interrupt;
+        use tokio::io::AsyncReadExt;
+
+        let tmp = tempfile::tempdir()?;
+        let head_object = |e_tag: &'static str| {
+            mock!(Client::head_object).then_output(move || {
+                HeadObjectOutput::builder()
+                    .e_tag(e_tag)
+                    .content_length(4)
+                    .build()
+            })
+        };
+        let get_object = |e_tag: &'static str, data: &'static [u8]| {
+            mock!(Client::get_object)
+                .match_requests(move |req| req.if_match() == Some(e_tag))
+                .then_output(move || {
+                    GetObjectOutput::builder()
+                        .body(ByteStream::from_static(data))
+                        .build()
+                })
+        };
+        let cache = DownloadCache::new(tmp.path().to_path_buf());
+        let read = |client: Client| {
+            let cache = cache.clone();
+            async move {
+                let mut data = vec![];
+                sums_s3(client)
+                    .set_download_cache(Some(cache))
+                    .reader(None)
+                    .await?
+                    .read_to_end(&mut data)
+                    .await?;
+                Ok::<_, anyhow::Error>(data)
+            }
+        };
+
+        // The object is only downloaded the first time it is read.
+        let client = mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[&head_object("\"a\""), &get_object("\"a\"", b"aaaa")]
+        );
+        assert_eq!(read(client).await?, b"aaaa");
+        let client = mock_client!(aws_sdk_s3, RuleMode::Sequential, &[&head_object("\"a\"")]);
+        assert_eq!(read(client).await?, b"aaaa");
+
+        // A changed object is downloaded again.
+        let client = mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[&head_object("\"b\""), &get_object("\"b\"", b"bbbb")]
+        );
+        assert_eq!(read(client).await?, b"bbbb");
+
+        // A download that stopped early is resumed from where it stopped.
+        interrupt(&cache, "s3://bucket/key/c", b"abcd", 2).await?;
+        let get_rest = mock!(Client::get_object)
+            .match_requests(|req| {
+                req.if_match() == Some("\"c\"") && req.range() == Some("bytes=2-")
+            })
+            .then_output(|| {
+                GetObjectOutput::builder()
+                    .body(ByteStream::from_static(b"cd"))
+                    .build()
+            });
+        let client = mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[&head_object("\"c\""), &get_rest]
+        );
+        assert_eq!(read(client).await?, b"abcd");
+        let client = mock_client!(aws_sdk_s3, RuleMode::Sequential, &[&head_object("\"c\"")]);
+        assert_eq!(read(client).await?, b"abcd");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_version_id() -> anyhow::Result<()> {
+        let is_version = |bucket: Option<&str>, key: Option<&str>, version_id: Option<&str>| {
+            bucket == Some("bucket") && key == Some("key") && version_id == Some("version")
+        };
+        let head_object = mock!(Client::head_object)
+            .match_requests(move |req| is_version(req.bucket(), req.key(), req.version_id()))
+            .then_output(|| HeadObjectOutput::builder().content_length(1).build());
+        let put_object = mock!(Client::put_object)
+            .match_requests(|req| {
+                req.bucket() == Some("bucket") && req.key() == Some("key.version.sums")
+            })
+            .then_output(|| PutObjectOutput::builder().build());
+
+        let mut s3 = S3Builder::default()
+            .with_client(Arc::new(mock_client!(
+                aws_sdk_s3,
+                RuleMode::Sequential,
+                &[&head_object, &put_object]
+            )))
+            .with_bucket("bucket".to_string())
+            .with_key("key".to_string())
+            .set_version_id(Some("version".to_string()))
+            .build()?;
+
+        assert_eq!(s3.location(), "s3://bucket/key?versionId=version");
+        assert_eq!(s3.file_size().await?, Some(1));
+        s3.put_sums(&tags_sums_file(1)?).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn test_list_objects() -> anyhow::Result<()> {
+        let client = mock_client!(aws_sdk_s3, RuleMode::Sequential, &mock_list_objects_rules());
+
+        assert_eq!(
+            S3::list_objects(&client, "bucket", "prefix/").await?,
+            vec!["prefix/a", "prefix/b", "prefix/c"]
+        );
+
+        Ok(())
+    }
+
+    /// Mock a listing of `s3://bucket/prefix/` split over two pages, with a sums file and a
+    /// directory marker that should be skipped.
+    pub(crate) fn mock_list_objects_rules() -> Vec<Rule> {
+        let object = |key: &str| types::Object::builder().key(key).build();
+        let first = mock!(Client::list_objects_v2)
+            .match_requests(|req| {
+                req.bucket() == Some("bucket")
+                    && req.prefix() == Some("prefix/")
+                    && req.continuation_token().is_none()
+            })
+            .then_output(move || {
+                ListObjectsV2Output::builder()
+                    .contents(object("prefix/"))
+                    .contents(object("prefix/a"))
+                    .contents(object("prefix/a.sums"))
+                    .contents(object("prefix/b"))
+                    .is_truncated(true)
+                    .next_continuation_token("token")
+                    .build()
+            });
+        let second = mock!(Client::list_objects_v2)
+            .match_requests(|req| {
+                req.bucket() == Some("bucket")
+                    && req.prefix() == Some("prefix/")
+                    && req.continuation_token() == Some("token")
+            })
+            .then_output(move || {
+                ListObjectsV2Output::builder()
+                    .contents(object("prefix/c"))
+                    .is_truncated(false)
+                    .build()
+            });
+
+        vec![first, second]
+    }
+
+    #[test]
+    fn test_sums_tags_too_large() -> anyhow::Result<()> {
+        let s3 = tags_s3(mock_client!(aws_sdk_s3, &[]));
+        let user_tags = (0..MAX_TAGS - 1)
+            .map(|i| {
+                Tag::builder()
+                    .key(format!("user-{}", i))
+                    .value("value")
+                    .build()
+            })
+            .collect::<result::Result<Vec<_>, _>>()?;
+
+        assert_eq!(
+            s3.sums_to_tags(&tags_sums_file(1)?, user_tags.clone())?
+                .len(),
+            MAX_TAGS
+        );
+
+        let err = s3
+            .sums_to_tags(&tags_sums_file(30)?, user_tags)
+            .unwrap_err();
+        assert!(matches!(err, SumsFileError(_)));
+        assert!(err.to_string().contains("too large"));
+
+        Ok(())
+    }
+
+    fn tags_s3(client: Client) -> S3 {
+        S3Builder::default()
+            .with_client(Arc::new(client))
+            .with_bucket("bucket".to_string())
+            .with_key("key".to_string())
+            .with_storage(SumsStorage::Tags)
+            .build()
+            .unwrap()
+    }
+
+    fn tags_sums_file(checksum_length: usize) -> anyhow::Result<SumsFile> {
+        Ok(SumsFile::new(
+            Some(1),
+            BTreeMap::from_iter(vec![
+                ("md5".parse()?, Checksum::new("a".repeat(checksum_length))),
+                (
+                    "sha256".parse()?,
+                    Checksum::new("b".repeat(checksum_length * 10)),
+                ),
+            ]),
+        ))
+    }
+
     fn assert_all_same(result: Vec<SumsFile>, expected: Vec<SumsFile>) {
         println!("{}", serde_json::to_string_pretty(&result).unwrap());
         println!("{}", serde_json::to_string_pretty(&expected).unwrap());
@@ -731,15 +1837,67 @@ pub(crate) mod test {
         )
     }
 
-    fn mock_multi_part_with_sha256() -> Client {
+    fn mock_multi_part_full_object() -> Client {
         let get_object_attributes = mock!(Client::get_object_attributes)
             .match_requests(|req| req.bucket() == Some("bucket") && req.key() == Some("key"))
             .then_output(|| {
+                let part = |part_number, size| {
+                    ObjectPart::builder()
+                        .part_number(part_number)
+                        .size(size)
+                        .build()
+                };
                 GetObjectAttributesOutput::builder()
                     .e_tag(EXPECTED_MD5_SUM_5)
                     .checksum(
                         types::Checksum::builder()
-                            .checksum_sha256(EXPECTED_SHA256_SUM_5)
+                            .checksum_crc32_c("AAAAAQ==")
+                            .checksum_type(ChecksumType::FullObject)
+                            .build(),
+                    )
+                    .object_parts(
+                        GetObjectAttributesParts::builder()
+                            .total_parts_count(5)
+                            .parts(part(1, 214748365))
+                            .parts(part(2, 214748365))
+                            .parts(part(3, 214748365))
+                            .parts(part(4, 214748365))
+                            .parts(part(5, 214748364))
+                            .build(),
+                    )
+                    .object_size(TEST_FILE_SIZE as i64)
+                    .build()
+            });
+
+        mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[
+                &head_object_size_rule(format!("\"{}\"", EXPECTED_MD5_SUM_5), Some(5), None),
+                &get_object_attributes,
+            ]
+        )
+    }
+
+    fn mock_multi_part_with_sha256() -> Client {
+        mock_multi_part_with_sha256_from(
+            Some(EXPECTED_SHA256_SUM_5.to_string()),
+            EXPECTED_SHA256_SUM_5,
+        )
+    }
+
+    fn mock_multi_part_with_sha256_from(
+        head_sha256: Option<String>,
+        attributes_sha256: &'static str,
+    ) -> Client {
+        let get_object_attributes = mock!(Client::get_object_attributes)
+            .match_requests(|req| req.bucket() == Some("bucket") && req.key() == Some("key"))
+            .then_output(move || {
+                GetObjectAttributesOutput::builder()
+                    .e_tag(EXPECTED_MD5_SUM_5)
+                    .checksum(
+                        types::Checksum::builder()
+                            .checksum_sha256(attributes_sha256)
                             .checksum_type(ChecksumType::Composite)
                             .build(),
                     )
@@ -793,11 +1951,7 @@ pub(crate) mod test {
             aws_sdk_s3,
             RuleMode::Sequential,
             &[
-                &head_object_size_rule(
-                    format!("\"{}\"", EXPECTED_MD5_SUM_5),
-                    Some(5),
-                    Some(EXPECTED_SHA256_SUM_5.to_string())
-                ),
+                &head_object_size_rule(format!("\"{}\"", EXPECTED_MD5_SUM_5), Some(5), head_sha256),
                 &get_object_attributes,
             ]
         )
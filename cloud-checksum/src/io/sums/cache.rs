@@ -0,0 +1,320 @@
+//! A local cache of downloaded objects, so that objects which are read repeatedly are only
+//! downloaded once.
+//!
+
+use crate::error::Result;
+use crate::io::sums::tee::TeeReader;
+use futures_util::{stream, StreamExt};
+use sha2::{Digest, Sha256};
+use std::fs::TryLockError;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::task::spawn_blocking;
+use tokio_util::io::{ReaderStream, StreamReader};
+use tracing::debug;
+
+/// The ending of cache entries that are still being downloaded.
+const PARTIAL_ENDING: &str = "part";
+
+/// A directory of downloaded objects. Entries are keyed by a string which identifies the
+/// contents of the object, e.g. its location and `ETag`, so that stale entries are never read.
+#[derive(Debug, Clone)]
+pub struct DownloadCache {
+    dir: PathBuf,
+    max_size: Option<u64>,
+}
+
+impl DownloadCache {
+    /// Create a cache in the directory, which is created if it does not exist.
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            max_size: None,
+        }
+    }
+
+    /// Set the maximum total size of the cache in bytes. The least recently used entries are
+    /// evicted when a new entry makes the cache larger than this.
+    pub fn set_max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Get the path of the entry for the key.
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(hex::encode(Sha256::digest(key.as_bytes())))
+    }
+
+    /// Open the cached entry for the key if it exists and has the expected size. Reading an
+    /// entry marks it as recently used.
+    pub async fn get(&self, key: &str, size: u64) -> Result<Option<File>> {
+        let path = self.path(key);
+        let file = match File::open(&path).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if file.metadata().await?.len() != size {
+            debug!(key, "ignoring cached entry with a different size");
+            return Ok(None);
+        }
+
+        let file = file.into_std().await;
+        let file = spawn_blocking(move || {
+            file.set_modified(SystemTime::now())?;
+            Ok::<_, io::Error>(file)
+        })
+        .await??;
+        debug!(key, path = %path.display(), "using cached download");
+        Ok(Some(File::from_std(file)))
+    }
+
+    /// Start downloading the entry for the key, or resume a previous download of it that
+    /// stopped early. The partial entry is locked while it is downloaded, so this returns
+    /// `None` if another download of the entry is in progress.
+    pub async fn partial(&self, key: &str, size: u64) -> Result<Option<PartialEntry>> {
+        fs::create_dir_all(&self.dir).await?;
+
+        let path = self.path(key);
+        let partial = path.with_extension(PARTIAL_ENDING);
+        let opened = spawn_blocking({
+            let partial = partial.clone();
+            move || {
+                let file = std::fs::File::options()
+                    .create(true)
+                    .append(true)
+                    .open(&partial)?;
+                match file.try_lock() {
+                    Ok(()) => {}
+                    Err(TryLockError::WouldBlock) => return Ok(None),
+                    Err(TryLockError::Error(err)) => return Err(err),
+                }
+
+                // A partial entry that is not smaller than the object cannot be resumed.
+                let mut offset = file.metadata()?.len();
+                if offset >= size {
+                    file.set_len(0)?;
+                    offset = 0;
+                }
+                Ok::<_, io::Error>(Some((file, offset)))
+            }
+        })
+        .await??;
+        let Some((file, offset)) = opened else {
+            debug!(key, "another download of the cached entry is in progress");
+            return Ok(None);
+        };
+
+        if offset > 0 {
+            debug!(key, offset, "resuming download into cache");
+        } else {
+            debug!(key, path = %path.display(), "downloading into cache");
+        }
+
+        Ok(Some(PartialEntry {
+            cache: self.clone(),
+            path,
+            partial,
+            file: File::from_std(file),
+            size,
+            offset,
+        }))
+    }
+
+    /// Remove the least recently used entries until the cache is within the maximum size,
+    /// never removing the entry that was just added.
+    async fn evict(&self, keep: &Path) -> Result<()> {
+        let Some(max_size) = self.max_size else {
+            return Ok(());
+        };
+
+        let mut entries = vec![];
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|ending| ending == PARTIAL_ENDING)
+            {
+                continue;
+            }
+            let metadata = entry.metadata().await?;
+            if metadata.is_file() {
+                entries.push((metadata.modified()?, metadata.len(), path));
+            }
+        }
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= max_size {
+                break;
+            }
+            if path == keep {
+                continue;
+            }
+
+            debug!(path = %path.display(), "evicting cached download");
+            match fs::remove_file(&path).await {
+                Ok(()) => total -= len,
+                // Another process may have evicted the entry already.
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => total -= len,
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A cache entry which is being downloaded, starting at `offset`.
+#[derive(Debug)]
+pub struct PartialEntry {
+    cache: DownloadCache,
+    path: PathBuf,
+    partial: PathBuf,
+    file: File,
+    size: u64,
+    offset: u64,
+}
+
+impl PartialEntry {
+    /// The number of bytes that were already downloaded, which the rest of the object starts
+    /// at.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Read the whole entry, where the bytes that were already downloaded are followed by
+    /// `rest`, which is written to the cache as it is read. This means that the object is
+    /// only read once, and a download that stops early can be resumed. The entry becomes
+    /// visible once all of it has been read, after which the cache is evicted.
+    pub async fn reader(
+        self,
+        rest: impl AsyncRead + Unpin + Send + 'static,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        let downloaded = File::open(&self.partial).await?.take(self.offset);
+        let reader = downloaded.chain(TeeReader::new(rest, self.file));
+
+        let (cache, path, partial, size) = (self.cache, self.path, self.partial, self.size);
+        let complete = stream::once(async move {
+            if fs::metadata(&partial).await?.len() != size {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the download ended before the end of the object",
+                ));
+            }
+            fs::rename(&partial, &path).await?;
+            cache.evict(&path).await.map_err(io::Error::other)
+        })
+        .filter_map(|result| async move { result.err().map(Err) });
+
+        Ok(Box::new(StreamReader::new(
+            ReaderStream::new(reader).chain(complete).boxed(),
+        )))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use anyhow::Result;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    async fn read(mut reader: impl AsyncRead + Unpin) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    /// Download the data into the cache.
+    pub(crate) async fn insert(
+        cache: &DownloadCache,
+        key: &str,
+        data: &'static [u8],
+    ) -> Result<()> {
+        let partial = cache.partial(key, data.len() as u64).await?.unwrap();
+        read(partial.reader(data).await?).await?;
+        Ok(())
+    }
+
+    /// Start downloading the data into the cache, stopping with an error after `n` bytes.
+    pub(crate) async fn interrupt(
+        cache: &DownloadCache,
+        key: &str,
+        data: &'static [u8],
+        n: usize,
+    ) -> Result<()> {
+        let partial = cache.partial(key, data.len() as u64).await?.unwrap();
+        let rest = StreamReader::new(stream::iter([
+            Ok(&data[..n]),
+            Err(io::Error::other("interrupted")),
+        ]));
+        assert!(read(partial.reader(rest).await?).await.is_err());
+
+        // The partial entry is unlocked once it has been written.
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while cache.partial(key, data.len() as u64).await?.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_cache() -> Result<()> {
+        let tmp = tempdir()?;
+        let cache = DownloadCache::new(tmp.path().join("cache")).set_max_size(Some(10));
+
+        assert!(cache.get("a", 4).await?.is_none());
+        insert(&cache, "a", b"aaaa").await?;
+        assert_eq!(read(cache.get("a", 4).await?.unwrap()).await?, b"aaaa");
+        // Entries with a different size are not used.
+        assert!(cache.get("a", 5).await?.is_none());
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        insert(&cache, "b", b"bbbb").await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        // Using `a` makes `b` the least recently used entry.
+        cache.get("a", 4).await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        insert(&cache, "c", b"cccc").await?;
+
+        assert!(cache.get("a", 4).await?.is_some());
+        assert!(cache.get("b", 4).await?.is_none());
+        assert!(cache.get("c", 4).await?.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_cache_resume() -> Result<()> {
+        let tmp = tempdir()?;
+        let cache = DownloadCache::new(tmp.path().to_path_buf());
+
+        interrupt(&cache, "a", b"abcd", 2).await?;
+        assert!(cache.get("a", 4).await?.is_none());
+
+        // Only the rest of the object is needed, and the whole object is read.
+        let partial = cache.partial("a", 4).await?.unwrap();
+        assert_eq!(partial.offset(), 2);
+        // Another download of the same entry cannot use the partial entry at the same time.
+        assert!(cache.partial("a", 4).await?.is_none());
+        assert_eq!(read(partial.reader(&b"cd"[..]).await?).await?, b"abcd");
+        assert_eq!(read(cache.get("a", 4).await?.unwrap()).await?, b"abcd");
+
+        // A download that ends early is not used.
+        let partial = cache.partial("b", 4).await?.unwrap();
+        assert!(read(partial.reader(&b"bb"[..]).await?).await.is_err());
+        assert!(cache.get("b", 4).await?.is_none());
+
+        Ok(())
+    }
+}
@@ -1,14 +1,45 @@
 //! A shared reader implementation which makes use on channels.
 //!
 
+use crate::error::Error::ConcurrencyError;
 use crate::error::Result;
 use crate::io::sums::SharedReader;
+use crate::stats::ChannelStats;
 use async_stream::stream;
 use futures_util::Stream;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
+
+/// Counters for how often channel readers had to wait. These can be shared between readers to
+/// aggregate over a whole run.
+#[derive(Debug, Default)]
+pub struct ChannelMetrics {
+    n_sent: AtomicU64,
+    n_full: AtomicU64,
+    n_received: AtomicU64,
+    n_empty: AtomicU64,
+}
+
+impl ChannelMetrics {
+    /// Get the current values of the counters.
+    pub fn stats(&self) -> ChannelStats {
+        ChannelStats {
+            n_sent: self.n_sent.load(Ordering::Relaxed),
+            n_full: self.n_full.load(Ordering::Relaxed),
+            n_received: self.n_received.load(Ordering::Relaxed),
+            n_empty: self.n_empty.load(Ordering::Relaxed),
+        }
+    }
+
+    fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
 
 /// The shared reader implementation using channels.
 #[derive(Debug)]
@@ -16,6 +47,8 @@ pub struct ChannelReader<R> {
     inner: BufReader<R>,
     txs: Vec<mpsc::Sender<Arc<[u8]>>>,
     capacity: usize,
+    metrics: Option<Arc<ChannelMetrics>>,
+    read_nanos: Option<Arc<AtomicU64>>,
 }
 
 impl<R> ChannelReader<R>
@@ -28,9 +61,17 @@ where
             inner: BufReader::new(inner),
             txs: vec![],
             capacity,
+            metrics: None,
+            read_nanos: None,
         }
     }
 
+    /// Track how often the channels were full or empty using the metrics.
+    pub fn set_metrics(mut self, metrics: Option<Arc<ChannelMetrics>>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     /// Get the inner buffered reader.
     pub fn into_inner(self) -> BufReader<R> {
         self.inner
@@ -40,26 +81,92 @@ where
     pub fn subscribe_stream(&mut self) -> impl Stream<Item = Result<Arc<[u8]>>> {
         let (tx, mut rx) = mpsc::channel(self.capacity);
         self.txs.push(tx);
+        let metrics = self.metrics.clone();
 
         stream! {
-            let mut msg = rx.recv().await;
+            let mut msg = Self::recv(&mut rx, metrics.as_deref()).await;
             // Poll the channel until the end is reached.
             while let Some(buf) = msg {
                 yield Ok(buf);
-                msg = rx.recv().await;
+                msg = Self::recv(&mut rx, metrics.as_deref()).await;
             }
         }
     }
 
+    /// Receive from the channel, counting when the receiver has to wait for the reader.
+    async fn recv(
+        rx: &mut mpsc::Receiver<Arc<[u8]>>,
+        metrics: Option<&ChannelMetrics>,
+    ) -> Option<Arc<[u8]>> {
+        let Some(metrics) = metrics else {
+            return rx.recv().await;
+        };
+
+        let buf = match rx.try_recv() {
+            Ok(buf) => Some(buf),
+            Err(TryRecvError::Empty) => {
+                ChannelMetrics::increment(&metrics.n_empty);
+                rx.recv().await
+            }
+            Err(TryRecvError::Disconnected) => None,
+        };
+        if buf.is_some() {
+            ChannelMetrics::increment(&metrics.n_received);
+        }
+        buf
+    }
+
+    /// Send to the channel, counting when the reader has to wait for the receiver.
+    async fn send(
+        tx: &mpsc::Sender<Arc<[u8]>>,
+        buf: Arc<[u8]>,
+        metrics: Option<&ChannelMetrics>,
+    ) -> Result<()> {
+        let Some(metrics) = metrics else {
+            return Ok(tx.send(buf).await?);
+        };
+
+        match tx.try_send(buf) {
+            Ok(()) => {}
+            Err(TrySendError::Full(buf)) => {
+                ChannelMetrics::increment(&metrics.n_full);
+                tx.send(buf).await?;
+            }
+            Err(TrySendError::Closed(buf)) => tx.send(buf).await?,
+        }
+        ChannelMetrics::increment(&metrics.n_sent);
+
+        Ok(())
+    }
+
     /// Send data to the channel until the end of the reader is reached. Returns the size of the file.
     pub async fn send_to_end(&mut self) -> Result<u64> {
         let txs = self.txs.drain(..);
 
         let mut size = 0;
         loop {
+            // Stop reading if a receiver was dropped, e.g. because its checksum failed, rather
+            // than reading the rest of the input.
+            if txs.as_ref().iter().any(mpsc::Sender::is_closed) {
+                return Err(ConcurrencyError(
+                    "stopped reading because a receiver was closed".to_string(),
+                ));
+            }
+
             // Read data into a buffer.
             let mut buf = vec![0; 1000];
-            let n = self.inner.read(&mut buf).await?;
+            let n = match &self.read_nanos {
+                None => self.inner.read(&mut buf).await?,
+                Some(read_nanos) => {
+                    let start = Instant::now();
+                    let n = self.inner.read(&mut buf).await?;
+                    read_nanos.fetch_add(
+                        u64::try_from(start.elapsed().as_nanos())?,
+                        Ordering::Relaxed,
+                    );
+                    n
+                }
+            };
 
             // Stop if there is no more data.
             if n == 0 {
@@ -72,7 +179,7 @@ where
             // copying it.
             let buf: Arc<[u8]> = Arc::from(&buf[0..n]);
             for tx in txs.as_ref() {
-                tx.send(buf.clone()).await?;
+                Self::send(tx, buf.clone(), self.metrics.as_deref()).await?;
             }
         }
 
@@ -93,6 +200,10 @@ where
     fn as_stream(&mut self) -> Pin<Box<dyn Stream<Item = Result<Arc<[u8]>>> + Send>> {
         Box::pin(self.subscribe_stream())
     }
+
+    fn time_reads(&mut self, read_nanos: Arc<AtomicU64>) {
+        self.read_nanos = Some(read_nanos);
+    }
 }
 
 #[cfg(test)]
@@ -103,6 +214,7 @@ pub(crate) mod test {
     use futures_util::StreamExt;
     use rand::RngCore;
     use std::io::Cursor;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_stream() -> Result<()> {
@@ -129,6 +241,54 @@ pub(crate) mod test {
         Ok(())
     }
 
+    // The clock is paused, so the sleeps order the sender and receiver without depending on
+    // how long they take to run.
+    #[tokio::test(start_paused = true)]
+    async fn test_metrics() -> Result<()> {
+        let metrics = Arc::new(ChannelMetrics::default());
+        let mut reader =
+            ChannelReader::new(Cursor::new(vec![0; 8000]), 1).set_metrics(Some(metrics.clone()));
+        let stream = reader.as_stream();
+
+        // A slow receiver fills the channel, and a receiver that starts first finds it empty.
+        let receive = stream.for_each(|_| tokio::time::sleep(Duration::from_millis(1)));
+        let send = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            reader.read_chunks().await
+        };
+        let (_, size) = tokio::join!(receive, send);
+        assert_eq!(size?, 8000);
+
+        let stats = metrics.stats();
+        assert_eq!(stats.n_sent, 8);
+        assert_eq!(stats.n_received, 8);
+        // The receiver only waits for the first chunk, and the reader waits for every other one.
+        assert_eq!(stats.n_full, 7);
+        assert_eq!(stats.n_empty, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_closed_receiver() -> Result<()> {
+        let mut reader = ChannelReader::new(Cursor::new(vec![0; 100000]), 1);
+        let stream = reader.as_stream();
+        let mut closed = reader.as_stream();
+
+        // One receiver stops after the first chunk while the other keeps receiving.
+        let receive = stream.count();
+        let close = async move {
+            closed.next().await;
+            drop(closed);
+        };
+        let (n_received, _, result) = tokio::join!(receive, close, reader.read_chunks());
+
+        assert!(result.is_err());
+        assert!(n_received < 10);
+
+        Ok(())
+    }
+
     pub(crate) async fn channel_reader<R>(inner: R) -> ChannelReader<R>
     where
         R: AsyncRead + Unpin,
@@ -0,0 +1,129 @@
+//! Decompress inputs before computing checksums.
+//!
+
+use async_compression::tokio::bufread::GzipDecoder;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, BufReader};
+
+/// A compression format that inputs are decompressed from before computing checksums.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    ValueEnum,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Decompress {
+    /// Gzip compressed data, including multiple concatenated members such as BGZF files.
+    Gzip,
+}
+
+impl Decompress {
+    /// Wrap a reader so that it returns the decompressed data. Truncated or corrupt data
+    /// returns an error rather than ending the stream early.
+    pub fn reader(
+        &self,
+        reader: Box<dyn AsyncRead + Unpin + Send>,
+    ) -> Box<dyn AsyncRead + Unpin + Send> {
+        match self {
+            Self::Gzip => {
+                let mut decoder = GzipDecoder::new(BufReader::new(reader));
+                decoder.multiple_members(true);
+                Box::new(decoder)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use anyhow::Result;
+    use flate2::write::GzEncoder;
+    use flate2::{Compression, GzBuilder};
+    use std::io::Write;
+    use tokio::io::AsyncReadExt;
+
+    /// Compress data into a single gzip member.
+    pub(crate) fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    async fn decompress(data: &[u8], chunk_size: usize) -> Result<Vec<u8>> {
+        // Chained readers return each chunk from a separate read.
+        let reader = data.chunks(chunk_size).fold(
+            Box::new(tokio::io::empty()) as Box<dyn AsyncRead + Unpin + Send>,
+            |reader, chunk| Box::new(reader.chain(std::io::Cursor::new(chunk.to_vec()))),
+        );
+
+        let mut out = vec![];
+        Decompress::Gzip
+            .reader(reader)
+            .read_to_end(&mut out)
+            .await?;
+        Ok(out)
+    }
+
+    #[tokio::test]
+    async fn decompress_gzip() -> Result<()> {
+        let data = (0..100000u32)
+            .flat_map(|i| (i % 251).to_le_bytes())
+            .collect::<Vec<_>>();
+        let compressed = gzip(&data);
+
+        for chunk_size in [1, 7, 1000, compressed.len()] {
+            assert_eq!(decompress(&compressed, chunk_size).await?, data);
+        }
+        assert_eq!(decompress(&gzip(b""), 1).await?, b"");
+
+        // Concatenated members are a single stream.
+        let members = [gzip(b"hello "), gzip(b"world")].concat();
+        assert_eq!(decompress(&members, 3).await?, b"hello world");
+
+        // Optional header fields are skipped.
+        let mut encoder = GzBuilder::new()
+            .extra(b"xx".to_vec())
+            .filename("n")
+            .comment("c")
+            .write(vec![], Compression::default());
+        encoder.write_all(b"hello world")?;
+        assert_eq!(decompress(&encoder.finish()?, 2).await?, b"hello world");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decompress_invalid_gzip() -> Result<()> {
+        let compressed = gzip(b"hello world");
+
+        // Truncated anywhere, including in the header and trailer.
+        for len in [0, 5, 15, compressed.len() - 1] {
+            assert!(decompress(&compressed[..len], 4).await.is_err());
+        }
+
+        let mut corrupt = compressed.clone();
+        let len = corrupt.len();
+        corrupt[len - 8] ^= 1;
+        assert!(decompress(&corrupt, 4).await.is_err());
+
+        assert!(decompress(b"hello world", 4).await.is_err());
+
+        let mut corrupt = compressed;
+        corrupt[10] = 0xff;
+        assert!(decompress(&corrupt, 4).await.is_err());
+
+        Ok(())
+    }
+}
@@ -1,19 +1,49 @@
 //! File-based sums file logic.
 //!
 
-use crate::checksum::file::SumsFile;
+use crate::checksum::file::{ByteRange, FileMetadata, SumsFile, SumsFormat};
 use crate::error::Error::ParseError;
-use crate::error::{ApiError, Result};
+use crate::error::{ApiError, Error, Result};
+use crate::io::copy::MultiPartOptions;
+use crate::io::sums::sparse::SparseReader;
 use crate::io::sums::ObjectSums;
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::io::SeekFrom;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs;
-use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::io::{empty, AsyncRead, AsyncReadExt, AsyncSeekExt};
+use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+use tracing::debug;
+
+/// Separates a tar archive from a member inside it, e.g. `archive.tar!path/inside`.
+pub const TAR_MEMBER_SEPARATOR: &str = ".tar!";
+/// Separates files that are read as a single concatenated file, e.g. `part1+part2`.
+pub const CONCAT_SEPARATOR: char = '+';
+
+/// The state of the sums file when it was first read. This is used to detect a concurrent
+/// modification of the sums file when writing it.
+#[derive(Debug, Clone, Default)]
+enum SumsFileState {
+    /// The sums file has not been read.
+    #[default]
+    Unknown,
+    /// The sums file did not exist.
+    Missing,
+    /// The sums file existed with these contents.
+    Read(SumsFile),
+}
 
 /// Build a file based sums object.
 #[derive(Debug, Default)]
 pub struct FileBuilder {
     file: Option<String>,
+    format: SumsFormat,
+    sums_destination: Option<String>,
+    output_dir: Option<String>,
+    sparse: bool,
 }
 
 impl FileBuilder {
@@ -23,6 +53,31 @@ impl FileBuilder {
         self
     }
 
+    /// Set the format of the sums file.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set a directory to read and write the sums file in, rather than next to the file.
+    pub fn set_sums_destination(mut self, sums_destination: Option<String>) -> Self {
+        self.sums_destination = sums_destination;
+        self
+    }
+
+    /// Set a directory to read and write the sums file in, which mirrors the absolute path of
+    /// the file.
+    pub fn set_output_dir(mut self, output_dir: Option<String>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Skip reading the holes of the file if it is sparse.
+    pub fn with_sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
     fn get_components(self) -> Result<String> {
         self.file
             .ok_or_else(|| ParseError("file is required for `FileBuilder`".to_string()))
@@ -30,7 +85,15 @@ impl FileBuilder {
 
     /// Build using the file name.
     pub fn build(self) -> Result<File> {
-        Ok(self.get_components()?.into())
+        let format = self.format;
+        let sums_destination = self.sums_destination.clone();
+        let output_dir = self.output_dir.clone();
+        let sparse = self.sparse;
+        Ok(File::from(self.get_components()?)
+            .with_format(format)
+            .set_sums_destination(sums_destination)
+            .set_output_dir(output_dir)
+            .with_sparse(sparse))
     }
 }
 
@@ -44,48 +107,407 @@ impl From<String> for File {
 #[derive(Debug, Clone)]
 pub struct File {
     file: String,
+    format: SumsFormat,
+    sums_destination: Option<String>,
+    output_dir: Option<String>,
+    sparse: bool,
+    sums_file_state: Arc<Mutex<SumsFileState>>,
 }
 
 impl File {
     /// Create a new file.
     pub fn new(file: String) -> Self {
-        Self { file }
+        Self {
+            file,
+            format: Default::default(),
+            sums_destination: None,
+            output_dir: None,
+            sparse: false,
+            sums_file_state: Default::default(),
+        }
+    }
+
+    /// Set the format of the sums file.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set a directory to read and write the sums file in, rather than next to the file.
+    pub fn set_sums_destination(mut self, sums_destination: Option<String>) -> Self {
+        self.sums_destination = sums_destination;
+        self
+    }
+
+    /// Set a directory to read and write the sums file in, which mirrors the absolute path of
+    /// the file, e.g. `sums/data/file.sums` for `/data/file`.
+    pub fn set_output_dir(mut self, output_dir: Option<String>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Skip reading the holes of the file if it is sparse. The zeros of holes are produced in
+    /// memory, so the data read is the same as reading the file normally.
+    pub fn with_sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    /// Split the target file into the tar archive and the member path if it uses the
+    /// `archive.tar!path/inside` syntax.
+    pub fn archive_member(&self) -> Option<(String, String)> {
+        let target = SumsFile::format_target_file(&self.file);
+        let index = target.find(TAR_MEMBER_SEPARATOR)?;
+        let (archive, member) = target.split_at(index + ".tar".len());
+
+        Some((archive.to_string(), Self::decode_member(&member[1..])))
+    }
+
+    /// Get the files that make up a virtual concatenation using the `part1+part2` syntax.
+    /// Parts after the first are relative to the directory of the first part. Returns `None`
+    /// if a file with the exact name exists, so that existing files containing `+` can still
+    /// be read.
+    pub async fn concat_parts(&self) -> Result<Option<Vec<String>>> {
+        let target = SumsFile::format_target_file(&self.file);
+        if !target.contains(CONCAT_SEPARATOR)
+            || self.archive_member().is_some()
+            || fs::try_exists(&target).await?
+        {
+            return Ok(None);
+        }
+
+        let mut parts = target.split(CONCAT_SEPARATOR);
+        let Some(first) = parts.next().map(Path::new) else {
+            return Ok(None);
+        };
+        let dir = first.parent().unwrap_or(Path::new(""));
+
+        Ok(Some(
+            iter::once(first.to_path_buf())
+                .chain(parts.map(|part| dir.join(part)))
+                .map(|part| part.to_string_lossy().to_string())
+                .collect(),
+        ))
+    }
+
+    /// Get the segments of files that make up the target as a path, an offset and an optional
+    /// length. The range is relative to the start of the target.
+    async fn segments(&self, range: Option<(u64, u64)>) -> Result<Vec<(String, u64, Option<u64>)>> {
+        if let Some(parts) = self.concat_parts().await? {
+            let mut segments = vec![];
+            let mut position = 0;
+            for part in parts {
+                let metadata = fs::metadata(&part).await?;
+                // Pipes have an unknown size, so they are read until the end.
+                if !metadata.is_file() && range.is_none() {
+                    segments.push((part, 0, None));
+                    continue;
+                }
+
+                let size = metadata.len();
+                let (start, end) = match range {
+                    Some((start, length)) => (
+                        start.clamp(position, position + size),
+                        (start + length).clamp(position, position + size),
+                    ),
+                    None => (position, position + size),
+                };
+
+                if start < end || range.is_none() {
+                    segments.push((part, start - position, Some(end - start)));
+                }
+                position += size;
+            }
+
+            return Ok(segments);
+        }
+
+        // Ranges of archive members are relative to the start of the member data.
+        if let Some((archive, member)) = self.archive_member() {
+            let member = Self::member_range(archive.to_string(), member).await?;
+            let segment = match range {
+                Some((start, length)) => (archive, member.offset + start, Some(length)),
+                None => (archive, member.offset, Some(member.length)),
+            };
+
+            return Ok(vec![segment]);
+        }
+
+        let file = SumsFile::format_target_file(&self.file);
+        Ok(vec![match range {
+            Some((start, length)) => (file, start, Some(length)),
+            None => (file, 0, None),
+        }])
+    }
+
+    /// Encode a member path so that it can be used as part of a sums file name next to the
+    /// archive. This allows sums files for multiple members to coexist.
+    fn encode_member(member: &str) -> String {
+        member.replace('%', "%25").replace('/', "%2F")
+    }
+
+    /// Decode a member path that was encoded using `encode_member`.
+    fn decode_member(member: &str) -> String {
+        member.replace("%2F", "/").replace("%25", "%")
+    }
+
+    /// Get the path of the sums file.
+    fn sums_path(&self) -> Result<String> {
+        let name = match self.archive_member() {
+            Some((archive, member)) => format!("{}!{}", archive, Self::encode_member(&member)),
+            None => self.file.to_string(),
+        };
+
+        match &self.output_dir {
+            Some(output_dir) => self.format.format_sums_file_under(&name, output_dir),
+            None => self
+                .format
+                .format_sums_file_at(&name, self.sums_destination.as_deref()),
+        }
+    }
+
+    /// Find the range of the data of a member inside a tar archive.
+    async fn member_range(archive: String, member: String) -> Result<ByteRange> {
+        spawn_blocking(move || {
+            let member_path = Path::new(member.trim_start_matches("./"));
+            let mut tar = tar::Archive::new(std::fs::File::open(&archive)?);
+
+            for entry in tar.entries()? {
+                let entry = entry?;
+                let path = entry.path()?;
+                if path.strip_prefix("./").unwrap_or(&path) == member_path {
+                    return Ok(ByteRange::new(entry.raw_file_position(), entry.size()));
+                }
+            }
+
+            Err(ParseError(format!(
+                "member `{}` not found in archive `{}`",
+                member, archive
+            )))
+        })
+        .await?
     }
 
     /// Get an existing sums file.
     pub async fn get_existing_sums(&self) -> Result<Option<SumsFile>> {
-        let path = SumsFile::format_sums_file(&self.file);
+        let path = self.sums_path()?;
 
         if !PathBuf::from(&path).exists() {
+            self.record_state(SumsFileState::Missing).await;
             return Ok(None);
         }
 
-        let mut file = fs::File::open(&path).await?;
+        // Writers hold an exclusive lock, so the sums file is never read while partially written.
+        let mut file = Self::lock(fs::File::open(&path).await?, false).await?;
+        let sums = self.read_sums(&mut file).await?;
+
+        self.record_state(match &sums {
+            Some(sums) => SumsFileState::Read(sums.clone()),
+            None => SumsFileState::Missing,
+        })
+        .await;
+        Ok(sums)
+    }
+
+    /// Read the sums file from an open and locked file, which is empty if it was just created.
+    async fn read_sums(&self, file: &mut fs::File) -> Result<Option<SumsFile>> {
         let mut buf = vec![];
         file.read_to_end(&mut buf).await?;
+        if buf.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(self.format.from_slice(&buf)?))
+    }
+
+    /// Record the state of the sums file the first time that it is read, so that a write merges
+    /// the checksums of any writer that modified it at any point after that.
+    async fn record_state(&self, state: SumsFileState) {
+        let mut current = self.sums_file_state.lock().await;
+        if matches!(*current, SumsFileState::Unknown) {
+            *current = state;
+        }
+    }
 
-        let sums = SumsFile::read_from_slice(&buf).await?;
-        Ok(Some(sums))
+    /// Wait for an advisory lock on an open sums file. The lock is shared between readers and
+    /// exclusive for writers, and is released when the file is closed.
+    async fn lock(file: fs::File, exclusive: bool) -> Result<fs::File> {
+        let file = file.into_std().await;
+        spawn_blocking(move || {
+            if exclusive {
+                file.lock()?;
+            } else {
+                file.lock_shared()?;
+            }
+            Ok(fs::File::from_std(file))
+        })
+        .await?
     }
 
-    /// Get the reader to the sums file.
-    pub async fn sums_reader(&self) -> Result<impl AsyncRead> {
-        let path = SumsFile::format_target_file(&self.file);
-        Ok(fs::File::open(&path).await?)
+    /// Get the reader to the sums file, optionally only reading the specified range.
+    pub async fn sums_reader(
+        &self,
+        range: Option<MultiPartOptions>,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        let range = range
+            .map(|range| {
+                let size = range
+                    .end
+                    .checked_sub(range.start)
+                    .ok_or_else(|| ParseError("invalid range".to_string()))?;
+                Ok::<_, Error>((range.start, size))
+            })
+            .transpose()?;
+
+        let mut reader: Option<Box<dyn AsyncRead + Unpin + Send>> = None;
+        for (path, start, length) in self.segments(range).await? {
+            // Only regular files can have holes, and pipes cannot be seeked.
+            let sparse = self.sparse && fs::metadata(&path).await?.is_file();
+            let next: Box<dyn AsyncRead + Unpin + Send> = if sparse {
+                Box::new(SparseReader::open(&path, start, length).await?)
+            } else {
+                let mut file = fs::File::open(&path).await?;
+                if start != 0 {
+                    file.seek(SeekFrom::Start(start)).await?;
+                }
+
+                match length {
+                    Some(length) => Box::new(file.take(length)),
+                    None => Box::new(file),
+                }
+            };
+            reader = Some(match reader {
+                Some(reader) => Box::new(reader.chain(next)),
+                None => next,
+            });
+        }
+
+        Ok(reader.unwrap_or_else(|| Box::new(empty())))
     }
 
-    /// Get the size of the target file.
+    /// Get the size of the target file. The size is unknown for files that are not regular
+    /// files, such as named pipes.
     pub async fn size(&self) -> Result<Option<u64>> {
+        if let Some((archive, member)) = self.archive_member() {
+            // A missing archive has no size, as with other missing files, but a corrupt archive
+            // or a missing member is an error.
+            if !fs::try_exists(&archive).await? {
+                return Ok(None);
+            }
+            return Ok(Some(Self::member_range(archive, member).await?.length));
+        }
+
+        if let Some(parts) = self.concat_parts().await? {
+            let mut size = 0;
+            for part in parts {
+                let Ok(metadata) = fs::metadata(part).await else {
+                    return Ok(None);
+                };
+                if !metadata.is_file() {
+                    return Ok(None);
+                }
+                size += metadata.len();
+            }
+
+            return Ok(Some(size));
+        }
+
         Ok(fs::metadata(SumsFile::format_target_file(&self.file))
             .await
             .ok()
+            .filter(|metadata| metadata.is_file())
             .map(|metadata| metadata.len()))
     }
 
-    /// Write the sums file to the configured location.
+    /// Get the device and inode of the target file. This is only available on unix systems,
+    /// and is not available for archive members or concatenated files as they do not map to
+    /// a single inode.
+    pub async fn id(&self) -> Result<Option<(u64, u64)>> {
+        if self.archive_member().is_some() || self.concat_parts().await?.is_some() {
+            return Ok(None);
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            Ok(fs::metadata(SumsFile::format_target_file(&self.file))
+                .await
+                .ok()
+                .map(|metadata| (metadata.dev(), metadata.ino())))
+        }
+
+        #[cfg(not(unix))]
+        Ok(None)
+    }
+
+    /// Get the modification time and permissions of the target file. For archive members, this
+    /// is the metadata of the archive. This is not available for concatenated files.
+    pub async fn metadata(&self) -> Result<FileMetadata> {
+        let path = match (self.archive_member(), self.concat_parts().await?) {
+            (Some((archive, _)), _) => archive,
+            (None, Some(_)) => return Ok(Default::default()),
+            (None, None) => SumsFile::format_target_file(&self.file),
+        };
+
+        let metadata = fs::metadata(path).await?;
+
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(metadata.permissions().mode() & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let mode = None;
+
+        Ok(FileMetadata::new(metadata.modified().ok(), mode))
+    }
+
+    /// Write the sums file to the configured location. Concurrent writers of the same sums
+    /// file are serialized using an exclusive advisory lock, which is held while the current
+    /// sums file is read, merged and written. If the sums file was modified by another writer
+    /// since it was read, the checksums of the other writer are kept, similar to conditional
+    /// writes of S3 sums objects.
     pub async fn write_sums(&self, sums_file: &SumsFile) -> Result<()> {
-        let path = SumsFile::format_sums_file(&self.file);
-        fs::write(&path, sums_file.to_json_string()?).await?;
+        let path = self.sums_path()?;
+        // The mirrored directories under an output directory or sums destination may not exist
+        // yet.
+        if self.output_dir.is_some() || self.sums_destination.is_some() {
+            if let Some(parent) = Path::new(&path).parent() {
+                fs::create_dir_all(parent).await?;
+            }
+        }
+
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .await?;
+        let mut file = Self::lock(file, true).await?;
+
+        let mut state = self.sums_file_state.lock().await;
+        let current = self.read_sums(&mut file).await?;
+        let modified = match (&*state, &current) {
+            (SumsFileState::Unknown, _) | (SumsFileState::Missing, None) => false,
+            (SumsFileState::Missing, Some(_)) => true,
+            (SumsFileState::Read(read), current) => Some(read) != current.as_ref(),
+        };
+
+        let mut sums_file = sums_file.clone();
+        if let (true, Some(current)) = (modified, current) {
+            debug!(path, "sums file was modified by another writer, merging");
+            let metadata = sums_file.metadata();
+            sums_file = current.merge(sums_file)?.with_metadata(metadata);
+        }
+
+        file.set_len(0).await?;
+        file.seek(SeekFrom::Start(0)).await?;
+        let sums_file = sums_file.write_to(self.format, file).await?;
+
+        // Later writes are conditional on this write.
+        *state = SumsFileState::Read(sums_file);
         Ok(())
     }
 }
@@ -96,8 +518,11 @@ impl ObjectSums for File {
         Ok(self.get_existing_sums().await?)
     }
 
-    async fn reader(&mut self) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
-        Ok(Box::new(self.sums_reader().await?))
+    async fn reader(
+        &mut self,
+        range: Option<MultiPartOptions>,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        self.sums_reader(range).await
     }
 
     async fn file_size(&mut self) -> Result<Option<u64>> {
@@ -112,7 +537,103 @@ impl ObjectSums for File {
         self.file.to_string()
     }
 
+    async fn file_id(&mut self) -> Result<Option<(u64, u64)>> {
+        self.id().await
+    }
+
+    async fn file_metadata(&mut self) -> Result<FileMetadata> {
+        self.metadata().await
+    }
+
     fn api_errors(&self) -> HashSet<ApiError> {
         HashSet::new()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::checksum::file::Checksum;
+    use anyhow::Result;
+    use futures_util::future::join_all;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn concurrent_writes() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = FileBuilder::default()
+            .with_file(tmp.path().join("file").to_string_lossy().to_string())
+            .build()?;
+        let sums_file = |n: usize| {
+            SumsFile::new(
+                Some(1),
+                [("md5".parse().unwrap(), Checksum::new("1".repeat(n)))].into(),
+            )
+        };
+
+        // Writers of different lengths never leave a mix of their contents.
+        let sums = (1..50).map(sums_file).collect::<Vec<_>>();
+        join_all(sums.iter().map(|sums| file.write_sums(sums)))
+            .await
+            .into_iter()
+            .collect::<crate::error::Result<Vec<_>>>()?;
+        assert!(sums.contains(&file.get_existing_sums().await?.unwrap()));
+
+        // Reading waits for a writer holding the lock.
+        let locked = std::fs::File::open(file.sums_path()?)?;
+        locked.lock()?;
+        let read = tokio::spawn(async move { file.get_existing_sums().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!read.is_finished());
+
+        drop(locked);
+        assert!(read.await??.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_updates() -> Result<()> {
+        let tmp = tempdir()?;
+        let path = tmp.path().join("file").to_string_lossy().to_string();
+        let kinds = ["md5", "sha1", "sha256", "crc32", "crc32c"];
+
+        // Each writer reads the missing sums file before any of them write, like concurrent
+        // generate tasks computing different checksums.
+        let mut writers = vec![];
+        for kind in kinds {
+            let file = FileBuilder::default().with_file(path.to_string()).build()?;
+            assert_eq!(file.get_existing_sums().await?, None);
+            writers.push((file, kind));
+        }
+
+        join_all(writers.iter().map(|(file, kind)| async move {
+            let sums = SumsFile::new(
+                Some(1),
+                [(kind.parse().unwrap(), Checksum::new(kind.to_string()))].into(),
+            );
+            file.write_sums(&sums).await
+        }))
+        .await
+        .into_iter()
+        .collect::<crate::error::Result<Vec<_>>>()?;
+
+        // No update is lost.
+        let sums = FileBuilder::default()
+            .with_file(path)
+            .build()?
+            .get_existing_sums()
+            .await?
+            .unwrap();
+        assert_eq!(sums.checksums.len(), kinds.len());
+        for kind in kinds {
+            assert_eq!(
+                sums.checksums[&kind.parse()?],
+                Checksum::new(kind.to_string())
+            );
+        }
+
+        Ok(())
+    }
+}
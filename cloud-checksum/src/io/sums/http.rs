@@ -0,0 +1,489 @@
+//! Read-only HTTP(S) objects, such as presigned urls.
+//!
+
+use crate::checksum::file::{Checksum, SumsFile};
+use crate::checksum::Ctx;
+use crate::error::Error::{HttpError, IOError};
+use crate::error::{ApiError, Result};
+use crate::io::copy::MultiPartOptions;
+use crate::io::sums::ObjectSums;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use futures_util::TryStreamExt;
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, CONTENT_RANGE, RANGE};
+use reqwest::{Client, StatusCode};
+use std::collections::HashSet;
+use std::io;
+use std::sync::LazyLock;
+use tokio::io::{empty, AsyncRead};
+use tokio_util::io::StreamReader;
+use tracing::warn;
+
+/// The header containing the base64 encoded MD5 of the body.
+const CONTENT_MD5: &str = "content-md5";
+/// The header containing base64 encoded digests of the body, e.g. `sha-256=<base64>`.
+const DIGEST: &str = "digest";
+
+/// The client shared by all HTTP objects, so that connections to the same host are reused.
+static CLIENT: LazyLock<Client> = LazyLock::new(Client::new);
+
+/// An object behind an HTTP(S) url. This is read-only, so sums files cannot be written.
+#[derive(Debug, Clone)]
+pub struct Http {
+    client: Client,
+    url: String,
+}
+
+impl Http {
+    /// Create a new HTTP object.
+    pub fn new(url: String) -> Self {
+        Self {
+            client: CLIENT.clone(),
+            url,
+        }
+    }
+
+    /// Get the url of the object.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Get the size of the object. This uses a single byte range request rather than `HEAD`
+    /// because presigned urls are only valid for `GET` requests.
+    pub async fn size(&self) -> Result<Option<u64>> {
+        let response = self
+            .client
+            .get(&self.url)
+            .header(RANGE, "bytes=0-0")
+            .send()
+            .await?;
+
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE => {
+                Ok(Self::range_size(response.headers()))
+            }
+            _ => Ok(response.error_for_status()?.content_length()),
+        }
+    }
+
+    /// Get the total size from the `Content-Range` of a partial response or an unsatisfiable
+    /// range for an empty object, e.g. `bytes 0-0/123` or `bytes */0`.
+    fn range_size(headers: &HeaderMap) -> Option<u64> {
+        headers
+            .get(CONTENT_RANGE)
+            .and_then(|range| range.to_str().ok())
+            .and_then(|range| range.rsplit_once('/'))
+            .and_then(|(_, size)| size.parse().ok())
+    }
+
+    /// Get the checksums declared by the server in the `Content-MD5` or `Digest` response
+    /// headers using a `HEAD` request. If `HEAD` is not allowed, e.g. for presigned urls, a
+    /// single byte range request is used instead, and its body is not read. The `Content-MD5`
+    /// of a partial response describes the range rather than the whole object, so only the
+    /// `Digest` header is used in that case.
+    pub async fn sums_from_headers(&self) -> Result<Option<SumsFile>> {
+        let response = self.client.head(&self.url).send().await?;
+        if !matches!(
+            response.status(),
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::FORBIDDEN
+        ) {
+            // The length of the body of a `HEAD` response is zero, so the header is used.
+            let headers = response.error_for_status()?.headers().clone();
+            let size = headers
+                .get(CONTENT_LENGTH)
+                .and_then(|length| length.to_str().ok())
+                .and_then(|length| length.parse().ok());
+            return Ok(self.parse_sums_headers(&headers, size));
+        }
+
+        let response = self
+            .client
+            .get(&self.url)
+            .header(RANGE, "bytes=0-0")
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT | StatusCode::RANGE_NOT_SATISFIABLE => {
+                let mut headers = response.headers().clone();
+                headers.remove(CONTENT_MD5);
+                Ok(self.parse_sums_headers(&headers, Self::range_size(&headers)))
+            }
+            _ => {
+                let response = response.error_for_status()?;
+                Ok(self.parse_sums_headers(response.headers(), response.content_length()))
+            }
+        }
+    }
+
+    /// Parse the `Content-MD5` and `Digest` headers into a sums file, e.g.
+    /// `Digest: sha-256=<base64>,md5=<base64>`. Headers that are absent, malformed or use an
+    /// unsupported algorithm are ignored, so this returns `None` if there are no known checksums.
+    pub fn parse_sums_headers(&self, headers: &HeaderMap, size: Option<u64>) -> Option<SumsFile> {
+        let values = |name| {
+            headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+        };
+        let content_md5 = values(CONTENT_MD5).map(|value| ("md5", value));
+        let digests = values(DIGEST)
+            .flat_map(|value| value.split(','))
+            .filter_map(|digest| digest.trim().split_once('='));
+
+        let mut sums_file = SumsFile::default().with_size(size);
+        for (algorithm, value) in content_md5.chain(digests) {
+            // The length of the digest is checked so that truncated values are not compared.
+            let (ctx, length) = match algorithm.to_lowercase().as_str() {
+                "md5" => ("md5", 16),
+                "sha" => ("sha1", 20),
+                "sha-256" => ("sha256", 32),
+                _ => continue,
+            };
+            let ctx: Ctx = ctx.parse().ok()?;
+            let Some(digest) = BASE64_STANDARD
+                .decode(value.trim())
+                .ok()
+                .filter(|digest| digest.len() == length)
+            else {
+                warn!(
+                    url = self.url,
+                    algorithm, "ignoring malformed checksum header"
+                );
+                continue;
+            };
+
+            let checksum = Checksum::new(ctx.digest_to_string(&digest));
+            sums_file.add_checksum(ctx, checksum);
+        }
+
+        (!sums_file.checksums.is_empty()).then_some(sums_file)
+    }
+
+    /// Get the object and convert it into an `AsyncRead`, optionally only reading the
+    /// range specified by the multipart options using a `Range` request.
+    pub async fn object_reader(
+        &self,
+        range: Option<MultiPartOptions>,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send + Sync>> {
+        let mut request = self.client.get(&self.url);
+        if let Some(range) = range {
+            let Some(range) = range.format_range() else {
+                return Ok(Box::new(empty()));
+            };
+            request = request.header(RANGE, range);
+
+            let response = request.send().await?.error_for_status()?;
+            if response.status() != StatusCode::PARTIAL_CONTENT {
+                return Err(HttpError(format!(
+                    "`{}` does not support range requests",
+                    self.url
+                )));
+            }
+
+            return Ok(Self::into_reader(response));
+        }
+
+        Ok(Self::into_reader(request.send().await?.error_for_status()?))
+    }
+
+    /// Convert a response body into an `AsyncRead`.
+    fn into_reader(response: reqwest::Response) -> Box<dyn AsyncRead + Unpin + Send + Sync> {
+        Box::new(StreamReader::new(
+            response.bytes_stream().map_err(io::Error::other),
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectSums for Http {
+    async fn sums_file(&mut self) -> Result<Option<SumsFile>> {
+        self.sums_from_headers().await
+    }
+
+    async fn metadata_sums(&mut self) -> Result<Option<SumsFile>> {
+        self.sums_from_headers().await
+    }
+
+    async fn reader(
+        &mut self,
+        range: Option<MultiPartOptions>,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        Ok(self.object_reader(range).await?)
+    }
+
+    async fn file_size(&mut self) -> Result<Option<u64>> {
+        self.size().await
+    }
+
+    async fn write_sums_file(&self, _sums_file: &SumsFile) -> Result<()> {
+        Err(IOError(io::Error::new(
+            io::ErrorKind::ReadOnlyFilesystem,
+            format!(
+                "cannot write a sums file for `{}` because http locations are read-only",
+                self.url
+            ),
+        )))
+    }
+
+    fn location(&self) -> String {
+        self.url.to_string()
+    }
+
+    fn api_errors(&self) -> HashSet<ApiError> {
+        HashSet::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::task::check::CheckTaskBuilder;
+    use crate::task::generate::GenerateTaskBuilder;
+    use crate::test::TestFileBuilder;
+    use anyhow::Result;
+    use sha2::Digest;
+    use tempfile::tempdir;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Serve the data over HTTP on a local port, supporting single `Range` requests.
+    /// Returns the url of the object.
+    pub(crate) async fn serve(data: Vec<u8>) -> Result<String> {
+        serve_with_headers(data, String::new()).await
+    }
+
+    /// Serve the data over HTTP, adding the headers to responses.
+    pub(crate) async fn serve_with_headers(data: Vec<u8>, extra: String) -> Result<String> {
+        serve_with(data, extra, true).await
+    }
+
+    /// Serve the data over HTTP, adding the headers to responses, and optionally responding
+    /// to `HEAD` requests with `405 Method Not Allowed`.
+    pub(crate) async fn serve_with(
+        data: Vec<u8>,
+        extra: String,
+        allow_head: bool,
+    ) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/object", listener.local_addr()?);
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let data = data.clone();
+                let extra = extra.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut lines = BufReader::new(read).lines();
+
+                    let mut range = None;
+                    let mut head = false;
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if line.starts_with("HEAD ") {
+                            head = true;
+                        }
+                        if line.is_empty() {
+                            break;
+                        }
+                        if let Some(value) = line.to_lowercase().strip_prefix("range: bytes=") {
+                            let (start, end) = value.split_once('-').unwrap();
+                            range =
+                                Some((start.parse::<u64>().unwrap(), end.parse::<u64>().unwrap()));
+                        }
+                    }
+
+                    let size = data.len() as u64;
+                    let (status, headers, body) = match range {
+                        _ if head && !allow_head => {
+                            ("405 Method Not Allowed", String::new(), vec![])
+                        }
+                        Some((start, _)) if start >= size => (
+                            "416 Range Not Satisfiable",
+                            format!("content-range: bytes */{}\r\n", size),
+                            vec![],
+                        ),
+                        Some((start, end)) => {
+                            let end = end.min(size - 1);
+                            (
+                                "206 Partial Content",
+                                format!(
+                                    "content-range: bytes {}-{}/{}\r\n{}",
+                                    start, end, size, extra
+                                ),
+                                data[start as usize..=end as usize].to_vec(),
+                            )
+                        }
+                        None => ("200 OK", extra, data),
+                    };
+
+                    let response = format!(
+                        "HTTP/1.1 {}\r\ncontent-length: {}\r\n{}connection: close\r\n\r\n",
+                        status,
+                        body.len(),
+                        headers
+                    );
+                    let _ = write.write_all(response.as_bytes()).await;
+                    if !head {
+                        let _ = write.write_all(&body).await;
+                    }
+                });
+            }
+        });
+
+        Ok(url)
+    }
+
+    #[tokio::test]
+    async fn test_http_reader() -> Result<()> {
+        let data = b"0123456789".to_vec();
+        let mut http = Http::new(serve(data.clone()).await?);
+
+        assert_eq!(http.file_size().await?, Some(10));
+        assert!(http.sums_file().await?.is_none());
+        assert!(http.write_sums_file(&SumsFile::default()).await.is_err());
+
+        let mut contents = vec![];
+        http.reader(None).await?.read_to_end(&mut contents).await?;
+        assert_eq!(contents, data);
+
+        let range = MultiPartOptions {
+            start: 2,
+            end: 5,
+            ..Default::default()
+        };
+        let mut contents = vec![];
+        http.reader(Some(range))
+            .await?
+            .read_to_end(&mut contents)
+            .await?;
+        assert_eq!(contents, b"234");
+
+        let mut empty = Http::new(serve(vec![]).await?);
+        assert_eq!(empty.file_size().await?, Some(0));
+
+        assert!(matches!(
+            empty.write_sums_file(&SumsFile::default()).await,
+            Err(IOError(err)) if err.kind() == io::ErrorKind::ReadOnlyFilesystem
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_http() -> Result<()> {
+        let test_file = TestFileBuilder::default().generate_test_defaults()?;
+        let data = tokio::fs::read(&test_file).await?;
+        let url = serve(data).await?;
+
+        let generate = |input: String, write| async move {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(input)
+                .with_context(vec!["md5".parse()?, "md5-aws-2".parse()?])
+                .with_capacity(10)
+                .set_write(write)
+                .build()
+                .await?
+                .run()
+                .await
+        };
+
+        let expected = generate(test_file.to_string_lossy().to_string(), false).await?;
+        // Writing is skipped for read-only locations rather than failing.
+        let sums = generate(url, true).await?;
+        assert_eq!(sums.sums_file(), expected.sums_file());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_http_sums_from_headers() -> Result<()> {
+        let data = b"0123456789".to_vec();
+        let md5 = BASE64_STANDARD.encode(md5::Md5::digest(&data));
+        let sha256 = BASE64_STANDARD.encode(sha2::Sha256::digest(&data));
+
+        let headers = format!(
+            "content-md5: {}\r\ndigest: unixsum=30637, SHA-256={}\r\n",
+            md5, sha256
+        );
+        let mut http = Http::new(serve_with_headers(data.clone(), headers.clone()).await?);
+        let sums_file = http.sums_file().await?.unwrap();
+        assert_eq!(sums_file.size, Some(10));
+        let sha256 = (
+            "sha256".parse()?,
+            Checksum::new(
+                "84d89877f0d4041efb6bf91a16f0248f2fd573e6af05c19f96bedb9f882f7882".to_string(),
+            ),
+        );
+        assert_eq!(
+            sums_file.checksums,
+            [
+                (
+                    "md5".parse()?,
+                    Checksum::new("781e5e245d69b566979b86e28d23f2c7".to_string())
+                ),
+                sha256.clone(),
+            ]
+            .into()
+        );
+
+        // Without `HEAD`, a range request is used, where the `Content-MD5` describes the range.
+        let mut http = Http::new(serve_with(data.clone(), headers, false).await?);
+        let sums_file = http.sums_file().await?.unwrap();
+        assert_eq!(sums_file.size, Some(10));
+        assert_eq!(sums_file.checksums, [sha256].into());
+
+        // Malformed and unsupported headers mean that there are no known checksums.
+        let mut http = Http::new(
+            serve_with_headers(
+                data,
+                "content-md5: abc\r\ndigest: sha-512=abc\r\n".to_string(),
+            )
+            .await?,
+        );
+        assert!(http.sums_file().await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_http_headers() -> Result<()> {
+        let test_file = TestFileBuilder::default().generate_test_defaults()?;
+        let data = tokio::fs::read(&test_file).await?;
+        let md5 = md5::Md5::digest(&data);
+        let tmp = tempdir()?;
+
+        let check = |content_md5: String| {
+            let test_file = test_file.to_string_lossy().to_string();
+            let destination = tmp.path().to_string_lossy().to_string();
+            let sums = format!("{}/{}.sums", destination, test_file.trim_start_matches('/'));
+            let data = data.clone();
+            async move {
+                GenerateTaskBuilder::default()
+                    .with_input_file_name(test_file)
+                    .with_context(vec!["md5".parse()?])
+                    .with_capacity(10)
+                    .set_sums_destination(Some(destination.clone()))
+                    .set_write(true)
+                    .build()
+                    .await?
+                    .run()
+                    .await?;
+
+                let url =
+                    serve_with_headers(data, format!("content-md5: {}\r\n", content_md5)).await?;
+                let check = CheckTaskBuilder::default()
+                    .with_input_files(vec![sums, url])
+                    .build()
+                    .await?
+                    .run()
+                    .await?;
+                Ok::<_, anyhow::Error>(check.state_objects().len())
+            }
+        };
+
+        // The declared checksum is compared without reading the object.
+        assert_eq!(check(BASE64_STANDARD.encode(md5)).await?, 1);
+        assert_eq!(check(BASE64_STANDARD.encode([0; 16])).await?, 2);
+
+        Ok(())
+    }
+}
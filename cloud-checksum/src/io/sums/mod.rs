@@ -1,22 +1,40 @@
 //! Implementations for reading data using IO and from cloud storage.
 //!
 
-use crate::checksum::file::SumsFile;
+use crate::checksum::file::{join_under, FileMetadata, SumsFile, SumsFormat, SumsStorage};
+use crate::error::Error::ParseError;
 use crate::error::{ApiError, Result};
-use crate::io::sums::aws::S3Builder;
+use crate::io::copy::MultiPartOptions;
+#[cfg(feature = "cloud")]
+use crate::io::default_s3_client;
+#[cfg(not(feature = "cloud"))]
+use crate::io::s3_unsupported;
+#[cfg(feature = "cloud")]
+use crate::io::sums::aws::{S3Builder, S3};
+use crate::io::sums::cache::DownloadCache;
 use crate::io::sums::file::FileBuilder;
-use crate::io::{default_s3_client, Provider};
-use aws_sdk_s3::Client;
+use crate::io::sums::http::Http;
+use crate::io::sums::source::SumsSource;
+use crate::io::{Client, Provider};
 use dyn_clone::DynClone;
 use futures_util::Stream;
 use std::collections::HashSet;
 use std::pin::Pin;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use tokio::io::AsyncRead;
 
+#[cfg(feature = "cloud")]
 pub mod aws;
+pub mod cache;
 pub mod channel;
+pub mod decompress;
 pub mod file;
+pub mod http;
+pub mod normalize;
+pub mod source;
+pub mod sparse;
+pub mod tee;
 
 /// The type returned when converting a shared reader into a stream.
 pub type ReaderStream = Pin<Box<dyn Stream<Item = Result<Arc<[u8]>>> + Send>>;
@@ -32,6 +50,10 @@ pub trait SharedReader {
     /// Convert the shared reader into a stream of the resulting bytes of reading
     /// the chunks.
     fn as_stream(&mut self) -> ReaderStream;
+
+    /// Add the time spent waiting for reads of the underlying reader to the counter in
+    /// nanoseconds. Reads are not timed by default.
+    fn time_reads(&mut self, _read_nanos: Arc<AtomicU64>) {}
 }
 
 /// Read operations on file based or cloud sums files.
@@ -40,8 +62,18 @@ pub trait ObjectSums: DynClone {
     /// Get an existing sums file for this object.
     async fn sums_file(&mut self) -> Result<Option<SumsFile>>;
 
-    /// Get a reader to the sums files.
-    async fn reader(&mut self) -> Result<Box<dyn AsyncRead + Unpin + Send>>;
+    /// Get the sums recorded in the metadata of the object itself rather than in a sums file,
+    /// such as the `ETag` of S3 objects.
+    async fn metadata_sums(&mut self) -> Result<Option<SumsFile>> {
+        Ok(None)
+    }
+
+    /// Get a reader to the sums files. If multipart options are specified, only the
+    /// range between the start and end is read.
+    async fn reader(
+        &mut self,
+        range: Option<MultiPartOptions>,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>>;
 
     /// Get the file size of the target file.
     async fn file_size(&mut self) -> Result<Option<u64>>;
@@ -52,6 +84,18 @@ pub trait ObjectSums: DynClone {
     /// Get the location of the object.
     fn location(&self) -> String;
 
+    /// Get the device and inode of the target file if this is a local file. Locations with the
+    /// same device and inode refer to the same physical file, e.g. through hardlinks or symlinks.
+    async fn file_id(&mut self) -> Result<Option<(u64, u64)>> {
+        Ok(None)
+    }
+
+    /// Get the metadata of the target file, such as the modification time, that can be
+    /// recorded in sums files.
+    async fn file_metadata(&mut self) -> Result<FileMetadata> {
+        Ok(Default::default())
+    }
+
     /// Any accumulated recoverable api errors.
     fn api_errors(&self) -> HashSet<ApiError>;
 }
@@ -63,15 +107,158 @@ dyn_clone::clone_trait_object!(ObjectSums);
 pub struct ObjectSumsBuilder {
     client: Option<Arc<Client>>,
     avoid_get_object_attributes: bool,
+    format: SumsFormat,
+    storage: SumsStorage,
+    sums_destination: Option<String>,
+    sums_source: Option<String>,
+    output_dir: Option<String>,
+    sparse: bool,
+    download_cache: Option<DownloadCache>,
 }
 
 impl ObjectSumsBuilder {
-    pub async fn build(self, url: String) -> Result<Box<dyn ObjectSums + Send>> {
+    pub async fn build(mut self, url: String) -> Result<Box<dyn ObjectSums + Send>> {
+        let Some(sums_source) = self.sums_source.take() else {
+            return Ok(self.build_object(url).await?);
+        };
+
+        if self.sums_destination.is_some() {
+            return Err(ParseError(
+                "a sums source cannot be used with a sums destination".to_string(),
+            ));
+        }
+        if self.output_dir.is_some() {
+            return Err(ParseError(
+                "a sums source cannot be used with an output directory".to_string(),
+            ));
+        }
+        let Provider::File { file: directory } = Provider::try_from(sums_source.as_str())? else {
+            return Err(ParseError(format!(
+                "sums source `{}` must be a local directory",
+                sums_source
+            )));
+        };
+
+        // Sums files of local files keep the path of the file, as with a sums destination.
+        let format = self.format;
+        let key = match Provider::try_from(url.as_str())? {
+            Provider::File { .. } => {
+                return Ok(self
+                    .set_sums_destination(Some(directory))
+                    .build_object(url)
+                    .await?);
+            }
+            #[cfg(not(feature = "cloud"))]
+            Provider::S3 { .. } => return Err(s3_unsupported(&url)),
+            #[cfg(feature = "cloud")]
+            Provider::S3 {
+                bucket,
+                key,
+                version_id,
+            } => {
+                if self.storage == SumsStorage::Tags {
+                    return Err(ParseError(
+                        "a sums source cannot be used when storing sums in object tags".to_string(),
+                    ));
+                }
+                // Objects with the same key in different buckets or versions have different sums.
+                format!(
+                    "{}/{}",
+                    bucket,
+                    S3::format_versioned_key(&key, version_id.as_deref())
+                )
+            }
+            Provider::Http { url } => {
+                let parsed = reqwest::Url::parse(&url)
+                    .map_err(|err| ParseError(format!("invalid url `{}`: {}", url, err)))?;
+                // Objects with the same path on different hosts have different sums.
+                let host = parsed.host_str().unwrap_or_default();
+                match parsed.port() {
+                    Some(port) => format!("{}:{}/{}", host, port, parsed.path()),
+                    None => format!("{}/{}", host, parsed.path()),
+                }
+            }
+        };
+
+        let sums = FileBuilder::default()
+            .with_file(join_under(&directory, &key)?)
+            .with_format(format)
+            .build()?;
+        Ok(Box::new(SumsSource::new(
+            self.build_object(url).await?,
+            sums,
+        )))
+    }
+
+    /// Build the object without a sums source.
+    async fn build_object(self, url: String) -> Result<Box<dyn ObjectSums + Send + Sync>> {
+        // An explicit sums file ending takes precedence over the configured format, unless the
+        // configured format has the same ending, e.g. pretty JSON for a `.sums` file.
+        let format = match SumsFormat::from_name(&url) {
+            Some(format) if format.ending() != self.format.ending() => format,
+            _ => self.format,
+        };
+        let destination_error = |destination: &str| {
+            ParseError(format!(
+                "sums destination `{}` must use the same storage as `{}`",
+                destination, url
+            ))
+        };
+
         match Provider::try_from(url.as_str())? {
             Provider::File { file } => {
-                Ok(Box::new(FileBuilder::default().with_file(file).build()?))
+                let sums_destination = self
+                    .sums_destination
+                    .as_deref()
+                    .map(|destination| match Provider::try_from(destination)? {
+                        Provider::File { file } => Ok(file),
+                        Provider::S3 { .. } | Provider::Http { .. } => {
+                            Err(destination_error(destination))
+                        }
+                    })
+                    .transpose()?;
+                if sums_destination.is_some() && self.output_dir.is_some() {
+                    return Err(ParseError(
+                        "a sums destination cannot be used with an output directory".to_string(),
+                    ));
+                }
+
+                Ok(Box::new(
+                    FileBuilder::default()
+                        .with_file(file)
+                        .with_format(format)
+                        .set_sums_destination(sums_destination)
+                        .set_output_dir(self.output_dir)
+                        .with_sparse(self.sparse)
+                        .build()?,
+                ))
             }
-            Provider::S3 { bucket, key } => {
+            #[cfg(not(feature = "cloud"))]
+            Provider::S3 { .. } => Err(s3_unsupported(&url)),
+            #[cfg(feature = "cloud")]
+            Provider::S3 {
+                bucket,
+                key,
+                version_id,
+            } => {
+                if self.storage == SumsStorage::Tags && self.sums_destination.is_some() {
+                    return Err(ParseError(
+                        "a sums destination cannot be used when storing sums in object tags"
+                            .to_string(),
+                    ));
+                }
+
+                let sums_destination = self
+                    .sums_destination
+                    .as_deref()
+                    .map(|destination| {
+                        if !destination.starts_with("s3://") {
+                            return Err(destination_error(destination));
+                        }
+                        Provider::parse_s3_prefix(destination)
+                    })
+                    .transpose()?;
+
                 let client = match self.client {
                     Some(client) => client,
                     None => Arc::new(default_s3_client().await?),
@@ -80,11 +267,26 @@ impl ObjectSumsBuilder {
                     S3Builder::default()
                         .with_key(key)
                         .with_bucket(bucket)
+                        .set_version_id(version_id)
                         .with_client(client)
                         .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
+                        .with_format(format)
+                        .with_storage(self.storage)
+                        .set_sums_destination(sums_destination)
+                        .set_download_cache(self.download_cache)
                         .build()?,
                 ))
             }
+            Provider::Http { url } => {
+                if self.sums_destination.is_some() {
+                    return Err(ParseError(format!(
+                        "a sums destination cannot be used with the read-only location `{}`",
+                        url
+                    )));
+                }
+
+                Ok(Box::new(Http::new(url)))
+            }
         }
     }
 
@@ -99,4 +301,54 @@ impl ObjectSumsBuilder {
         self.avoid_get_object_attributes = avoid_get_object_attributes;
         self
     }
+
+    /// Set the format used to read and write sums files.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set where sums files are stored for S3 objects.
+    pub fn with_storage(mut self, storage: SumsStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Set a location to read and write sums files in, rather than next to the object. This
+    /// must use the same storage as the object, e.g. a directory for files or an
+    /// `s3://bucket/prefix/` for S3 objects.
+    pub fn set_sums_destination(mut self, sums_destination: Option<String>) -> Self {
+        self.sums_destination = sums_destination;
+        self
+    }
+
+    /// Set a local directory to read and write sums files in, which can be used with objects
+    /// in any storage. The sums file is keyed by the bucket, key and version of S3 objects, e.g.
+    /// `manifests/bucket/key.sums`, by the host and path of HTTP URLs, or by the path of local
+    /// files. Keys that would escape the directory using `..` are rejected.
+    pub fn set_sums_source(mut self, sums_source: Option<String>) -> Self {
+        self.sums_source = sums_source;
+        self
+    }
+
+    /// Set a local directory to read and write the sums files of local files in, which mirrors
+    /// their absolute paths, e.g. `sums/data/file.sums` for `/data/file`. This is useful when
+    /// the files are read-only. Objects in other storage are not affected.
+    pub fn set_output_dir(mut self, output_dir: Option<String>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Skip reading the holes of sparse local files. Objects in other storage are not affected.
+    pub fn with_sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    /// Set a local cache of downloaded objects, so that reading an S3 object again reuses its
+    /// bytes if its `ETag` has not changed. Objects in other storage are not affected.
+    pub fn set_download_cache(mut self, download_cache: Option<DownloadCache>) -> Self {
+        self.download_cache = download_cache;
+        self
+    }
 }
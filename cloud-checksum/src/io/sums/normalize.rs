@@ -0,0 +1,126 @@
+//! A reader which normalizes line endings of text data.
+//!
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// The size of the buffer used to read from the inner reader.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// Wraps a reader, converting CRLF line endings to LF. Lone CR bytes are kept as they are.
+#[derive(Debug)]
+pub struct NormalizeNewlines<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    pending_cr: bool,
+    eof: bool,
+}
+
+impl<R> NormalizeNewlines<R> {
+    /// Create a new normalizing reader.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(BUFFER_SIZE),
+            pos: 0,
+            pending_cr: false,
+            eof: false,
+        }
+    }
+
+    /// Convert a chunk of data, holding back a trailing CR until it is known whether an LF
+    /// follows it in the next chunk.
+    fn normalize(&mut self, data: &[u8]) {
+        self.buf.clear();
+        self.pos = 0;
+
+        for &byte in data {
+            if self.pending_cr && byte != b'\n' {
+                self.buf.push(b'\r');
+            }
+
+            self.pending_cr = byte == b'\r';
+            if !self.pending_cr {
+                self.buf.push(byte);
+            }
+        }
+    }
+}
+
+impl<R> AsyncRead for NormalizeNewlines<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        out: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            if this.pos < this.buf.len() {
+                let n = out.remaining().min(this.buf.len() - this.pos);
+                out.put_slice(&this.buf[this.pos..this.pos + n]);
+                this.pos += n;
+                return Poll::Ready(Ok(()));
+            }
+
+            if this.eof {
+                if this.pending_cr {
+                    this.pending_cr = false;
+                    this.buf = vec![b'\r'];
+                    this.pos = 0;
+                    continue;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut data = [0; BUFFER_SIZE];
+            let mut read = ReadBuf::new(&mut data);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read))?;
+
+            if read.filled().is_empty() {
+                this.eof = true;
+            } else {
+                this.normalize(read.filled());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use anyhow::Result;
+    use tokio::io::AsyncReadExt;
+
+    async fn normalize(chunks: &[&[u8]]) -> Result<Vec<u8>> {
+        // Chained readers return each chunk from a separate read.
+        let reader = chunks.iter().fold(
+            Box::new(tokio::io::empty()) as Box<dyn AsyncRead + Unpin + '_>,
+            |reader, chunk| Box::new(reader.chain(*chunk)),
+        );
+
+        let mut out = vec![];
+        NormalizeNewlines::new(reader).read_to_end(&mut out).await?;
+        Ok(out)
+    }
+
+    #[tokio::test]
+    async fn normalize_newlines() -> Result<()> {
+        assert_eq!(normalize(&[b"a\r\nb\r\n"]).await?, b"a\nb\n");
+        assert_eq!(normalize(&[b"a\nb\n"]).await?, b"a\nb\n");
+        // A CR at the end of one chunk followed by an LF in the next.
+        assert_eq!(normalize(&[b"a\r", b"\nb\r", b"\n"]).await?, b"a\nb\n");
+        // Lone CRs, including at the end of the input, are kept.
+        assert_eq!(normalize(&[b"a\rb\r", b"c\r"]).await?, b"a\rb\rc\r");
+        assert_eq!(normalize(&[b"\r\r\n", b"\r"]).await?, b"\r\n\r");
+        assert_eq!(normalize(&[]).await?, b"");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+//! Read and write sums files in a different location to the objects that they describe.
+//!
+
+use crate::checksum::file::{FileMetadata, SumsFile};
+use crate::error::{ApiError, Result};
+use crate::io::copy::MultiPartOptions;
+use crate::io::sums::file::File;
+use crate::io::sums::ObjectSums;
+use std::collections::HashSet;
+use std::path::Path;
+use tokio::fs;
+use tokio::io::AsyncRead;
+
+/// An object whose sums file is stored in a local directory, keyed by the path of the object.
+/// The data, size and metadata of the object are still read from the object itself.
+#[derive(Clone)]
+pub struct SumsSource {
+    object: Box<dyn ObjectSums + Send + Sync>,
+    sums: File,
+}
+
+impl SumsSource {
+    /// Create a new sums source for an object, where `sums` is the file that the sums file is
+    /// named after, e.g. `manifests/key` for `manifests/key.sums`.
+    pub fn new(object: Box<dyn ObjectSums + Send + Sync>, sums: File) -> Self {
+        Self { object, sums }
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectSums for SumsSource {
+    async fn sums_file(&mut self) -> Result<Option<SumsFile>> {
+        let metadata_sums = self.object.metadata_sums().await?;
+
+        match (metadata_sums, self.sums.get_existing_sums().await?) {
+            (Some(metadata_sums), Some(existing)) => Ok(Some(metadata_sums.merge(existing)?)),
+            (metadata_sums, existing) => Ok(existing.or(metadata_sums)),
+        }
+    }
+
+    async fn reader(
+        &mut self,
+        range: Option<MultiPartOptions>,
+    ) -> Result<Box<dyn AsyncRead + Unpin + Send>> {
+        self.object.reader(range).await
+    }
+
+    async fn file_size(&mut self) -> Result<Option<u64>> {
+        self.object.file_size().await
+    }
+
+    async fn write_sums_file(&self, sums_file: &SumsFile) -> Result<()> {
+        // Keys can contain directories which may not exist yet.
+        if let Some(parent) = Path::new(&self.sums.location()).parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        self.sums.write_sums(sums_file).await
+    }
+
+    fn location(&self) -> String {
+        self.object.location()
+    }
+
+    async fn file_id(&mut self) -> Result<Option<(u64, u64)>> {
+        self.object.file_id().await
+    }
+
+    async fn file_metadata(&mut self) -> Result<FileMetadata> {
+        self.object.file_metadata().await
+    }
+
+    fn api_errors(&self) -> HashSet<ApiError> {
+        self.object.api_errors()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::checksum::file::SumsFile;
+    use crate::io::sums::http::test::serve;
+    use crate::io::sums::ObjectSumsBuilder;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn sums_source() -> Result<()> {
+        let tmp = tempdir()?;
+        let manifests = tmp.path().join("manifests").to_string_lossy().to_string();
+        let url = serve(b"test".to_vec()).await?;
+
+        let builder = || ObjectSumsBuilder::default().set_sums_source(Some(manifests.clone()));
+
+        let mut object = builder().build(url.clone()).await?;
+        assert_eq!(object.location(), url);
+        assert_eq!(object.file_size().await?, Some(4));
+        assert!(object.sums_file().await?.is_none());
+
+        // The sums file is keyed by the host and path of the URL, even though HTTP is read-only.
+        let sums = SumsFile::new(Some(4), Default::default());
+        object.write_sums_file(&sums).await?;
+        let host = url
+            .trim_start_matches("http://")
+            .trim_end_matches("/object");
+        assert!(tmp
+            .path()
+            .join(format!("manifests/{}/object.sums", host))
+            .exists());
+        assert_eq!(
+            builder().build(url.clone()).await?.sums_file().await?,
+            Some(sums)
+        );
+
+        // The same path on a different host does not use the same sums file.
+        let other_host = url.replace("127.0.0.1", "localhost");
+        assert!(builder()
+            .build(other_host)
+            .await?
+            .sums_file()
+            .await?
+            .is_none());
+
+        // Local files keep their path in the sums source.
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&file, b"test").await?;
+        let sums = SumsFile::new(Some(4), Default::default());
+        builder()
+            .build(file.clone())
+            .await?
+            .write_sums_file(&sums)
+            .await?;
+        let sums = format!("{}/{}.sums", manifests, file.trim_start_matches('/'));
+        assert!(tokio::fs::try_exists(sums).await?);
+
+        // The sums source must be local, and cannot be combined with a sums destination.
+        assert!(ObjectSumsBuilder::default()
+            .set_sums_source(Some("s3://bucket/prefix/".to_string()))
+            .build(url.clone())
+            .await
+            .is_err());
+        assert!(builder()
+            .set_sums_destination(Some(manifests.clone()))
+            .build(url)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cloud")]
+    #[tokio::test]
+    async fn sums_source_s3() -> Result<()> {
+        use aws_sdk_s3::Client;
+        use aws_smithy_mocks::mock_client;
+        use std::sync::Arc;
+
+        let tmp = tempdir()?;
+        let manifests = tmp.path().join("manifests").to_string_lossy().to_string();
+        let write = |url: &str| {
+            let url = url.to_string();
+            let manifests = manifests.clone();
+            async move {
+                let client: Client = mock_client!(aws_sdk_s3, &[]);
+                ObjectSumsBuilder::default()
+                    .set_client(Some(Arc::new(client)))
+                    .set_sums_source(Some(manifests))
+                    .build(url)
+                    .await?
+                    .write_sums_file(&SumsFile::default())
+                    .await?;
+                Ok::<_, anyhow::Error>(())
+            }
+        };
+
+        // The same key in different buckets or versions does not use the same sums file.
+        write("s3://a/path/key").await?;
+        write("s3://b/path/key").await?;
+        write("s3://a/path/key?versionId=version").await?;
+        assert!(tmp.path().join("manifests/a/path/key.sums").exists());
+        assert!(tmp.path().join("manifests/b/path/key.sums").exists());
+        assert!(tmp
+            .path()
+            .join("manifests/a/path/key.version.sums")
+            .exists());
+
+        // Empty segments are removed, and keys cannot escape the sums source.
+        write("s3://c/path//./key").await?;
+        assert!(tmp.path().join("manifests/c/path/key.sums").exists());
+        assert!(write("s3://a/../../key").await.is_err());
+        assert!(!tmp.path().join("key.sums").exists());
+
+        Ok(())
+    }
+}
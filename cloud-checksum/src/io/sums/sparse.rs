@@ -0,0 +1,243 @@
+//! A reader which skips the holes of sparse files.
+//!
+
+use crate::error::Result;
+use std::collections::VecDeque;
+use std::io;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+use tokio::task::spawn_blocking;
+
+/// The size of the buffer used to read data and produce the zeros of holes.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// A region of a file which either contains data or is a hole that reads as zeros.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Extent {
+    offset: u64,
+    length: u64,
+    hole: bool,
+}
+
+impl Extent {
+    /// Create an extent containing data.
+    fn data(offset: u64, length: u64) -> Self {
+        Self {
+            offset,
+            length,
+            hole: false,
+        }
+    }
+
+    /// Create an extent for a hole.
+    fn hole(offset: u64, length: u64) -> Self {
+        Self {
+            offset,
+            length,
+            hole: true,
+        }
+    }
+}
+
+/// Reads a file while producing the zeros of holes in memory instead of reading them from
+/// the file. The output is identical to reading the file normally. Holes are only detected on
+/// Linux, and the whole file is read as data on other systems or on filesystems that do not
+/// support them.
+#[derive(Debug)]
+pub struct SparseReader {
+    file: fs::File,
+    extents: VecDeque<Extent>,
+    positioned: bool,
+    seeking: bool,
+    buf: Vec<u8>,
+}
+
+impl SparseReader {
+    /// Open a file for reading starting at the offset, optionally only reading up to the
+    /// length.
+    pub async fn open(path: impl AsRef<Path>, offset: u64, length: Option<u64>) -> Result<Self> {
+        let file = fs::File::open(path).await?.into_std().await;
+        let (file, extents) = spawn_blocking(move || {
+            let end = match length {
+                Some(length) => offset + length,
+                None => file.metadata()?.len(),
+            };
+            let extents = extents(&file, offset, end)?;
+            Ok::<_, io::Error>((file, extents))
+        })
+        .await??;
+
+        Ok(Self {
+            file: fs::File::from_std(file),
+            extents: extents.into(),
+            positioned: false,
+            seeking: false,
+            buf: vec![0; BUFFER_SIZE],
+        })
+    }
+}
+
+/// Find the data and hole extents of a file between the start and end.
+#[cfg(target_os = "linux")]
+fn extents(file: &std::fs::File, start: u64, end: u64) -> io::Result<Vec<Extent>> {
+    use std::os::fd::AsRawFd;
+
+    let seek = |offset: u64, whence: libc::c_int| {
+        let offset = libc::off_t::try_from(offset).map_err(io::Error::other)?;
+        // SAFETY: `lseek` only changes the offset of the open file descriptor, which is
+        // repositioned before any data is read.
+        let result = unsafe { libc::lseek(file.as_raw_fd(), offset, whence) };
+        u64::try_from(result).map_err(|_| io::Error::last_os_error())
+    };
+
+    let mut extents = vec![];
+    let mut position = start;
+    while position < end {
+        let data = match seek(position, libc::SEEK_DATA) {
+            Ok(data) => data.min(end),
+            // There is no more data after the position, so the rest is a hole.
+            Err(err) if err.raw_os_error() == Some(libc::ENXIO) => end,
+            // The filesystem does not support finding holes.
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) && extents.is_empty() => {
+                return Ok(vec![Extent::data(start, end - start)]);
+            }
+            Err(err) => return Err(err),
+        };
+        if data > position {
+            extents.push(Extent::hole(position, data - position));
+        }
+        if data == end {
+            break;
+        }
+
+        let hole = seek(data, libc::SEEK_HOLE)?.min(end);
+        extents.push(Extent::data(data, hole - data));
+        position = hole;
+    }
+
+    Ok(extents)
+}
+
+/// Find the data and hole extents of a file between the start and end.
+#[cfg(not(target_os = "linux"))]
+fn extents(_file: &std::fs::File, start: u64, end: u64) -> io::Result<Vec<Extent>> {
+    Ok(vec![Extent::data(start, end - start)])
+}
+
+impl AsyncRead for SparseReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        out: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        loop {
+            let Some(extent) = this.extents.front_mut() else {
+                return Poll::Ready(Ok(()));
+            };
+            if extent.length == 0 {
+                this.extents.pop_front();
+                this.positioned = false;
+                continue;
+            }
+
+            let n = usize::try_from(extent.length)
+                .unwrap_or(usize::MAX)
+                .min(out.remaining())
+                .min(BUFFER_SIZE);
+
+            let n = if extent.hole {
+                this.buf[..n].fill(0);
+                out.put_slice(&this.buf[..n]);
+                n
+            } else {
+                // Data after a hole is read from the start of its extent.
+                if !this.positioned {
+                    if !this.seeking {
+                        Pin::new(&mut this.file).start_seek(SeekFrom::Start(extent.offset))?;
+                        this.seeking = true;
+                    }
+                    ready!(Pin::new(&mut this.file).poll_complete(cx))?;
+                    this.seeking = false;
+                    this.positioned = true;
+                }
+
+                let mut read = ReadBuf::new(&mut this.buf[..n]);
+                ready!(Pin::new(&mut this.file).poll_read(cx, &mut read))?;
+                if read.filled().is_empty() {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "file was truncated while reading",
+                    )));
+                }
+                out.put_slice(read.filled());
+                read.filled().len()
+            };
+
+            extent.offset += n as u64;
+            extent.length -= n as u64;
+            return Poll::Ready(Ok(()));
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::test::TestFileBuilder;
+    use anyhow::Result;
+    use rand::RngCore;
+    use std::io::{Seek, Write};
+    use tempfile::tempdir;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn sparse_reader() -> Result<()> {
+        let tmp = tempdir()?;
+        let path = tmp.path().join("sparse");
+
+        // Data separated by holes, with a hole at the end.
+        let mut data = vec![0; 10000];
+        TestFileBuilder::default()
+            .with_constant_seed()
+            .into_rng()
+            .fill_bytes(&mut data);
+        let mut file = std::fs::File::create(&path)?;
+        for offset in [0, 1 << 20, 3 << 20] {
+            file.seek(SeekFrom::Start(offset))?;
+            file.write_all(&data)?;
+        }
+        file.set_len(5 << 20)?;
+        drop(file);
+
+        let path = &path;
+        let read = |offset, length| async move {
+            let mut out = vec![];
+            SparseReader::open(path, offset, length)
+                .await?
+                .read_to_end(&mut out)
+                .await?;
+            Ok::<_, anyhow::Error>(out)
+        };
+
+        let expected = std::fs::read(path)?;
+        assert_eq!(read(0, None).await?, expected);
+        // Ranges starting and ending inside data and holes.
+        for (offset, length) in [
+            (500, 2 << 20),
+            ((1 << 20) + 500, 100),
+            (2 << 20, 3 << 20),
+            (5 << 20, 0),
+        ] {
+            let range = usize::try_from(offset)?..usize::try_from(offset + length)?;
+            assert_eq!(read(offset, Some(length)).await?, expected[range]);
+        }
+
+        Ok(())
+    }
+}
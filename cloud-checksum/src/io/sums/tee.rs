@@ -0,0 +1,114 @@
+//! A reader which writes a copy of the data that it reads.
+//!
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Wraps a reader, writing everything that is read from it to a writer, e.g. to save the
+/// input while computing its checksums. The writer is flushed once the reader ends.
+#[derive(Debug)]
+pub struct TeeReader<R, W> {
+    inner: R,
+    writer: W,
+    pending: Vec<u8>,
+    written: usize,
+}
+
+impl<R, W> TeeReader<R, W> {
+    /// Create a new tee reader.
+    pub fn new(inner: R, writer: W) -> Self {
+        Self {
+            inner,
+            writer,
+            pending: vec![],
+            written: 0,
+        }
+    }
+}
+
+impl<R, W> TeeReader<R, W>
+where
+    W: AsyncWrite + Unpin,
+{
+    /// Write the data of previous reads that has not been written yet.
+    fn poll_write_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.pending.len() {
+            let n =
+                ready!(Pin::new(&mut self.writer).poll_write(cx, &self.pending[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+
+        self.pending.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R, W> AsyncRead for TeeReader<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        out: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        // The previous data must be written before reading more, so that it stays in order.
+        ready!(this.poll_write_pending(cx))?;
+
+        let filled = out.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, out))?;
+        let data = &out.filled()[filled..];
+        if data.is_empty() {
+            ready!(Pin::new(&mut this.writer).poll_flush(cx))?;
+            return Poll::Ready(Ok(()));
+        }
+
+        // Start writing straight away, and finish writing on the next read if the writer is
+        // not ready.
+        this.pending.extend_from_slice(data);
+        if let Poll::Ready(Err(err)) = this.poll_write_pending(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use anyhow::Result;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn tee_reader() -> Result<()> {
+        let data: Vec<u8> = (0..=255u8).cycle().take(100000).collect();
+
+        // A small duplex buffer means that the writer is often not ready.
+        let (writer, mut copy) = tokio::io::duplex(1000);
+        let copied = tokio::spawn(async move {
+            let mut out = vec![];
+            copy.read_to_end(&mut out).await?;
+            Ok::<_, std::io::Error>(out)
+        });
+
+        let mut out = vec![];
+        let mut tee = TeeReader::new(data.as_slice(), writer);
+        tee.read_to_end(&mut out).await?;
+        drop(tee);
+
+        assert_eq!(out, data);
+        assert_eq!(copied.await??, data);
+
+        Ok(())
+    }
+}
@@ -4,6 +4,7 @@ pub mod task;
 
 pub mod cli;
 pub mod io;
+pub mod profile;
 pub mod stats;
 #[doc(hidden)]
 pub mod test;
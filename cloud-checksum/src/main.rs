@@ -4,6 +4,7 @@ use cloud_checksum::error::Result;
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Command::parse_args()?;
+    args.logging.init()?;
 
     args.execute().await?;
 
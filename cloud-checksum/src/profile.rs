@@ -0,0 +1,134 @@
+//! Checksum profiles which set default options from a config file.
+//!
+
+use crate::checksum::file::SumsFormat;
+use crate::checksum::Ctx;
+use crate::error::Error::ParseError;
+use crate::error::Result;
+use parse_size::parse_size;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// The location of the profiles file within the config directory.
+pub const PROFILES_FILE: &str = "cloud-checksum/profiles.toml";
+
+/// A named set of default options, e.g. a standard set of checksums that a team uses so that
+/// everyone produces comparable sums files. Options set on the command line or using
+/// environment variables take precedence over the profile.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Profile {
+    /// The checksums to generate.
+    pub(crate) checksum: Option<Vec<Ctx>>,
+    /// The part size to use for multipart copies, e.g. `16mib`.
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub(crate) part_size: Option<u64>,
+    /// The number of inputs or parts to process at the same time.
+    pub(crate) concurrency: Option<u64>,
+    /// The format of sums files to read and write.
+    pub(crate) format: Option<SumsFormat>,
+}
+
+impl Profile {
+    /// The default profiles file, under `$XDG_CONFIG_HOME` or `~/.config`.
+    pub fn default_file() -> Option<PathBuf> {
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .filter(|config| !config.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config.join(PROFILES_FILE))
+    }
+
+    /// Load a profile by name from a profiles file. Each profile is a table in the file, e.g.
+    /// `[standard]`.
+    pub fn load(file: &Path, name: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(file).map_err(|err| {
+            ParseError(format!(
+                "failed to read profiles file `{}`: {}",
+                file.display(),
+                err
+            ))
+        })?;
+
+        Self::parse(&contents, name)
+            .map_err(|err| ParseError(format!("in profiles file `{}`: {}", file.display(), err)))
+    }
+
+    /// Parse a profile by name from the contents of a profiles file.
+    pub fn parse(contents: &str, name: &str) -> Result<Self> {
+        let mut profiles: HashMap<String, Profile> =
+            toml::from_str(contents).map_err(|err| ParseError(err.to_string()))?;
+
+        profiles
+            .remove(name)
+            .ok_or_else(|| ParseError(format!("profile `{}` does not exist", name)))
+    }
+}
+
+/// Deserialize a size with an optional unit, e.g. `16mib`.
+fn deserialize_size<'de, D>(deserializer: D) -> std::result::Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|size| parse_size(&size).map_err(D::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    const PROFILES: &str = r#"
+[standard]
+checksum = ["sha256", "crc32c", "md5-aws-16mib"]
+part-size = "16mib"
+concurrency = 4
+format = "msgpack"
+
+[empty]
+"#;
+
+    #[test]
+    fn parse_profile() -> Result<()> {
+        let profile = Profile::parse(PROFILES, "standard")?;
+        assert_eq!(
+            profile,
+            Profile {
+                checksum: Some(vec![
+                    "sha256".parse()?,
+                    "crc32c".parse()?,
+                    "md5-aws-16mib".parse()?
+                ]),
+                part_size: Some(16 * 1024 * 1024),
+                concurrency: Some(4),
+                format: Some(SumsFormat::Msgpack),
+            }
+        );
+        assert_eq!(Profile::parse(PROFILES, "empty")?, Profile::default());
+
+        assert!(Profile::parse(PROFILES, "missing").is_err());
+        assert!(Profile::parse("[standard]\nunknown = 1", "standard").is_err());
+        assert!(Profile::parse("[standard]\nchecksum = [\"abc\"]", "standard").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_profile() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("profiles.toml");
+        assert!(Profile::load(&file, "standard").is_err());
+
+        std::fs::write(&file, PROFILES)?;
+        assert_eq!(Profile::load(&file, "standard")?.concurrency, Some(4));
+
+        Ok(())
+    }
+}
@@ -1,15 +1,16 @@
 //! Structs related to output statistics.
 //!
 
-use crate::checksum::file::Checksum;
+use crate::checksum::file::{Checksum, SumsFile};
 use crate::checksum::Ctx;
 use crate::cli::CopyMode;
 use crate::error::ApiError;
 use crate::task::check::{CheckTask, GroupBy};
 use crate::task::copy::CopyTask;
 use crate::task::generate::GenerateTask;
+use crate::task::repair::RepairTask;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::time::Duration;
 
 /// Stats from running a `generate` command.
@@ -26,6 +27,12 @@ pub struct GenerateStats {
     /// The API errors if there was permission issues for object attributes.
     #[serde(skip_serializing_if = "HashSet::is_empty")]
     pub(crate) api_errors: HashSet<ApiError>,
+    /// A digest over all the generated sums files when using `--manifest-digest`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) manifest_digest: Option<String>,
+    /// How often the reader channels were full or empty when using `--channel-metrics`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) channel_metrics: Option<ChannelStats>,
 }
 
 impl GenerateStats {
@@ -40,12 +47,57 @@ impl GenerateStats {
             elapsed_seconds,
             stats: stats
                 .into_iter()
-                .filter(|stat| !stat.checksums_generated.0.is_empty())
+                .filter(|stat| {
+                    stat.timed_out || stat.error.is_some() || !stat.checksums_generated.0.is_empty()
+                })
                 .collect(),
             check_stats: check_stats.map(Box::new),
             api_errors,
+            manifest_digest: None,
+            channel_metrics: None,
         }
     }
+
+    /// The number of files that timed out.
+    pub fn n_timed_out(&self) -> usize {
+        self.stats.iter().filter(|stat| stat.timed_out).count()
+    }
+
+    /// The number of files that failed when using `--continue-on-error`.
+    pub fn n_failed(&self) -> usize {
+        self.stats
+            .iter()
+            .filter(|stat| stat.error.is_some())
+            .count()
+    }
+
+    /// Set the digest over all the generated sums files.
+    pub fn set_manifest_digest(mut self, manifest_digest: Option<String>) -> Self {
+        self.manifest_digest = manifest_digest;
+        self
+    }
+
+    /// Set the channel reader metrics.
+    pub fn set_channel_metrics(mut self, channel_metrics: Option<ChannelStats>) -> Self {
+        self.channel_metrics = channel_metrics;
+        self
+    }
+}
+
+/// How often the channels between the reader and the checksum processes had to wait. A high
+/// number of full channels means that the checksums are the bottleneck and the reader was
+/// blocked, whereas a high number of empty channels means that the reader is the bottleneck
+/// and the checksums were starved. Increasing `--channel-capacity` can help if both occur.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// The number of chunks sent to a channel by the reader.
+    pub(crate) n_sent: u64,
+    /// The number of sends where the channel was full and the reader had to wait.
+    pub(crate) n_full: u64,
+    /// The number of chunks received from a channel by the checksum processes.
+    pub(crate) n_received: u64,
+    /// The number of receives where the channel was empty and a checksum process had to wait.
+    pub(crate) n_empty: u64,
 }
 
 /// A checksum pair represents the reason that a check command succeeded.
@@ -97,6 +149,15 @@ pub struct GenerateFileStats {
     pub(crate) updated: bool,
     /// The set of checksums that were generated.
     pub(crate) checksums_generated: ChecksumStats,
+    /// Whether generating checksums for the file did not complete within the operation timeout.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) timed_out: bool,
+    /// The error if generating checksums for the file failed when using `--continue-on-error`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    /// How long reading and computing checksums took when using `--timings`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) timings: Option<GenerateTimings>,
 }
 
 impl GenerateFileStats {
@@ -106,17 +167,182 @@ impl GenerateFileStats {
             input,
             updated,
             checksums_generated,
+            timed_out: false,
+            error: None,
+            timings: None,
+        }
+    }
+
+    /// Create generate stats for a file that timed out.
+    pub fn timed_out(input: String) -> Self {
+        Self {
+            timed_out: true,
+            ..Self::new(input, false, ChecksumStats(vec![]))
+        }
+    }
+
+    /// Create generate stats for a file that failed.
+    pub fn failed(input: String, error: String) -> Self {
+        Self {
+            error: Some(error),
+            ..Self::new(input, false, ChecksumStats(vec![]))
         }
     }
 
     /// Create generate stats from a task.
     pub fn from_task(task: GenerateTask) -> Self {
+        let timings = task.timings();
         let (_, object, updated, checksums_generated) = task.into_inner();
 
-        Self::new(object.location(), updated, checksums_generated.into())
+        Self {
+            timings,
+            ..Self::new(object.location(), updated, checksums_generated.into())
+        }
+    }
+
+    /// Create generate stats from a repair task.
+    pub fn from_repair_task(task: RepairTask) -> Self {
+        let (location, updated, checksums_generated) = task.into_inner();
+
+        Self::new(location, updated, checksums_generated.into())
+    }
+
+    /// Shorten the generated checksums for display if a length is set.
+    pub fn shorten(mut self, len: Option<usize>) -> Self {
+        if let Some(len) = len {
+            self.checksums_generated
+                .0
+                .iter_mut()
+                .for_each(|pair| pair.value = pair.value.shorten(len));
+        }
+        self
+    }
+}
+
+/// How long the reader and the checksum processes of an input took. A reader that spends most
+/// of its time waiting for reads is limited by I/O, and reading more inputs at the same time
+/// with `--concurrency` can help. Checksums that are busy for most of their time are limited
+/// by CPU, and more worker threads, set with the `TOKIO_WORKER_THREADS` environment variable,
+/// can help. If both wait for each other for most of their time, increasing
+/// `--channel-capacity` can help.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct GenerateTimings {
+    /// The task which reads the input and sends it to the checksums.
+    pub(crate) reader: TaskTiming,
+    /// The time that the reader spent waiting for reads of the input to complete in seconds,
+    /// which is the I/O time. This is part of the wall time of the reader but not its busy time,
+    /// because reads wait for the network or the blocking thread pool.
+    pub(crate) read_seconds: f64,
+    /// The tasks which compute each checksum, which are CPU-bound. The wall time is the
+    /// longest of any checksum, and the busy time is the total over all checksums.
+    pub(crate) checksums: TaskTiming,
+}
+
+/// How long a task took.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct TaskTiming {
+    /// The time from the task starting to finishing in seconds.
+    pub(crate) wall_seconds: f64,
+    /// The time that the task was running rather than waiting in seconds, e.g. for the reader
+    /// to wait for the checksums or for the checksums to wait for data.
+    pub(crate) busy_seconds: f64,
+}
+
+impl TaskTiming {
+    /// Create a task timing.
+    pub fn new(wall: Duration, busy: Duration) -> Self {
+        Self {
+            wall_seconds: wall.as_secs_f64(),
+            busy_seconds: busy.as_secs_f64(),
+        }
+    }
+
+    /// Add the timing of another task that ran at the same time, using the longest wall time
+    /// and the total busy time.
+    pub fn add(&mut self, other: TaskTiming) {
+        self.wall_seconds = self.wall_seconds.max(other.wall_seconds);
+        self.busy_seconds += other.busy_seconds;
+    }
+}
+
+/// The differences between generated sums and a baseline sums file when using
+/// `generate --compare-to`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SumsDiff {
+    /// Time taken in seconds.
+    pub(crate) elapsed_seconds: f64,
+    /// The input that sums were generated for.
+    pub(crate) input: String,
+    /// The baseline sums file.
+    pub(crate) baseline: String,
+    /// Checksums that were generated but are not in the baseline.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) added: Vec<ChecksumPair>,
+    /// Checksums in the baseline that were not generated.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) removed: Vec<ChecksumPair>,
+    /// Checksums that have a different value to the baseline.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) changed: Vec<ChangedChecksum>,
+    /// Whether the sums file was updated with the generated sums when using `--update`.
+    pub(crate) updated: bool,
+}
+
+impl SumsDiff {
+    /// Compare generated sums to a baseline sums file.
+    pub fn new(
+        elapsed: Duration,
+        (input, generated): (String, &SumsFile),
+        (baseline, baseline_sums): (String, &SumsFile),
+        updated: bool,
+    ) -> Self {
+        let pairs = |from: &SumsFile, to: &SumsFile| {
+            from.checksums
+                .iter()
+                .filter(|(kind, _)| !to.checksums.contains_key(kind))
+                .map(|(kind, value)| ChecksumPair::new(kind.clone(), value.clone()))
+                .collect()
+        };
+
+        Self {
+            elapsed_seconds: elapsed.as_secs_f64(),
+            input,
+            baseline,
+            added: pairs(generated, baseline_sums),
+            removed: pairs(baseline_sums, generated),
+            changed: generated
+                .checksums
+                .iter()
+                .filter_map(|(kind, value)| {
+                    let baseline = baseline_sums.checksums.get(kind)?;
+                    (baseline != value).then(|| ChangedChecksum {
+                        kind: kind.clone(),
+                        baseline: baseline.clone(),
+                        value: value.clone(),
+                    })
+                })
+                .collect(),
+            updated,
+        }
+    }
+
+    /// Whether the generated sums are different to the baseline.
+    pub fn is_different(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty() || !self.changed.is_empty()
     }
 }
 
+/// A checksum with a different value to the baseline.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChangedChecksum {
+    /// The kind of checksum, e.g. `md5`.
+    pub(crate) kind: Ctx,
+    /// The value of the checksum in the baseline.
+    pub(crate) baseline: Checksum,
+    /// The value of the generated checksum.
+    pub(crate) value: Checksum,
+}
+
 /// Represents stats from a `check` operation.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CheckStats {
@@ -132,7 +358,7 @@ pub struct CheckStats {
     /// Comparison groups. Files in the same group are considered equal or comparable depending
     /// on the comparison type.
     #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub(crate) groups: Vec<Vec<String>>,
+    pub(crate) groups: Vec<Vec<CheckMember>>,
     /// The set of sums that were updated if using `--update`.
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub(crate) updated: Vec<String>,
@@ -142,6 +368,14 @@ pub struct CheckStats {
     /// The API errors if there was permission issues for object attributes.
     #[serde(skip_serializing_if = "HashSet::is_empty")]
     pub(crate) api_errors: HashSet<ApiError>,
+    /// Inputs that could not be read when using `--continue-on-error`, and their errors. These
+    /// are not part of any group.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) failed: BTreeMap<String, String>,
+    /// Groups that are not proven to be the same when using `--min-strength`, because they only
+    /// share checksums that are weaker than the minimum strength.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) weakly_matched: Vec<CheckComparison>,
 }
 
 impl CheckStats {
@@ -159,13 +393,62 @@ impl CheckStats {
             elapsed_seconds,
             comparison_type,
             compared,
-            groups,
+            groups: groups
+                .into_iter()
+                .map(|group| group.into_iter().map(CheckMember::new).collect())
+                .collect(),
             updated,
             generate_stats,
             api_errors,
+            failed: BTreeMap::new(),
+            weakly_matched: vec![],
         }
     }
 
+    /// The number of inputs that failed, including any that failed to generate sums when
+    /// using `--missing`.
+    pub fn n_failed(&self) -> usize {
+        let generate_failed = self.generate_stats.iter().flat_map(|stats| {
+            stats
+                .stats
+                .iter()
+                .filter(|stat| stat.error.is_some())
+                .map(|stat| &stat.input)
+        });
+
+        self.failed
+            .keys()
+            .chain(generate_failed)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Set the groups that only matched using weak checksums.
+    pub fn with_weakly_matched(mut self, weakly_matched: Vec<CheckComparison>) -> Self {
+        self.weakly_matched = weakly_matched;
+        self
+    }
+
+    /// Set the inputs that failed.
+    pub fn with_failed(mut self, failed: BTreeMap<String, String>) -> Self {
+        self.failed = failed;
+        self
+    }
+
+    /// Whether the inputs did not all end up in the same group, i.e. not all inputs are equal
+    /// or comparable depending on the comparison type.
+    pub fn is_mismatch(&self) -> bool {
+        self.groups.len() > 1
+    }
+
+    /// Mark the group members that are the same physical file as another member.
+    pub fn with_hardlinked(mut self, hardlinked: &BTreeSet<String>) -> Self {
+        self.groups.iter_mut().flatten().for_each(|member| {
+            member.hardlinked = hardlinked.contains(&member.location);
+        });
+        self
+    }
+
     /// Create check stats from a task.
     pub fn from_task(
         group_by: GroupBy,
@@ -173,6 +456,9 @@ impl CheckStats {
         elapsed: Duration,
         generate_stats: Option<GenerateStats>,
     ) -> Self {
+        let hardlinked = task.hardlinked().clone();
+        let failed = task.failed().clone();
+        let weakly_matched = task.weakly_matched().to_vec();
         let (objects, compared, updated, api_errors) = task.into_inner();
 
         Self::new(
@@ -184,6 +470,37 @@ impl CheckStats {
             generate_stats,
             api_errors,
         )
+        .with_hardlinked(&hardlinked)
+        .with_failed(failed)
+        .with_weakly_matched(weakly_matched)
+    }
+}
+
+/// A member of a comparison group from a `check` operation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CheckMember {
+    /// The location of the member.
+    pub(crate) location: String,
+    /// Whether this member is the same physical file as another member of the group, such as a
+    /// hardlink or symlink to the same file. These are physically shared rather than just
+    /// logically identical. This only applies to local files.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) hardlinked: bool,
+}
+
+impl CheckMember {
+    /// Create a new member that is not hardlinked.
+    pub fn new(location: String) -> Self {
+        Self {
+            location,
+            hardlinked: false,
+        }
+    }
+}
+
+impl PartialEq<String> for CheckMember {
+    fn eq(&self, other: &String) -> bool {
+        &self.location == other
     }
 }
 
@@ -212,6 +529,14 @@ pub struct CopyStats {
     /// matching checksum.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(crate) reason: Option<ChecksumPair>,
+    /// Whether the copy was verified using a checksum computed while the data was uploaded,
+    /// rather than by checking the sums after the copy.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) verified_during_copy: bool,
+    /// The result of verifying the destination after the copy when using
+    /// `--verify-after-write`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) verified_after_write: Option<WriteVerification>,
     /// The number of retries if there was permission issues for copying metadata or tags.
     pub(crate) n_retries: u64,
     /// The API errors if there was permission issues for copying metadata or tags.
@@ -239,7 +564,17 @@ impl CopyStats {
             skipped,
             sums_mismatch,
             copy_mode: copy_task.copy_mode(),
-            reason: check_stats.as_ref().and_then(Option::<ChecksumPair>::from),
+            reason: check_stats
+                .as_ref()
+                .and_then(Option::<ChecksumPair>::from)
+                .or_else(|| copy_task.verified().cloned())
+                .or_else(|| {
+                    copy_task
+                        .verified_after_write()
+                        .map(|verification| verification.reason.clone())
+                }),
+            verified_during_copy: copy_task.verified().is_some(),
+            verified_after_write: copy_task.verified_after_write().cloned(),
             n_retries: copy_task.n_retries(),
             api_errors: copy_task.api_errors(),
             check_stats,
@@ -247,8 +582,32 @@ impl CopyStats {
     }
 }
 
+/// The result of comparing the destination of a copy to the source after it was written.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WriteVerification {
+    /// The checksum that matched between the source and the destination.
+    pub(crate) reason: ChecksumPair,
+    /// Whether the checksum of the destination came from its native object attributes, such as
+    /// S3 additional checksums, rather than from reading it.
+    pub(crate) native: bool,
+    /// The number of bytes that were read again to compute checksums after the copy. This is
+    /// zero when the source sums and the native checksums of the destination are used.
+    pub(crate) bytes_reread: u64,
+}
+
+impl WriteVerification {
+    /// Create a new write verification.
+    pub fn new(reason: ChecksumPair, native: bool, bytes_reread: u64) -> Self {
+        Self {
+            reason,
+            native,
+            bytes_reread,
+        }
+    }
+}
+
 /// The specific comparison that a `check` performed.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CheckComparison {
     /// The location of files that were affected by this check.
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -263,3 +622,239 @@ impl CheckComparison {
         Self { locations, reason }
     }
 }
+
+/// Information about an object from the `info` command, including the checksums that are
+/// already available natively without reading the object.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ObjectInfo {
+    /// The location of the object.
+    pub(crate) location: String,
+    /// The size of the object.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) size: Option<u64>,
+    /// The storage class of the object, e.g. `STANDARD`.
+    pub(crate) storage_class: String,
+    /// The `ETag` of the object.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) e_tag: Option<String>,
+    /// Whether the object was uploaded using a multipart upload.
+    pub(crate) multipart: bool,
+    /// The number of parts if the object was uploaded using a multipart upload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) part_count: Option<u64>,
+    /// The part size of a multipart upload if all parts except the last have the same size.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) part_size: Option<u64>,
+    /// The checksums that are available from the object metadata.
+    pub(crate) checksums: ChecksumStats,
+}
+
+/// Stats from running the `bisect` command.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BisectStats {
+    /// The input that was checked.
+    pub(crate) input: String,
+    /// The location of the known-good sums file.
+    pub(crate) sums_file: String,
+    /// The AWS checksum whose parts were compared.
+    pub(crate) checksum: String,
+    /// The size recorded in the sums file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) expected_size: Option<u64>,
+    /// The size of the input.
+    pub(crate) size: u64,
+    /// The number of parts recorded in the sums file.
+    pub(crate) n_parts: usize,
+    /// The parts whose checksums differ, including parts that only exist on one side.
+    pub(crate) mismatched: Vec<PartMismatch>,
+}
+
+impl BisectStats {
+    /// Whether any part differs from the sums file.
+    pub fn is_mismatch(&self) -> bool {
+        !self.mismatched.is_empty()
+    }
+}
+
+/// A part whose checksum differs from the known-good sums file.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PartMismatch {
+    /// The zero-based index of the part.
+    pub(crate) index: usize,
+    /// The byte offset where the part starts.
+    pub(crate) offset: u64,
+    /// The length of the part in bytes.
+    pub(crate) length: u64,
+    /// The recorded part checksum, if the sums file has this part.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) expected: Option<String>,
+    /// The computed part checksum, if the input has this part.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) actual: Option<String>,
+}
+
+/// Stats from running the `diff` command.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DiffStats {
+    /// The first object that was compared.
+    pub(crate) first: String,
+    /// The second object that was compared.
+    pub(crate) second: String,
+    /// The number of bytes that are identical before the first difference.
+    pub(crate) bytes_compared: u64,
+    /// The offset of the first byte that differs, if the objects differ.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) offset: Option<u64>,
+    /// The object that ended first, if the objects only differ in their length.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) shorter: Option<String>,
+}
+
+impl DiffStats {
+    /// Whether the objects differ.
+    pub fn is_mismatch(&self) -> bool {
+        self.offset.is_some()
+    }
+}
+
+/// Stats from running the `dataset-id` command.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DatasetIdStats {
+    /// Time taken in seconds.
+    pub(crate) elapsed_seconds: f64,
+    /// The digest that identifies the dataset.
+    pub(crate) dataset_id: String,
+    /// The number of files in the dataset.
+    pub(crate) n_files: usize,
+    /// The total size of the files in bytes.
+    pub(crate) size: u64,
+}
+
+/// Stats from running the `prune` command.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PruneStats {
+    /// Time taken in seconds.
+    pub(crate) elapsed_seconds: f64,
+    /// The checksums removed from the sums file of each input. Inputs without a sums file have
+    /// no checksums removed.
+    pub(crate) pruned: BTreeMap<String, Vec<Ctx>>,
+    /// The API errors if there was permission issues for object attributes.
+    #[serde(skip_serializing_if = "HashSet::is_empty")]
+    pub(crate) api_errors: HashSet<ApiError>,
+}
+
+/// Stats from running the `touch` command.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TouchStats {
+    /// Time taken in seconds.
+    pub(crate) elapsed_seconds: f64,
+    /// The size updates of the sums files that were changed. Sums files that were already up
+    /// to date and inputs without a sums file are not included.
+    pub(crate) touched: BTreeMap<String, SizeUpdate>,
+    /// The API errors if there was permission issues for object attributes.
+    #[serde(skip_serializing_if = "HashSet::is_empty")]
+    pub(crate) api_errors: HashSet<ApiError>,
+}
+
+/// The recorded size of a sums file before and after it was touched. The sizes are the same
+/// if only the metadata changed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeUpdate {
+    /// The size that was previously recorded.
+    pub(crate) previous: Option<u64>,
+    /// The current size of the object.
+    pub(crate) size: u64,
+}
+
+/// Stats from running the `self-test` command.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SelfTestStats {
+    /// Whether every algorithm passed.
+    pub(crate) passed: bool,
+    /// The results of each algorithm.
+    pub(crate) results: Vec<AlgorithmResult>,
+}
+
+impl SelfTestStats {
+    /// Create new self-test stats.
+    pub fn new(results: Vec<AlgorithmResult>) -> Self {
+        Self {
+            passed: results.iter().all(|result| result.passed),
+            results,
+        }
+    }
+
+    /// The number of algorithms that failed.
+    pub fn n_failed(&self) -> usize {
+        self.results.iter().filter(|result| !result.passed).count()
+    }
+}
+
+/// The known-answer results of a single checksum algorithm.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AlgorithmResult {
+    /// The checksum, e.g. `md5` or `md5-aws-256kib`.
+    pub(crate) checksum: String,
+    /// Whether all vectors of the checksum matched.
+    pub(crate) passed: bool,
+    /// The vectors that did not match.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) failures: Vec<VectorFailure>,
+}
+
+impl AlgorithmResult {
+    /// Create a new result for a checksum with no vectors.
+    pub fn new(checksum: String) -> Self {
+        Self {
+            checksum,
+            passed: true,
+            failures: vec![],
+        }
+    }
+
+    /// Add the result of a vector.
+    pub fn add(&mut self, failure: Option<VectorFailure>) {
+        if let Some(failure) = failure {
+            self.passed = false;
+            self.failures.push(failure);
+        }
+    }
+}
+
+/// A known-answer vector that did not match.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct VectorFailure {
+    /// The name of the input.
+    pub(crate) input: String,
+    /// The expected checksum.
+    pub(crate) expected: String,
+    /// The checksum that was computed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) actual: Option<String>,
+    /// The error if the checksum could not be computed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    #[test]
+    fn check_stats_mismatch() {
+        let stats = |groups| {
+            CheckStats::new(
+                0.0,
+                GroupBy::Equality,
+                vec![],
+                groups,
+                vec![],
+                None,
+                HashSet::new(),
+            )
+        };
+
+        assert!(!stats(vec![vec!["a".to_string(), "b".to_string()]]).is_mismatch());
+        assert!(stats(vec![vec!["a".to_string()], vec!["b".to_string()]]).is_mismatch());
+    }
+}
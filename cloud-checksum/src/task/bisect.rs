@@ -0,0 +1,284 @@
+//! Find the byte ranges of an object that differ from the part checksums in a sums file.
+//!
+
+use crate::checksum::file::SumsFile;
+use crate::checksum::Ctx;
+use crate::error::Error::{NotFound, SumsFileError};
+use crate::error::Result;
+use crate::io::copy::{ObjectCopy, ObjectCopyBuilder};
+use crate::io::sums::channel::ChannelReader;
+use crate::io::sums::{ObjectSumsBuilder, SharedReader};
+use crate::io::Client;
+use crate::io::Provider;
+use crate::stats::{BisectStats, PartMismatch};
+use std::sync::Arc;
+use tokio::try_join;
+
+/// Build a bisect task.
+#[derive(Default)]
+pub struct BisectTaskBuilder {
+    input: String,
+    sums_file: Option<String>,
+    checksum: Option<Ctx>,
+    client: Option<Arc<Client>>,
+    capacity: usize,
+}
+
+impl BisectTaskBuilder {
+    /// Set the input to check.
+    pub fn with_input(mut self, input: String) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Set the known-good sums file with recorded part checksums. By default, the sums file of
+    /// the input is used.
+    pub fn set_sums_file(mut self, sums_file: Option<String>) -> Self {
+        self.sums_file = sums_file;
+        self
+    }
+
+    /// Set the AWS checksum whose parts are compared. By default, the recorded checksum with
+    /// the most parts is used, as it narrows down the differences the most.
+    pub fn set_checksum(mut self, checksum: Option<Ctx>) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Set the S3 client to use.
+    pub fn set_client(mut self, client: Option<Arc<Client>>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Set the reader capacity.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Build a bisect task. This reads the known-good sums file, but not the input.
+    pub async fn build(self) -> Result<BisectTask> {
+        let sums_location = self.sums_file.as_deref().unwrap_or(&self.input).to_string();
+        let mut object = ObjectSumsBuilder::default()
+            .set_client(self.client.clone())
+            .build(self.input.to_string())
+            .await?;
+        let expected = match &self.sums_file {
+            Some(sums_file) => {
+                ObjectSumsBuilder::default()
+                    .set_client(self.client.clone())
+                    .build(sums_file.to_string())
+                    .await?
+                    .sums_file()
+                    .await?
+            }
+            None => object.sums_file().await?,
+        }
+        .ok_or_else(|| NotFound(format!("sums file for `{}`", sums_location)))?;
+
+        if expected.range.is_some()
+            || expected.normalized_newlines
+            || expected.decompressed.is_some()
+        {
+            return Err(SumsFileError(
+                "part checksums can only be compared for sums files of the whole input as it is stored"
+                    .to_string(),
+            ));
+        }
+
+        let ctx = match self.checksum {
+            Some(ctx) if expected.parts(&ctx).is_some() => ctx,
+            Some(ctx) => {
+                return Err(SumsFileError(format!(
+                    "the sums file has no part checksums for `{}`",
+                    ctx
+                )))
+            }
+            // Prefer the most parts, using the checksum order to break ties.
+            None => expected
+                .parts
+                .iter()
+                .max_by(|(a, a_parts), (b, b_parts)| {
+                    a_parts.len().cmp(&b_parts.len()).then_with(|| b.cmp(a))
+                })
+                .map(|(ctx, _)| ctx.clone())
+                .ok_or_else(|| {
+                    SumsFileError(
+                        "the sums file has no part checksums, generate it with `--record-parts`"
+                            .to_string(),
+                    )
+                })?,
+        };
+
+        let mut generate = ctx.clone();
+        generate.set_file_size(object.file_size().await?);
+
+        let source = ObjectCopyBuilder::default()
+            .set_client(self.client)
+            .set_source(Some(Provider::try_from(self.input.as_str())?))
+            .build()
+            .await?;
+
+        Ok(BisectTask {
+            input: self.input,
+            sums_location,
+            expected,
+            ctx,
+            generate,
+            source,
+            capacity: self.capacity,
+        })
+    }
+}
+
+/// Execute the bisect task.
+pub struct BisectTask {
+    input: String,
+    sums_location: String,
+    expected: SumsFile,
+    ctx: Ctx,
+    generate: Ctx,
+    source: Box<dyn ObjectCopy + Send + Sync>,
+    capacity: usize,
+}
+
+impl BisectTask {
+    /// Recompute the part checksums of the input and report the parts which differ from the
+    /// known-good sums file.
+    pub async fn run(mut self) -> Result<BisectStats> {
+        let content = self.source.download(None).await?;
+        let mut reader = ChannelReader::new(content.into_inner(), self.capacity);
+        let stream = reader.as_stream();
+
+        let (size, output) =
+            try_join!(reader.read_chunks(), self.generate.generate_output(stream))?;
+        let actual = output.part_checksums.unwrap_or_default();
+        let expected = self.expected.parts(&self.ctx).unwrap_or_default();
+
+        // Parts that only exist in the sums file use the recorded part sizes, repeating the
+        // last part size.
+        let expected_sizes = self.ctx.part_sizes().unwrap_or_default();
+        let expected_size = |index: usize| {
+            expected_sizes
+                .get(index)
+                .or(expected_sizes.last())
+                .copied()
+                .unwrap_or_default()
+        };
+
+        let mut offset = 0;
+        let mut mismatched = vec![];
+        for index in 0..actual.len().max(expected.len()) {
+            let actual = actual.get(index);
+            let expected = expected.get(index).map(|checksum| checksum.as_str());
+            let length = actual
+                .map(|(part_size, _)| *part_size)
+                .unwrap_or_else(|| expected_size(index));
+
+            if actual.map(|(_, checksum)| checksum.as_str()) != expected {
+                mismatched.push(PartMismatch {
+                    index,
+                    offset,
+                    length,
+                    expected: expected.map(ToString::to_string),
+                    actual: actual.map(|(_, checksum)| checksum.to_string()),
+                });
+            }
+            offset += length;
+        }
+
+        Ok(BisectStats {
+            input: self.input,
+            sums_file: self.sums_location,
+            checksum: self.ctx.to_string(),
+            expected_size: self.expected.size,
+            size,
+            n_parts: expected.len(),
+            mismatched,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::task::generate::GenerateTaskBuilder;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn bisect() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("bisect").to_string_lossy().to_string();
+        let mut data: Vec<u8> = (0..=255u8).cycle().take(100).collect();
+        tokio::fs::write(&name, &data).await?;
+
+        let generate = |record_parts| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec!["md5-aws-10b".parse().unwrap(), "md5".parse().unwrap()])
+                .with_capacity(10)
+                .with_record_parts(record_parts)
+                .with_overwrite(true)
+                .write()
+        };
+        let bisect = || {
+            BisectTaskBuilder::default()
+                .with_input(name.to_string())
+                .with_capacity(10)
+        };
+
+        generate(false).build().await?.run().await?;
+        assert!(bisect().build().await.is_err());
+
+        let sums = generate(true).build().await?.run().await?.into_inner().0;
+        assert_eq!(sums.parts(&"md5-aws-10b".parse()?).unwrap().len(), 10);
+        assert!(sums.parts(&"md5".parse()?).is_none());
+
+        let stats = bisect().build().await?.run().await?;
+        assert!(stats.mismatched.is_empty());
+        assert_eq!(stats.n_parts, 10);
+
+        // Corrupt bytes in the fourth and seventh parts.
+        data[35] ^= 0xff;
+        data[69] ^= 0xff;
+        tokio::fs::write(&name, &data).await?;
+
+        let stats = bisect().build().await?.run().await?;
+        assert_eq!(
+            stats
+                .mismatched
+                .iter()
+                .map(|part| (part.index, part.offset, part.length))
+                .collect::<Vec<_>>(),
+            [(3, 30, 10), (6, 60, 10)]
+        );
+
+        // A truncated input reports the missing parts.
+        tokio::fs::write(&name, &data[..85]).await?;
+        let stats = bisect().build().await?.run().await?;
+        assert_eq!(stats.size, 85);
+        assert_eq!(
+            stats
+                .mismatched
+                .iter()
+                .map(|part| (part.index, part.offset, part.length, part.actual.is_some()))
+                .collect::<Vec<_>>(),
+            [
+                (3, 30, 10, true),
+                (6, 60, 10, true),
+                (8, 80, 5, true),
+                (9, 85, 10, false)
+            ]
+        );
+
+        assert!(bisect()
+            .set_checksum(Some("md5".parse()?))
+            .build()
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+//! Compute the rolling and strong checksums of fixed-size blocks of an object.
+//!
+
+use crate::checksum::aws_etag::MIB;
+use crate::checksum::rolling::BlockSums;
+use crate::error::Error::ParseError;
+use crate::error::Result;
+use crate::io::sums::ObjectSumsBuilder;
+use crate::io::Client;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The largest allowed block size. Each block is read into memory before it is checksummed.
+pub const MAX_BLOCK_SIZE: u64 = 64 * MIB;
+
+/// Build a blocks task.
+#[derive(Default)]
+pub struct BlocksTaskBuilder {
+    input: String,
+    block_size: u64,
+    client: Option<Arc<Client>>,
+}
+
+impl BlocksTaskBuilder {
+    /// Set the object to compute block checksums for.
+    pub fn with_input(mut self, input: String) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Set the size of each block in bytes.
+    pub fn with_block_size(mut self, block_size: u64) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Set the S3 client to use.
+    pub fn set_client(mut self, client: Option<Arc<Client>>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Build a blocks task. This opens the object, but does not read it.
+    pub async fn build(self) -> Result<BlocksTask> {
+        if self.block_size == 0 {
+            return Err(ParseError("the block size must not be zero".to_string()));
+        }
+        if self.block_size > MAX_BLOCK_SIZE {
+            return Err(ParseError(format!(
+                "the block size must not be larger than {} bytes",
+                MAX_BLOCK_SIZE
+            )));
+        }
+
+        let reader = ObjectSumsBuilder::default()
+            .set_client(self.client)
+            .build(self.input)
+            .await?
+            .reader(None)
+            .await?;
+
+        Ok(BlocksTask {
+            block_size: self.block_size,
+            reader,
+        })
+    }
+}
+
+/// Execute the blocks task.
+pub struct BlocksTask {
+    block_size: u64,
+    reader: Box<dyn AsyncRead + Unpin + Send>,
+}
+
+impl BlocksTask {
+    /// Read the object one block at a time, computing the checksums of each block.
+    pub async fn run(mut self) -> Result<BlockSums> {
+        let mut sums = BlockSums::new(self.block_size);
+        let mut buf = Vec::with_capacity(usize::try_from(self.block_size)?);
+        loop {
+            buf.clear();
+            let n = (&mut self.reader)
+                .take(self.block_size)
+                .read_to_end(&mut buf)
+                .await?;
+            if n == 0 {
+                return Ok(sums);
+            }
+
+            sums.push(&buf);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::checksum::rolling::BlockSum;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn blocks() -> Result<()> {
+        let tmp = tempdir()?;
+        let input = tmp.path().join("input").to_string_lossy().to_string();
+        let data: Vec<u8> = (0..=255u8).cycle().take(2500).collect();
+        tokio::fs::write(&input, &data).await?;
+
+        let blocks = |block_size| {
+            BlocksTaskBuilder::default()
+                .with_input(input.to_string())
+                .with_block_size(block_size)
+                .build()
+        };
+
+        let sums = blocks(1000).await?.run().await?;
+        assert_eq!(sums.size, 2500);
+        assert_eq!(sums.block_size, 1000);
+        assert_eq!(
+            sums.blocks,
+            data.chunks(1000).map(BlockSum::new).collect::<Vec<_>>()
+        );
+        assert_eq!(sums.blocks[2], BlockSum::new(&data[2000..]));
+
+        assert!(blocks(0).await.is_err());
+        assert!(blocks(MAX_BLOCK_SIZE + 1).await.is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+//! Stream objects to a writer, optionally computing checksums.
+//!
+
+use crate::checksum::file::{ByteRange, Checksum, SumsFile};
+use crate::checksum::Ctx;
+use crate::error::Result;
+use crate::io::copy::{MultiPartOptions, ObjectCopy, ObjectCopyBuilder};
+use crate::io::sums::channel::ChannelReader;
+use crate::io::sums::{ObjectSumsBuilder, SharedReader};
+use crate::io::Client;
+use crate::io::Provider;
+use futures_util::future::join_all;
+use futures_util::StreamExt;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::try_join;
+
+/// Build a cat task.
+#[derive(Default)]
+pub struct CatTaskBuilder {
+    source: String,
+    client: Option<Arc<Client>>,
+    ctxs: Vec<Ctx>,
+    capacity: usize,
+    offset: Option<u64>,
+    length: Option<u64>,
+}
+
+impl CatTaskBuilder {
+    /// Set the source.
+    pub fn with_source(mut self, source: String) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Set the S3 client to use.
+    pub fn set_client(mut self, client: Option<Arc<Client>>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Set the checksums to compute while streaming.
+    pub fn with_context(mut self, ctxs: Vec<Ctx>) -> Self {
+        self.ctxs = ctxs;
+        self
+    }
+
+    /// Set the reader capacity.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Only stream data starting at this byte offset.
+    pub fn set_offset(mut self, offset: Option<u64>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Only stream this many bytes.
+    pub fn set_length(mut self, length: Option<u64>) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Build a cat task.
+    pub async fn build(mut self) -> Result<CatTask> {
+        let file_size = ObjectSumsBuilder::default()
+            .set_client(self.client.clone())
+            .build(self.source.to_string())
+            .await?
+            .file_size()
+            .await?;
+
+        let range = ByteRange::from_options(self.offset, self.length, file_size)?;
+        let file_size = range.map(|range| range.length).or(file_size);
+        self.ctxs
+            .iter_mut()
+            .for_each(|ctx| ctx.set_file_size(file_size));
+
+        let object = ObjectCopyBuilder::default()
+            .set_client(self.client)
+            .set_source(Some(Provider::try_from(self.source.as_str())?))
+            .build()
+            .await?;
+
+        Ok(CatTask {
+            object,
+            ctxs: self.ctxs,
+            capacity: self.capacity,
+            range,
+        })
+    }
+}
+
+/// Execute the cat task.
+pub struct CatTask {
+    object: Box<dyn ObjectCopy + Send + Sync>,
+    ctxs: Vec<Ctx>,
+    capacity: usize,
+    range: Option<ByteRange>,
+}
+
+impl CatTask {
+    /// Stream the object to the writer, returning a sums file containing any checksums that
+    /// were computed along the way.
+    pub async fn run<W>(self, mut writer: W) -> Result<SumsFile>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        // A part number is required to download a range rather than the whole object.
+        let multi_part = self.range.map(|range| MultiPartOptions {
+            part_number: Some(1),
+            total_parts: Some(1),
+            ..range.into()
+        });
+        let content = self.object.download(multi_part).await?;
+
+        let mut reader = ChannelReader::new(content.into_inner(), self.capacity);
+        let mut output = reader.as_stream();
+        let mut ctxs = self.ctxs;
+        let streams: Vec<_> = ctxs.iter().map(|_| reader.as_stream()).collect();
+
+        let write = async {
+            while let Some(chunk) = output.next().await {
+                writer.write_all(&chunk?).await?;
+            }
+            writer.flush().await?;
+            Ok(())
+        };
+        let generate = async {
+            join_all(
+                ctxs.iter_mut()
+                    .zip(streams)
+                    .map(|(ctx, stream)| ctx.generate(stream)),
+            )
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+        };
+
+        let (size, _, digests) = try_join!(reader.read_chunks(), write, generate)?;
+
+        let checksums = ctxs.into_iter().zip(digests).map(|(ctx, digest)| {
+            let checksum = Checksum::new(ctx.digest_to_string(&digest));
+            (ctx, checksum)
+        });
+
+        Ok(SumsFile::new(Some(size), BTreeMap::from_iter(checksums)).with_range(self.range))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_cat_range() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("cat").to_string_lossy().to_string();
+        tokio::fs::write(&name, "hello world").await?;
+
+        let mut output = vec![];
+        let sums = CatTaskBuilder::default()
+            .with_source(name)
+            .with_context(vec!["md5".parse()?])
+            .with_capacity(10)
+            .set_offset(Some(6))
+            .build()
+            .await?
+            .run(&mut output)
+            .await?;
+
+        assert_eq!(output, b"world");
+        assert_eq!(sums.size, Some(5));
+        assert_eq!(sums.range, Some(ByteRange::new(6, 5)));
+        assert_eq!(
+            sums.checksums[&"md5".parse()?],
+            Checksum::new("7d793037a0760186574b0282f2f435e7".to_string())
+        );
+
+        Ok(())
+    }
+}
@@ -1,12 +1,16 @@
 //! Performs the check task to determine if files are identical from .sums files.
 //!
 
-use crate::checksum::file::{Checksum, SumsFile};
+use crate::checksum::file::{Checksum, SumsFile, SumsFormat, SumsStorage};
 use crate::checksum::Ctx;
+use crate::cli::Strength;
+use crate::error::Error::CheckError;
 use crate::error::{ApiError, Error, Result};
+use crate::io::sums::file::File;
 use crate::io::sums::{ObjectSums, ObjectSumsBuilder};
+use crate::io::Client;
+use crate::io::Provider;
 use crate::stats::{CheckComparison, ChecksumPair};
-use aws_sdk_s3::Client;
 use clap::ValueEnum;
 use futures_util::future::join_all;
 use serde::{Deserialize, Serialize};
@@ -14,8 +18,10 @@ use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
 use std::fmt::{Debug, Formatter};
-use std::hash::{DefaultHasher, Hash, Hasher};
+use std::hash::{Hash, Hasher};
+use std::mem;
 use std::sync::Arc;
+use tracing::{debug, info, warn};
 
 /// Build a check task.
 #[derive(Debug)]
@@ -26,6 +32,13 @@ pub struct CheckTaskBuilder {
     update: bool,
     clients: Vec<Option<Arc<Client>>>,
     avoid_get_object_attributes: bool,
+    format: SumsFormat,
+    storage: SumsStorage,
+    sums_source: Option<String>,
+    output_dir: Option<String>,
+    strict_merge: bool,
+    continue_on_error: bool,
+    min_strength: Option<Strength>,
 }
 
 impl Default for CheckTaskBuilder {
@@ -38,6 +51,13 @@ impl Default for CheckTaskBuilder {
             // Ensure at least one element in the vector to repeat.
             clients: vec![None],
             avoid_get_object_attributes: Default::default(),
+            format: Default::default(),
+            storage: Default::default(),
+            sums_source: Default::default(),
+            output_dir: Default::default(),
+            strict_merge: Default::default(),
+            continue_on_error: Default::default(),
+            min_strength: Default::default(),
         }
     }
 }
@@ -85,6 +105,53 @@ impl CheckTaskBuilder {
         self
     }
 
+    /// Set the format used to read and write sums files.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set where sums files are stored for S3 objects.
+    pub fn with_storage(mut self, storage: SumsStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Read and write sums files in a local directory, independent of where the inputs are
+    /// stored.
+    pub fn set_sums_source(mut self, sums_source: Option<String>) -> Self {
+        self.sums_source = sums_source;
+        self
+    }
+
+    /// Read and write the sums files of local files in a directory which mirrors their
+    /// absolute paths, rather than next to the inputs.
+    pub fn set_output_dir(mut self, output_dir: Option<String>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Return an error when merging identical sums files that contain different values for
+    /// the same checksum, rather than overwriting them.
+    pub fn with_strict_merge(mut self, strict_merge: bool) -> Self {
+        self.strict_merge = strict_merge;
+        self
+    }
+
+    /// Leave out inputs that could not be read and record their errors, rather than failing
+    /// the whole check.
+    pub fn with_continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    /// Only consider sums files to be the same if they share a checksum that is at least as
+    /// strong as this.
+    pub fn set_min_strength(mut self, min_strength: Option<Strength>) -> Self {
+        self.min_strength = min_strength;
+        self
+    }
+
     /// Build a check task.
     pub async fn build(mut self) -> Result<CheckTask> {
         let group_by = self.group_by;
@@ -97,38 +164,57 @@ impl CheckTaskBuilder {
             .collect::<Vec<_>>();
         self.files.retain(|file| !in_memory.contains(&file));
 
-        let (objects, errors): (Vec<_>, Vec<_>) = join_all(
+        let sums_source = &self.sums_source;
+        let output_dir = &self.output_dir;
+        let results = join_all(
             self.files
                 .into_iter()
                 .zip(self.clients.into_iter().cycle())
                 .map(|(file, client)| async move {
-                    let mut sums = ObjectSumsBuilder::default()
-                        .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
-                        .set_client(client)
-                        .build(file.to_string())
-                        .await?;
-
-                    let file_size = sums.file_size().await?;
-                    let existing = sums
-                        .sums_file()
-                        .await?
-                        .unwrap_or_else(|| SumsFile::new(file_size, Default::default()));
-
-                    let errors = sums.api_errors();
-                    Ok((
-                        (
-                            SumsKey((existing, sums.location())),
-                            BTreeSet::from_iter(vec![State::ObjectSums(sums)]),
-                        ),
-                        errors,
-                    ))
+                    let result = async {
+                        let mut sums = ObjectSumsBuilder::default()
+                            .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
+                            .with_format(self.format)
+                            .with_storage(self.storage)
+                            .set_sums_source(sums_source.clone())
+                            .set_output_dir(output_dir.clone())
+                            .set_client(client)
+                            .build(file.to_string())
+                            .await?;
+
+                        let file_size = sums.file_size().await?;
+                        let existing = sums
+                            .sums_file()
+                            .await?
+                            .unwrap_or_else(|| SumsFile::new(file_size, Default::default()));
+
+                        let errors = sums.api_errors();
+                        Ok::<_, Error>((
+                            (
+                                SumsKey((existing, sums.location())),
+                                BTreeSet::from_iter(vec![State::ObjectSums(sums)]),
+                            ),
+                            errors,
+                        ))
+                    }
+                    .await;
+                    (file, result)
                 }),
         )
-        .await
-        .into_iter()
-        .collect::<Result<Vec<_>>>()?
-        .into_iter()
-        .unzip();
+        .await;
+
+        let mut failed = BTreeMap::new();
+        let mut succeeded = vec![];
+        for (file, result) in results {
+            match result {
+                Err(err) if self.continue_on_error => {
+                    warn!(input = file, %err, "skipping input that could not be read");
+                    failed.insert(file, err.to_string());
+                }
+                result => succeeded.push(result?),
+            }
+        }
+        let (objects, errors): (Vec<_>, Vec<_>) = succeeded.into_iter().unzip();
 
         let mut objects = BTreeMap::from_iter(objects);
         let errors = HashSet::from_iter(
@@ -144,11 +230,26 @@ impl CheckTaskBuilder {
             );
         }
 
+        if let Some(SumsKey((_, location))) = objects
+            .keys()
+            .find(|SumsKey((sums, _))| sums.checksums.keys().any(Ctx::is_null))
+        {
+            return Err(CheckError(format!(
+                "the null checksum cannot be used to check files: {}",
+                location
+            )));
+        }
+
         Ok(CheckTask {
             objects: CheckObjects(objects),
             group_by,
             update: self.update,
             api_errors: errors,
+            format: self.format,
+            storage: self.storage,
+            strict_merge: self.strict_merge,
+            min_strength: self.min_strength,
+            failed,
             ..Default::default()
         })
     }
@@ -189,6 +290,17 @@ impl State {
         }
     }
 
+    /// Get the device and inode of the location if it is a local file.
+    pub async fn file_id(&mut self) -> Result<Option<(u64, u64)>> {
+        match self {
+            State::ObjectSums(object) => object.file_id().await,
+            State::ExistingSums((location, _)) => match Provider::try_from(location.as_str())? {
+                Provider::File { file } => File::new(file).id().await,
+                _ => Ok(None),
+            },
+        }
+    }
+
     /// Get the api errors.
     pub fn api_errors(&mut self) -> HashSet<ApiError> {
         match self {
@@ -204,6 +316,8 @@ impl State {
         sums: &SumsFile,
         client: Option<Arc<Client>>,
         avoid_get_object_attributes: bool,
+        format: SumsFormat,
+        storage: SumsStorage,
     ) -> Result<()> {
         match self {
             State::ObjectSums(object) => object.write_sums_file(sums).await,
@@ -211,6 +325,8 @@ impl State {
                 ObjectSumsBuilder::default()
                     .set_client(client)
                     .with_avoid_get_object_attributes(avoid_get_object_attributes)
+                    .with_format(format)
+                    .with_storage(storage)
                     .build(location.to_string())
                     .await?
                     .write_sums_file(sums)
@@ -292,14 +408,41 @@ impl CheckObjects {
         self.0
     }
 
-    /// Get the groups of locations that were compared.
+    /// Get the groups of locations that were compared. Locations are sorted within each
+    /// group, and groups are sorted by their first location, so that the output does not
+    /// depend on the order of the inputs or the sums files that were merged.
     pub fn to_groups(&self) -> Vec<Vec<String>> {
         let mut groups = Vec::with_capacity(self.0.len());
         for state in self.0.values() {
-            groups.push(state.iter().map(|state| state.location()).collect());
+            let mut group: Vec<_> = state.iter().map(|state| state.location()).collect();
+            group.sort();
+            groups.push(group);
         }
+        groups.sort();
         groups
     }
+
+    /// Get the locations that refer to the same physical file as another location in their
+    /// group, such as hardlinks or symlinks to the same file. This only applies to local files.
+    pub async fn to_hardlinked(&self) -> Result<BTreeSet<String>> {
+        let mut hardlinked = BTreeSet::new();
+        for states in self.0.values() {
+            let mut ids: BTreeMap<_, Vec<_>> = BTreeMap::new();
+            for state in states {
+                if let Some(id) = state.clone().file_id().await? {
+                    ids.entry(id).or_default().push(state.location());
+                }
+            }
+
+            hardlinked.extend(
+                ids.into_values()
+                    .filter(|locations| locations.len() > 1)
+                    .flatten(),
+            );
+        }
+
+        Ok(hardlinked)
+    }
 }
 
 impl Hash for CheckObjects {
@@ -316,65 +459,103 @@ pub struct CheckTask {
     update: bool,
     compared_directly: Vec<CheckComparison>,
     updated: Vec<String>,
+    hardlinked: BTreeSet<String>,
     client: Option<Arc<Client>>,
     api_errors: HashSet<ApiError>,
     avoid_get_object_attributes: bool,
+    format: SumsFormat,
+    storage: SumsStorage,
+    strict_merge: bool,
+    min_strength: Option<Strength>,
+    weakly_matched: Vec<CheckComparison>,
+    failed: BTreeMap<String, String>,
 }
 
 impl CheckTask {
-    fn hash<T: Hash>(value: &T) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        value.hash(&mut hasher);
-        hasher.finish()
+    /// Find the root of an element in a union-find, compressing the path along the way.
+    fn find(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
     }
 
-    /// Groups sums files based on a comparison function.
-    async fn merge_fn<F>(mut self, compare: F) -> Result<Self>
+    /// Groups sums files based on a comparison function. If `strict` is set, an error is
+    /// returned when merging files that contain different values for the same checksum.
+    ///
+    /// The comparison is performed once for each pair of sums files that are not already in
+    /// the same group, and groups are joined using a union-find. Each group is then merged
+    /// into a single sums file. This finds the connected groups of sums files, including
+    /// those that are only indirectly the same through other files.
+    async fn merge_fn<F>(mut self, compare: F, strict: bool) -> Result<Self>
     where
         for<'a> F: Fn(&'a SumsFile, &'a SumsFile) -> Option<(&'a Ctx, &'a Checksum)>,
     {
-        // This might be more efficient using graph algorithms to find a set of connected
-        // graphs based on the equality of the sums files.
+        let objects = mem::take(&mut self.objects.0)
+            .into_iter()
+            .collect::<Vec<_>>();
 
-        let mut state = Self::hash(&self.objects);
-        let mut prev_state = state.wrapping_add(1);
-        // Loop until the set of sums files does not change between iterations, i.e.
-        // until the hash of the previous and current iteration is the same.
-        while prev_state != state {
-            // BTreeMap files are sorted already.
-            let mut objects = self.objects.0.into_iter().collect::<Vec<_>>();
-            let mut reprocess = Vec::with_capacity(objects.len());
-
-            // Process a single sums file at a time.
-            'outer: while let Some((SumsKey((a, a_location)), mut a_locations)) = objects.pop() {
-                // Check to see if it can be merged with another sums file in the list.
-                for (SumsKey((b, b_location)), b_locations) in objects.iter_mut() {
-                    // If it can be merged with another file, do the merge and add it back in for
-                    // the next loop.
-                    if let Some((ctx, checksum)) = compare(&a, b) {
-                        self.compared_directly.push(CheckComparison::new(
-                            vec![a_location, b_location.to_string()],
-                            ChecksumPair::new(ctx.clone(), checksum.clone()),
-                        ));
+        let mut parents = (0..objects.len()).collect::<Vec<_>>();
+        for i in 0..objects.len() {
+            for j in i + 1..objects.len() {
+                let (root_i, root_j) = (Self::find(&mut parents, i), Self::find(&mut parents, j));
+                if root_i == root_j {
+                    continue;
+                }
 
-                        b_locations.append(&mut a_locations);
+                let (SumsKey((a, a_location)), _) = &objects[j];
+                let (SumsKey((b, b_location)), _) = &objects[i];
+                if let Some((ctx, checksum)) = compare(a, b) {
+                    debug!(
+                        a = %a_location,
+                        b = %b_location,
+                        checksum = %ctx,
+                        "matched"
+                    );
+                    parents[root_j] = root_i;
+
+                    self.compared_directly.push(CheckComparison::new(
+                        vec![a_location.to_string(), b_location.to_string()],
+                        ChecksumPair::new(ctx.clone(), checksum.clone()),
+                    ));
+                }
+            }
+        }
 
-                        b.merge_mut(a);
+        let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (i, object) in objects.into_iter().enumerate() {
+            groups
+                .entry(Self::find(&mut parents, i))
+                .or_default()
+                .push(object);
+        }
 
-                        continue 'outer;
-                    }
+        let mut merged = BTreeMap::new();
+        for group in groups.into_values() {
+            let mut group = group.into_iter();
+            let Some((SumsKey((mut b, b_location)), mut b_locations)) = group.next() else {
+                continue;
+            };
+
+            for (SumsKey((a, a_location)), mut a_locations) in group {
+                if strict {
+                    b.merge_strict(a).map_err(|err| {
+                        CheckError(format!(
+                            "merging `{}` and `{}`: {}",
+                            a_location, b_location, err
+                        ))
+                    })?;
+                } else {
+                    b.merge_mut(a);
                 }
 
-                // If it could not be merged, add it back into the list for re-processing.
-                reprocess.push((SumsKey((a, a_location)), a_locations));
+                b_locations.append(&mut a_locations);
             }
 
-            self.objects = CheckObjects(BTreeMap::from_iter(reprocess));
-
-            // Update the hashes of the current and previous lists.
-            prev_state = state;
-            state = Self::hash(&self.objects);
+            merged.insert(SumsKey((b, b_location)), b_locations);
         }
+        self.objects = CheckObjects(merged);
 
         Ok(self)
     }
@@ -383,14 +564,38 @@ impl CheckTask {
     /// be performed. This can find sums files that are indirectly identical through
     /// other files. E.g. a.sums is equal to b.sums, and b.sums is equal to c.sums, but
     /// a.sums is not directly equal to c.sums because of different checksum types.
+    ///
+    /// With a minimum strength, only checksums that are at least as strong are used to merge
+    /// sums files. Groups that would otherwise be the same through weaker checksums are recorded
+    /// as weakly matched.
     pub async fn merge_same(mut self) -> Result<Self> {
-        self = self.merge_fn(|a, b| a.is_same(b)).await?;
+        let strict = self.strict_merge;
+        let min_strength = self.min_strength;
+        self = self
+            .merge_fn(|a, b| a.is_same_with_strength(b, min_strength), strict)
+            .await?;
+
+        if min_strength.is_some() {
+            let objects = self.objects.0.keys().collect::<Vec<_>>();
+            for (i, SumsKey((a, a_location))) in objects.iter().enumerate() {
+                for SumsKey((b, b_location)) in &objects[i + 1..] {
+                    if let Some((ctx, checksum)) = a.is_same(b) {
+                        self.weakly_matched.push(CheckComparison::new(
+                            vec![a_location.to_string(), b_location.to_string()],
+                            ChecksumPair::new(ctx.clone(), checksum.clone()),
+                        ));
+                    }
+                }
+            }
+        }
+
         Ok(self)
     }
 
     /// Determine the set of checksums for all files.
     pub async fn merge_comparable(mut self) -> Result<Self> {
-        self = self.merge_fn(|a, b| a.comparable(b)).await?;
+        // Comparable files are expected to have different checksum values.
+        self = self.merge_fn(|a, b| a.comparable(b), false).await?;
         // The checksum value doesn't mean much if two sums files are comparable but not equal,
         // so it should be cleared.
         let mut files = BTreeMap::new();
@@ -412,6 +617,8 @@ impl CheckTask {
         let update = self.update && matches!(self.group_by, GroupBy::Equality);
         let avoid_get_object_attributes = self.avoid_get_object_attributes;
         let client = self.client.clone();
+        let format = self.format;
+        let storage = self.storage;
         let mut result = match self.group_by {
             GroupBy::Equality => Ok::<_, Error>(self.merge_same().await?),
             GroupBy::Comparability => Ok(self.merge_comparable().await?),
@@ -424,10 +631,25 @@ impl CheckTask {
                     let mut location = location.clone();
                     let current = location.sums_file().await?;
 
+                    // Keep any metadata recorded for this location rather than the metadata
+                    // of the file that it was merged with.
+                    let file = file.clone().with_metadata(
+                        current.as_ref().map(SumsFile::metadata).unwrap_or_default(),
+                    );
+
                     result.api_errors.extend(location.api_errors());
-                    if current.as_ref() != Some(file) {
+                    // HTTP locations are read-only, so their sums cannot be updated.
+                    let read_only = Provider::try_from(location.location().as_str())?.is_http();
+                    if current.as_ref() != Some(&file) && !read_only {
+                        debug!(location = %location.location(), "updating sums file");
                         location
-                            .write_sums_file(file, client.clone(), avoid_get_object_attributes)
+                            .write_sums_file(
+                                &file,
+                                client.clone(),
+                                avoid_get_object_attributes,
+                                format,
+                                storage,
+                            )
                             .await?;
                         updated_sums.push(location.location());
                     }
@@ -435,7 +657,13 @@ impl CheckTask {
             }
         }
 
+        info!(
+            groups = result.objects.0.len(),
+            updated = updated_sums.len(),
+            "checked sums"
+        );
         result.updated = updated_sums;
+        result.hardlinked = result.objects.to_hardlinked().await?;
 
         Ok(result)
     }
@@ -462,6 +690,21 @@ impl CheckTask {
         &self.objects.0
     }
 
+    /// Get the locations that refer to the same physical file as another location in their group.
+    pub fn hardlinked(&self) -> &BTreeSet<String> {
+        &self.hardlinked
+    }
+
+    /// Get the inputs that could not be read, and their errors.
+    pub fn failed(&self) -> &BTreeMap<String, String> {
+        &self.failed
+    }
+
+    /// Get the groups that only matched using checksums weaker than the minimum strength.
+    pub fn weakly_matched(&self) -> &[CheckComparison] {
+        self.weakly_matched.as_slice()
+    }
+
     /// Get the comparisons.
     pub fn compared_directly(&self) -> &[CheckComparison] {
         self.compared_directly.as_slice()
@@ -487,10 +730,13 @@ pub(crate) mod test {
     use crate::checksum::file::Checksum;
     use crate::error::Error;
     use crate::io::sums::file::FileBuilder;
+    use crate::stats::{CheckMember, CheckStats};
     use crate::test::TEST_FILE_SIZE;
     use anyhow::Result;
     use std::collections::BTreeMap;
+    use std::hash::DefaultHasher;
     use std::path::Path;
+    use std::time::Duration;
     use tempfile::{tempdir, TempDir};
 
     #[tokio::test]
@@ -653,8 +899,263 @@ pub(crate) mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_check_hardlinked() -> Result<()> {
+        let tmp = tempdir()?;
+        let files = write_test_files_one_group(tmp).await?;
+
+        tokio::fs::write(&files[0], "data").await?;
+        tokio::fs::hard_link(&files[0], &files[1]).await?;
+
+        // The order of the inputs does not affect the output.
+        let check = CheckTaskBuilder::default()
+            .with_input_files(files.iter().rev().map(|name| name.to_string()).collect())
+            .build()
+            .await?
+            .run()
+            .await?;
+
+        assert_eq!(
+            check.hardlinked(),
+            &BTreeSet::from([files[0].to_string(), files[1].to_string()])
+        );
+
+        let stats = CheckStats::from_task(GroupBy::Equality, check, Duration::default(), None);
+        assert_eq!(
+            stats.groups,
+            [[
+                CheckMember {
+                    location: files[0].to_string(),
+                    hardlinked: true
+                },
+                CheckMember {
+                    location: files[1].to_string(),
+                    hardlinked: true
+                },
+                CheckMember::new(files[2].to_string()),
+            ]]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_merge_matches_fixed_point() -> Result<()> {
+        assert_same_groups(write_test_files_one_group(tempdir()?).await?).await?;
+        assert_same_groups(write_test_files_not_comparable(tempdir()?).await?).await?;
+        assert_same_groups(write_test_files_multiple_groups(tempdir()?).await?).await?;
+
+        Ok(())
+    }
+
+    /// Assert that the union-find grouping is the same as the fixed-point grouping.
+    async fn assert_same_groups(files: Vec<String>) -> Result<()> {
+        let build = || {
+            CheckTaskBuilder::default()
+                .with_input_files(files.clone())
+                .build()
+        };
+        let sorted_groups = |objects: &CheckObjects| {
+            let mut groups = objects.to_groups();
+            groups.sort();
+            groups
+        };
+
+        let result = build().await?.merge_same().await?.objects;
+        let expected = merge_fixed_point(build().await?.objects, |a, b| a.is_same(b));
+        assert_eq!(sorted_groups(&result), sorted_groups(&expected));
+        assert_eq!(
+            result.0.keys().map(|key| &key.0 .0).collect::<Vec<_>>(),
+            expected.0.keys().map(|key| &key.0 .0).collect::<Vec<_>>()
+        );
+
+        let result = build().await?.merge_comparable().await?.objects;
+        let expected = merge_fixed_point(build().await?.objects, |a, b| a.comparable(b));
+        assert_eq!(sorted_groups(&result), sorted_groups(&expected));
+
+        Ok(())
+    }
+
+    /// Merge sums files by repeatedly scanning the list until no more merges occur. This is
+    /// the previous grouping algorithm, used as a reference.
+    fn merge_fixed_point<F>(mut objects: CheckObjects, compare: F) -> CheckObjects
+    where
+        for<'a> F: Fn(&'a SumsFile, &'a SumsFile) -> Option<(&'a Ctx, &'a Checksum)>,
+    {
+        let hash = |objects: &CheckObjects| {
+            let mut hasher = DefaultHasher::new();
+            objects.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let mut state = hash(&objects);
+        let mut prev_state = state.wrapping_add(1);
+        while prev_state != state {
+            let mut list = objects.0.into_iter().collect::<Vec<_>>();
+            let mut reprocess = Vec::with_capacity(list.len());
+
+            'outer: while let Some((SumsKey((a, a_location)), mut a_locations)) = list.pop() {
+                for (SumsKey((b, _)), b_locations) in list.iter_mut() {
+                    if compare(&a, b).is_some() {
+                        b.merge_mut(a);
+                        b_locations.append(&mut a_locations);
+                        continue 'outer;
+                    }
+                }
+
+                reprocess.push((SumsKey((a, a_location)), a_locations));
+            }
+
+            objects = CheckObjects(BTreeMap::from_iter(reprocess));
+            prev_state = state;
+            state = hash(&objects);
+        }
+
+        objects
+    }
+
+    #[tokio::test]
+    async fn test_check_strict_merge() -> Result<()> {
+        let sums = |sha1: &str| -> Result<SumsFile> {
+            Ok(SumsFile::new(
+                Some(TEST_FILE_SIZE),
+                BTreeMap::from_iter(vec![
+                    ("md5".parse()?, Checksum::new("123".to_string())),
+                    ("sha1".parse()?, Checksum::new(sha1.to_string())),
+                ]),
+            ))
+        };
+        let builder = || -> Result<CheckTaskBuilder> {
+            Ok(CheckTaskBuilder::default().with_sums_files(vec![
+                ("a".to_string(), sums("456")?),
+                ("b".to_string(), sums("789")?),
+            ]))
+        };
+
+        let check = builder()?.build().await?.run().await?;
+        assert_eq!(check.objects.0.len(), 1);
+
+        let result = builder()?
+            .with_strict_merge(true)
+            .build()
+            .await?
+            .run()
+            .await;
+        assert!(matches!(result, Err(CheckError(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_min_strength() -> Result<()> {
+        let sums = |checksums: &[(&str, &str)]| -> Result<SumsFile> {
+            Ok(SumsFile::new(
+                Some(TEST_FILE_SIZE),
+                checksums
+                    .iter()
+                    .map(|(ctx, checksum)| Ok((ctx.parse()?, Checksum::new(checksum.to_string()))))
+                    .collect::<Result<_>>()?,
+            ))
+        };
+        let builder = || -> Result<CheckTaskBuilder> {
+            Ok(CheckTaskBuilder::default().with_sums_files(vec![
+                ("a".to_string(), sums(&[("md5", "123"), ("crc32", "456")])?),
+                ("b".to_string(), sums(&[("md5", "123")])?),
+                (
+                    "c".to_string(),
+                    sums(&[("sha256", "789"), ("crc32", "456")])?,
+                ),
+                ("d".to_string(), sums(&[("sha256", "789")])?),
+            ]))
+        };
+
+        let check = builder()?.build().await?.run().await?;
+        assert_eq!(check.objects.0.len(), 1);
+        assert!(check.weakly_matched().is_empty());
+
+        let check = builder()?
+            .set_min_strength(Some(Strength::Md5))
+            .build()
+            .await?
+            .run()
+            .await?;
+        assert_eq!(check.objects.to_groups(), [vec!["a", "b"], vec!["c", "d"]]);
+        assert_eq!(check.weakly_matched().len(), 1);
+        assert_eq!(check.weakly_matched()[0].reason.kind, "crc32".parse()?);
+
+        let check = builder()?
+            .set_min_strength(Some(Strength::Sha256))
+            .build()
+            .await?
+            .run()
+            .await?;
+        assert_eq!(check.objects.0.len(), 3);
+        assert_eq!(check.weakly_matched().len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_stable_order() -> Result<()> {
+        let sums = |checksum: &str| -> Result<SumsFile> {
+            Ok(SumsFile::new(
+                Some(TEST_FILE_SIZE),
+                BTreeMap::from_iter(vec![("md5".parse()?, Checksum::new(checksum.to_string()))]),
+            ))
+        };
+        // Sums files are ordered by their contents internally, so the group with the largest
+        // checksum contains the smallest location.
+        let inputs = vec![
+            ("e".to_string(), sums("1")?),
+            ("b".to_string(), sums("1")?),
+            ("d".to_string(), sums("2")?),
+            ("a".to_string(), sums("3")?),
+            ("f".to_string(), sums("3")?),
+            ("c".to_string(), sums("2")?),
+        ];
+        let expected = vec![vec!["a", "f"], vec!["b", "e"], vec!["c", "d"]];
+
+        for shift in 0..inputs.len() {
+            let mut shuffled = inputs.clone();
+            shuffled.rotate_left(shift);
+            if shift % 2 == 1 {
+                shuffled.reverse();
+            }
+
+            let check = CheckTaskBuilder::default()
+                .with_sums_files(shuffled)
+                .build()
+                .await?
+                .run()
+                .await?;
+            assert_eq!(check.objects.to_groups(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_null() -> Result<()> {
+        let sums = SumsFile::new(
+            Some(TEST_FILE_SIZE),
+            BTreeMap::from_iter(vec![("null".parse()?, Checksum::new("".to_string()))]),
+        );
+
+        let result = CheckTaskBuilder::default()
+            .with_sums_files(vec![
+                ("a".to_string(), sums.clone()),
+                ("b".to_string(), sums),
+            ])
+            .build()
+            .await;
+
+        assert!(matches!(result, Err(CheckError(_))));
+
+        Ok(())
+    }
+
     pub(crate) async fn write_test_files_one_group(tmp: TempDir) -> Result<Vec<String>, Error> {
-        let path = tmp.into_path();
+        let path = tmp.keep();
 
         let mut names = write_test_files(&path).await?;
 
@@ -680,7 +1181,7 @@ pub(crate) mod test {
     pub(crate) async fn write_test_files_not_comparable(
         tmp: TempDir,
     ) -> Result<Vec<String>, Error> {
-        let path = tmp.into_path();
+        let path = tmp.keep();
 
         let mut names = write_test_files(&path).await?;
 
@@ -706,7 +1207,7 @@ pub(crate) mod test {
     pub(crate) async fn write_test_files_multiple_groups(
         tmp: TempDir,
     ) -> Result<Vec<String>, Error> {
-        let path = tmp.into_path();
+        let path = tmp.keep();
 
         let mut names = write_test_files(&path).await?;
 
@@ -2,21 +2,27 @@
 //!
 
 use crate::checksum::aws_etag::PREFERRED_PART_SIZES;
-use crate::checksum::file::SumsFile;
+use crate::checksum::file::{Checksum, SumsFile, SumsFormat};
 use crate::checksum::Ctx;
 use crate::cli::{CopyMode, MetadataCopy};
 use crate::error::Error::CopyError;
 use crate::error::{ApiError, Error, Result};
-use crate::io::copy::{CopyResult, CopyState, MultiPartOptions, ObjectCopy, ObjectCopyBuilder};
+use crate::io::copy::verify::CopyVerifier;
+use crate::io::copy::{
+    CopyContent, CopyResult, CopyState, MultiPartOptions, ObjectCopy, ObjectCopyBuilder,
+};
 use crate::io::sums::ObjectSumsBuilder;
+use crate::io::Client;
 use crate::io::Provider;
-use aws_sdk_s3::Client;
+use crate::stats::{ChecksumPair, WriteVerification};
+use crate::task::generate::GenerateTaskBuilder;
 use futures_util::future::join_all;
 use serde::{Deserialize, Serialize};
 use serde_json::to_string;
 use std::collections::HashSet;
 use std::future::Future;
 use std::sync::Arc;
+use tracing::{debug, info};
 
 pub const DEFAULT_MULTIPART_THRESHOLD: u64 = 8 * 1024 * 1024; // 8mib
 
@@ -35,6 +41,13 @@ pub struct CopyTaskBuilder {
     concurrency: Option<usize>,
     api_errors: HashSet<ApiError>,
     avoid_get_object_attributes: bool,
+    format: SumsFormat,
+    verify_during_copy: bool,
+    verify_after_write: bool,
+    algorithm_from_destination: bool,
+    send_checksums: bool,
+    capacity: usize,
+    source_sums: Option<Option<SumsFile>>,
 }
 
 /// Settings that determine the part size and additional checksums to use.
@@ -136,6 +149,130 @@ impl CopyTaskBuilder {
         self
     }
 
+    /// Set the format used to read sums files at the source.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Verify the copy by computing a checksum over the data as it is uploaded and comparing it
+    /// to the sums at the source. This only applies to download-upload copies.
+    pub fn with_verify_during_copy(mut self, verify_during_copy: bool) -> Self {
+        self.verify_during_copy = verify_during_copy;
+        self
+    }
+
+    /// Send checksums that were already computed for the source with uploads, so that the
+    /// destination validates and stores them rather than computing them again.
+    pub fn with_send_checksums(mut self, send_checksums: bool) -> Self {
+        self.send_checksums = send_checksums;
+        self
+    }
+
+    /// Verify the destination after the copy by comparing its checksum to the sums at the
+    /// source, failing the copy if they differ. Native checksums of the destination are used
+    /// where possible, otherwise the destination is read to compute a checksum.
+    pub fn with_verify_after_write(mut self, verify_after_write: bool) -> Self {
+        self.verify_after_write = verify_after_write;
+        self
+    }
+
+    /// When verifying after the copy, compute the checksum that the destination reports at the
+    /// source if the source sums do not already contain it, rather than reading the
+    /// destination. This uses whichever algorithm the destination chose, such as the default
+    /// checksum of an S3 bucket.
+    pub fn with_algorithm_from_destination(mut self, algorithm_from_destination: bool) -> Self {
+        self.algorithm_from_destination = algorithm_from_destination;
+        self
+    }
+
+    /// Set the reader capacity used when computing checksums to verify the copy.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Read the sums at the source, only reading them once.
+    async fn source_sums(&mut self) -> Result<Option<SumsFile>> {
+        if let Some(sums) = &self.source_sums {
+            return Ok(sums.clone());
+        }
+
+        let mut object = ObjectSumsBuilder::default()
+            .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
+            .with_format(self.format)
+            .set_client(self.source_client.clone())
+            .build(self.source.to_string())
+            .await?;
+
+        self.api_errors.extend(object.api_errors());
+
+        let sums = object.sums_file().await?;
+        self.source_sums = Some(sums.clone());
+        Ok(sums)
+    }
+
+    /// Create a verifier using a checksum from the source sums. The sums must cover the whole
+    /// object without any transformation of the data.
+    async fn verifier(&mut self, size: u64) -> Result<Option<(Ctx, CopyVerifier)>> {
+        let Some(sums) = self.whole_source_sums(size).await? else {
+            return Ok(None);
+        };
+
+        Ok(sums.checksums.into_iter().next().map(|(kind, expected)| {
+            let mut ctx = kind.clone();
+            ctx.set_file_size(Some(size));
+            (kind, CopyVerifier::new(ctx, expected))
+        }))
+    }
+
+    /// Read the sums at the source if they cover the whole object without any transformation
+    /// of the data, so that they can be compared to the copied data.
+    async fn whole_source_sums(&mut self, size: u64) -> Result<Option<SumsFile>> {
+        Ok(self.source_sums().await?.filter(|sums| {
+            sums.range.is_none()
+                && !sums.normalized_newlines
+                && sums.decompressed.is_none()
+                && sums.size.is_none_or(|sums_size| sums_size == size)
+        }))
+    }
+
+    /// Find the checksums of the additional checksum of the copy that were already computed for
+    /// the source. The whole object checksum is used for single part uploads, and the part
+    /// checksums are used for multipart uploads if the parts line up with the copy. Checksums
+    /// that S3 represents differently, such as little-endian CRCs, are never sent.
+    async fn precomputed(
+        &mut self,
+        settings: &CopySettings,
+        destination: &Provider,
+        size: u64,
+    ) -> Result<(Option<Checksum>, Vec<Checksum>)> {
+        if !settings.ctx.is_aws_additional_value() {
+            debug!(ctx = %settings.ctx, "not sending checksums that S3 does not store");
+            return Ok((None, vec![]));
+        }
+
+        let Some(sums) = self.whole_source_sums(size).await? else {
+            return Ok((None, vec![]));
+        };
+
+        match (&settings.ctx, settings.part_size) {
+            (Ctx::Regular(_), None) => Ok((sums.checksums.get(&settings.ctx).cloned(), vec![])),
+            (Ctx::AWSEtag(_), Some(part_size)) => {
+                let parts = sums.parts(&settings.ctx).unwrap_or_default();
+                let lines_up = settings.ctx.is_preferred_multipart(destination) == Some(part_size)
+                    && parts.len() as u64 == size.div_ceil(part_size).max(1);
+                if !lines_up {
+                    debug!("not sending part checksums that do not line up with the copy");
+                    return Ok((None, vec![]));
+                }
+
+                Ok((None, parts.to_vec()))
+            }
+            _ => Ok((None, vec![])),
+        }
+    }
+
     /// Return whether multipart is available.
     fn is_multipart(
         object_size: u64,
@@ -230,15 +367,7 @@ impl CopyTaskBuilder {
 
         // Only use the sums file if the size is not set at the source.
         let sums = if self.part_size.is_none() {
-            let mut object = ObjectSumsBuilder::default()
-                .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
-                .set_client(self.source_client.clone())
-                .build(self.source.to_string())
-                .await?;
-
-            self.api_errors.extend(object.api_errors());
-
-            object.sums_file().await?
+            self.source_sums().await?
         } else {
             None
         };
@@ -379,16 +508,45 @@ impl CopyTaskBuilder {
             )
         };
 
-        let state = source_copy.initialize_state().await?;
+        let mut state = source_copy.initialize_state().await?;
 
         let concurrency = self
             .concurrency
             .ok_or_else(|| CopyError("concurrency not set".to_string()))?;
 
-        let (this, settings) = self
+        let (mut this, settings) = self
             .use_settings(destination.clone(), destination_copy.as_ref(), &state)
             .await?;
 
+        if this.send_checksums && copy_mode.is_download_upload() {
+            let (precomputed, parts) = this
+                .precomputed(&settings, &destination, state.size())
+                .await?;
+            state.set_precomputed(precomputed, parts);
+        }
+
+        let verifier = if this.verify_during_copy && copy_mode.is_download_upload() {
+            this.verifier(state.size()).await?
+        } else {
+            None
+        };
+        let write_verifier = if this.verify_after_write {
+            Some(WriteVerifier {
+                source: this.source.to_string(),
+                destination: this.destination.to_string(),
+                source_sums: this.whole_source_sums(state.size()).await?,
+                source_client: this.source_client.clone(),
+                destination_client: this.destination_client.clone(),
+                avoid_get_object_attributes: this.avoid_get_object_attributes,
+                format: this.format,
+                fallback: settings.ctx.clone(),
+                algorithm_from_destination: this.algorithm_from_destination,
+                capacity: this.capacity,
+            })
+        } else {
+            None
+        };
+
         let copy_task = CopyTask {
             additional_sums: settings.ctx,
             part_size: settings.part_size,
@@ -404,6 +562,10 @@ impl CopyTaskBuilder {
             bytes_transferred: 0,
             n_retries: 0,
             api_errors: this.api_errors,
+            verifier,
+            verified: None,
+            write_verifier,
+            verified_after_write: None,
         };
 
         Ok(copy_task)
@@ -439,9 +601,189 @@ pub struct CopyTask {
     bytes_transferred: u64,
     n_retries: u64,
     api_errors: HashSet<ApiError>,
+    verifier: Option<(Ctx, CopyVerifier)>,
+    verified: Option<ChecksumPair>,
+    write_verifier: Option<WriteVerifier>,
+    verified_after_write: Option<WriteVerification>,
+}
+
+/// Verifies the destination against the sums of the source once the copy has been written.
+struct WriteVerifier {
+    source: String,
+    destination: String,
+    source_sums: Option<SumsFile>,
+    source_client: Option<Arc<Client>>,
+    destination_client: Option<Arc<Client>>,
+    avoid_get_object_attributes: bool,
+    format: SumsFormat,
+    fallback: Ctx,
+    algorithm_from_destination: bool,
+    capacity: usize,
+}
+
+impl WriteVerifier {
+    /// Compare the destination to the source, returning an error if they differ. The native
+    /// checksums of the destination, such as S3 additional checksums, are used if they are
+    /// comparable to the source sums. Otherwise, the destination is read to compute a checksum.
+    /// If the source has no sums, it is also read to compute the checksum added by the copy.
+    async fn verify(
+        self,
+        size: u64,
+        api_errors: &mut HashSet<ApiError>,
+    ) -> Result<WriteVerification> {
+        let mut destination = ObjectSumsBuilder::default()
+            .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
+            .with_format(self.format)
+            .set_client(self.destination_client.clone())
+            .build(self.destination.to_string())
+            .await?;
+        let native = destination.metadata_sums().await?;
+        api_errors.extend(destination.api_errors());
+
+        self.verify_native(size, native).await
+    }
+
+    /// Compare the destination to the source using the native checksums of the destination.
+    async fn verify_native(self, size: u64, native: Option<SumsFile>) -> Result<WriteVerification> {
+        // Unless the source sums can already be compared, compute the checksum that the
+        // destination reports at the source instead of reading the destination.
+        let native_kind = native
+            .as_ref()
+            .filter(|native| {
+                self.algorithm_from_destination
+                    && self
+                        .source_sums
+                        .as_ref()
+                        .is_none_or(|source_sums| source_sums.comparable(native).is_none())
+            })
+            .and_then(|native| native.checksums.keys().next());
+        let mut bytes_reread = 0;
+        if let (Some(native), Some(kind)) = (&native, native_kind) {
+            // The checksums of the native sums are finalized, so a new checksum is parsed
+            // from their name.
+            let kind: Ctx = kind.to_string().parse()?;
+            debug!(checksum = %kind, "using the algorithm of the destination");
+            let source_sums = self
+                .generate(&self.source, self.source_client.clone(), kind)
+                .await?;
+            bytes_reread += size;
+            if let Some((kind, _)) = source_sums.comparable(native) {
+                return Self::compare(kind.clone(), &source_sums, native, true, bytes_reread);
+            }
+        }
+
+        let source_sums = match &self.source_sums {
+            Some(source_sums) => source_sums.clone(),
+            None => {
+                bytes_reread += size;
+                self.generate(
+                    &self.source,
+                    self.source_client.clone(),
+                    self.fallback.clone(),
+                )
+                .await?
+            }
+        };
+
+        if let Some(native) = &native {
+            if let Some((kind, _)) = source_sums.comparable(native) {
+                return Self::compare(kind.clone(), &source_sums, native, true, bytes_reread);
+            }
+        }
+
+        // The checksums of generated sums are finalized, so a new checksum is used to read the
+        // destination.
+        let kind = self
+            .source_sums
+            .as_ref()
+            .and_then(|source_sums| source_sums.checksums.keys().next().cloned())
+            .unwrap_or_else(|| self.fallback.clone());
+        let destination_sums = self
+            .generate(
+                &self.destination,
+                self.destination_client.clone(),
+                kind.clone(),
+            )
+            .await?;
+        bytes_reread += size;
+
+        Self::compare(kind, &source_sums, &destination_sums, false, bytes_reread)
+    }
+
+    /// Compare the checksum of the source and destination.
+    fn compare(
+        kind: Ctx,
+        source: &SumsFile,
+        destination: &SumsFile,
+        native: bool,
+        bytes_reread: u64,
+    ) -> Result<WriteVerification> {
+        let (Some(expected), Some(actual)) = (
+            source.checksums.get(&kind),
+            destination.checksums.get(&kind),
+        ) else {
+            return Err(CopyError(format!(
+                "the `{}` checksum could not be determined to verify the copy",
+                kind
+            )));
+        };
+
+        if expected != actual {
+            return Err(CopyError(format!(
+                "the `{}` checksum of the destination `{}` does not match the source `{}` after copying",
+                kind,
+                actual.as_str(),
+                expected.as_str()
+            )));
+        }
+
+        debug!(
+            checksum = %kind,
+            value = actual.as_str(),
+            native,
+            bytes_reread,
+            "verified after write"
+        );
+        Ok(WriteVerification::new(
+            ChecksumPair::new(kind, actual.clone()),
+            native,
+            bytes_reread,
+        ))
+    }
+
+    /// Read an object to compute a checksum.
+    async fn generate(
+        &self,
+        input: &str,
+        client: Option<Arc<Client>>,
+        ctx: Ctx,
+    ) -> Result<SumsFile> {
+        Ok(GenerateTaskBuilder::default()
+            .with_input_file_name(input.to_string())
+            .with_context(vec![ctx])
+            .with_capacity(self.capacity)
+            .set_client(client)
+            .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
+            .with_format(self.format)
+            .with_overwrite(true)
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0)
+    }
 }
 
 impl CopyTask {
+    /// Update the running checksum with the content as it is uploaded, if verifying.
+    fn verify_content(verifier: Option<&CopyVerifier>, data: CopyContent) -> CopyContent {
+        match verifier {
+            Some(verifier) => verifier.wrap(data),
+            None => data,
+        }
+    }
+
     async fn run_multipart<FnC, FutC, FnR, FutR, R>(
         &self,
         part_size: u64,
@@ -459,7 +801,8 @@ impl CopyTask {
         let mut n_retries = 0;
         let mut api_errors = vec![];
 
-        let n_parts = self.object_size.div_ceil(part_size);
+        // The first part is always uploaded, even if the object is empty.
+        let n_parts = self.object_size.div_ceil(part_size).max(1);
 
         let mut start = 0;
         let mut end = part_size;
@@ -500,6 +843,7 @@ impl CopyTask {
 
                 let options = MultiPartOptions {
                     part_number: Some(*part_number),
+                    total_parts: Some(n_parts),
                     start,
                     end,
                     upload_id: upload_id.clone(),
@@ -561,6 +905,7 @@ impl CopyTask {
         // Complete the upload
         let options = MultiPartOptions {
             part_number: None,
+            total_parts: Some(n_parts),
             start,
             end,
             upload_id: upload_id.clone(),
@@ -578,6 +923,12 @@ impl CopyTask {
     /// Runs the copy task and return the output.
     pub async fn run(mut self) -> Result<Self> {
         self.state.set_additional_ctx(self.additional_sums.clone());
+        debug!(
+            copy_mode = ?self.copy_mode,
+            part_size = self.part_size,
+            size = self.state.size(),
+            "copying"
+        );
 
         let (bytes_transferred, n_retries, api_errors) = match (self.copy_mode, self.part_size) {
             (CopyMode::ServerSide, None) => {
@@ -596,6 +947,7 @@ impl CopyTask {
             }
             (CopyMode::DownloadUpload, None) => {
                 let data = self.source_copy.download(None).await?;
+                let data = Self::verify_content(self.verifier.as_ref().map(|(_, v)| v), data);
                 let upload = self
                     .destination_copy
                     .upload(data, None, &self.state)
@@ -610,11 +962,13 @@ impl CopyTask {
             (CopyMode::DownloadUpload, Some(part_size)) => {
                 let source = self.source_copy.clone();
                 let destination = self.destination_copy.clone();
+                let verifier = self.verifier.as_ref().map(|(_, verifier)| verifier.clone());
 
                 self.run_multipart(
                     part_size,
                     |option, _| async move { source.download(Some(option.clone())).await },
                     |data, options, state| async move {
+                        let data = Self::verify_content(verifier.as_ref(), data);
                         destination.upload(data, Some(options), &state).await
                     },
                 )
@@ -622,11 +976,24 @@ impl CopyTask {
             }
         };
 
+        info!(bytes_transferred, n_retries, "copied");
+        if let Some((kind, verifier)) = self.verifier.take() {
+            let (_, checksum) = verifier.finalize()?;
+            debug!(checksum = %kind, value = checksum.as_str(), "verified during copy");
+            self.verified = Some(ChecksumPair::new(kind, checksum));
+        }
         self.bytes_transferred = bytes_transferred;
         self.n_retries = n_retries;
         self.api_errors
             .extend::<HashSet<ApiError>>(HashSet::from_iter(api_errors));
 
+        if let Some(write_verifier) = self.write_verifier.take() {
+            let verification = write_verifier
+                .verify(self.state.size(), &mut self.api_errors)
+                .await?;
+            self.verified_after_write = Some(verification);
+        }
+
         Ok(self)
     }
 
@@ -659,22 +1026,49 @@ impl CopyTask {
     pub fn n_retries(&self) -> u64 {
         self.n_retries
     }
+
+    /// Get the checksum that verified the copy while it was uploaded, if any.
+    pub fn verified(&self) -> Option<&ChecksumPair> {
+        self.verified.as_ref()
+    }
+
+    /// Get the result of verifying the destination after the copy was written, if any.
+    pub fn verified_after_write(&self) -> Option<&WriteVerification> {
+        self.verified_after_write.as_ref()
+    }
 }
 
 #[cfg(test)]
 pub(crate) mod test {
     use super::*;
+    use crate::checksum::checksum_bytes;
+    use crate::checksum::file::Checksum;
+    #[cfg(feature = "cloud")]
+    use crate::io::copy::aws::S3Builder;
+    #[cfg(feature = "cloud")]
     use crate::io::sums::aws::test::{
-        mock_multi_part_etag_only_rule, mock_single_part_etag_only_rule,
+        error_response, mock_multi_part_etag_only_rule, mock_single_part_etag_only_rule,
     };
+    use crate::io::sums::http::test::serve;
+    #[cfg(feature = "cloud")]
     use crate::test::{TestFileBuilder, TEST_FILE_SIZE};
     use anyhow::Result;
-    use aws_sdk_s3::operation::get_object::GetObjectError;
+    #[cfg(feature = "cloud")]
     use aws_sdk_s3::operation::get_object_tagging::GetObjectTaggingOutput;
+    #[cfg(feature = "cloud")]
     use aws_sdk_s3::operation::head_object::HeadObjectOutput;
-    use aws_sdk_s3::types::error::NoSuchKey;
+    #[cfg(feature = "cloud")]
+    use aws_sdk_s3::operation::put_object::PutObjectOutput;
+    #[cfg(feature = "cloud")]
+    use aws_sdk_s3::operation::upload_part::UploadPartOutput;
+    #[cfg(feature = "cloud")]
     use aws_sdk_s3::Client;
-    use aws_smithy_mocks_experimental::{mock, mock_client, Rule, RuleMode};
+    #[cfg(feature = "cloud")]
+    use aws_smithy_mocks::{mock, mock_client, Rule, RuleMode};
+    #[cfg(feature = "cloud")]
+    use base64::prelude::BASE64_STANDARD;
+    #[cfg(feature = "cloud")]
+    use base64::Engine;
     use tempfile::tempdir;
     use tokio::fs::File;
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -708,12 +1102,430 @@ pub(crate) mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_copy_verify_during_copy() -> Result<()> {
+        let tmp = tempdir()?;
+        let source = tmp.path().join("source");
+        let destination = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        tokio::fs::write(&source, "hello world").await?;
+
+        let builder = CopyTaskBuilder::default()
+            .with_concurrency(2)
+            .with_copy_mode(CopyMode::DownloadUpload)
+            .with_multipart_threshold(Some(1))
+            .with_part_size(Some(4))
+            .with_verify_during_copy(true)
+            .with_source(source.to_string_lossy().to_string());
+
+        // Without source sums, there is nothing to verify against.
+        let copy = builder
+            .clone()
+            .with_destination(destination("no_sums"))
+            .build()
+            .await?
+            .run()
+            .await?;
+        assert!(copy.verified().is_none());
+
+        GenerateTaskBuilder::default()
+            .with_input_file_name(source.to_string_lossy().to_string())
+            .with_context(vec!["md5-aws-4b".parse()?])
+            .with_capacity(10)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?;
+
+        let copy = builder
+            .clone()
+            .with_destination(destination("verified"))
+            .build()
+            .await?
+            .run()
+            .await?;
+        assert_eq!(
+            copy.verified().map(|pair| pair.value.as_str()),
+            Some(checksum_bytes("md5-aws-4b", b"hello world")?.as_str())
+        );
+        assert_eq!(
+            tokio::fs::read(destination("verified")).await?,
+            b"hello world"
+        );
+
+        // The source data no longer matches its sums.
+        tokio::fs::write(&source, "hello there").await?;
+        assert!(builder
+            .with_destination(destination("mismatch"))
+            .build()
+            .await?
+            .run()
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_copy_verify_after_write() -> Result<()> {
+        let tmp = tempdir()?;
+        let source = tmp.path().join("source");
+        let destination = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        tokio::fs::write(&source, "hello world").await?;
+
+        let builder = CopyTaskBuilder::default()
+            .with_concurrency(2)
+            .with_capacity(10)
+            .with_verify_after_write(true)
+            .with_source(source.to_string_lossy().to_string());
+
+        // Without source sums, both the source and destination are read.
+        let copy = builder
+            .clone()
+            .with_destination(destination("no_sums"))
+            .build()
+            .await?
+            .run()
+            .await?;
+        let verification = copy.verified_after_write().unwrap();
+        assert!(!verification.native);
+        assert_eq!(verification.bytes_reread, 22);
+
+        GenerateTaskBuilder::default()
+            .with_input_file_name(source.to_string_lossy().to_string())
+            .with_context(vec!["md5".parse()?])
+            .with_capacity(10)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?;
+
+        let copy = builder
+            .clone()
+            .with_destination(destination("verified"))
+            .build()
+            .await?
+            .run()
+            .await?;
+        let verification = copy.verified_after_write().unwrap();
+        assert_eq!(verification.reason.kind, "md5".parse()?);
+        assert_eq!(
+            verification.reason.value.as_str(),
+            checksum_bytes("md5", b"hello world")?
+        );
+        assert!(!verification.native);
+        assert_eq!(verification.bytes_reread, 11);
+
+        // The source data no longer matches its sums, so the destination does not either.
+        tokio::fs::write(&source, "hello there").await?;
+        assert!(builder
+            .with_destination(destination("mismatch"))
+            .build()
+            .await?
+            .run()
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cloud")]
+    #[tokio::test]
+    async fn test_copy_precomputed() -> Result<()> {
+        let tmp = tempdir()?;
+        let source = tmp.path().join("source").to_string_lossy().to_string();
+        tokio::fs::write(&source, "hello world").await?;
+        let destination = Provider::try_from("s3://bucket/key")?;
+
+        let mut builder = CopyTaskBuilder::default().with_source(source.to_string());
+        assert_eq!(
+            builder
+                .precomputed(
+                    &CopySettings::new(None, "sha256".parse()?, 11),
+                    &destination,
+                    11
+                )
+                .await?,
+            (None, vec![])
+        );
+
+        GenerateTaskBuilder::default()
+            .with_input_file_name(source.to_string())
+            .with_context(vec![
+                "sha256".parse()?,
+                "sha256-aws-4b".parse()?,
+                "crc32c-le".parse()?,
+            ])
+            .with_capacity(10)
+            .with_record_parts(true)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?;
+
+        let mut builder = CopyTaskBuilder::default().with_source(source.to_string());
+        let (whole, parts) = builder
+            .precomputed(
+                &CopySettings::new(None, "sha256".parse()?, 11),
+                &destination,
+                11,
+            )
+            .await?;
+        assert_eq!(
+            whole.unwrap().as_str(),
+            checksum_bytes("sha256", b"hello world")?
+        );
+        assert!(parts.is_empty());
+
+        let ctx: Ctx = "sha256-aws-4b".parse()?;
+        let (whole, parts) = builder
+            .precomputed(
+                &CopySettings::new(Some(4), ctx.clone(), 11),
+                &destination,
+                11,
+            )
+            .await?;
+        assert!(whole.is_none());
+        assert_eq!(
+            parts.iter().map(|part| part.as_str()).collect::<Vec<_>>(),
+            vec![
+                checksum_bytes("sha256", b"hell")?,
+                checksum_bytes("sha256", b"o wo")?,
+                checksum_bytes("sha256", b"rld")?
+            ]
+        );
+
+        // Parts that do not line up with the copy are not sent.
+        assert_eq!(
+            builder
+                .precomputed(&CopySettings::new(Some(8), ctx, 11), &destination, 11)
+                .await?,
+            (None, vec![])
+        );
+
+        // Little-endian values are not in the format that S3 expects.
+        assert_eq!(
+            builder
+                .precomputed(
+                    &CopySettings::new(None, "crc32c-le".parse()?, 11),
+                    &destination,
+                    11
+                )
+                .await?,
+            (None, vec![])
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cloud")]
+    #[tokio::test]
+    async fn test_upload_precomputed() -> Result<()> {
+        let base64 = |data: &[u8]| -> Result<String> {
+            Ok(BASE64_STANDARD.encode(hex::decode(checksum_bytes("sha256", data)?)?))
+        };
+        let whole = base64(b"hello world")?;
+        let part = base64(b"hell")?;
+
+        let put_object = mock!(Client::put_object)
+            .match_requests(move |req| req.checksum_sha256() == Some(whole.as_str()))
+            .then_output(|| PutObjectOutput::builder().build());
+        let upload_part = mock!(Client::upload_part)
+            .match_requests(move |req| {
+                req.part_number() == Some(1) && req.checksum_sha256() == Some(part.as_str())
+            })
+            .then_output(|| UploadPartOutput::builder().e_tag("etag").build());
+        let client = Arc::new(mock_client!(
+            aws_sdk_s3,
+            RuleMode::Sequential,
+            &[&put_object, &upload_part]
+        ));
+        let s3 = S3Builder::default()
+            .with_client(client)
+            .with_destination("bucket", "key")
+            .build()?;
+
+        let mut state = CopyState::new(11, None, None);
+        state.set_additional_ctx("sha256".parse()?);
+        state.set_precomputed(
+            Some(Checksum::new(checksum_bytes("sha256", b"hello world")?)),
+            vec![],
+        );
+        s3.upload(
+            CopyContent::new(Box::new(b"hello world".as_slice())),
+            None,
+            &state,
+        )
+        .await?;
+
+        state.set_additional_ctx("sha256-aws-4b".parse()?);
+        state.set_precomputed(
+            None,
+            vec![Checksum::new(checksum_bytes("sha256", b"hell")?)],
+        );
+        s3.upload(
+            CopyContent::new(Box::new(b"hell".as_slice())),
+            Some(MultiPartOptions {
+                part_number: Some(1),
+                total_parts: Some(3),
+                start: 0,
+                end: 4,
+                upload_id: Some("upload_id".to_string()),
+                ..Default::default()
+            }),
+            &state,
+        )
+        .await?;
+
+        assert_eq!(put_object.num_calls(), 1);
+        assert_eq!(upload_part.num_calls(), 1);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cloud")]
+    #[tokio::test]
+    async fn test_verify_native_s3() -> Result<()> {
+        let client = || {
+            Arc::new(mock_client!(
+                aws_sdk_s3,
+                RuleMode::Sequential,
+                mock_single_part_etag_only_rule().as_slice()
+            ))
+        };
+        // The source sums have the same checksum that the destination reports.
+        let source_sums = crate::io::sums::aws::S3Builder::default()
+            .with_client(client())
+            .with_bucket("bucket".to_string())
+            .with_key("key".to_string())
+            .build()?
+            .sums_from_metadata()
+            .await?;
+
+        let verifier = WriteVerifier {
+            source: "source".to_string(),
+            destination: "s3://bucket/key".to_string(),
+            source_sums: Some(source_sums),
+            source_client: None,
+            destination_client: Some(client()),
+            avoid_get_object_attributes: false,
+            format: Default::default(),
+            fallback: "md5".parse()?,
+            algorithm_from_destination: false,
+            capacity: 10,
+        };
+
+        // Neither the source nor the destination is read when using the native checksums.
+        let verification = verifier.verify(TEST_FILE_SIZE, &mut HashSet::new()).await?;
+        assert!(verification.native);
+        assert_eq!(verification.bytes_reread, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_algorithm_from_destination() -> Result<()> {
+        let tmp = tempdir()?;
+        let source = tmp.path().join("source").to_string_lossy().to_string();
+        let destination = tmp.path().join("destination").to_string_lossy().to_string();
+        tokio::fs::write(&source, "hello world").await?;
+        tokio::fs::write(&destination, "hello world").await?;
+
+        let verifier = |algorithm_from_destination| WriteVerifier {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            source_sums: None,
+            source_client: None,
+            destination_client: None,
+            avoid_get_object_attributes: false,
+            format: Default::default(),
+            fallback: "md5".parse().unwrap(),
+            algorithm_from_destination,
+            capacity: 10,
+        };
+        // The checksum that the destination reports, e.g. the default of an S3 bucket.
+        let native_sized = |size: u64, checksum: &str| {
+            Some(SumsFile::new(
+                Some(size),
+                [(
+                    "crc32c".parse().unwrap(),
+                    Checksum::new(checksum.to_string()),
+                )]
+                .into(),
+            ))
+        };
+        let native = |checksum: &str| native_sized(11, checksum);
+        let expected = checksum_bytes("crc32c", b"hello world")?;
+
+        // Only the source is read to compute the algorithm of the destination.
+        let verification = verifier(true).verify_native(11, native(&expected)).await?;
+        assert_eq!(verification.reason.kind, "crc32c".parse()?);
+        assert_eq!(verification.reason.value.as_str(), expected);
+        assert!(verification.native);
+        assert_eq!(verification.bytes_reread, 11);
+
+        // Otherwise, the source and destination are read using the fallback.
+        let verification = verifier(false).verify_native(11, native(&expected)).await?;
+        assert_eq!(verification.reason.kind, "md5".parse()?);
+        assert!(!verification.native);
+        assert_eq!(verification.bytes_reread, 22);
+
+        // If the native sums cannot be compared after reading the source, both are read again.
+        let verification = verifier(true)
+            .verify_native(11, native_sized(12, &expected))
+            .await?;
+        assert_eq!(verification.reason.kind, "md5".parse()?);
+        assert_eq!(verification.bytes_reread, 33);
+
+        assert!(verifier(true)
+            .verify_native(11, native("00000000"))
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_copy_http() -> Result<()> {
+        let tmp = tempdir()?;
+        let destination = tmp.path().join("destination");
+        let source = serve(b"test".to_vec()).await?;
+
+        let copy = CopyTaskBuilder::default()
+            .with_concurrency(10)
+            .with_multipart_threshold(Some(1))
+            .with_part_size(Some(2))
+            .with_source(source.clone())
+            .with_destination(destination.to_string_lossy().to_string())
+            .build()
+            .await?;
+        assert!(copy.copy_mode().is_download_upload());
+
+        let copy = copy.run().await?;
+        assert_eq!(copy.bytes_transferred, 4);
+        assert_eq!(tokio::fs::read(destination).await?, b"test");
+
+        // HTTP locations cannot be written to.
+        assert!(CopyTaskBuilder::default()
+            .with_source(tmp.path().join("destination").to_string_lossy().to_string())
+            .with_destination(source)
+            .build()
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "cloud")]
     #[tokio::test]
     async fn copy_settings() -> Result<()> {
         let test_file = TestFileBuilder::default().generate_test_defaults()?;
 
-        let single_part = mock_single_part_etag_only_rule();
-        let multipart = mock_multi_part_etag_only_rule();
+        // Rules are only used once, so each client gets new ones.
+        let single_part = mock_single_part_etag_only_rule;
+        let multipart = mock_multi_part_etag_only_rule;
 
         let builder = CopyTaskBuilder::default()
             .with_concurrency(10)
@@ -723,21 +1535,21 @@ pub(crate) mod test {
         let lt_threshold = builder
             .clone()
             .with_multipart_threshold(Some(TEST_FILE_SIZE + 1))
-            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, single_part.as_slice())));
+            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, &single_part())));
         assert_eq!(lt_threshold.build().await?.part_size, None);
 
         // S3 to S3 will always prefer the original upload settings so even if the size is greater than the
         // threshold, it should still be single part.
         let gt_threshold = builder
             .clone()
-            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, single_part.as_slice())));
+            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, &single_part())));
         assert_eq!(gt_threshold.build().await?.part_size, None);
 
         // If it was originally multipart, it should prefer that even if below the threshold.
         let multipart_lt_threshold = builder
             .clone()
             .with_multipart_threshold(Some(TEST_FILE_SIZE + 1))
-            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, multipart.as_slice())));
+            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, &multipart())));
         assert_eq!(
             multipart_lt_threshold.build().await?.part_size,
             Some(214748365)
@@ -745,7 +1557,7 @@ pub(crate) mod test {
 
         let multipart_gt_threshold = builder
             .clone()
-            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, multipart.as_slice())));
+            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, &multipart())));
         assert_eq!(
             multipart_gt_threshold.build().await?.part_size,
             Some(214748365)
@@ -755,12 +1567,12 @@ pub(crate) mod test {
         let part_size_set = builder
             .clone()
             .with_part_size(Some(5242880))
-            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, single_part.as_slice())));
+            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, &single_part())));
         assert_eq!(part_size_set.build().await?.part_size, Some(5242880));
         let part_size_set_multipart = builder
             .clone()
             .with_part_size(Some(5242880))
-            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, multipart.as_slice())));
+            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, &multipart())));
         assert_eq!(
             part_size_set_multipart.build().await?.part_size,
             Some(5242880)
@@ -789,18 +1601,19 @@ pub(crate) mod test {
         let part_size_err_max = builder
             .clone()
             .with_part_size(Some(60000000000))
-            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, single_part.as_slice())));
+            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, &single_part())));
         assert!(part_size_err_max.build().await.is_err());
         // If the part size exceeds the limits, this should be an error.
         let part_size_err_min = builder
             .clone()
             .with_part_size(Some(1))
-            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, single_part.as_slice())));
+            .with_source_client(Arc::new(mock_size(TEST_FILE_SIZE, &single_part())));
         assert!(part_size_err_min.build().await.is_err());
 
         Ok(())
     }
 
+    #[cfg(feature = "cloud")]
     fn mock_size(size: u64, attributes: &[Rule]) -> Client {
         let get_object = mock_not_found_rule("key.sums".to_string());
         let head_object = mock!(Client::head_object)
@@ -830,9 +1643,10 @@ pub(crate) mod test {
         )
     }
 
+    #[cfg(feature = "cloud")]
     pub(crate) fn mock_not_found_rule(key: String) -> Rule {
         mock!(Client::get_object)
             .match_requests(move |req| req.bucket() == Some("bucket") && req.key() == Some(&key))
-            .then_error(move || GetObjectError::NoSuchKey(NoSuchKey::builder().build()))
+            .then_http_response(|| error_response(404, "NoSuchKey"))
     }
 }
@@ -0,0 +1,170 @@
+//! Avoids computing checksums more than once for identical files in the same run.
+//!
+
+use crate::checksum::file::SumsFile;
+use crate::error::Result;
+use crate::io::sums::file::File;
+use crate::io::Provider;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+/// The number of bytes at the start of a file that are hashed to find possible duplicates.
+pub const DEDUP_PREFIX_SIZE: u64 = 64 * 1024;
+
+/// Caches sums files computed during a run, keyed by the file size and a hash of the start of
+/// the file. This key is cheap to compute but can have false positives, so the full contents
+/// are compared before a cached sums file is reused. Only local files are cached. The cache can
+/// be shared between concurrent tasks, and it is only locked to access the entries, not while
+/// files are read.
+#[derive(Debug, Default)]
+pub struct DedupCache {
+    entries: Mutex<Entries>,
+}
+
+/// The cached sums files and the paths they were computed for, keyed by the size and prefix hash.
+type Entries = HashMap<(u64, u64), Vec<(String, SumsFile)>>;
+
+impl DedupCache {
+    /// Get the path and cache key of an input if it is a local file. Archive members and
+    /// concatenated files are not cached.
+    async fn key(input: &str) -> Result<Option<(String, (u64, u64))>> {
+        let Provider::File { file } = Provider::try_from(input)? else {
+            return Ok(None);
+        };
+        let object = File::new(file.to_string());
+        if object.archive_member().is_some() || object.concat_parts().await?.is_some() {
+            return Ok(None);
+        }
+
+        let path = SumsFile::format_target_file(&file);
+        let Ok(metadata) = fs::metadata(&path).await else {
+            return Ok(None);
+        };
+
+        let mut prefix = vec![];
+        fs::File::open(&path)
+            .await?
+            .take(DEDUP_PREFIX_SIZE)
+            .read_to_end(&mut prefix)
+            .await?;
+        let mut hasher = DefaultHasher::new();
+        prefix.hash(&mut hasher);
+
+        Ok(Some((path, (metadata.len(), hasher.finish()))))
+    }
+
+    /// Get a sums file that was computed for an input with identical contents.
+    pub async fn get(&self, input: &str) -> Result<Option<SumsFile>> {
+        let Some((path, key)) = Self::key(input).await? else {
+            return Ok(None);
+        };
+
+        let candidates = self.entries().get(&key).cloned().unwrap_or_default();
+        for (candidate, sums) in candidates {
+            if Self::same_contents(&candidate, &path).await? {
+                return Ok(Some(sums));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Lock the entries.
+    fn entries(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().expect("dedup cache lock poisoned")
+    }
+
+    /// Add a computed sums file for an input.
+    pub async fn insert(&self, input: &str, sums: SumsFile) -> Result<()> {
+        if let Some((path, key)) = Self::key(input).await? {
+            self.entries().entry(key).or_default().push((path, sums));
+        }
+
+        Ok(())
+    }
+
+    /// Compare the full contents of two files.
+    async fn same_contents(a: &str, b: &str) -> Result<bool> {
+        let mut a = BufReader::new(fs::File::open(a).await?);
+        let mut b = BufReader::new(fs::File::open(b).await?);
+
+        loop {
+            let a_buf = a.fill_buf().await?;
+            let b_buf = b.fill_buf().await?;
+            if a_buf.is_empty() || b_buf.is_empty() {
+                return Ok(a_buf.is_empty() && b_buf.is_empty());
+            }
+
+            let n = a_buf.len().min(b_buf.len());
+            if a_buf[..n] != b_buf[..n] {
+                return Ok(false);
+            }
+
+            a.consume(n);
+            b.consume(n);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::checksum::file::Checksum;
+    use crate::task::generate::GenerateTaskBuilder;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_dedup_cache() -> Result<()> {
+        let tmp = tempdir()?;
+        let path = |name| tmp.path().join(name).to_string_lossy().to_string();
+        let (a, b, c) = (path("a"), path("b"), path("c"));
+
+        // `c` has the same size and prefix as `a` and `b`, but different contents.
+        let data = vec![1; DEDUP_PREFIX_SIZE as usize + 10];
+        let mut different = data.clone();
+        *different.last_mut().unwrap() = 2;
+        fs::write(&a, &data).await?;
+        fs::write(&b, &data).await?;
+        fs::write(&c, &different).await?;
+
+        let generate = |input: &str, precomputed| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(input.to_string())
+                .with_context(vec!["md5".parse().unwrap()])
+                .with_capacity(10)
+                .set_precomputed(precomputed)
+        };
+
+        let cache = DedupCache::default();
+        let sums = generate(&a, None).build().await?.run().await?;
+        cache.insert(&a, sums.sums_file().clone()).await?;
+
+        let cached = cache.get(&b).await?;
+        assert_eq!(cached.as_ref(), Some(sums.sums_file()));
+        assert!(cache.get(&c).await?.is_none());
+
+        // Precomputed checksums are used instead of reading the file.
+        let mut precomputed = cached.unwrap();
+        precomputed
+            .checksums
+            .insert("md5".parse()?, Checksum::new("precomputed".to_string()));
+        let (output, _, _, generated) = generate(&b, Some(precomputed))
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner();
+        assert_eq!(
+            output.checksums[&"md5".parse()?],
+            Checksum::new("precomputed".to_string())
+        );
+        assert_eq!(output.size, Some(data.len() as u64));
+        assert_eq!(generated.len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+//! Compare two objects byte by byte, stopping at the first difference.
+//!
+
+use crate::error::Result;
+use crate::io::sums::ObjectSumsBuilder;
+use crate::io::Client;
+use crate::stats::DiffStats;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::try_join;
+
+/// The number of bytes of each object that are compared at a time.
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Build a diff task.
+#[derive(Default)]
+pub struct DiffTaskBuilder {
+    first: String,
+    second: String,
+    client: Option<Arc<Client>>,
+}
+
+impl DiffTaskBuilder {
+    /// Set the first object to compare.
+    pub fn with_first(mut self, first: String) -> Self {
+        self.first = first;
+        self
+    }
+
+    /// Set the second object to compare.
+    pub fn with_second(mut self, second: String) -> Self {
+        self.second = second;
+        self
+    }
+
+    /// Set the S3 client to use.
+    pub fn set_client(mut self, client: Option<Arc<Client>>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Build a diff task. This opens both objects, but does not read them.
+    pub async fn build(self) -> Result<DiffTask> {
+        let reader = |input: String| {
+            let client = self.client.clone();
+            async move {
+                ObjectSumsBuilder::default()
+                    .set_client(client)
+                    .build(input)
+                    .await?
+                    .reader(None)
+                    .await
+            }
+        };
+        let (first_reader, second_reader) = try_join!(
+            reader(self.first.to_string()),
+            reader(self.second.to_string())
+        )?;
+
+        Ok(DiffTask {
+            first: self.first,
+            second: self.second,
+            first_reader,
+            second_reader,
+        })
+    }
+}
+
+/// Execute the diff task.
+pub struct DiffTask {
+    first: String,
+    second: String,
+    first_reader: Box<dyn AsyncRead + Unpin + Send>,
+    second_reader: Box<dyn AsyncRead + Unpin + Send>,
+}
+
+impl DiffTask {
+    /// Read both objects in lockstep and report the offset of the first byte that differs. The
+    /// objects are only read up to the first difference.
+    pub async fn run(mut self) -> Result<DiffStats> {
+        let mut first_buf = vec![0; BUFFER_SIZE];
+        let mut second_buf = vec![0; BUFFER_SIZE];
+
+        let mut bytes_compared = 0;
+        loop {
+            let (first_n, second_n) = try_join!(
+                Self::fill(&mut self.first_reader, &mut first_buf),
+                Self::fill(&mut self.second_reader, &mut second_buf)
+            )?;
+
+            // Either a byte differs, or one object ended before the other.
+            let n = first_n.min(second_n);
+            let position = first_buf[..n]
+                .iter()
+                .zip(&second_buf[..n])
+                .position(|(first, second)| first != second)
+                .or((first_n != second_n).then_some(n));
+
+            if let Some(position) = position {
+                bytes_compared += position as u64;
+                let shorter = (position == n).then(|| {
+                    if first_n < second_n {
+                        self.first.to_string()
+                    } else {
+                        self.second.to_string()
+                    }
+                });
+
+                return Ok(DiffStats {
+                    first: self.first,
+                    second: self.second,
+                    bytes_compared,
+                    offset: Some(bytes_compared),
+                    shorter,
+                });
+            }
+            if n == 0 {
+                return Ok(DiffStats {
+                    first: self.first,
+                    second: self.second,
+                    bytes_compared,
+                    offset: None,
+                    shorter: None,
+                });
+            }
+
+            bytes_compared += n as u64;
+        }
+    }
+
+    /// Read until the buffer is full or the reader ends, returning the number of bytes read.
+    async fn fill(reader: &mut (dyn AsyncRead + Unpin + Send), buf: &mut [u8]) -> Result<usize> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            let n = reader.read(&mut buf[filled..]).await?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+
+        Ok(filled)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn diff() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = |name: &str| tmp.path().join(name).to_string_lossy().to_string();
+        let data: Vec<u8> = (0..=255u8).cycle().take(3 * BUFFER_SIZE + 100).collect();
+        tokio::fs::write(name("first"), &data).await?;
+
+        let diff = |second: &[u8]| {
+            let second = second.to_vec();
+            let (first, path) = (name("first"), name("second"));
+            async move {
+                tokio::fs::write(&path, second).await?;
+                Ok::<_, anyhow::Error>(
+                    DiffTaskBuilder::default()
+                        .with_first(first)
+                        .with_second(path)
+                        .build()
+                        .await?
+                        .run()
+                        .await?,
+                )
+            }
+        };
+
+        let stats = diff(&data).await?;
+        assert!(!stats.is_mismatch());
+        assert_eq!(stats.bytes_compared, data.len() as u64);
+
+        // A difference after the first buffer.
+        let mut changed = data.clone();
+        changed[BUFFER_SIZE + 5] ^= 0xff;
+        let stats = diff(&changed).await?;
+        assert_eq!(stats.offset, Some(BUFFER_SIZE as u64 + 5));
+        assert!(stats.shorter.is_none());
+
+        // A truncated object differs where it ends.
+        let stats = diff(&data[..BUFFER_SIZE * 2]).await?;
+        assert_eq!(stats.offset, Some(BUFFER_SIZE as u64 * 2));
+        assert_eq!(stats.shorter, Some(name("second")));
+
+        Ok(())
+    }
+}
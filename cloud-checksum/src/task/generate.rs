@@ -1,19 +1,35 @@
 //! Generate checksums for files.
 //!
 
-use crate::checksum::file::{Checksum, SumsFile};
+use crate::checksum::checkpoint::{Checkpoint, Checkpointer};
+use crate::checksum::file::{ByteRange, Checksum, FileMetadata, SumsFile, SumsFormat, SumsStorage};
 use crate::checksum::Ctx;
-use crate::error::Error::GenerateError;
+use crate::cli::{OverwritePolicy, Strength};
+use crate::error::Error::{GenerateError, SizeMismatch};
 use crate::error::{ApiError, Error, Result};
-use crate::io::sums::channel::ChannelReader;
+use crate::io::sums::cache::DownloadCache;
+use crate::io::sums::channel::{ChannelMetrics, ChannelReader};
+use crate::io::sums::decompress::Decompress;
+use crate::io::sums::normalize::NormalizeNewlines;
+use crate::io::sums::tee::TeeReader;
 use crate::io::sums::{ObjectSums, ObjectSumsBuilder, SharedReader};
+use crate::io::Client;
+use crate::io::Provider;
+use crate::stats::{GenerateTimings, TaskTiming};
 use crate::task::check::{CheckObjects, SumsKey};
 use crate::task::generate::Task::{ChecksumTask, ReadTask};
-use aws_sdk_s3::Client;
-use futures_util::future::join_all;
+use futures_util::stream::FuturesUnordered;
+use futures_util::StreamExt;
 use std::collections::{BTreeMap, HashSet};
-use std::sync::Arc;
-use tokio::task::JoinHandle;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio_util::task::AbortOnDropHandle;
+use tracing::{debug, info};
 
 /// Define the kind of task that is running.
 #[derive(Debug)]
@@ -26,14 +42,34 @@ pub enum Task {
 #[derive(Default)]
 pub struct GenerateTaskBuilder {
     input_file_name: String,
-    overwrite: bool,
+    overwrite: OverwritePolicy,
     verify: bool,
     ctxs: Vec<Ctx>,
     reader: Option<Box<dyn SharedReader + Send>>,
     capacity: usize,
+    channel_metrics: Option<Arc<ChannelMetrics>>,
     write: bool,
     client: Option<Arc<Client>>,
     avoid_get_object_attributes: bool,
+    offset: Option<u64>,
+    length: Option<u64>,
+    format: SumsFormat,
+    storage: SumsStorage,
+    record_metadata: bool,
+    record_parts: bool,
+    strict_s3: bool,
+    sums_destination: Option<String>,
+    sums_source: Option<String>,
+    output_dir: Option<String>,
+    sparse: bool,
+    precomputed: Option<SumsFile>,
+    checkpoint: Option<PathBuf>,
+    normalize_newlines: bool,
+    decompress: Option<Decompress>,
+    input_size: Option<u64>,
+    download_cache: Option<DownloadCache>,
+    tee: Option<PathBuf>,
+    timings: bool,
 }
 
 impl GenerateTaskBuilder {
@@ -43,8 +79,18 @@ impl GenerateTaskBuilder {
         self
     }
 
-    /// Set whether to overwrite existing files.
-    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+    /// Set whether to overwrite existing files. This always overwrites if true, and otherwise
+    /// only computes missing checksums.
+    pub fn with_overwrite(self, overwrite: bool) -> Self {
+        self.with_overwrite_policy(if overwrite {
+            OverwritePolicy::Always
+        } else {
+            OverwritePolicy::Missing
+        })
+    }
+
+    /// Set when to overwrite existing files.
+    pub fn with_overwrite_policy(mut self, overwrite: OverwritePolicy) -> Self {
         self.overwrite = overwrite;
         self
     }
@@ -73,6 +119,18 @@ impl GenerateTaskBuilder {
         self
     }
 
+    /// Track how often the reader channels were full or empty using the metrics.
+    pub fn set_channel_metrics(mut self, channel_metrics: Option<Arc<ChannelMetrics>>) -> Self {
+        self.channel_metrics = channel_metrics;
+        self
+    }
+
+    /// Measure how long the reader and the checksum tasks take.
+    pub fn with_timings(mut self, timings: bool) -> Self {
+        self.timings = timings;
+        self
+    }
+
     /// Set the S3 client to use.
     pub fn with_client(self, client: Arc<Client>) -> Self {
         self.set_client(Some(client))
@@ -101,11 +159,146 @@ impl GenerateTaskBuilder {
         self
     }
 
+    /// Set the format used to read and write sums files.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set where sums files are stored for S3 objects.
+    pub fn with_storage(mut self, storage: SumsStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Record the modification time and permissions of the input in the sums file.
+    pub fn with_record_metadata(mut self, record_metadata: bool) -> Self {
+        self.record_metadata = record_metadata;
+        self
+    }
+
+    /// Record the part checksums of AWS checksums in the sums file.
+    pub fn with_record_parts(mut self, record_parts: bool) -> Self {
+        self.record_parts = record_parts;
+        self
+    }
+
+    /// Error if an AWS checksum has parts that could not belong to an S3 object, rather than
+    /// only warning about too many parts.
+    pub fn with_strict_s3(mut self, strict_s3: bool) -> Self {
+        self.strict_s3 = strict_s3;
+        self
+    }
+
+    /// Read and write the sums file in this location rather than next to the input, e.g. a
+    /// directory for files or an `s3://bucket/prefix/` for S3 objects.
+    pub fn set_sums_destination(mut self, sums_destination: Option<String>) -> Self {
+        self.sums_destination = sums_destination;
+        self
+    }
+
+    /// Read and write the sums file in a local directory, independent of where the input is
+    /// stored, e.g. `manifests/` for `s3://bucket/key` uses `manifests/bucket/key.sums`.
+    pub fn set_sums_source(mut self, sums_source: Option<String>) -> Self {
+        self.sums_source = sums_source;
+        self
+    }
+
+    /// Read and write the sums files of local files in a directory which mirrors their
+    /// absolute paths, rather than next to the input.
+    pub fn set_output_dir(mut self, output_dir: Option<String>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Skip reading the holes of sparse local files, producing their zeros in memory instead.
+    pub fn with_sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    /// Use the checksums from a sums file computed for identical contents instead of reading
+    /// the input. This only applies if it contains all the checksums that need to be computed.
+    pub fn set_precomputed(mut self, precomputed: Option<SumsFile>) -> Self {
+        self.precomputed = precomputed;
+        self
+    }
+
+    /// Only compute checksums starting at this byte offset of the input.
+    pub fn set_offset(mut self, offset: Option<u64>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Only compute checksums over this many bytes of the input.
+    pub fn set_length(mut self, length: Option<u64>) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Persist the progress of generating checksums to this file, and resume from it if it
+    /// exists.
+    pub fn set_checkpoint(mut self, checkpoint: Option<PathBuf>) -> Self {
+        self.checkpoint = checkpoint;
+        self
+    }
+
+    /// Convert CRLF line endings to LF before computing checksums, and record this in the
+    /// sums file. A reader that is set directly must already be normalized.
+    pub fn with_normalize_newlines(mut self, normalize_newlines: bool) -> Self {
+        self.normalize_newlines = normalize_newlines;
+        self
+    }
+
+    /// Decompress the input before computing checksums, and record this in the sums file. A
+    /// reader that is set directly must already be decompressed.
+    pub fn set_decompress(mut self, decompress: Option<Decompress>) -> Self {
+        self.decompress = decompress;
+        self
+    }
+
+    /// Set the size of an input that cannot be determined in advance, such as stdin or a named
+    /// pipe. This allows using part numbers with AWS checksums, and it is an error if the
+    /// input is a different size.
+    pub fn set_input_size(mut self, input_size: Option<u64>) -> Self {
+        self.input_size = input_size;
+        self
+    }
+
+    /// Set a local cache of downloaded objects, so that S3 objects which were read before are
+    /// not downloaded again.
+    pub fn set_download_cache(mut self, download_cache: Option<DownloadCache>) -> Self {
+        self.download_cache = download_cache;
+        self
+    }
+
+    /// Write a copy of the input to this file while computing its checksums, so that it is
+    /// saved and checksummed from a single read. The input is always read, so the specified
+    /// checksums are computed again even if they exist. An existing sums file is still not
+    /// modified with the `never` overwrite policy. This does not apply when reading directly
+    /// from a reader, which should be wrapped in a `TeeReader` instead.
+    pub fn set_tee(mut self, tee: Option<PathBuf>) -> Self {
+        self.tee = tee;
+        self
+    }
+
     /// Build a generate task.
     pub async fn build(mut self) -> Result<GenerateTask> {
+        let strict_s3 = self.strict_s3;
+        self.ctxs
+            .iter_mut()
+            .for_each(|ctx| ctx.set_strict_s3(strict_s3));
+
         let mut sums = ObjectSumsBuilder::default()
             .set_client(self.client)
             .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
+            .with_format(self.format)
+            .with_storage(self.storage)
+            .set_sums_destination(self.sums_destination)
+            .set_sums_source(self.sums_source)
+            .set_output_dir(self.output_dir)
+            .with_sparse(self.sparse)
+            .set_download_cache(self.download_cache)
             .build(self.input_file_name.to_string())
             .await?;
 
@@ -114,38 +307,200 @@ impl GenerateTaskBuilder {
         } else {
             None
         };
-
-        let mode = if self.overwrite {
-            OverwriteMode::Overwrite
-        } else if self.verify {
-            OverwriteMode::Verify
+        let metadata = if self.record_metadata && !self.input_file_name.is_empty() {
+            sums.file_metadata().await?
         } else {
-            OverwriteMode::None
+            Default::default()
+        };
+
+        let mut write = self.write;
+        let mut mode = match self.overwrite {
+            OverwritePolicy::Always => OverwriteMode::Overwrite,
+            _ if self.verify => OverwriteMode::Verify,
+            // The input must be read to write a copy of it, so the checksums are computed, but
+            // an existing sums file is still never modified.
+            OverwritePolicy::Never if self.tee.is_some() && existing_output.is_some() => {
+                write = false;
+                OverwriteMode::Overwrite
+            }
+            OverwritePolicy::Never => OverwriteMode::Never,
+            _ if self.tee.is_some() => OverwriteMode::Overwrite,
+            _ => OverwriteMode::None,
         };
 
-        let reader: Box<dyn SharedReader + Send> = if let Some(reader) = self.reader {
+        // The size of the normalized or decompressed input is not known until it has been
+        // read.
+        let transformed = self.normalize_newlines || self.decompress.is_some();
+        let mut input_size = self.input_size.filter(|_| !transformed);
+
+        let mut range = None;
+        let mut checkpointer = None;
+        let mut current_size = input_size;
+        let reader: Box<dyn SharedReader + Send> = if let Some(reader) = self.reader.take() {
+            if self.checkpoint.is_some() {
+                return Err(GenerateError(
+                    "a checkpoint cannot be used when reading from stdin".to_string(),
+                ));
+            }
+            if input_size.is_some() {
+                self.ctxs
+                    .iter_mut()
+                    .for_each(|ctx| ctx.set_file_size(input_size));
+            }
             reader
         } else {
-            let file_size = sums.file_size().await?;
+            if self.normalize_newlines && self.checkpoint.is_some() {
+                return Err(GenerateError(
+                    "a checkpoint cannot be used when normalizing newlines".to_string(),
+                ));
+            }
+            if self.decompress.is_some() && self.checkpoint.is_some() {
+                return Err(GenerateError(
+                    "a checkpoint cannot be used when decompressing".to_string(),
+                ));
+            }
+            if self.tee.is_some() && self.checkpoint.is_some() {
+                return Err(GenerateError(
+                    "a checkpoint cannot be used when writing a copy of the input".to_string(),
+                ));
+            }
+
+            // Named pipes have an unknown size, like stdin.
+            let mut file_size = sums.file_size().await?;
+            if file_size.is_some() {
+                input_size = None;
+            } else {
+                file_size = self.input_size;
+            }
+            range = ByteRange::from_options(self.offset, self.length, file_size)?;
+            if let Some(range) = range {
+                file_size = Some(range.length);
+            }
+
+            let ctx_file_size = file_size.filter(|_| !transformed);
+            current_size = ctx_file_size;
             self.ctxs
                 .iter_mut()
-                .for_each(|ctx| ctx.set_file_size(file_size));
-            let reader = sums.reader().await?;
+                .for_each(|ctx| ctx.set_file_size(ctx_file_size));
+
+            let mut read_range = range.map(Into::into);
+            if let Some(path) = self.checkpoint.take() {
+                // Verifying also generates the existing checksums, which need to be resumable.
+                let mut ctxs = self.ctxs.clone();
+                if let (OverwriteMode::Verify, Some(existing)) = (&mode, &existing_output) {
+                    ctxs.extend(existing.checksums.keys().cloned());
+                }
+                for ctx in &ctxs {
+                    ctx.checkpoint(0)?;
+                }
+
+                let checkpoint = match Checkpoint::read(&path).await? {
+                    Some(checkpoint)
+                        if checkpoint.size != file_size || checkpoint.range != range =>
+                    {
+                        return Err(GenerateError(format!(
+                            "the checkpoint `{}` is for a different input size or byte range",
+                            path.to_string_lossy()
+                        )));
+                    }
+                    Some(checkpoint) => checkpoint,
+                    None => Checkpoint::new(file_size, range),
+                };
+
+                // Read from the earliest point that any checksum needs to resume from.
+                let start = checkpoint.resume_offset(&ctxs);
+                if start > 0 {
+                    info!(checkpoint = %path.display(), start, "resuming from checkpoint");
+                    let file_size = file_size.ok_or_else(|| {
+                        GenerateError("cannot resume without knowing the file size".to_string())
+                    })?;
+                    let offset = range.map(|range| range.offset).unwrap_or_default();
+                    read_range = Some(ByteRange::new(offset + start, file_size - start).into());
+                }
+
+                checkpointer = Some(Checkpointer::new(path, checkpoint, start));
+            }
+            let mut reader = sums.reader(read_range).await?;
+            if let Some(tee) = &self.tee {
+                reader = Box::new(TeeReader::new(reader, tokio::fs::File::create(tee).await?));
+            }
+            if let Some(decompress) = &self.decompress {
+                reader = decompress.reader(reader);
+            }
+            if self.normalize_newlines {
+                reader = Box::new(NormalizeNewlines::new(reader));
+            }
 
-            let reader = ChannelReader::new(reader, self.capacity);
+            let reader =
+                ChannelReader::new(reader, self.capacity).set_metrics(self.channel_metrics);
             Box::new(reader)
         };
 
+        if self.ctxs.iter().any(Ctx::is_missing_file_size) {
+            return Err(GenerateError(if transformed {
+                "cannot use part number syntax when normalizing newlines or decompressing"
+                    .to_string()
+            } else {
+                "cannot use part number syntax without knowing the input size, which can be set with `--input-size`"
+                    .to_string()
+            }));
+        }
+
+        // A changed size means the existing checksums are stale, so they are replaced.
+        if let (OverwriteMode::None, OverwritePolicy::SizeChanged, Some(existing)) =
+            (&mode, self.overwrite, &existing_output)
+        {
+            if current_size.is_none() || existing.size != current_size {
+                debug!(
+                    existing = existing.size,
+                    input = current_size,
+                    "replacing existing sums file because the input size changed"
+                );
+                mode = OverwriteMode::Overwrite;
+            }
+        }
+
+        // Existing sums for a different byte range, newline normalization or decompression
+        // cannot be reused, and should only be replaced if overwriting.
+        let existing_output = match existing_output {
+            Some(existing)
+                if GenerateTask::is_incompatible(
+                    &existing,
+                    range,
+                    self.normalize_newlines,
+                    self.decompress,
+                ) =>
+            {
+                if write && !matches!(mode, OverwriteMode::Overwrite) {
+                    return Err(GenerateTask::incompatible_error());
+                }
+                debug!("ignoring existing sums file for different input bytes");
+                None
+            }
+            existing => existing,
+        };
+
         let task = GenerateTask {
             tasks: Default::default(),
+            reader_task: None,
             overwrite: mode,
             existing_output,
+            range,
+            normalized_newlines: self.normalize_newlines,
+            decompressed: self.decompress,
+            metadata,
+            record_parts: self.record_parts,
+            precomputed: self.precomputed,
+            input_size,
+            checkpointer,
             reader: Some(reader),
-            write: self.write,
+            // HTTP locations are read-only, so sums are only reported and not written.
+            write: write && !Provider::try_from(self.input_file_name.as_str())?.is_http(),
             object_sums: sums,
             updated: false,
             output: Default::default(),
             checksums_generated: Default::default(),
+            timings: self.timings.then(Default::default),
         };
 
         let task = task.add_tasks(HashSet::from_iter(self.ctxs))?;
@@ -157,30 +512,146 @@ impl GenerateTaskBuilder {
 enum OverwriteMode {
     #[default]
     None,
+    Never,
     Verify,
     Overwrite,
 }
 
 /// Execute the generate checksums tasks.
 pub struct GenerateTask {
-    tasks: Vec<JoinHandle<Result<Task>>>,
+    tasks: Vec<AbortOnDropHandle<Result<Task>>>,
+    reader_task: Option<usize>,
     overwrite: OverwriteMode,
     existing_output: Option<SumsFile>,
+    range: Option<ByteRange>,
+    normalized_newlines: bool,
+    decompressed: Option<Decompress>,
+    metadata: FileMetadata,
+    record_parts: bool,
+    precomputed: Option<SumsFile>,
+    input_size: Option<u64>,
+    checkpointer: Option<Checkpointer>,
     reader: Option<Box<dyn SharedReader + Send>>,
     write: bool,
     object_sums: Box<dyn ObjectSums + Send>,
     updated: bool,
     output: SumsFile,
     checksums_generated: BTreeMap<Ctx, Checksum>,
+    timings: Option<Arc<StdMutex<GenerateTimings>>>,
+}
+
+/// A future which measures the time from when it is first polled to when it completes, and the
+/// time spent polling it, which is the time that it was busy rather than waiting.
+struct Timed<F> {
+    future: Pin<Box<F>>,
+    start: Option<Instant>,
+    busy: Duration,
+}
+
+impl<F> Timed<F> {
+    /// Measure the future.
+    fn new(future: F) -> Self {
+        Self {
+            future: Box::pin(future),
+            start: None,
+            busy: Duration::ZERO,
+        }
+    }
+}
+
+impl<F: Future> Future for Timed<F> {
+    type Output = (F::Output, TaskTiming);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let now = Instant::now();
+        let start = *self.start.get_or_insert(now);
+        let poll = self.future.as_mut().poll(cx);
+        self.busy += now.elapsed();
+
+        poll.map(|output| (output, TaskTiming::new(start.elapsed(), self.busy)))
+    }
 }
 
 impl GenerateTask {
+    /// Whether an existing sums file is for a different byte range, newline normalization or
+    /// decompression than the generated checksums, in which case it cannot be reused.
+    fn is_incompatible(
+        existing: &SumsFile,
+        range: Option<ByteRange>,
+        normalized_newlines: bool,
+        decompressed: Option<Decompress>,
+    ) -> bool {
+        (existing.range != range
+            || existing.normalized_newlines != normalized_newlines
+            || existing.decompressed != decompressed)
+            && !existing.is_empty()
+    }
+
+    /// The error for replacing an incompatible existing sums file without overwriting.
+    fn incompatible_error() -> Error {
+        GenerateError(
+            "the existing sums file is for a different byte range, newline normalization or decompression, use overwrite to replace it"
+                .to_string(),
+        )
+    }
+
+    /// Spawns a task, measuring it if timings are enabled. The timing is added to the timing
+    /// that is selected from the timings of this task.
+    fn spawn<F>(&mut self, task: F, select: fn(&mut GenerateTimings) -> &mut TaskTiming)
+    where
+        F: Future<Output = Result<Task>> + Send + 'static,
+    {
+        let handle = match self.timings.clone() {
+            None => tokio::spawn(task),
+            Some(timings) => tokio::spawn(async move {
+                let (result, timing) = Timed::new(task).await;
+                select(&mut timings.lock().expect("timings lock poisoned")).add(timing);
+                result
+            }),
+        };
+
+        self.tasks.push(AbortOnDropHandle::new(handle));
+    }
+
     /// Spawns a task which reads from the buffered reader.
     pub fn add_reader_task(mut self) -> Result<Self> {
         let mut reader = self.reader.take().expect("reader already taken");
-        self.tasks.push(tokio::spawn(async move {
-            Ok(ReadTask(reader.read_chunks().await?))
-        }));
+        // A resumed input is only read from the checkpoint onwards.
+        let start = self
+            .checkpointer
+            .as_ref()
+            .map(Checkpointer::start)
+            .unwrap_or_default();
+        let input_size = self.input_size;
+        let timings = self.timings.clone().map(|timings| {
+            let read_nanos = Arc::new(AtomicU64::new(0));
+            reader.time_reads(read_nanos.clone());
+            (timings, read_nanos)
+        });
+        self.reader_task = Some(self.tasks.len());
+        self.spawn(
+            async move {
+                let size = reader.read_chunks().await?;
+                if let Some((timings, read_nanos)) = timings {
+                    timings.lock().expect("timings lock poisoned").read_seconds +=
+                        Duration::from_nanos(read_nanos.load(Ordering::Relaxed)).as_secs_f64();
+                }
+
+                // A stream that ends early, e.g. a truncated download, must not be checksummed as
+                // if it was the whole input.
+                if let Some(input_size) =
+                    input_size.filter(|input_size| *input_size != start + size)
+                {
+                    return Err(SizeMismatch(format!(
+                        "read {} bytes from the input, which does not match the input size of {input_size} bytes",
+                        start + size
+                    )));
+                }
+
+                Ok(ReadTask(size))
+            },
+            |timings| &mut timings.reader,
+        );
         Ok(self)
     }
 
@@ -191,13 +662,21 @@ impl GenerateTask {
             .as_mut()
             .map(|reader| reader.as_stream())
             .expect("missing reader");
-        self.tasks.push(tokio::spawn(async move {
-            let stream = ctx.generate(stream);
-
-            let digest = stream.await?;
+        let checkpointer = self.checkpointer.clone();
+        self.spawn(
+            async move {
+                let (ctx, digest) = match checkpointer {
+                    Some(checkpointer) => checkpointer.generate(ctx, stream).await?,
+                    None => {
+                        let digest = ctx.generate(stream).await?;
+                        (ctx, digest)
+                    }
+                };
 
-            Ok(ChecksumTask(Box::new((ctx, digest))))
-        }));
+                Ok(ChecksumTask(Box::new((ctx, digest))))
+            },
+            |timings| &mut timings.checksums,
+        );
 
         self
     }
@@ -236,35 +715,138 @@ impl GenerateTask {
                     })
                     .transpose()?;
             }
+            // If never overwriting, nothing is computed when there is an existing file.
+            OverwriteMode::Never if existing.is_some() => checksums.clear(),
             // If it's overwriting, just use the checksums as specified on the command line.
             _ => {}
         }
 
+        // Precomputed checksums can only be used if they contain everything that is needed.
+        let normalized_newlines = self.normalized_newlines;
+        let decompressed = self.decompressed;
+        self.precomputed = self.precomputed.take().filter(|precomputed| {
+            precomputed.normalized_newlines == normalized_newlines
+                && precomputed.decompressed == decompressed
+                && !checksums.is_empty()
+                && checksums
+                    .iter()
+                    .all(|ctx| precomputed.checksums.contains_key(ctx))
+        });
+
         // Only perform generate tasks if there is something to do.
-        if !checksums.is_empty() {
+        let location = self.object_sums.location();
+        if let Some(precomputed) = &self.precomputed {
+            info!(
+                location,
+                "using precomputed checksums of identical contents"
+            );
+            self.checksums_generated = precomputed
+                .checksums
+                .iter()
+                .filter(|(ctx, _)| checksums.contains(ctx))
+                .map(|(ctx, checksum)| (ctx.clone(), checksum.clone()))
+                .collect();
+        } else if !checksums.is_empty() {
+            let names = checksums.iter().map(Ctx::to_string).collect::<Vec<_>>();
+            debug!(
+                location,
+                checksums = names.join(","),
+                "generating checksums"
+            );
             self = self.add_generate_tasks(checksums).add_reader_task()?;
+        } else {
+            info!(location, "skipping because all checksums already exist");
         }
 
         Ok(self)
     }
 
+    /// Wait for all tasks to complete. The reader is aborted once a checksum fails, so the other
+    /// tasks finish soon after, without reading the rest of the input. A reader that has already
+    /// read the whole input is not interrupted, as it does not wait on anything after that. If
+    /// the input was a different size than expected, the size mismatch of the reader is
+    /// returned, as it is likely to be why a checksum failed. Otherwise, the error of the first
+    /// checksum that failed is returned before the error of the reader. The remaining tasks are
+    /// aborted when they are dropped, including when waiting for them is cancelled, e.g. by an
+    /// operation timeout.
+    async fn join_tasks(
+        tasks: Vec<AbortOnDropHandle<Result<Task>>>,
+        reader_task: Option<usize>,
+    ) -> Result<Vec<Task>> {
+        let reader = reader_task
+            .and_then(|index| tasks.get(index))
+            .map(AbortOnDropHandle::abort_handle);
+        let mut pending = tasks
+            .into_iter()
+            .enumerate()
+            .map(|(index, task)| async move { (index, task.await) })
+            .collect::<FuturesUnordered<_>>();
+
+        let mut results = vec![];
+        let mut reader_err = None;
+        let mut task_err = None;
+        while let Some((index, result)) = pending.next().await {
+            let is_reader = Some(index) == reader_task;
+            match result {
+                // The reader was aborted because a checksum failed.
+                Err(err) if is_reader && err.is_cancelled() => {}
+                result => match result.map_err(Error::from).and_then(|result| result) {
+                    Ok(task) => results.push(task),
+                    Err(err) if is_reader => reader_err = Some(err),
+                    Err(err) => {
+                        if let Some(reader) = &reader {
+                            reader.abort();
+                        }
+                        task_err.get_or_insert(err);
+                    }
+                },
+            }
+        }
+
+        match (reader_err, task_err) {
+            (Some(reader_err @ SizeMismatch(_)), _) => Err(reader_err),
+            (_, Some(err)) | (Some(err), None) => Err(err),
+            (None, None) => Ok(results),
+        }
+    }
+
     /// Runs the generate task, returning an output file.
     pub async fn run(mut self) -> Result<Self> {
-        let mut file_size = 0;
+        let mut file_size = self
+            .precomputed
+            .as_ref()
+            .and_then(|precomputed| precomputed.size)
+            .unwrap_or_default();
         let tasks: Vec<_> = self.tasks.drain(..).collect();
-        let checksums = join_all(tasks)
-            .await
+        let mut parts = BTreeMap::new();
+        let checksums = Self::join_tasks(tasks, self.reader_task)
+            .await?
             .into_iter()
-            .map(|val| {
-                let task = val??;
+            .map(|task| {
                 match task {
                     ReadTask(size) => {
-                        file_size = size;
+                        // A resumed input is only read from the checkpoint onwards.
+                        file_size = size
+                            + self
+                                .checkpointer
+                                .as_ref()
+                                .map(Checkpointer::start)
+                                .unwrap_or_default();
                         Ok(None)
                     }
                     ChecksumTask(ctx) => {
                         let (ctx, digest) = *ctx;
 
+                        if let Some(part_checksums) =
+                            ctx.part_checksums().filter(|_| self.record_parts)
+                        {
+                            let part_checksums = part_checksums
+                                .into_iter()
+                                .map(|(_, checksum)| Checksum::new(checksum))
+                                .collect::<Vec<_>>();
+                            parts.insert(ctx.clone(), part_checksums);
+                        }
+
                         let checksum = ctx.digest_to_string(&digest);
                         Ok(Some((ctx, Checksum::new(checksum))))
                     }
@@ -274,15 +856,21 @@ impl GenerateTask {
             .into_iter()
             .flatten();
 
-        self.checksums_generated = BTreeMap::from_iter(checksums);
-        let new_file = SumsFile::new(Some(file_size), self.checksums_generated.clone());
+        self.checksums_generated.extend(checksums);
+        let new_file = SumsFile::new(Some(file_size), self.checksums_generated.clone())
+            .with_parts(parts)
+            .with_range(self.range)
+            .with_normalized_newlines(self.normalized_newlines)
+            .with_decompressed(self.decompressed);
 
-        let output = match self.existing_output.clone() {
+        let mut output = match self.existing_output.clone() {
             Some(file) if !matches!(self.overwrite, OverwriteMode::Overwrite) => {
                 file.merge(new_file)?
             }
             _ => new_file,
         };
+        // Metadata is only kept when it is recorded, so that it is not stale.
+        output.set_metadata(self.metadata.clone());
 
         if output.checksums.is_empty() {
             return Err(GenerateError(
@@ -290,20 +878,59 @@ impl GenerateTask {
             ));
         }
 
+        self.output = output;
         if self.write {
-            let current = self.object_sums.sums_file().await?;
-
-            if current.as_ref() != Some(&output) {
-                self.object_sums.write_sums_file(&output).await?;
-                self.updated = true;
-            }
+            self.write_sums_file().await?;
         }
 
-        self.output = output;
+        // All checksums are complete, so the progress no longer needs to be kept.
+        if let Some(checkpointer) = &self.checkpointer {
+            checkpointer.remove().await?;
+        }
 
         Ok(self)
     }
 
+    /// Write the computed sums file if it differs from the current one, returning whether it
+    /// was written. This is called by `run` if the task was built with `write`, otherwise it
+    /// can be called after inspecting the computed sums file.
+    /// An existing sums file is never modified if overwriting is disabled, and a sums file for
+    /// different input bytes is only replaced if overwriting.
+    pub async fn write_sums_file(&mut self) -> Result<bool> {
+        // The null checksum is only used for benchmarking and is never written.
+        let mut written = self.output.clone();
+        written.checksums.retain(|ctx, _| !ctx.is_null());
+        let current = self.object_sums.sums_file().await?;
+
+        let location = self.object_sums.location();
+        if let Some(current) = current.as_ref().filter(|current| !current.is_empty()) {
+            if matches!(self.overwrite, OverwriteMode::Never) {
+                debug!(location, "not modifying the existing sums file");
+                return Ok(false);
+            }
+            if !matches!(self.overwrite, OverwriteMode::Overwrite)
+                && Self::is_incompatible(
+                    current,
+                    self.range,
+                    self.normalized_newlines,
+                    self.decompressed,
+                )
+            {
+                return Err(Self::incompatible_error());
+            }
+        }
+
+        if !written.checksums.is_empty() && current.as_ref() != Some(&written) {
+            debug!(location, "writing sums file");
+            self.object_sums.write_sums_file(&written).await?;
+            self.updated = true;
+            Ok(true)
+        } else {
+            debug!(location, "sums file is unchanged");
+            Ok(false)
+        }
+    }
+
     /// Get the inner values.
     pub fn into_inner(
         self,
@@ -330,6 +957,31 @@ impl GenerateTask {
     pub fn sums_file(&self) -> &SumsFile {
         &self.output
     }
+
+    /// Return the checksums that were computed by this task, rather than taken from an existing
+    /// sums file.
+    pub fn checksums_generated(&self) -> &BTreeMap<Ctx, Checksum> {
+        &self.checksums_generated
+    }
+
+    /// Whether the sums file was written.
+    pub fn updated(&self) -> bool {
+        self.updated
+    }
+
+    /// Get how long the reader and checksum tasks took if timings are enabled and the input
+    /// was read.
+    pub fn timings(&self) -> Option<GenerateTimings> {
+        self.timings
+            .as_ref()
+            .filter(|_| self.reader_task.is_some())
+            .map(|timings| *timings.lock().expect("timings lock poisoned"))
+    }
+
+    /// Get the location of the input.
+    pub fn location(&self) -> String {
+        self.object_sums.location()
+    }
 }
 
 /// Holds a file name and checksum context.
@@ -366,20 +1018,27 @@ impl SumCtxPairs {
         self.0
     }
 
-    /// Get the additional checksums required from a group of comparables sums files.
-    pub fn from_comparable(files: CheckObjects) -> Result<Option<Self>> {
+    /// Get the additional checksums required from a group of comparables sums files. Only
+    /// checksums with at least the minimum strength are considered if it is set.
+    pub fn from_comparable(
+        files: CheckObjects,
+        min_strength: Option<Strength>,
+    ) -> Result<Option<Self>> {
         // Get the checksum which contains the most amount of occurrences across groups of sums files.
+        // For ties, prefer `crc32c`, which is the cheapest to compute and which S3 can return as an
+        // additional checksum, and then other additional checksums such as `sha256` over MD5 `ETag`s.
         let file_ctx = files
             .0
-            .iter()
-            .flat_map(|(file, _)| file.0 .0.checksums.keys().cloned())
+            .keys()
+            .flat_map(|file| file.0 .0.checksums.keys().cloned())
+            .filter(|ctx| min_strength.is_none_or(|min_strength| ctx.strength() >= min_strength))
             .fold(BTreeMap::new(), |mut map, val| {
                 // Count occurrences
                 map.entry(val).and_modify(|count| *count += 1).or_insert(1);
                 map
             })
             .into_iter()
-            .max_by(|(_, a), (_, b)| a.cmp(b))
+            .max_by_key(|(ctx, count)| (*count, ctx.is_crc32c(), !ctx.is_md5()))
             .map(|(k, _)| k);
 
         if let Some(mut file_ctx) = file_ctx {
@@ -416,20 +1075,26 @@ impl From<Vec<SumCtxPair>> for SumCtxPairs {
 pub(crate) mod test {
     use super::*;
     use crate::checksum::aws_etag::test::expected_md5_1gib;
+    use crate::checksum::checksum_bytes;
     use crate::checksum::standard::test::{
         EXPECTED_CRC32C_BE_SUM, EXPECTED_CRC32_BE_SUM, EXPECTED_MD5_SUM, EXPECTED_SHA1_SUM,
         EXPECTED_SHA256_SUM,
     };
     use crate::checksum::standard::StandardCtx;
     use crate::io::sums::channel::test::channel_reader;
+    use crate::io::sums::decompress::test::gzip;
     use crate::io::sums::file::FileBuilder;
     use crate::task::check::test::write_test_files_not_comparable;
     use crate::task::check::{CheckTaskBuilder, GroupBy};
     use crate::test::{TestFileBuilder, TEST_FILE_SIZE};
     use anyhow::Result;
+    use std::io::{Seek, SeekFrom, Write};
     use std::path::Path;
+    use std::sync::atomic::{AtomicU64, Ordering};
     use tempfile::tempdir;
     use tokio::fs::File;
+    use tokio::io::AsyncReadExt;
+    use tokio_util::io::InspectReader;
 
     #[tokio::test]
     async fn test_sum_ctx_pairs() -> Result<()> {
@@ -443,12 +1108,70 @@ pub(crate) mod test {
             .await?;
         let (objects, _, _, _) = check.run().await?.into_inner();
 
-        let result = SumCtxPairs::from_comparable(objects)?.unwrap();
+        let result = SumCtxPairs::from_comparable(objects, None)?.unwrap();
 
+        // Every checksum is in one group, so the group without `crc32c` computes it.
         assert_eq!(
             result,
             vec![SumCtxPair::new(
-                files[2].to_string(),
+                files[0].to_string(),
+                Ctx::Regular(StandardCtx::crc32c())
+            )]
+            .into()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sum_ctx_pairs_prefer_additional() -> Result<()> {
+        let tmp = tempdir()?;
+        let a = tmp.path().join("a").to_string_lossy().to_string();
+        let b = tmp.path().join("b").to_string_lossy().to_string();
+        tokio::fs::write(&a, b"data").await?;
+        tokio::fs::write(&b, b"data").await?;
+
+        // Similar to an S3 object that has an `ETag` and additional checksums.
+        let sums = SumsFile::new(
+            Some(4),
+            BTreeMap::from_iter(vec![
+                ("md5".parse()?, Checksum::new("123".to_string())),
+                ("sha256".parse()?, Checksum::new("789".to_string())),
+                ("crc32c".parse()?, Checksum::new("456".to_string())),
+            ]),
+        );
+        FileBuilder::default()
+            .with_file(a.to_string())
+            .build()?
+            .write_sums(&sums)
+            .await?;
+
+        let from_comparable = |min_strength| {
+            let input = vec![a.to_string(), b.to_string()];
+            async move {
+                let check = CheckTaskBuilder::default()
+                    .with_input_files(input)
+                    .with_group_by(GroupBy::Comparability)
+                    .build()
+                    .await?;
+                let (objects, _, _, _) = check.run().await?.into_inner();
+                Ok::<_, anyhow::Error>(SumCtxPairs::from_comparable(objects, min_strength)?)
+            }
+        };
+
+        // `crc32c` is the cheapest to compute, even if stronger checksums are available.
+        assert_eq!(
+            from_comparable(None).await?.unwrap(),
+            vec![SumCtxPair::new(
+                b.to_string(),
+                Ctx::Regular(StandardCtx::crc32c())
+            )]
+            .into()
+        );
+        assert_eq!(
+            from_comparable(Some(Strength::Sha256)).await?.unwrap(),
+            vec![SumCtxPair::new(
+                b.to_string(),
                 Ctx::Regular(StandardCtx::sha256())
             )]
             .into()
@@ -502,6 +1225,993 @@ pub(crate) mod test {
         .await
     }
 
+    #[tokio::test]
+    async fn test_generate_tar_member() -> Result<()> {
+        let tmp = tempdir()?;
+        let archive = tmp.path().join("archive.tar");
+
+        let mut builder = tar::Builder::new(std::fs::File::create(&archive)?);
+        for (path, data) in [("dir/hello", "hello"), ("dir/world", "world")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, path, data.as_bytes())?;
+        }
+        builder.finish()?;
+
+        let archive = archive.to_string_lossy().to_string();
+        for (member, expected) in [
+            ("dir/hello", "5d41402abc4b2a76b9719d911017c592"), // pragma: allowlist secret
+            ("dir/world", "7d793037a0760186574b0282f2f435e7"), // pragma: allowlist secret
+        ] {
+            let file = GenerateTaskBuilder::default()
+                .with_input_file_name(format!("{}!{}", archive, member))
+                .with_context(vec!["md5".parse()?])
+                .with_capacity(10)
+                .write()
+                .build()
+                .await?
+                .run()
+                .await?
+                .into_inner()
+                .0;
+
+            assert_eq!(file.size, Some(5));
+            assert_eq!(
+                file.checksums[&"md5".parse()?],
+                Checksum::new(expected.to_string())
+            );
+
+            let sums_file = format!("{}!{}.sums", archive, member.replace('/', "%2F"));
+            assert_eq!(
+                FileBuilder::default()
+                    .with_file(sums_file)
+                    .build()?
+                    .get_existing_sums()
+                    .await?,
+                Some(file)
+            );
+        }
+
+        assert!(GenerateTaskBuilder::default()
+            .with_input_file_name(format!("{}!dir/missing", archive))
+            .with_context(vec!["md5".parse()?])
+            .build()
+            .await
+            .is_err());
+
+        // Corrupt archives are an error rather than a member without a size.
+        let member_size = |archive: String| async move {
+            FileBuilder::default()
+                .with_file(format!("{}!dir/hello", archive))
+                .build()?
+                .size()
+                .await
+        };
+        let corrupt = tmp.path().join("corrupt.tar");
+        std::fs::write(&corrupt, [1; 1024])?;
+        assert!(member_size(corrupt.to_string_lossy().to_string())
+            .await
+            .is_err());
+        let missing = tmp.path().join("missing.tar");
+        assert_eq!(
+            member_size(missing.to_string_lossy().to_string()).await?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_concat() -> Result<()> {
+        let tmp = tempdir()?;
+        tokio::fs::write(tmp.path().join("a"), "hello ").await?;
+        tokio::fs::write(tmp.path().join("b"), "world").await?;
+
+        let name = format!("{}+b", tmp.path().join("a").to_string_lossy());
+        let file = GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["md5".parse()?, "md5-aws-2".parse()?])
+            .with_capacity(10)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        assert_eq!(file.size, Some(11));
+        assert_eq!(
+            file.checksums[&"md5".parse()?],
+            Checksum::new("5eb63bbbe01eeed093cb22bb8f5acdc3".to_string()) // pragma: allowlist secret
+        );
+        assert_eq!(
+            file.checksums[&"md5-aws-6b".parse()?],
+            Checksum::new("e09e4fd6265b36115fe3db32df945d84-6b".to_string()) // pragma: allowlist secret
+        );
+        assert!(tokio::fs::try_exists(format!("{}.sums", name)).await?);
+
+        let file = GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["md5".parse()?])
+            .with_capacity(10)
+            .set_offset(Some(3))
+            .set_length(Some(5))
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        assert_eq!(
+            file.checksums[&"md5".parse()?],
+            Checksum::new("405a146923df6f329f0f3e072fbfa173".to_string()) // pragma: allowlist secret
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_record_metadata() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("metadata").to_string_lossy().to_string();
+        tokio::fs::write(&name, "hello world").await?;
+
+        let generate = |record_metadata| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec!["md5".parse().unwrap()])
+                .with_capacity(10)
+                .with_record_metadata(record_metadata)
+                .write()
+        };
+
+        let file = generate(true).build().await?.run().await?.into_inner().0;
+        let metadata = tokio::fs::metadata(&name).await?;
+        assert_eq!(
+            file.metadata(),
+            FileMetadata::new(metadata.modified().ok(), file.mode)
+        );
+        assert!(file.mtime.is_some());
+        #[cfg(unix)]
+        assert!(file.mode.is_some());
+
+        let sums = tokio::fs::read_to_string(format!("{}.sums", name)).await?;
+        assert!(sums.contains("\"mtime\""));
+
+        let file = generate(false).build().await?.run().await?.into_inner().0;
+        assert_eq!(file.metadata(), FileMetadata::default());
+        let sums = tokio::fs::read_to_string(format!("{}.sums", name)).await?;
+        assert!(!sums.contains("\"mtime\""));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_sums_destination() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("file").to_string_lossy().to_string();
+        let destination = tmp.path().join("sums").to_string_lossy().to_string();
+        tokio::fs::write(&name, "hello world").await?;
+        tokio::fs::create_dir(&destination).await?;
+
+        let generate = |ctx: &str| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec![ctx.parse().unwrap()])
+                .with_capacity(10)
+                .set_sums_destination(Some(destination.to_string()))
+                .write()
+        };
+
+        generate("md5").build().await?.run().await?;
+        assert!(!Path::new(&format!("{}.sums", name)).exists());
+        // The sums file keeps the path of the input under the destination.
+        let sums = format!("{}/{}.sums", destination, name.trim_start_matches('/'));
+        assert!(Path::new(&sums).exists());
+
+        // Existing sums are read from the destination.
+        let file = generate("sha1").build().await?.run().await?.into_inner().0;
+        assert!(file.checksums.contains_key(&"md5".parse()?));
+        assert!(file.checksums.contains_key(&"sha1".parse()?));
+
+        // Inputs with the same file name in different directories do not collide.
+        let mut written = vec![];
+        for dir in ["a", "b"] {
+            let name = tmp
+                .path()
+                .join(dir)
+                .join("x.bam")
+                .to_string_lossy()
+                .to_string();
+            tokio::fs::create_dir(tmp.path().join(dir)).await?;
+            tokio::fs::write(&name, dir).await?;
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec!["md5".parse()?])
+                .with_capacity(10)
+                .set_sums_destination(Some(destination.to_string()))
+                .write()
+                .build()
+                .await?
+                .run()
+                .await?;
+
+            let sums = format!("{}/{}.sums", destination, name.trim_start_matches('/'));
+            written.push(SumsFile::read_from_slice(&tokio::fs::read(sums).await?).await?);
+        }
+        assert_ne!(written[0], written[1]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_strict_s3() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&name, vec![0; 2048]).await?;
+
+        let generate = |strict_s3| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec!["md5-aws-1kib".parse().unwrap()])
+                .with_capacity(10)
+                .with_strict_s3(strict_s3)
+        };
+
+        assert!(generate(false).build().await?.run().await.is_ok());
+        let err = generate(true).build().await?.run().await.err().unwrap();
+        assert!(err.to_string().contains("S3 part size limits"), "{err}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_null() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("null").to_string_lossy().to_string();
+        tokio::fs::write(&name, "hello world").await?;
+
+        let file = GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["null".parse()?])
+            .with_capacity(10)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        assert_eq!(file.size, Some(11));
+        assert_eq!(
+            file.checksums[&"null".parse()?],
+            Checksum::new("".to_string())
+        );
+        assert_eq!(
+            FileBuilder::default()
+                .with_file(name.to_string())
+                .build()?
+                .get_existing_sums()
+                .await?,
+            None
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_empty() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("empty").to_string_lossy().to_string();
+        tokio::fs::write(&name, "").await?;
+
+        let ctxs = vec![
+            "md5".parse()?,
+            "md5-aws-2".parse()?,
+            "sha256-aws-8mib".parse()?,
+        ];
+        let file = GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(ctxs)
+            .with_capacity(10)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        assert_eq!(file.size, Some(0));
+        assert_eq!(
+            file.checksums[&"md5".parse()?],
+            Checksum::new("d41d8cd98f00b204e9800998ecf8427e".to_string()) // pragma: allowlist secret
+        );
+        assert_eq!(
+            file.checksums[&"md5-aws-1b".parse()?],
+            Checksum::new("59adb24ef3cdbe0297f05b395827453f-1b".to_string()) // pragma: allowlist secret
+        );
+        assert!(file.checksums.contains_key(&"sha256-aws-1b".parse()?));
+
+        // The written sums file can be read back.
+        assert_eq!(
+            FileBuilder::default()
+                .with_file(name.to_string())
+                .build()?
+                .get_existing_sums()
+                .await?,
+            Some(file)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_part_checksums_not_written() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("parts").to_string_lossy().to_string();
+        tokio::fs::write(&name, "hello world").await?;
+
+        // Many small parts are used internally, but only the top-level ETag is written.
+        let file = GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["md5-aws-1b".parse()?])
+            .with_capacity(10)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        let written: serde_json::Value =
+            serde_json::from_str(&tokio::fs::read_to_string(format!("{name}.sums")).await?)?;
+        assert_eq!(
+            written,
+            serde_json::json!({
+                "version": file.version,
+                "size": 11,
+                "md5-aws-1b": file.checksums[&"md5-aws-1b".parse()?].as_str(),
+            })
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_range() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("range").to_string_lossy().to_string();
+        tokio::fs::write(&name, "hello world").await?;
+
+        let builder = || {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec!["md5".parse().unwrap()])
+                .with_capacity(10)
+                .set_offset(Some(6))
+        };
+
+        let file = builder()
+            .set_length(Some(5))
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        assert_eq!(file.size, Some(5));
+        assert_eq!(file.range, Some(ByteRange::new(6, 5)));
+        assert_eq!(
+            file.checksums[&"md5".parse()?],
+            Checksum::new("7d793037a0760186574b0282f2f435e7".to_string())
+        );
+        assert_eq!(
+            FileBuilder::default()
+                .with_file(name.to_string())
+                .build()?
+                .get_existing_sums()
+                .await?,
+            Some(file)
+        );
+
+        assert!(builder().set_length(Some(6)).build().await.is_err());
+        assert!(GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["md5".parse()?])
+            .with_capacity(10)
+            .write()
+            .build()
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_normalize_newlines() -> Result<()> {
+        let tmp = tempdir()?;
+        let crlf = tmp.path().join("crlf").to_string_lossy().to_string();
+        let lf = tmp.path().join("lf").to_string_lossy().to_string();
+        tokio::fs::write(&crlf, "hello\r\nworld\r\n").await?;
+        tokio::fs::write(&lf, "hello\nworld\n").await?;
+
+        let builder = |name: &str, normalize_newlines: bool| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec!["md5".parse().unwrap(), "md5-aws-4b".parse().unwrap()])
+                .with_capacity(10)
+                .with_normalize_newlines(normalize_newlines)
+                .write()
+        };
+
+        let crlf_file = builder(&crlf, true)
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+        let lf_file = builder(&lf, true)
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        assert!(crlf_file.normalized_newlines);
+        assert_eq!(crlf_file.size, Some(12));
+        assert_eq!(crlf_file, lf_file);
+        assert_eq!(
+            crlf_file.checksums[&"md5".parse()?],
+            Checksum::new(checksum_bytes("md5", b"hello\nworld\n")?)
+        );
+
+        // Raw checksums cannot be mixed with the normalized sums file unless overwriting.
+        assert!(builder(&crlf, false).build().await.is_err());
+        let raw = builder(&crlf, false)
+            .with_overwrite(true)
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+        assert!(!raw.normalized_newlines);
+        assert_eq!(raw.size, Some(14));
+        assert!(raw.comparable(&lf_file).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_sparse() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("sparse").to_string_lossy().to_string();
+        let mut file = std::fs::File::create(&name)?;
+        file.seek(SeekFrom::Start(1 << 20))?;
+        file.write_all(b"hello world")?;
+        file.set_len(3 << 20)?;
+
+        let generate = |sparse: bool| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec![
+                    "sha256".parse().unwrap(),
+                    "md5-aws-1mib".parse().unwrap(),
+                ])
+                .with_capacity(10)
+                .with_sparse(sparse)
+                .with_overwrite(true)
+                .build()
+        };
+
+        let dense = generate(false).await?.run().await?.into_inner().0;
+        let sparse = generate(true).await?.run().await?.into_inner().0;
+
+        assert_eq!(sparse.size, Some(3 << 20));
+        assert_eq!(sparse, dense);
+
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_generate_named_pipe() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        let pipe = tmp.path().join("pipe").to_string_lossy().to_string();
+        tokio::fs::write(&file, b"hello world").await?;
+        let path = std::ffi::CString::new(pipe.as_str())?;
+        // SAFETY: the path is a valid nul-terminated string.
+        assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o600) }, 0);
+
+        let generate = |name: &str, input_size: Option<u64>| {
+            let writer = (name == pipe).then(|| {
+                let pipe = pipe.to_string();
+                std::thread::spawn(move || std::fs::write(pipe, b"hello world"))
+            });
+            let task = GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec!["md5-aws-2".parse().unwrap()])
+                .with_capacity(10)
+                .set_input_size(input_size)
+                .with_overwrite(true)
+                .build();
+            async move {
+                let result =
+                    async { Ok::<_, Error>(task.await?.run().await?.into_inner().0) }.await;
+                // The writer fails with a broken pipe if the generate task stopped reading.
+                if let Some(writer) = writer {
+                    let _ = writer.join();
+                }
+                result
+            }
+        };
+
+        let expected = generate(&file, None).await?;
+        assert_eq!(generate(&pipe, Some(11)).await?, expected);
+        // The size of a pipe is unknown, so part numbers need the input size.
+        assert!(generate(&pipe, None).await.is_err());
+        assert!(matches!(
+            generate(&pipe, Some(12)).await,
+            Err(SizeMismatch(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_join_tasks_error_order() -> Result<()> {
+        let task = |delay: u64, result: crate::error::Result<Task>| {
+            AbortOnDropHandle::new(tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                result
+            }))
+        };
+        let join = |checksum_delay: u64, reader_delay: u64, reader_err: crate::error::Error| {
+            GenerateTask::join_tasks(
+                vec![
+                    task(checksum_delay, Err(GenerateError("checksum".to_string()))),
+                    task(reader_delay, Err(reader_err)),
+                ],
+                Some(1),
+            )
+        };
+
+        // A size mismatch of the reader is returned even if a checksum failed after it.
+        let err = join(50, 0, SizeMismatch("reader".to_string()))
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, SizeMismatch(_)), "{err}");
+        // Otherwise, the error of the checksum is returned before the error of the reader.
+        let err = join(50, 0, GenerateError("reader".to_string()))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("checksum"), "{err}");
+        // A reader that is still reading when a checksum fails is aborted.
+        let err = join(0, 60_000, SizeMismatch("reader".to_string()))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("checksum"), "{err}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_truncated_stream() -> Result<()> {
+        let generate = |data: &'static [u8], input_size: Option<u64>| async move {
+            GenerateTaskBuilder::default()
+                .with_context(vec!["md5".parse()?, "sha256-aws-4b".parse()?])
+                .with_reader(ChannelReader::new(data, 10))
+                .set_input_size(input_size)
+                .build()
+                .await?
+                .run()
+                .await
+        };
+
+        assert!(generate(b"hello world", Some(11)).await.is_ok());
+        // A stream which ends early or has extra data is an error rather than a wrong checksum.
+        let err = generate(b"hello", Some(11)).await.err().unwrap();
+        assert!(matches!(err, SizeMismatch(_)), "{err}");
+        let err = generate(b"hello world!", Some(11)).await.err().unwrap();
+        assert!(matches!(err, SizeMismatch(_)), "{err}");
+        // There is nothing to compare to without an input size.
+        assert!(generate(b"hello", None).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_stops_on_failed_checksum() -> Result<()> {
+        let size = 1 << 32;
+        let read = Arc::new(AtomicU64::new(0));
+        let reader = InspectReader::new(tokio::io::repeat(0).take(size), {
+            let read = read.clone();
+            move |buf: &[u8]| {
+                read.fetch_add(buf.len() as u64, Ordering::Relaxed);
+            }
+        });
+
+        let mut task = GenerateTaskBuilder::default()
+            .with_context(vec!["md5".parse()?])
+            .with_reader(ChannelReader::new(reader, 10))
+            .build()
+            .await?;
+        task.tasks.push(AbortOnDropHandle::new(tokio::spawn(async {
+            Err(GenerateError("injected failure".to_string()))
+        })));
+
+        let err = task.run().await.err().unwrap();
+        assert!(err.to_string().contains("injected failure"), "{err}");
+        assert!(read.load(Ordering::Relaxed) < size);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_inspect_before_write() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&name, b"hello world").await?;
+
+        let mut task = GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["md5".parse()?])
+            .with_capacity(10)
+            .build()
+            .await?
+            .run()
+            .await?;
+
+        let expected = Checksum::new(checksum_bytes("md5", b"hello world")?);
+        assert_eq!(task.sums_file().checksums[&"md5".parse()?], expected);
+        assert_eq!(task.checksums_generated()[&"md5".parse()?], expected);
+        assert!(!task.updated());
+        assert!(!Path::new(&format!("{name}.sums")).exists());
+
+        // The sums file is only written when asked, and only if it changed.
+        assert!(task.write_sums_file().await?);
+        assert!(task.updated());
+        assert!(!task.write_sums_file().await?);
+        assert_eq!(
+            FileBuilder::default()
+                .with_file(name)
+                .build()?
+                .get_existing_sums()
+                .await?
+                .as_ref(),
+            Some(task.sums_file())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_inspect_before_write_existing() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&name, b"hello world").await?;
+
+        let generate = |ctx: &str, policy| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec![ctx.parse().unwrap()])
+                .with_capacity(10)
+                .with_overwrite_policy(policy)
+        };
+        generate("md5", OverwritePolicy::Missing)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?;
+        let existing = tokio::fs::read(format!("{name}.sums")).await?;
+
+        // Sums for a different byte range are not written over the existing sums file.
+        let ranged = |policy| {
+            generate("sha1", policy)
+                .set_offset(Some(1))
+                .set_length(Some(5))
+        };
+        let mut task = ranged(OverwritePolicy::Missing)
+            .build()
+            .await?
+            .run()
+            .await?;
+        assert!(matches!(
+            task.write_sums_file().await,
+            Err(GenerateError(_))
+        ));
+        assert_eq!(tokio::fs::read(format!("{name}.sums")).await?, existing);
+
+        // An existing sums file is never modified if overwriting is disabled.
+        let mut task = generate("sha1", OverwritePolicy::Never)
+            .build()
+            .await?
+            .run()
+            .await?;
+        assert!(!task.write_sums_file().await?);
+        assert_eq!(tokio::fs::read(format!("{name}.sums")).await?, existing);
+
+        // Overwriting replaces the existing sums file.
+        let mut task = ranged(OverwritePolicy::Always).build().await?.run().await?;
+        assert!(task.write_sums_file().await?);
+        assert_ne!(tokio::fs::read(format!("{name}.sums")).await?, existing);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_decompress() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("file.gz").to_string_lossy().to_string();
+        tokio::fs::write(&name, gzip(b"hello world")).await?;
+
+        let builder = || {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(vec!["md5".parse().unwrap(), "md5-aws-4b".parse().unwrap()])
+                .with_capacity(10)
+                .set_decompress(Some(Decompress::Gzip))
+                .write()
+        };
+
+        let file = builder().build().await?.run().await?.into_inner().0;
+        assert_eq!(file.decompressed, Some(Decompress::Gzip));
+        assert_eq!(file.size, Some(11));
+        assert_eq!(
+            file.checksums[&"md5".parse()?],
+            Checksum::new(checksum_bytes("md5", b"hello world")?)
+        );
+
+        // Truncated data is an error rather than a checksum of the partial data.
+        let compressed = tokio::fs::read(&name).await?;
+        tokio::fs::write(&name, &compressed[..compressed.len() - 4]).await?;
+        assert!(builder()
+            .with_overwrite(true)
+            .build()
+            .await?
+            .run()
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_many_parts() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("many_parts").to_string_lossy().to_string();
+        tokio::fs::write(&name, vec![0; 10000]).await?;
+
+        // The size of a sums file does not depend on the number of parts.
+        GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["md5-aws-1b".parse()?, "md5".parse()?])
+            .with_capacity(10)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?;
+
+        let written = tokio::fs::read(format!("{name}.sums")).await?;
+        assert!(written.len() < 200, "{}", written.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_checkpoint() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("checkpoint").to_string_lossy().to_string();
+        let path = tmp.path().join("checkpoint.json");
+        let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        tokio::fs::write(&name, &data).await?;
+
+        let checksums = vec!["crc32c".parse()?, "md5-aws-100b".parse()?];
+        let builder = |checkpoint: Option<PathBuf>| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(name.to_string())
+                .with_context(checksums.clone())
+                .with_capacity(10)
+                .set_offset(Some(100))
+                .set_checkpoint(checkpoint)
+        };
+        let expected = builder(None).build().await?.run().await?.into_inner().0;
+
+        // Simulate an interrupted run by checkpointing partway through the range.
+        let mut checkpoint = Checkpoint::new(Some(900), Some(ByteRange::new(100, 900)));
+        for mut ctx in checksums.clone() {
+            ctx.set_file_size(Some(900));
+            ctx.update(Arc::from(&data[100..550]))?;
+            checkpoint
+                .checksums
+                .insert(ctx.clone(), ctx.checkpoint(450)?);
+        }
+        checkpoint.write(&path).await?;
+
+        let file = builder(Some(path.clone()))
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+        assert_eq!(file, expected);
+        assert!(!path.exists());
+
+        // Checksums that cannot be resumed are an error.
+        let result = GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["sha256".parse()?])
+            .set_checkpoint(Some(path.clone()))
+            .build()
+            .await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_msgpack() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("msgpack").to_string_lossy().to_string();
+        tokio::fs::write(&name, "hello world").await?;
+
+        let file = GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["md5".parse()?])
+            .with_capacity(10)
+            .with_format(SumsFormat::Msgpack)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        assert!(!Path::new(&format!("{name}.sums")).exists());
+        let existing = ObjectSumsBuilder::default()
+            .build(format!("{name}.sums.bin"))
+            .await?
+            .sums_file()
+            .await?;
+        assert_eq!(existing, Some(file));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_pretty() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("pretty").to_string_lossy().to_string();
+        tokio::fs::write(&name, "hello world").await?;
+
+        let file = GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_context(vec!["md5".parse()?, "sha1".parse()?])
+            .with_capacity(10)
+            .with_format(SumsFormat::PrettyJson)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?
+            .into_inner()
+            .0;
+
+        let written = tokio::fs::read_to_string(format!("{name}.sums")).await?;
+        assert_eq!(written, file.to_json_string_pretty()?);
+
+        // Pretty sums files are read like any other JSON sums file.
+        let existing = ObjectSumsBuilder::default()
+            .build(format!("{name}.sums"))
+            .await?
+            .sums_file()
+            .await?;
+        assert_eq!(existing, Some(file));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_generate_overwrite_policy() -> Result<()> {
+        let tmp = tempdir()?;
+        let name = tmp.path().join("policy").to_string_lossy().to_string();
+        tokio::fs::write(&name, "hello world").await?;
+
+        let generate = |policy: OverwritePolicy, ctx: &'static str| {
+            let name = name.to_string();
+            async move {
+                Ok::<_, anyhow::Error>(
+                    GenerateTaskBuilder::default()
+                        .with_input_file_name(name)
+                        .with_overwrite_policy(policy)
+                        .with_context(vec![ctx.parse()?])
+                        .with_capacity(10)
+                        .write()
+                        .build()
+                        .await?
+                        .run()
+                        .await?
+                        .into_inner()
+                        .0,
+                )
+            }
+        };
+        let keys = |file: &SumsFile| {
+            file.checksums
+                .keys()
+                .map(Ctx::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        let existing = generate(OverwritePolicy::Missing, "md5").await?;
+
+        // An existing sums file is not changed.
+        let file = generate(OverwritePolicy::Never, "sha1").await?;
+        assert_eq!(file, existing);
+
+        // Missing checksums are merged into the existing sums file.
+        let file = generate(OverwritePolicy::Missing, "sha1").await?;
+        assert_eq!(keys(&file), vec!["md5", "sha1"]);
+
+        // The same size keeps existing checksums, even if the contents changed.
+        tokio::fs::write(&name, "hello there").await?;
+        let file = generate(OverwritePolicy::SizeChanged, "md5").await?;
+        let md5 = "md5".parse()?;
+        assert_eq!(file.checksums[&md5], existing.checksums[&md5]);
+        assert_eq!(keys(&file), vec!["md5", "sha1"]);
+
+        // A different size replaces the sums file.
+        tokio::fs::write(&name, "hello").await?;
+        let file = generate(OverwritePolicy::SizeChanged, "md5").await?;
+        assert_eq!(keys(&file), vec!["md5"]);
+        assert_eq!(file.size, Some(5));
+        assert_eq!(
+            file.checksums[&md5],
+            Checksum::new("5d41402abc4b2a76b9719d911017c592".to_string()) // pragma: allowlist secret
+        );
+
+        let file = generate(OverwritePolicy::Always, "sha1").await?;
+        assert_eq!(keys(&file), vec!["sha1"]);
+
+        // Writing a copy of the input reads it, but still does not change the sums file.
+        let tee = tmp.path().join("tee");
+        let sums = format!("{}.sums", name);
+        let existing = tokio::fs::read(&sums).await?;
+        GenerateTaskBuilder::default()
+            .with_input_file_name(name.to_string())
+            .with_overwrite_policy(OverwritePolicy::Never)
+            .with_context(vec!["md5".parse()?])
+            .with_capacity(10)
+            .set_tee(Some(tee.clone()))
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?;
+        assert_eq!(tokio::fs::read_to_string(&tee).await?, "hello");
+        assert_eq!(tokio::fs::read(&sums).await?, existing);
+
+        Ok(())
+    }
+
     pub(crate) async fn generate_for(
         name: &str,
         tasks: Vec<&str>,
@@ -1,6 +1,14 @@
 //! Task definitions for different commands.
 //!
 
+pub mod bisect;
+pub mod blocks;
+pub mod cat;
 pub mod check;
 pub mod copy;
+pub mod dedup;
+pub mod diff;
 pub mod generate;
+pub mod prune;
+pub mod repair;
+pub mod touch;
@@ -0,0 +1,202 @@
+//! Remove checksums from existing sums files, keeping only a subset of algorithms.
+//!
+
+use crate::checksum::file::{SumsFile, SumsFormat, SumsStorage};
+use crate::checksum::Ctx;
+use crate::error::Error::SumsFileError;
+use crate::error::{ApiError, Result};
+use crate::io::sums::{ObjectSums, ObjectSumsBuilder};
+use crate::io::Client;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// Build a prune task.
+#[derive(Debug, Default)]
+pub struct PruneTaskBuilder {
+    input_file_name: String,
+    keep: Vec<Ctx>,
+    allow_empty: bool,
+    client: Option<Arc<Client>>,
+    format: SumsFormat,
+    storage: SumsStorage,
+    sums_source: Option<String>,
+    output_dir: Option<String>,
+}
+
+impl PruneTaskBuilder {
+    /// Set the input file name. This can be the object or its sums file.
+    pub fn with_input_file_name(mut self, input_file_name: String) -> Self {
+        self.input_file_name = input_file_name;
+        self
+    }
+
+    /// Set the checksums to keep in the sums file.
+    pub fn with_keep(mut self, keep: Vec<Ctx>) -> Self {
+        self.keep = keep;
+        self
+    }
+
+    /// Allow pruning to remove every checksum from the sums file.
+    pub fn with_allow_empty(mut self, allow_empty: bool) -> Self {
+        self.allow_empty = allow_empty;
+        self
+    }
+
+    /// Set the S3 client to use.
+    pub fn set_client(mut self, client: Option<Arc<Client>>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Set the format used to read and write sums files.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set where sums files are stored for S3 objects.
+    pub fn with_storage(mut self, storage: SumsStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Read and write sums files in a local directory, independent of where the input is
+    /// stored.
+    pub fn set_sums_source(mut self, sums_source: Option<String>) -> Self {
+        self.sums_source = sums_source;
+        self
+    }
+
+    /// Read and write the sums files of local files in a directory which mirrors their
+    /// absolute paths, rather than next to the input.
+    pub fn set_output_dir(mut self, output_dir: Option<String>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Build a prune task. This reads the existing sums file and removes the checksums that
+    /// are not kept, but does not write it. It is an error if no checksums would be left,
+    /// unless empty sums files are allowed.
+    pub async fn build(self) -> Result<PruneTask> {
+        let mut object_sums = ObjectSumsBuilder::default()
+            .set_client(self.client)
+            .with_format(self.format)
+            .with_storage(self.storage)
+            .set_sums_source(self.sums_source)
+            .set_output_dir(self.output_dir)
+            .build(self.input_file_name)
+            .await?;
+
+        let location = object_sums.location();
+        let mut pruned = object_sums.sums_file().await?;
+        let mut removed = vec![];
+        match &mut pruned {
+            None => info!(location, "skipping because there is no sums file to prune"),
+            Some(sums_file) => {
+                removed = sums_file.retain(&self.keep);
+                if sums_file.is_empty() && !removed.is_empty() && !self.allow_empty {
+                    return Err(SumsFileError(format!(
+                        "pruning `{}` would remove all of its checksums, use --allow-empty to \
+                        allow this",
+                        location
+                    )));
+                }
+            }
+        }
+
+        Ok(PruneTask {
+            object_sums,
+            pruned,
+            removed,
+        })
+    }
+}
+
+/// Execute the prune task.
+pub struct PruneTask {
+    object_sums: Box<dyn ObjectSums + Send>,
+    pruned: Option<SumsFile>,
+    removed: Vec<Ctx>,
+}
+
+impl PruneTask {
+    /// Runs the prune task, writing the sums file if any checksums were removed.
+    pub async fn run(self) -> Result<Self> {
+        if let (Some(pruned), false) = (&self.pruned, self.removed.is_empty()) {
+            debug!(location = self.object_sums.location(), "pruning sums file");
+            self.object_sums.write_sums_file(pruned).await?;
+        }
+
+        Ok(self)
+    }
+
+    /// Get the api errors.
+    pub fn api_errors(&self) -> HashSet<ApiError> {
+        self.object_sums.api_errors()
+    }
+
+    /// Get the inner values.
+    pub fn into_inner(self) -> (String, Vec<Ctx>) {
+        (self.object_sums.location(), self.removed)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::checksum::file::Checksum;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn prune() -> Result<()> {
+        let tmp = tempdir()?;
+        let input = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&input, b"data").await?;
+
+        let sums_file = SumsFile::new(
+            Some(4),
+            [
+                ("md5".parse()?, Checksum::new("1".to_string())),
+                ("sha256".parse()?, Checksum::new("2".to_string())),
+                ("crc32c".parse()?, Checksum::new("3".to_string())),
+            ]
+            .into(),
+        );
+        tokio::fs::write(format!("{}.sums", input), sums_file.to_json_string()?).await?;
+
+        let prune = |keep: &str, allow_empty| {
+            PruneTaskBuilder::default()
+                .with_input_file_name(input.to_string())
+                .with_keep(keep.split(',').map(|ctx| ctx.parse().unwrap()).collect())
+                .with_allow_empty(allow_empty)
+                .build()
+        };
+        let read = || async {
+            SumsFile::read_from_slice(&tokio::fs::read(format!("{}.sums", input)).await?).await
+        };
+
+        let (_, removed) = prune("sha256,crc32c", false)
+            .await?
+            .run()
+            .await?
+            .into_inner();
+        assert_eq!(removed, vec!["md5".parse()?]);
+        let pruned = read().await?;
+        assert_eq!(pruned.size, Some(4));
+        assert_eq!(
+            pruned.checksums.keys().cloned().collect::<HashSet<_>>(),
+            HashSet::from(["crc32c".parse()?, "sha256".parse()?])
+        );
+
+        // Removing every checksum is an error, and the sums file is left as it is.
+        assert!(prune("sha1", false).await.is_err());
+        assert_eq!(read().await?, pruned);
+
+        prune("sha1", true).await?.run().await?;
+        assert!(read().await?.checksums.is_empty());
+
+        Ok(())
+    }
+}
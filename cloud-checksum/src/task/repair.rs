@@ -0,0 +1,352 @@
+//! Repair existing sums files that are missing required checksums.
+//!
+
+use crate::checksum::file::{Checksum, SumsFile, SumsFormat, SumsStorage};
+use crate::checksum::Ctx;
+use crate::error::Error::SumsFileError;
+use crate::error::{ApiError, Result};
+use crate::io::sums::{ObjectSums, ObjectSumsBuilder};
+use crate::io::Client;
+use crate::task::generate::GenerateTaskBuilder;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// Build a repair task.
+#[derive(Debug, Default)]
+pub struct RepairTaskBuilder {
+    input_file_name: String,
+    ensure: Vec<Ctx>,
+    capacity: usize,
+    client: Option<Arc<Client>>,
+    avoid_get_object_attributes: bool,
+    format: SumsFormat,
+    storage: SumsStorage,
+    sums_source: Option<String>,
+    output_dir: Option<String>,
+}
+
+impl RepairTaskBuilder {
+    /// Set the input file name. This can be the object or its sums file.
+    pub fn with_input_file_name(mut self, input_file_name: String) -> Self {
+        self.input_file_name = input_file_name;
+        self
+    }
+
+    /// Set the checksums that the sums file must contain.
+    pub fn with_ensure(mut self, ensure: Vec<Ctx>) -> Self {
+        self.ensure = ensure;
+        self
+    }
+
+    /// Set the reader capacity.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set the S3 client to use.
+    pub fn set_client(mut self, client: Option<Arc<Client>>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Avoid `GetObjectAttributes` calls.
+    pub fn with_avoid_get_object_attributes(mut self, avoid_get_object_attributes: bool) -> Self {
+        self.avoid_get_object_attributes = avoid_get_object_attributes;
+        self
+    }
+
+    /// Set the format used to read and write sums files.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set where sums files are stored for S3 objects.
+    pub fn with_storage(mut self, storage: SumsStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Read and write sums files in a local directory, independent of where the input is
+    /// stored.
+    pub fn set_sums_source(mut self, sums_source: Option<String>) -> Self {
+        self.sums_source = sums_source;
+        self
+    }
+
+    /// Read and write the sums files of local files in a directory which mirrors their
+    /// absolute paths, rather than next to the input.
+    pub fn set_output_dir(mut self, output_dir: Option<String>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Build a repair task. This reads the existing sums file, but not the object.
+    pub async fn build(self) -> Result<RepairTask> {
+        let mut object_sums = ObjectSumsBuilder::default()
+            .set_client(self.client.clone())
+            .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
+            .with_format(self.format)
+            .with_storage(self.storage)
+            .set_sums_source(self.sums_source.clone())
+            .set_output_dir(self.output_dir.clone())
+            .build(self.input_file_name.to_string())
+            .await?;
+
+        let location = object_sums.location();
+        let existing = object_sums.sums_file().await?;
+        let generate = match &existing {
+            None => {
+                info!(location, "skipping because there is no sums file to repair");
+                None
+            }
+            Some(existing) => {
+                let missing = self
+                    .ensure
+                    .into_iter()
+                    .filter(|ctx| !existing.checksums.contains_key(ctx))
+                    .collect::<Vec<_>>();
+
+                if missing.is_empty() {
+                    debug!(location, "sums file already has the required checksums");
+                    None
+                } else {
+                    // Compute the checksums over the same bytes as the existing ones.
+                    let range = existing.range;
+                    Some(
+                        GenerateTaskBuilder::default()
+                            .with_input_file_name(self.input_file_name)
+                            .with_context(missing)
+                            .with_capacity(self.capacity)
+                            .set_client(self.client)
+                            .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
+                            .with_format(self.format)
+                            .with_storage(self.storage)
+                            .set_sums_source(self.sums_source)
+                            .set_output_dir(self.output_dir)
+                            .set_offset(range.map(|range| range.offset))
+                            .set_length(range.map(|range| range.length))
+                            .with_normalize_newlines(existing.normalized_newlines)
+                            .set_decompress(existing.decompressed),
+                    )
+                }
+            }
+        };
+
+        Ok(RepairTask {
+            generate,
+            object_sums,
+            existing,
+            updated: false,
+            checksums_generated: Default::default(),
+        })
+    }
+}
+
+/// Execute the repair task.
+pub struct RepairTask {
+    generate: Option<GenerateTaskBuilder>,
+    object_sums: Box<dyn ObjectSums + Send>,
+    existing: Option<SumsFile>,
+    updated: bool,
+    checksums_generated: BTreeMap<Ctx, Checksum>,
+}
+
+impl RepairTask {
+    /// Runs the repair task, computing only the missing checksums and adding them to the
+    /// existing sums file. Everything else in the sums file is kept as it is. It is an error
+    /// if the object changed since the sums file was generated, because the new checksums
+    /// would then not describe the same data as the existing ones.
+    pub async fn run(mut self) -> Result<Self> {
+        let (Some(generate), Some(existing)) = (self.generate.take(), self.existing.as_mut())
+        else {
+            return Ok(self);
+        };
+        Self::check_unchanged(self.object_sums.as_mut(), existing).await?;
+
+        let (_, _, _, checksums_generated) = generate.build().await?.run().await?.into_inner();
+        existing.merge_mut(SumsFile::new(existing.size, checksums_generated.clone()));
+
+        debug!(
+            location = self.object_sums.location(),
+            "repairing sums file"
+        );
+        self.object_sums.write_sums_file(existing).await?;
+        self.updated = true;
+        self.checksums_generated = checksums_generated;
+
+        Ok(self)
+    }
+
+    /// Check that the current size and modification time of the object match the sums file.
+    /// The size is only compared if the sums file describes the whole object as it is stored,
+    /// and the modification time only if it was recorded.
+    async fn check_unchanged(
+        object_sums: &mut (dyn ObjectSums + Send),
+        existing: &SumsFile,
+    ) -> Result<()> {
+        let location = object_sums.location();
+        let whole_object = existing.range.is_none()
+            && !existing.normalized_newlines
+            && existing.decompressed.is_none();
+        if whole_object && existing.size.is_some() {
+            let size = object_sums.file_size().await?;
+            if size.is_some() && size != existing.size {
+                return Err(SumsFileError(format!(
+                    "cannot repair `{}` because its size of {} bytes does not match the size of \
+                    {} bytes in the sums file",
+                    location,
+                    size.unwrap_or_default(),
+                    existing.size.unwrap_or_default()
+                )));
+            }
+        }
+
+        if let Some(mtime) = &existing.metadata().mtime {
+            if object_sums.file_metadata().await?.mtime.as_ref() != Some(mtime) {
+                return Err(SumsFileError(format!(
+                    "cannot repair `{}` because it was modified after the sums file was generated",
+                    location
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the location of the object.
+    pub fn location(&self) -> String {
+        self.object_sums.location()
+    }
+
+    /// Get the api errors.
+    pub fn api_errors(&self) -> HashSet<ApiError> {
+        self.object_sums.api_errors()
+    }
+
+    /// Get the inner values.
+    pub fn into_inner(self) -> (String, bool, BTreeMap<Ctx, Checksum>) {
+        (
+            self.object_sums.location(),
+            self.updated,
+            self.checksums_generated,
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::checksum::checksum_bytes;
+    use crate::io::sums::file::FileBuilder;
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    async fn repair(file: &str) -> Result<RepairTask> {
+        Ok(RepairTaskBuilder::default()
+            .with_input_file_name(file.to_string())
+            .with_ensure(vec!["md5".parse()?, "sha256".parse()?])
+            .with_capacity(10)
+            .build()
+            .await?
+            .run()
+            .await?)
+    }
+
+    async fn read_sums(file: &str) -> Result<Option<SumsFile>> {
+        Ok(FileBuilder::default()
+            .with_file(file.to_string())
+            .build()?
+            .get_existing_sums()
+            .await?)
+    }
+
+    #[tokio::test]
+    async fn test_repair() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&file, b"hello world").await?;
+
+        // Without a sums file there is nothing to repair.
+        let (_, updated, _) = repair(&file).await?.into_inner();
+        assert!(!updated);
+        assert!(read_sums(&file).await?.is_none());
+
+        GenerateTaskBuilder::default()
+            .with_input_file_name(file.to_string())
+            .with_context(vec!["md5".parse()?])
+            .with_capacity(10)
+            .with_record_metadata(true)
+            .write()
+            .build()
+            .await?
+            .run()
+            .await?;
+        let original = read_sums(&file).await?.unwrap();
+
+        // Only the missing checksum is generated, and the rest of the sums file is kept.
+        let (_, updated, generated) = repair(&format!("{file}.sums")).await?.into_inner();
+        assert!(updated);
+        assert_eq!(generated.keys().collect::<Vec<_>>(), [&"sha256".parse()?]);
+
+        let repaired = read_sums(&file).await?.unwrap();
+        assert_eq!(
+            repaired.checksums[&"sha256".parse()?].as_str(),
+            checksum_bytes("sha256", b"hello world")?
+        );
+        assert_eq!(
+            repaired.checksums[&"md5".parse()?],
+            original.checksums[&"md5".parse()?]
+        );
+        assert_eq!(repaired.metadata(), original.metadata());
+
+        // A complete sums file is not touched, so the data is not read again.
+        tokio::fs::write(&file, b"hello there").await?;
+        let (_, updated, _) = repair(&file).await?.into_inner();
+        assert!(!updated);
+        assert_eq!(read_sums(&file).await?, Some(repaired));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_repair_changed_object() -> Result<()> {
+        let tmp = tempdir()?;
+        let file = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&file, b"hello world").await?;
+
+        let generate = |record_metadata| {
+            GenerateTaskBuilder::default()
+                .with_input_file_name(file.to_string())
+                .with_context(vec!["md5".parse().unwrap()])
+                .with_capacity(10)
+                .with_record_metadata(record_metadata)
+                .write()
+                .build()
+        };
+        generate(false).await?.run().await?;
+        let original = read_sums(&file).await?;
+
+        // The new checksums would not describe the same data as the existing ones.
+        tokio::fs::write(&file, b"hello world!").await?;
+        let err = repair(&file).await.err().unwrap();
+        assert!(err.to_string().contains("size"), "{err}");
+        assert_eq!(read_sums(&file).await?, original);
+
+        // The modification time is compared if it was recorded.
+        tokio::fs::remove_file(format!("{file}.sums")).await?;
+        generate(true).await?.run().await?;
+        let original = read_sums(&file).await?;
+        std::fs::File::options()
+            .write(true)
+            .open(&file)?
+            .set_modified(std::time::UNIX_EPOCH)?;
+        let err = repair(&file).await.err().unwrap();
+        assert!(err.to_string().contains("modified"), "{err}");
+        assert_eq!(read_sums(&file).await?, original);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! Update the recorded size and metadata of existing sums files without computing checksums.
+//!
+
+use crate::checksum::file::{SumsFile, SumsFormat, SumsStorage};
+use crate::error::Error::SumsFileError;
+use crate::error::{ApiError, Result};
+use crate::io::sums::{ObjectSums, ObjectSumsBuilder};
+use crate::io::Client;
+use crate::stats::SizeUpdate;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Build a touch task.
+#[derive(Debug, Default)]
+pub struct TouchTaskBuilder {
+    input_file_name: String,
+    record_metadata: bool,
+    force: bool,
+    client: Option<Arc<Client>>,
+    avoid_get_object_attributes: bool,
+    format: SumsFormat,
+    storage: SumsStorage,
+    sums_source: Option<String>,
+    output_dir: Option<String>,
+}
+
+impl TouchTaskBuilder {
+    /// Set the input file name. This can be the object or its sums file.
+    pub fn with_input_file_name(mut self, input_file_name: String) -> Self {
+        self.input_file_name = input_file_name;
+        self
+    }
+
+    /// Also update the recorded metadata of the object, such as the modification time.
+    pub fn with_record_metadata(mut self, record_metadata: bool) -> Self {
+        self.record_metadata = record_metadata;
+        self
+    }
+
+    /// Write the sums file even though its checksums are not re-verified. Without this, it is
+    /// an error if the sums file would be updated.
+    pub fn with_force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Set the S3 client to use.
+    pub fn set_client(mut self, client: Option<Arc<Client>>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Avoid `GetObjectAttributes` calls.
+    pub fn with_avoid_get_object_attributes(mut self, avoid_get_object_attributes: bool) -> Self {
+        self.avoid_get_object_attributes = avoid_get_object_attributes;
+        self
+    }
+
+    /// Set the format used to read and write sums files.
+    pub fn with_format(mut self, format: SumsFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set where sums files are stored for S3 objects.
+    pub fn with_storage(mut self, storage: SumsStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Read and write sums files in a local directory, independent of where the input is
+    /// stored.
+    pub fn set_sums_source(mut self, sums_source: Option<String>) -> Self {
+        self.sums_source = sums_source;
+        self
+    }
+
+    /// Read and write the sums files of local files in a directory which mirrors their
+    /// absolute paths, rather than next to the input.
+    pub fn set_output_dir(mut self, output_dir: Option<String>) -> Self {
+        self.output_dir = output_dir;
+        self
+    }
+
+    /// Build a touch task. This reads the existing sums file and the current size of the
+    /// object, but not the object itself. It is an error if the sums file describes a byte
+    /// range or transformed data, because then its size is not the size of the object, or if
+    /// the sums file would be updated without `force`.
+    pub async fn build(self) -> Result<TouchTask> {
+        let mut object_sums = ObjectSumsBuilder::default()
+            .set_client(self.client)
+            .with_avoid_get_object_attributes(self.avoid_get_object_attributes)
+            .with_format(self.format)
+            .with_storage(self.storage)
+            .set_sums_source(self.sums_source)
+            .set_output_dir(self.output_dir)
+            .build(self.input_file_name)
+            .await?;
+
+        let location = object_sums.location();
+        let Some(existing) = object_sums.sums_file().await? else {
+            info!(location, "skipping because there is no sums file to touch");
+            return Ok(TouchTask {
+                object_sums,
+                touched: None,
+                update: None,
+            });
+        };
+
+        if existing.range.is_some()
+            || existing.normalized_newlines
+            || existing.decompressed.is_some()
+        {
+            return Err(SumsFileError(format!(
+                "cannot touch `{}` because it does not describe the whole object as it is stored",
+                location
+            )));
+        }
+
+        let Some(size) = object_sums.file_size().await? else {
+            return Err(SumsFileError(format!(
+                "cannot touch `{}` because the size of the object is unknown",
+                location
+            )));
+        };
+        let mut touched = existing.clone().with_size(Some(size));
+        if self.record_metadata {
+            touched.set_metadata(object_sums.file_metadata().await?);
+        }
+
+        if touched != existing && !self.force {
+            return Err(SumsFileError(format!(
+                "not touching `{}` without `--force` because its checksums would not be \
+                re-verified, and may not match the object",
+                location
+            )));
+        }
+        let update = (touched != existing).then(|| {
+            warn!(
+                location,
+                "updating the sums file without re-verifying its checksums, which may not match \
+                the object"
+            );
+            SizeUpdate {
+                previous: existing.size,
+                size,
+            }
+        });
+
+        Ok(TouchTask {
+            object_sums,
+            touched: Some(touched),
+            update,
+        })
+    }
+}
+
+/// Execute the touch task.
+pub struct TouchTask {
+    object_sums: Box<dyn ObjectSums + Send>,
+    touched: Option<SumsFile>,
+    update: Option<SizeUpdate>,
+}
+
+impl TouchTask {
+    /// Runs the touch task, writing the sums file if the size or metadata changed.
+    pub async fn run(self) -> Result<Self> {
+        if let (Some(touched), Some(_)) = (&self.touched, &self.update) {
+            debug!(location = self.object_sums.location(), "touching sums file");
+            self.object_sums.write_sums_file(touched).await?;
+        }
+
+        Ok(self)
+    }
+
+    /// Get the api errors.
+    pub fn api_errors(&self) -> HashSet<ApiError> {
+        self.object_sums.api_errors()
+    }
+
+    /// Get the inner values.
+    pub fn into_inner(self) -> (String, Option<SizeUpdate>) {
+        (self.object_sums.location(), self.update)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::checksum::file::{ByteRange, Checksum};
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn touch() -> Result<()> {
+        let tmp = tempdir()?;
+        let input = tmp.path().join("file").to_string_lossy().to_string();
+        tokio::fs::write(&input, b"data").await?;
+
+        let sums_file = SumsFile::new(
+            Some(100),
+            [("md5".parse()?, Checksum::new("1".to_string()))].into(),
+        );
+        tokio::fs::write(format!("{}.sums", input), sums_file.to_json_string()?).await?;
+
+        let touch = |record_metadata| {
+            TouchTaskBuilder::default()
+                .with_input_file_name(input.to_string())
+                .with_record_metadata(record_metadata)
+                .with_force(true)
+                .build()
+        };
+        let read = || async {
+            SumsFile::read_from_slice(&tokio::fs::read(format!("{}.sums", input)).await?).await
+        };
+
+        // Updating the sums file requires forcing it, as the checksums are not verified.
+        let unforced = TouchTaskBuilder::default()
+            .with_input_file_name(input.to_string())
+            .build()
+            .await;
+        assert!(unforced.is_err());
+        assert_eq!(read().await?, sums_file);
+
+        let (_, update) = touch(false).await?.run().await?.into_inner();
+        assert_eq!(
+            update,
+            Some(SizeUpdate {
+                previous: Some(100),
+                size: 4
+            })
+        );
+        // The checksums are kept as they are, even though they were not verified.
+        let touched = read().await?;
+        assert_eq!(touched.size, Some(4));
+        assert_eq!(touched.checksums, sums_file.checksums);
+        assert!(touched.mtime.is_none());
+
+        // Nothing changes if the size is already correct.
+        let (_, update) = touch(false).await?.run().await?.into_inner();
+        assert!(update.is_none());
+
+        touch(true).await?.run().await?;
+        assert!(read().await?.mtime.is_some());
+
+        // A sums file of a byte range does not have the size of the object.
+        let ranged = sums_file.with_range(Some(ByteRange::new(0, 1)));
+        tokio::fs::write(format!("{}.sums", input), ranged.to_json_string()?).await?;
+        assert!(touch(false).await.is_err());
+
+        Ok(())
+    }
+}
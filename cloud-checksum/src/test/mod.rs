@@ -11,11 +11,17 @@ use rand::rngs::StdRng;
 use rand::{RngCore, SeedableRng};
 use std::collections::HashMap;
 use std::fs;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 use std::sync::{LazyLock, Mutex};
 
 const CONSTANT_SEED: u64 = 42;
 
+/// The number of bytes generated at a time, so that large files are not held in memory. This
+/// is a multiple of the word size of the random number generator, so the contents do not depend
+/// on it.
+const CHUNK_SIZE: u64 = 1024 * 1024;
+
 /// The default benchmark file size. 10 MB.
 pub const BENCH_FILE_SIZE: u64 = 10485760;
 
@@ -44,6 +50,8 @@ pub struct TestFileBuilder {
     file_name: String,
     overwrite: bool,
     constant_value: Option<u8>,
+    seed: Option<u64>,
+    sized: bool,
 }
 
 impl Default for TestFileBuilder {
@@ -55,6 +63,8 @@ impl Default for TestFileBuilder {
             file_name: TEST_FILE_NAME.to_string(),
             overwrite: false,
             constant_value: None,
+            seed: None,
+            sized: false,
         }
     }
 }
@@ -63,6 +73,7 @@ impl TestFileBuilder {
     /// Add the random seed to generate the file with.
     pub fn with_random_seed(mut self, seed: u64) -> Self {
         self.rng = StdRng::seed_from_u64(seed);
+        self.seed = Some(seed);
         self
     }
 
@@ -83,6 +94,15 @@ impl TestFileBuilder {
         self
     }
 
+    /// Generate a reproducible file of exactly this size, e.g. `5 MiB + 1` bytes to test part
+    /// boundaries. The file name is suffixed with the size and the seed or constant value, so
+    /// that files with different contents do not collide. This uses the constant seed unless
+    /// another seed or a constant value is set.
+    pub fn with_size(mut self, size: u64) -> Self {
+        self.sized = true;
+        self.with_file_size(size)
+    }
+
     /// Set the file name.
     pub fn with_file_name(mut self, file_name: String) -> Self {
         self.file_name = file_name;
@@ -128,18 +148,15 @@ impl TestFileBuilder {
             .lock()
             .map_err(|err| FileGenerate(err.to_string()))?;
 
+        if self.sized {
+            return self.generate_sized(&mut files);
+        }
+
         if files.contains_key(&self.file_name) {
             return Ok(files[&self.file_name].clone());
         }
 
-        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .parent()
-            .unwrap()
-            .to_path_buf();
-
-        let directory = root.join(&self.directory);
-        fs::create_dir_all(&directory)?;
-
+        let directory = self.create_directory()?;
         let file = directory.join(&self.file_name);
 
         if !file.exists() {
@@ -158,4 +175,101 @@ impl TestFileBuilder {
 
         Ok(file)
     }
+
+    /// Create the directory that files are generated in.
+    fn create_directory(&self) -> Result<PathBuf> {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap()
+            .to_path_buf();
+
+        let directory = root.join(&self.directory);
+        fs::create_dir_all(&directory)?;
+
+        Ok(directory)
+    }
+
+    /// Generate a file set using `with_size`. These are cached by their path, written in chunks
+    /// and generated again if the existing file has a different size or `overwrite` is set.
+    fn generate_sized(&mut self, files: &mut HashMap<String, PathBuf>) -> Result<PathBuf> {
+        if self.seed.is_none() && self.constant_value.is_none() {
+            self.rng = StdRng::seed_from_u64(CONSTANT_SEED);
+            self.seed = Some(CONSTANT_SEED);
+        }
+        let file_name = match self.constant_value {
+            Some(value) => format!("{}_{}_c{}", self.file_name, self.file_size, value),
+            None => format!(
+                "{}_{}_s{}",
+                self.file_name,
+                self.file_size,
+                self.seed.unwrap_or(CONSTANT_SEED)
+            ),
+        };
+
+        let directory = self.create_directory()?;
+        let file = directory.join(&file_name);
+        let key = file.to_string_lossy().to_string();
+        if files.contains_key(&key) && !self.overwrite {
+            return Ok(files[&key].clone());
+        }
+
+        // A file that was only partially written, e.g. by an interrupted run, is generated again.
+        let exists = fs::metadata(&file).is_ok_and(|metadata| metadata.len() == self.file_size);
+        if !exists || self.overwrite {
+            let partial = directory.join(format!("{}.partial", file_name));
+            let mut writer = BufWriter::new(fs::File::create(&partial)?);
+            let mut buf = vec![0; CHUNK_SIZE as usize];
+            let mut remaining = self.file_size;
+            while remaining > 0 {
+                let chunk = &mut buf[..remaining.min(CHUNK_SIZE) as usize];
+                match self.constant_value {
+                    Some(value) => chunk.fill(value),
+                    None => self.rng.fill_bytes(chunk),
+                }
+                writer.write_all(chunk)?;
+                remaining -= chunk.len() as u64;
+            }
+            writer.flush()?;
+            drop(writer);
+
+            fs::rename(&partial, &file)?;
+        }
+
+        files.insert(key, file.clone());
+
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::aws_etag::MIB;
+    use anyhow::Result;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[test]
+    fn generate_sized_file_is_deterministic() -> Result<()> {
+        let first = tempdir()?;
+        let second = tempdir()?;
+        let generate = |dir: &Path, seed: u64| {
+            TestFileBuilder::default()
+                .with_directory(dir.to_path_buf())
+                .with_random_seed(seed)
+                .with_size(3 * MIB + 3)
+                .generate()
+        };
+
+        let data = fs::read(generate(first.path(), 1)?)?;
+        assert_eq!(data, fs::read(generate(second.path(), 1)?)?);
+        assert_ne!(data, fs::read(generate(first.path(), 2)?)?);
+
+        // Generating in chunks gives the same contents as generating all at once.
+        let mut expected = vec![0; data.len()];
+        StdRng::seed_from_u64(1).fill_bytes(&mut expected);
+        assert_eq!(data, expected);
+
+        Ok(())
+    }
 }
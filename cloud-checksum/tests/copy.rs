@@ -1,11 +1,14 @@
 //! Integration tests for copying files that work on AWS S3 directly. This uses a smaller 10MiB file
-//! to increase speeds and requires AWS credentials and a test bucket.
+//! to increase speeds and requires AWS credentials and a test bucket, set using
+//! `CLOUD_CHECKSUM_TEST_BUCKET_URI`.
 //!
 
 use anyhow::Result;
 use aws_sdk_s3::operation::head_object::HeadObjectOutput;
-use aws_sdk_s3::types::ChecksumMode;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{ChecksumMode, CompletedMultipartUpload, CompletedPart};
 use aws_sdk_s3::Client;
+use cloud_checksum::checksum::aws_etag::{predict_e_tag, MIB};
 use cloud_checksum::cli::{Command, CredentialProvider};
 use cloud_checksum::io::{create_s3_client, Provider};
 use cloud_checksum::test::TestFileBuilder;
@@ -22,6 +25,8 @@ use tempfile::TempDir;
 struct TestConfig {
     bucket_uri: String,
     endpoint_url: Option<String>,
+    #[serde(default)]
+    force_path_style: bool,
 }
 
 impl TestConfig {
@@ -56,6 +61,12 @@ impl TestConfig {
                 endpoint_url.to_string(),
             ]);
         }
+        if self.force_path_style {
+            commands.extend([
+                "--source-force-path-style".to_string(),
+                "--destination-force-path-style".to_string(),
+            ]);
+        }
 
         commands
     }
@@ -71,6 +82,7 @@ async fn copy_test() -> Result<()> {
         None,
         None,
         config.endpoint_url.as_deref(),
+        config.force_path_style,
     )
     .await?;
 
@@ -89,6 +101,75 @@ async fn copy_test() -> Result<()> {
     Ok(())
 }
 
+#[ignore]
+#[tokio::test]
+async fn predict_e_tag_test() -> Result<()> {
+    let config = TestConfig::load()?;
+    let file = TestFileBuilder::default().generate_bench_defaults()?;
+    let client = create_s3_client(
+        &CredentialProvider::DefaultEnvironment,
+        None,
+        None,
+        config.endpoint_url.as_deref(),
+        config.force_path_style,
+    )
+    .await?;
+
+    // Upload using the SDK directly so that the prediction is not checked against the copy code.
+    let part_size = 5 * MIB;
+    let uri = config.format_uri("predict_e_tag");
+    let (bucket, key) = Provider::try_from(uri.as_str())?.into_s3()?;
+    let upload = client
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(&key)
+        .send()
+        .await?;
+    let upload_id = upload.upload_id().unwrap();
+
+    let mut data = vec![];
+    File::open(&file)?.read_to_end(&mut data)?;
+
+    let mut parts = CompletedMultipartUpload::builder();
+    for (part_number, part) in (1..).zip(data.chunks(part_size as usize)) {
+        let output = client
+            .upload_part()
+            .bucket(&bucket)
+            .key(&key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(part.to_vec()))
+            .send()
+            .await?;
+        parts = parts.parts(
+            CompletedPart::builder()
+                .part_number(part_number)
+                .set_e_tag(output.e_tag)
+                .build(),
+        );
+    }
+
+    let output = client
+        .complete_multipart_upload()
+        .bucket(&bucket)
+        .key(&key)
+        .upload_id(upload_id)
+        .multipart_upload(parts.build())
+        .send()
+        .await?;
+
+    assert_eq!(
+        output.e_tag,
+        Some(predict_e_tag(&file, Some(part_size)).await?)
+    );
+    assert_eq!(
+        get_head_object(&client, uri.as_ref()).await?.e_tag,
+        output.e_tag
+    );
+
+    Ok(())
+}
+
 /// Test a multipart copy to S3.
 async fn local_s3_multipart(file: &Path, config: &TestConfig, client: &Client) -> Result<()> {
     let uri = config.format_uri("multipart");